@@ -0,0 +1,61 @@
+//! Compares parsing a movie with large sample tables in turn and with its tracks parsed in
+//! parallel. Run with `cargo bench --bench parallel`; criterion compares each run with the one
+//! before it.
+use criterion::{criterion_group, criterion_main, Criterion};
+use media_atoms::Parser;
+
+const TRACKS: usize = 8;
+const SAMPLES: u32 = 400_000;
+
+fn atom(atom_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+  let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+  bytes.extend_from_slice(atom_type);
+  bytes.extend_from_slice(payload);
+  bytes
+}
+
+/// A full atom table of `count` entries of `fields` 32-bit values each.
+fn table(atom_type: &[u8; 4], prefix: &[u32], count: u32, fields: &[u32]) -> Vec<u8> {
+  let mut payload = vec![0; 4];
+  payload.extend(prefix.iter().flat_map(|x| x.to_be_bytes()));
+  payload.extend_from_slice(&count.to_be_bytes());
+  for _ in 0..count {
+    payload.extend(fields.iter().flat_map(|x| x.to_be_bytes()));
+  }
+  atom(atom_type, &payload)
+}
+
+/// A movie of [`TRACKS`] tracks of [`SAMPLES`] samples, each sample in its own chunk and with its
+/// own size, duration and composition offset.
+fn movie() -> Vec<u8> {
+  let stbl = [
+    table(b"stts", &[], SAMPLES, &[1, 1001]),
+    table(b"ctts", &[], SAMPLES, &[1, 2002]),
+    table(b"stsc", &[], 1, &[1, 1, 1]),
+    table(b"stsz", &[0], SAMPLES, &[1000]),
+    table(b"stco", &[], SAMPLES, &[4096]),
+  ].concat();
+  let trak = atom(b"trak", &atom(b"mdia", &atom(b"minf", &atom(b"stbl", &stbl))));
+  let mut bytes = atom(b"ftyp", b"isom\0\0\0\0isom");
+  bytes.extend(atom(b"moov", &trak.repeat(TRACKS)));
+  bytes
+}
+
+fn parallel(c: &mut Criterion) {
+  let path = std::env::temp_dir().join(format!("media_atoms_bench_{}.mp4", std::process::id()));
+  std::fs::write(&path, movie()).unwrap();
+  // The thread count is left out of the name so runs on other machines compare with the baseline.
+  let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+  println!("parsing in parallel on {} threads", threads);
+  let mut group = c.benchmark_group(format!("{} tracks of {} samples", TRACKS, SAMPLES));
+  group.sample_size(10);
+  group.bench_function("in turn", |b| b.iter(|| assert!(Parser::from_path(&path).unwrap().parse().is_clean())));
+  group.bench_function("in parallel", |b| {
+    b.iter(|| assert!(Parser::from_path(&path).unwrap().parse_parallel().is_clean()))
+  });
+  group.finish();
+  std::fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, parallel);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+//! Benchmarks parsing a camera clip and a file of many tiny atoms from disk, where every header
+//! is a read of the file, and from memory, which is as fast as reading them can be. Run with
+//! `cargo bench --bench parse`; criterion compares each run with the one before it.
+use std::io::Cursor;
+use std::path::Path;
+use criterion::{criterion_group, criterion_main, Criterion};
+use media_atoms::Parser;
+
+const TINY_ATOMS: usize = 50_000;
+
+fn atom(atom_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+  let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+  bytes.extend_from_slice(atom_type);
+  bytes.extend_from_slice(payload);
+  bytes
+}
+
+/// A movie whose user data holds [`TINY_ATOMS`] atoms that are only a header.
+fn tiny_atoms() -> Vec<u8> {
+  let mut bytes = atom(b"ftyp", b"isom\0\0\0\0isom");
+  bytes.extend(atom(b"moov", &atom(b"udta", &atom(b"free", &[]).repeat(TINY_ATOMS))));
+  bytes
+}
+
+fn bench_file(c: &mut Criterion, name: &str, path: &Path) {
+  let bytes = std::fs::read(path).unwrap();
+  let mut group = c.benchmark_group(name);
+  group.bench_function("from the file", |b| {
+    b.iter(|| assert!(Parser::from_path(path).unwrap().parse().error().is_none()))
+  });
+  group.bench_function("from memory", |b| {
+    b.iter(|| assert!(Parser::new(Cursor::new(&bytes[..])).unwrap().parse().error().is_none()))
+  });
+  group.finish();
+}
+
+fn sample(c: &mut Criterion) {
+  bench_file(c, "sample.mp4", Path::new("resources/tests/sample.mp4"));
+}
+
+fn tiny(c: &mut Criterion) {
+  let path = std::env::temp_dir().join(format!("media_atoms_bench_tiny_{}.mp4", std::process::id()));
+  std::fs::write(&path, tiny_atoms()).unwrap();
+  bench_file(c, &format!("{} tiny atoms", TINY_ATOMS), &path);
+  std::fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, sample, tiny);
+criterion_main!(benches);
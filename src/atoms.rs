@@ -1,23 +1,59 @@
-use std::fs;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::default::Default;
-use super::parse_state::{Result, ParseError};
-use containers::*;
-use leaves::*;
+use super::parse_state::{Result, ParseError, ParseContext};
+pub use containers::*;
+pub use leaves::*;
 
 pub trait AtomLike {
   fn atom_size(&self) -> u64;
   fn atom_type(&self) -> &str;
   fn atom_location(&self) -> u64;
   fn header_size(&self) -> u32;
+  /// The bytes of the file the atom takes up, its header included.
+  fn byte_range(&self) -> std::ops::Range<u64> {
+    self.atom_location()..self.atom_location().saturating_add(self.atom_size())
+  }
+  /// Whether `offset` is one of the bytes of the atom, see [`AtomLike::byte_range`].
+  fn contains_offset(&self, offset: u64) -> bool {
+    self.byte_range().contains(&offset)
+  }
+}
+
+impl<T: AtomLike + ?Sized> AtomLike for &T {
+  fn atom_size(&self) -> u64 { (**self).atom_size() }
+  fn atom_type(&self) -> &str { (**self).atom_type() }
+  fn atom_location(&self) -> u64 { (**self).atom_location() }
+  fn header_size(&self) -> u32 { (**self).header_size() }
 }
 
+/// Anything the parser can read atoms from. The atom constructors take it as a trait object so
+/// they are compiled once rather than once per reader type.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
 pub trait Container {
   fn children(&self) -> &Vec<AtomNodes>;
-  fn set_children(&mut self, children: Vec<AtomNodes>) -> ();
+  fn children_mut(&mut self) -> &mut Vec<AtomNodes>;
+  fn set_children(&mut self, children: Vec<AtomNodes>);
+  /// Every node below this container in document order, parents before their children.
+  fn descendants(&self) -> AtomIter<'_> {
+    AtomIter::new(self.children())
+  }
+}
+
+/// Common view over the `stco` and `co64` chunk offset atoms. They only differ in the width of the
+/// offsets they store, so anything consuming chunk offsets should go through this trait.
+pub trait ChunkOffsetTable {
+  /// The chunk offsets as absolute file positions, without collecting them, for tables too
+  /// large to copy.
+  fn iter_offsets(&self) -> Box<dyn Iterator<Item = u64> + '_>;
+  fn offsets(&self) -> Vec<u64> { self.iter_offsets().collect() }
+  fn len(&self) -> usize;
+  fn is_empty(&self) -> bool { self.len() == 0 }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct FullAtom {
   version: u8,
   flags: u32,
@@ -27,7 +63,7 @@ impl FullAtom {
   pub fn new(file: &mut dyn Read) -> Result<FullAtom> {
     let mut result = FullAtom { ..Default::default() };
     let mut tmp :[u8;4] = [0;4];
-    file.read(&mut tmp)?;
+    file.read_exact(&mut tmp)?;
     result.version = tmp[0];
     tmp[0] = 0;
     result.flags = u32::from_be_bytes(tmp);
@@ -35,6 +71,11 @@ impl FullAtom {
   }
   pub fn version(&self) -> u8 { self.version }
   pub fn flags(&self) -> u32 {self.flags }
+  /// The version and flags as they are stored in the atom.
+  pub fn to_bytes(&self) -> [u8; 4] {
+    let flags = self.flags.to_be_bytes();
+    [self.version, flags[1], flags[2], flags[3]]
+  }
 }
 
 impl std::fmt::Display for FullAtom {
@@ -43,19 +84,267 @@ impl std::fmt::Display for FullAtom {
   }
 }
 
+fn read_u8(file: &mut dyn Read) -> Result<u8> {
+  let mut tmp: [u8;1] = [0;1];
+  file.read_exact(&mut tmp)?;
+  Ok(tmp[0])
+}
+
+fn read_u16(file: &mut dyn Read) -> Result<u16> {
+  let mut tmp: [u8;2] = [0;2];
+  file.read_exact(&mut tmp)?;
+  Ok(u16::from_be_bytes(tmp))
+}
+
+fn read_u32(file: &mut dyn Read) -> Result<u32> {
+  let mut tmp: [u8;4] = [0;4];
+  file.read_exact(&mut tmp)?;
+  Ok(u32::from_be_bytes(tmp))
+}
+
+fn read_u64(file: &mut dyn Read) -> Result<u64> {
+  let mut tmp: [u8;8] = [0;8];
+  file.read_exact(&mut tmp)?;
+  Ok(u64::from_be_bytes(tmp))
+}
+
+/// Fills as much of `buf` as the reader has left, retrying reads that return early. Returns how
+/// many bytes were read, which is less than the length of `buf` only at the end of the reader.
+fn read_up_to(file: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+  let mut filled = 0;
+  while filled < buf.len() {
+    match file.read(&mut buf[filled..]) {
+      Ok(0) => break,
+      Ok(read) => filled += read,
+      Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+      Err(err) => return Err(err.into()),
+    }
+  }
+  Ok(filled)
+}
+
+/// Reads a time or duration field, which is 64 bits wide in version 1 full atoms and 32 bits
+/// otherwise.
+fn read_versioned(file: &mut dyn Read, version: u8) -> Result<u64> {
+  if version == 1 { read_u64(file) } else { read_u32(file).map(u64::from) }
+}
+
+/// Reads the big-endian fields of the payload of a leaf atom in order. New leaves should read
+/// their payload with it: it reads the payload once, failing like [`AtomHeader::read_payload`]
+/// for atoms cut short by the end of the file, and a field that runs past the end of the payload
+/// fails with [`ParseError::TruncatedField`], which has the atom type and where the field starts.
+pub(crate) struct AtomReader {
+  header: AtomHeader,
+  payload: Vec<u8>,
+  position: usize,
+}
+
+impl AtomReader {
+  pub(crate) fn new(header: AtomHeader, file: &mut dyn ReadSeek) -> Result<AtomReader> {
+    Ok(AtomReader::from_payload(header, header.read_payload(file)?))
+  }
+  /// A reader of `payload`, the bytes after the header of the atom `header` is for.
+  pub(crate) fn from_payload(header: AtomHeader, payload: Vec<u8>) -> AtomReader {
+    AtomReader { header, payload, position: 0 }
+  }
+  /// The number of bytes of the payload that have not been read.
+  pub(crate) fn remaining(&self) -> usize { self.payload.len() - self.position }
+  /// The next `len` bytes of the payload.
+  pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&[u8]> {
+    if len > self.remaining() {
+      let offset = self.header.atom_location() + u64::from(self.header.header_size()) + self.position as u64;
+      return Err(ParseError::TruncatedField { atom_type: self.header.four_cc(), atom_offset: self.header.atom_location(),
+                                              offset, expected: len as u64, got: self.remaining() as u64 });
+    }
+    self.position += len;
+    Ok(&self.payload[self.position - len..self.position])
+  }
+  fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    bytes.copy_from_slice(self.read_bytes(N)?);
+    Ok(bytes)
+  }
+  pub(crate) fn skip(&mut self, len: usize) -> Result<()> {
+    self.read_bytes(len).map(|_| ())
+  }
+  pub(crate) fn read_u8(&mut self) -> Result<u8> { Ok(self.read_array::<1>()?[0]) }
+  pub(crate) fn read_u16(&mut self) -> Result<u16> { self.read_array().map(u16::from_be_bytes) }
+  pub(crate) fn read_u32(&mut self) -> Result<u32> { self.read_array().map(u32::from_be_bytes) }
+  pub(crate) fn read_u64(&mut self) -> Result<u64> { self.read_array().map(u64::from_be_bytes) }
+  // No leaf reads signed or fixed point fields through the reader yet.
+  #[allow(dead_code)]
+  pub(crate) fn read_i32(&mut self) -> Result<i32> { self.read_array().map(i32::from_be_bytes) }
+  pub(crate) fn read_fourcc(&mut self) -> Result<FourCC> { self.read_array().map(FourCC::new) }
+  /// A signed 16.16 fixed point value, such as a rate or a width.
+  #[allow(dead_code)]
+  pub(crate) fn read_fixed_16_16(&mut self) -> Result<f64> { self.read_i32().map(|value| f64::from(value) / 65536.0) }
+  /// A time or duration field, 64 bits wide in version 1 full atoms and 32 bits otherwise.
+  pub(crate) fn read_versioned(&mut self, version: u8) -> Result<u64> {
+    if version == 1 { self.read_u64() } else { self.read_u32().map(u64::from) }
+  }
+  pub(crate) fn read_full_atom(&mut self) -> Result<FullAtom> {
+    let [version, flags @ ..] = self.read_array::<4>()?;
+    Ok(FullAtom { version, flags: u32::from_be_bytes([0, flags[0], flags[1], flags[2]]) })
+  }
+}
+
+#[test]
+fn should_read_the_fields_of_a_payload() {
+  let mut payload = vec![1, 0, 0, 2];
+  payload.extend_from_slice(&[0xfe, 0xab, 0xcd, 0x12, 0x34, 0x56, 0x78]);
+  payload.extend_from_slice(&(-2i32).to_be_bytes());
+  payload.extend_from_slice(&(1u64 << 40).to_be_bytes());
+  payload.extend_from_slice(b"mp42");
+  payload.extend_from_slice(&[0x00, 0x01, 0x80, 0x00, 0xff, 0xff, 0x00, 0x00]);
+  payload.extend_from_slice(&[0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 10, 7, 8]);
+  let mut reader = AtomReader::from_payload(AtomHeader::synthesized(b"test", 8 + payload.len() as u64, 100), payload);
+  let full_atom = reader.read_full_atom().unwrap();
+  assert_eq!((1, 2), (full_atom.version(), full_atom.flags()));
+  assert_eq!(0xfe, reader.read_u8().unwrap());
+  assert_eq!(0xabcd, reader.read_u16().unwrap());
+  assert_eq!(0x12345678, reader.read_u32().unwrap());
+  assert_eq!(-2, reader.read_i32().unwrap());
+  assert_eq!(1 << 40, reader.read_u64().unwrap());
+  assert_eq!(reader.read_fourcc().unwrap(), "mp42");
+  assert_eq!(1.5, reader.read_fixed_16_16().unwrap());
+  assert_eq!(-1.0, reader.read_fixed_16_16().unwrap());
+  assert_eq!(9, reader.read_versioned(0).unwrap());
+  assert_eq!(10, reader.read_versioned(1).unwrap());
+  assert_eq!(2, reader.remaining());
+  reader.skip(1).unwrap();
+  assert_eq!(&[8], reader.read_bytes(1).unwrap());
+  assert_eq!(0, reader.remaining());
+}
+
+#[test]
+fn should_name_where_a_field_that_is_cut_short_starts() {
+  let payload = vec![0, 0, 0, 0, 0, 0];
+  let mut reader = AtomReader::from_payload(AtomHeader::synthesized(b"test", 14, 100), payload);
+  reader.read_full_atom().unwrap();
+  let err = reader.read_u32().unwrap_err();
+  assert!(matches!(err, ParseError::TruncatedField { offset: 112, expected: 4, got: 2, .. }), "{:?}", err);
+  assert_eq!("test at 100 (0x64): the field at 112 (0x70) needs 4 bytes but only 2 are left", err.to_string());
+  // A failed read consumes nothing.
+  assert_eq!(2, reader.remaining());
+  assert_eq!(0, reader.read_u16().unwrap());
+  assert!(reader.skip(1).is_err());
+}
+
+/// A four character code, as used for atom types and brands. Four-CCs are Latin-1 rather than
+/// UTF-8, so the `©` (0xA9) that starts iTunes metadata types such as `©nam` is decoded to two
+/// bytes of text while [`FourCC::raw`] keeps the original four.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FourCC {
+  raw: [u8; 4],
+  name: [u8; 8],
+  len: u8,
+}
+
+impl FourCC {
+  pub fn new(raw: [u8; 4]) -> FourCC {
+    let mut four_cc = FourCC { raw, ..Default::default() };
+    for byte in raw {
+      let len = four_cc.len as usize;
+      four_cc.len += char::from(byte).encode_utf8(&mut four_cc.name[len..]).len() as u8;
+    }
+    four_cc
+  }
+  /// The four bytes as they appear in the file.
+  pub fn raw(&self) -> [u8; 4] { self.raw }
+  pub fn as_str(&self) -> &str { std::str::from_utf8(&self.name[..self.len as usize]).unwrap_or("") }
+  /// Whether this is the four-CC `name`, ignoring ASCII case and the spaces that pad short codes,
+  /// so `qt` and `QT  ` both match `qt  `. Meant for names typed by people rather than for types
+  /// read from a file, which should be compared exactly.
+  pub fn matches(&self, name: &str) -> bool { type_matches(self.as_str(), name) }
+}
+
+impl std::fmt::Display for FourCC {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+impl std::fmt::Debug for FourCC {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{:?}", self.as_str())
+  }
+}
+impl PartialEq<str> for FourCC {
+  fn eq(&self, other: &str) -> bool { self.as_str() == other }
+}
+impl PartialEq<&str> for FourCC {
+  fn eq(&self, other: &&str) -> bool { self.as_str() == *other }
+}
+impl From<&str> for FourCC {
+  fn from(name: &str) -> FourCC { FourCC::new(four_cc_from_str(name)) }
+}
+
+/// The four-CC for `name`, the reverse of the Latin-1 decoding done by [`FourCC`].
+fn four_cc_from_str(name: &str) -> [u8; 4] {
+  let mut four_cc = [b' '; 4];
+  for (byte, c) in four_cc.iter_mut().zip(name.chars()) {
+    *byte = if u32::from(c) <= 0xff { c as u8 } else { b'?' };
+  }
+  four_cc
+}
+
+/// Whether `atom_type` is `name`, as [`FourCC::matches`] compares them.
+pub(crate) fn type_matches(atom_type: &str, name: &str) -> bool {
+  atom_type.trim_end_matches(' ').eq_ignore_ascii_case(name.trim())
+}
+
+/// `uuid` in the canonical hyphenated form, such as `85c0b687-820f-11e0-8111-f4ce462b6a48`.
+pub(crate) fn uuid_string(uuid: &[u8; 16]) -> String {
+  let mut text = String::with_capacity(36);
+  for (index, byte) in uuid.iter().enumerate() {
+    if matches!(index, 4 | 6 | 8 | 10) {
+      text.push('-');
+    }
+    text.push_str(&format!("{:02x}", byte));
+  }
+  text
+}
+
+/// The largest atom [`AtomHeader::read_atom`] reads into memory. Atoms that big, such as `mdat`,
+/// are read in pieces instead, see [`leaves::MdatAtom::payload_reader`].
+pub const DEFAULT_MAX_ATOM_SIZE: u64 = 1 << 30;
+
 #[derive(Default, Copy, Clone)]
 pub struct AtomHeader {
   atom_size: u64,
-  atom_type: [u8;4],
+  atom_type: FourCC,
   atom_location: u64,
   header_size: u32,
+  size_to_eof: bool,
+  extended_type: Option<[u8; 16]>,
+  /// The most bytes [`AtomHeader::read_atom`] and [`AtomHeader::read_payload`] read into memory,
+  /// set from [`ParseContext::max_atom_read`] for the atoms of a parse and
+  /// [`DEFAULT_MAX_ATOM_SIZE`] when not set. It is not part of what the header is, so headers that
+  /// differ only in it are equal.
+  read_limit: Option<u64>,
 }
 
+impl PartialEq for AtomHeader {
+  fn eq(&self, other: &AtomHeader) -> bool {
+    (self.atom_size, self.atom_type, self.atom_location, self.header_size, self.size_to_eof, self.extended_type) ==
+      (other.atom_size, other.atom_type, other.atom_location, other.header_size, other.size_to_eof, other.extended_type)
+  }
+}
+impl Eq for AtomHeader {}
+
 impl AtomHeader {
-  pub fn new<T>(file: &mut T) -> Result<AtomHeader>
-  where T: Read + Seek {
+  pub fn new(file: &mut dyn ReadSeek) -> Result<AtomHeader> {
+    AtomHeader::new_within(file, None)
+  }
+  /// Reads a header that is known to end by `end`, the size of the file or the end of the parent
+  /// atom. An atom with a size of 0, usually a final `mdat` written by a streaming recorder,
+  /// extends to `end`. Without `end` the size is left at 0.
+  pub fn new_within(file: &mut dyn ReadSeek, end: Option<u64>) -> Result<AtomHeader> {
     let mut buf: [u8; 8] = [0; 8];
-    let mut readout = file.read(buf.as_mut())?;
+    let mut readout = read_up_to(file, &mut buf)?;
+    if readout < buf.len() {
+      let offset = file.stream_position()? - readout as u64;
+      return Err(ParseError::TruncatedHeader { offset, expected: buf.len() as u64, got: readout as u64 });
+    }
 
     let mut atom_size = [0; 4];
     atom_size[..4].clone_from_slice(&buf[0..4]);
@@ -65,38 +354,139 @@ impl AtomHeader {
     atom_type[..4].clone_from_slice(&buf[4..8]);
 
     if 1 == atom_size {
-      readout += file.read(buf.as_mut()).unwrap();
+      let extended = read_up_to(file, &mut buf)?;
+      if extended < buf.len() {
+        let offset = file.stream_position()? - (readout + extended) as u64;
+        return Err(ParseError::TruncatedAtom { atom_type: FourCC::new(atom_type), offset, expected: 16,
+                                               got: (readout + extended) as u64 });
+      }
+      readout += extended;
       atom_size = u64::from_be_bytes(buf);
     }
-    let atom_location = file.seek(SeekFrom::Current(0))? - readout as u64;
+    let atom_location = file.stream_position()? - readout as u64;
+    // Four-CCs are printable, apart from the © that starts iTunes metadata types.
+    if !atom_type.iter().all(|x| (0x20..=0x7e).contains(x) || *x == 0xa9) {
+      return Err(ParseError::Misaligned { offset: atom_location, four_cc: atom_type });
+    }
+    // A `uuid` atom is typed by the 16 byte UUID that follows, which is part of its header.
+    let mut extended_type = None;
+    if &atom_type == b"uuid" {
+      let mut uuid = [0; 16];
+      let read = read_up_to(file, &mut uuid)?;
+      if read < uuid.len() {
+        return Err(ParseError::TruncatedAtom { atom_type: FourCC::new(atom_type), offset: atom_location,
+                                               expected: readout as u64 + 16, got: (readout + read) as u64 });
+      }
+      readout += read;
+      extended_type = Some(uuid);
+    }
     let header_size = readout as u32;
-    Ok(AtomHeader{atom_size, atom_type, atom_location, header_size})
+    let size_to_eof = atom_size == 0 && end.is_some();
+    if let (0, Some(end)) = (atom_size, end) {
+      atom_size = end.saturating_sub(atom_location);
+    }
+    Ok(AtomHeader{atom_size, atom_type: FourCC::new(atom_type), atom_location, header_size, size_to_eof, extended_type,
+                  read_limit: None})
+  }
+  /// A header for an atom of `atom_type` that is not read from a file, using the extended size
+  /// form only when the size needs it.
+  pub fn synthesized(atom_type: &[u8; 4], atom_size: u64, atom_location: u64) -> AtomHeader {
+    let header_size = if atom_size > u32::MAX as u64 { 16 } else { 8 };
+    AtomHeader { atom_size, atom_type: FourCC::new(*atom_type), atom_location, header_size,
+                 size_to_eof: false, extended_type: None, read_limit: None }
+  }
+  /// Like [`AtomHeader::synthesized`], always using the extended size form, as a muxer that
+  /// reserves room for a growing `mdat` does.
+  pub fn synthesized_extended(atom_type: &[u8; 4], atom_size: u64, atom_location: u64) -> AtomHeader {
+    AtomHeader { header_size: 16, ..AtomHeader::synthesized(atom_type, atom_size, atom_location) }
   }
   pub fn new_from(atom: &dyn AtomLike) -> AtomHeader {
-    let mut val = AtomHeader{atom_size: atom.atom_size(),
+    AtomHeader{atom_size: atom.atom_size(),
+      atom_type: FourCC::new(four_cc_from_str(atom.atom_type())),
       atom_location: atom.atom_location(),
       header_size: atom.header_size(),
-      ..Default::default()
-    };
-    val.atom_type.clone_from_slice(atom.atom_type().as_bytes());
-    val
+      size_to_eof: false,
+      extended_type: None,
+      read_limit: None,
+    }
+  }
+  /// The atom type as read, before any decoding to text.
+  pub fn four_cc(&self) -> FourCC { self.atom_type }
+  /// Whether the size is in the 64-bit extended form, a size field of 1 followed by the real size
+  /// after the type, which makes the header 16 bytes rather than 8.
+  pub fn uses_extended_size(&self) -> bool {
+    self.header_size - if self.extended_type.is_some() { 16 } else { 0 } == 16
+  }
+  /// The UUID that is the real type of a `uuid` atom, read right after the size and type and
+  /// counted in [`AtomHeader::header_size`].
+  pub fn extended_type(&self) -> Option<[u8; 16]> { self.extended_type }
+  /// Whether the size field was 0, meaning the atom extends to the end of the file or of its
+  /// parent, and the size was taken from there.
+  pub fn is_size_to_eof(&self) -> bool { self.size_to_eof }
+  /// Absolute offset one past the last byte of the atom. An extended 64-bit size can claim more
+  /// than a file offset can hold, so this fails instead of wrapping around.
+  pub fn end_location(&self) -> Result<u64> {
+    self.atom_location.checked_add(self.atom_size).ok_or_else(|| {
+      ParseError::parse_failed(self, format!("size {} overflows", self.atom_size))
+    })
   }
-  pub fn read_atom<T>(&self, file: &mut T) -> Result<Vec<u8>> where T: Read + Seek {
+  /// The same header, whose atom is read into memory only when it is at most `max_size` bytes.
+  pub(crate) fn with_read_limit(self, max_size: u64) -> AtomHeader {
+    AtomHeader { read_limit: Some(max_size), ..self }
+  }
+  fn read_limit(&self) -> u64 { self.read_limit.unwrap_or(DEFAULT_MAX_ATOM_SIZE) }
+  /// Reads the whole atom, header included, into memory. Fails with [`ParseError::TruncatedAtom`]
+  /// when the file ends before the atom does, and with [`ParseError::AtomTooLarge`] for atoms
+  /// over the [`ParseOptions::max_atom_read`](crate::ParseOptions::max_atom_read) of the parse the
+  /// header is from, or [`DEFAULT_MAX_ATOM_SIZE`] bytes.
+  pub fn read_atom(&self, file: &mut dyn ReadSeek) -> Result<Vec<u8>> {
+    self.read_atom_with_limit(file, self.read_limit())
+  }
+  /// Like [`AtomHeader::read_atom`], reading atoms of up to `max_size` bytes.
+  pub fn read_atom_with_limit(&self, file: &mut dyn ReadSeek, max_size: u64) -> Result<Vec<u8>> {
+    if self.atom_size() > max_size {
+      return Err(ParseError::AtomTooLarge { atom_type: self.atom_type, offset: self.atom_location,
+                                            size: self.atom_size, limit: max_size });
+    }
     let mut buf = Vec::new();
-    buf.resize(self.atom_size() as usize, 0);
     file.seek(SeekFrom::Start(self.atom_location()))?;
-    let read = file.read(buf.as_mut_slice())?;
-    if read == self.atom_size() as usize {
+    file.take(self.atom_size()).read_to_end(&mut buf)?;
+    if buf.len() as u64 == self.atom_size() {
+      Ok(buf)
+    } else {
+      Err(self.truncated(buf.len() as u64))
+    }
+  }
+  /// Reads the payload of the atom, the bytes after its header, into memory. Fails like
+  /// [`AtomHeader::read_atom`], counting the header among the bytes that were present.
+  pub fn read_payload(&self, file: &mut dyn ReadSeek) -> Result<Vec<u8>> {
+    self.read_payload_with_limit(file, self.read_limit())
+  }
+  /// Like [`AtomHeader::read_payload`], reading atoms of up to `max_size` bytes.
+  pub fn read_payload_with_limit(&self, file: &mut dyn ReadSeek, max_size: u64) -> Result<Vec<u8>> {
+    if self.atom_size() > max_size {
+      return Err(ParseError::AtomTooLarge { atom_type: self.atom_type, offset: self.atom_location,
+                                            size: self.atom_size, limit: max_size });
+    }
+    let header_size = u64::from(self.header_size).min(self.atom_size);
+    let mut buf = Vec::new();
+    file.seek(SeekFrom::Start(self.atom_location() + header_size))?;
+    file.take(self.atom_size() - header_size).read_to_end(&mut buf)?;
+    if header_size + buf.len() as u64 == self.atom_size() {
       Ok(buf)
     } else {
-      Err(ParseError::FailedToReadOutAtom(self.atom_type().to_string(), self.atom_size(), read))
+      Err(self.truncated(header_size + buf.len() as u64))
     }
   }
+  /// A [`ParseError::TruncatedAtom`] for this atom, when only `got` of its bytes are in the file.
+  pub(crate) fn truncated(&self, got: u64) -> ParseError {
+    ParseError::TruncatedAtom { atom_type: self.atom_type, offset: self.atom_location, expected: self.atom_size, got }
+  }
 }
 
 impl AtomLike for AtomHeader {
   fn atom_size(&self) -> u64 { self.atom_size }
-  fn atom_type(&self) -> &str { std::str::from_utf8(&self.atom_type).unwrap() }
+  fn atom_type(&self) -> &str { self.atom_type.as_str() }
   fn atom_location(&self) -> u64 { self.atom_location }
   fn header_size(&self) -> u32 { self.header_size }
 }
@@ -116,22 +506,227 @@ impl std::fmt::Display for AtomHeader {
 }
 impl From<&dyn AtomLike> for AtomHeader {
   fn from(item: &dyn AtomLike) -> Self {
-    let mut res = AtomHeader{ atom_size:item.atom_size(),
-      atom_location:item.atom_location(),
-      header_size: item.header_size(),
-      ..Default::default()
-    };
-    res.atom_type.clone_from_slice(item.atom_type().as_bytes());
-    res
+    AtomHeader::new_from(item)
   }
 }
 
+#[test]
+fn should_report_misalignment_for_a_type_that_is_not_text() {
+  let mut file = std::io::Cursor::new(vec![0, 0, 0, 16, 0x01, 0x9c, 0x00, 0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+  match AtomHeader::new(&mut file) {
+    Err(ParseError::Misaligned { offset: 0, four_cc }) => assert_eq!([0x01, 0x9c, 0x00, 0xff], four_cc),
+    other => panic!("unexpected result {:?}", other),
+  }
+  let mut file = std::io::Cursor::new(vec![0, 0, 0, 8, 0xa9, b'n', b'a', b'm']);
+  assert_eq!("©nam", AtomHeader::new(&mut file).unwrap().atom_type());
+}
+
+#[test]
+fn should_keep_a_latin_1_atom_type_outside_of_metadata_as_unknown() {
+  let mut file = std::io::Cursor::new(vec![0, 0, 0, 12, 0xa9, b'n', b'a', b'm', 0, 0, 0, 0]);
+  let header = AtomHeader::new(&mut file).unwrap();
+  assert_eq!([0xa9, b'n', b'a', b'm'], header.four_cc().raw());
+  assert_eq!(header.four_cc(), "©nam");
+  assert_eq!("type: ©nam, size: 12, location: 0", header.to_string());
+  match AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap() {
+    AtomNodes::Atom(leaves::Atoms::UnknownAtom(atom)) => {
+      assert_eq!("©nam", atom.four_cc().to_string());
+      assert!(matches!(atom.reason(), leaves::UnknownReason::Unrecognized));
+    }
+    other => panic!("expected an unknown atom, got {:?}", other),
+  }
+}
+
+#[test]
+fn should_read_the_uuid_of_a_uuid_atom_as_part_of_its_header() {
+  let uuid = [0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48];
+  let mut bytes = vec![0, 0, 0, 28];
+  bytes.extend_from_slice(b"uuid");
+  bytes.extend_from_slice(&uuid);
+  bytes.extend_from_slice(&[1, 2, 3, 4]);
+  let header = AtomHeader::new(&mut std::io::Cursor::new(&bytes)).unwrap();
+  assert_eq!((24, Some(uuid), false), (header.header_size(), header.extended_type(), header.uses_extended_size()));
+  assert_eq!("85c0b687-820f-11e0-8111-f4ce462b6a48", uuid_string(&uuid));
+
+  let mut extended = vec![0, 0, 0, 1];
+  extended.extend_from_slice(b"uuid");
+  extended.extend_from_slice(&36u64.to_be_bytes());
+  extended.extend_from_slice(&uuid);
+  extended.extend_from_slice(&[1, 2, 3, 4]);
+  let header = AtomHeader::new(&mut std::io::Cursor::new(&extended)).unwrap();
+  assert_eq!((36, 32, true), (header.atom_size(), header.header_size(), header.uses_extended_size()));
+
+  match AtomHeader::new(&mut std::io::Cursor::new(&bytes[..20])) {
+    Err(ParseError::TruncatedAtom { offset: 0, expected: 24, got: 20, .. }) => (),
+    other => panic!("expected TruncatedAtom, got {:?}", other),
+  }
+}
+
+#[test]
+fn should_match_a_four_cc_typed_without_padding_in_any_case() {
+  assert!(FourCC::from("qt  ").matches("qt"));
+  assert!(FourCC::from("qt  ").matches("QT  "));
+  assert!(FourCC::from("mp42").matches(" MP42"));
+  assert!(!FourCC::from("mp42").matches("mp4"));
+  assert!(!FourCC::from("qt  ").matches("q t"));
+}
+
+#[test]
+fn should_report_a_header_cut_short() {
+  let mut file = std::io::Cursor::new(vec![0, 0, 0, 16, b'f', b'r']);
+  match AtomHeader::new(&mut file) {
+    Err(ParseError::TruncatedHeader { offset: 0, expected: 8, got: 6 }) => (),
+    other => panic!("expected TruncatedHeader, got {:?}", other),
+  }
+  let mut file = std::io::Cursor::new(vec![0, 0, 0, 1, b'm', b'd', b'a', b't', 0, 0, 0, 0]);
+  match AtomHeader::new(&mut file) {
+    Err(ParseError::TruncatedAtom { atom_type, offset: 0, expected: 16, got: 12 }) => assert_eq!(atom_type, "mdat"),
+    other => panic!("expected TruncatedAtom, got {:?}", other),
+  }
+}
+
+#[test]
+fn should_parse_an_extended_size_header() {
+  let mut bytes = vec![0, 0, 0, 1];
+  bytes.extend_from_slice(b"mdat");
+  bytes.extend_from_slice(&(5u64 << 32).to_be_bytes());
+  bytes.extend_from_slice(&[0; 8]);
+  let mut file = std::io::Cursor::new(bytes);
+  let header = AtomHeader::new(&mut file).unwrap();
+  assert_eq!(("mdat", 5 << 32, 0, 16), (header.atom_type(), header.atom_size(), header.atom_location(), header.header_size()));
+  assert!(header.uses_extended_size());
+  assert_eq!(16, file.stream_position().unwrap());
+  // Claiming more than fits in memory fails before anything is allocated.
+  assert!(matches!(header.read_atom(&mut file), Err(ParseError::AtomTooLarge { size, .. }) if size == 5 << 32));
+  assert!(matches!(header.read_payload(&mut file), Err(ParseError::AtomTooLarge { .. })));
+
+  let synthesized = AtomHeader::synthesized_extended(b"mdat", 24, 8);
+  assert_eq!((16, 24, 8), (synthesized.header_size(), synthesized.atom_size(), synthesized.atom_location()));
+  assert!(synthesized.uses_extended_size());
+  assert!(!AtomHeader::synthesized(b"mdat", 24, 8).uses_extended_size());
+  assert!(AtomHeader::synthesized(b"mdat", 5 << 32, 8).uses_extended_size());
+}
+
 #[test]
 fn should_parse_a_header() {
   let mut file = std::fs::File::open("resources/tests/free.mp4").unwrap();
   assert!(AtomHeader::new(&mut file).is_ok());
 }
 
+#[test]
+fn should_extend_an_atom_of_size_0_to_the_end() {
+  let mut file = std::fs::File::open("resources/tests/mdat_to_eof.mp4").unwrap();
+  file.seek(SeekFrom::Start(32)).unwrap();
+  let header = AtomHeader::new_within(&mut file, Some(104)).unwrap();
+  assert_eq!(("mdat", 32, 72), (header.atom_type(), header.atom_location(), header.atom_size()));
+  assert!(header.is_size_to_eof());
+  assert_eq!(72, header.read_atom(&mut file).unwrap().len());
+
+  file.seek(SeekFrom::Start(32)).unwrap();
+  let header = AtomHeader::new(&mut file).unwrap();
+  assert_eq!(0, header.atom_size());
+  assert!(!header.is_size_to_eof());
+  file.rewind().unwrap();
+  assert!(!AtomHeader::new_within(&mut file, Some(104)).unwrap().is_size_to_eof());
+
+  let results = crate::Parser::from_path("resources/tests/mdat_to_eof.mp4").unwrap().parse();
+  let sizes: Vec<(&str, u64)> = results.iter().filter(|node| !matches!(node, AtomNodes::Container(_))).map(|node| (node.atom_type(), node.atom_size())).collect();
+  assert_eq!(vec![("ftyp", 32), ("mdat", 72)], sizes);
+}
+
+#[test]
+fn structural_hash_ignores_location_but_not_shape() {
+  let mut file = std::fs::File::open("resources/tests/moov.mp4").unwrap();
+  let moov = AtomNodes::new(AtomHeader::new(&mut file).unwrap(), &mut file, &ParseContext::default()).unwrap();
+  let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+  file.seek(SeekFrom::Start(32)).unwrap();
+  let shifted = AtomNodes::new(AtomHeader::new(&mut file).unwrap(), &mut file, &ParseContext::default()).unwrap();
+  assert_eq!(32, shifted.atom_location());
+  assert_eq!(moov.structural_hash(), shifted.structural_hash());
+
+  let mut grown = moov.clone();
+  if let AtomNodes::Container(container) = &mut grown {
+    let mut children = container.children().clone();
+    children.push(children[0].clone());
+    container.set_children(children);
+  }
+  assert_ne!(moov.structural_hash(), grown.structural_hash());
+}
+
+#[test]
+fn references_to_atoms_are_atom_like() {
+  fn describe<A: AtomLike>(atom: A) -> String {
+    format!("{}", AtomHeader::new_from(&atom))
+  }
+  let mut file = std::fs::File::open("resources/tests/free.mp4").unwrap();
+  let header = AtomHeader::new(&mut file).unwrap();
+  let node = AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap();
+  let as_dyn: &dyn AtomLike = &node;
+  assert_eq!(describe(header), describe(as_dyn));
+  let by_reference = describe(&node);
+  assert_eq!(by_reference, describe(node));
+  assert!(by_reference.starts_with("type: free"));
+}
+
+#[test]
+fn walk_mut_edits_the_tree_in_place() {
+  let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+  let len = file.seek(SeekFrom::End(0)).unwrap();
+  let mut root = AtomNodes::Container(ContainerAtoms::Root(RootAtom::new(&mut file, len, &ParseContext::default()).unwrap()));
+  let mut cleared = 0;
+  root.walk_mut(|node| if let AtomNodes::Atom(Atoms::Free(free)) = node {
+    free.set_atom_size(0);
+    cleared += 1;
+  });
+  assert_eq!(1, cleared);
+  let mut sizes = Vec::new();
+  root.walk(|node| if node.atom_type() == "free" { sizes.push(node.atom_size()) });
+  assert_eq!(vec![0], sizes);
+}
+
+#[test]
+fn should_reach_the_typed_atoms_of_a_node() {
+  let parse = || crate::Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+  let (results, again) = (parse(), parse());
+  let moov = results.find("moov").unwrap();
+  assert!(moov.as_moov().is_some() && moov.as_atom().is_none() && moov.as_ftyp().is_none());
+  assert_eq!(Some(4), moov.as_container().map(|moov| moov.children().len()));
+  let trak = results.find("moov/trak").unwrap();
+  assert_eq!(Some(3), trak.as_trak().map(|trak| trak.children().len()));
+  assert!(trak.as_container().is_some() && trak.fields().is_empty());
+
+  // Atoms parsed from the same bytes are equal.
+  let ftyp = results.find("ftyp").unwrap();
+  assert!(ftyp.as_ftyp().is_some() && ftyp.as_container().is_none());
+  assert_eq!(ftyp.as_ftyp(), again.find("ftyp").and_then(AtomNodes::as_ftyp));
+  for path in ["moov/mvhd", "moov/trak/tkhd", "moov/trak/mdia/mdhd", "moov/trak/mdia/hdlr", "moov/trak/mdia/minf/stbl/stsd"] {
+    let (node, other) = (results.find(path).unwrap(), again.find(path).unwrap());
+    assert_eq!((node.as_mvhd(), node.as_tkhd(), node.as_mdhd()), (other.as_mvhd(), other.as_tkhd(), other.as_mdhd()));
+    assert_eq!((node.as_hdlr(), node.as_stsd()), (other.as_hdlr(), other.as_stsd()));
+    assert!(node.as_atom().is_some(), "{}", path);
+  }
+  assert_ne!(results.find("moov/trak/tkhd").and_then(AtomNodes::as_tkhd),
+             results.find_all("moov/trak/tkhd")[1].as_tkhd());
+
+  let fields = ftyp.fields();
+  assert_eq!(Some(&(String::from("major_brand"), crate::FieldValue::Text(String::from("isom")))), fields.first());
+  assert!(fields.iter().all(|(name, _)| name != "atom_size"));
+}
+
+#[test]
+fn parses_through_a_read_seek_trait_object() {
+  let bytes = std::fs::read("resources/tests/moov.mp4").unwrap();
+  let readers: Vec<Box<dyn ReadSeek>> = vec![
+    Box::new(std::fs::File::open("resources/tests/moov.mp4").unwrap()),
+    Box::new(std::io::Cursor::new(bytes)),
+  ];
+  let hashes: Vec<u64> = readers.into_iter().map(|mut reader| {
+    let header = AtomHeader::new(reader.as_mut()).unwrap();
+    AtomNodes::new(header, reader.as_mut(), &ParseContext::default()).unwrap().structural_hash()
+  }).collect();
+  assert_eq!(hashes[0], hashes[1]);
+}
+
 #[derive(Debug, Clone)]
 pub enum AtomNodes {
   Container(ContainerAtoms),
@@ -139,21 +734,240 @@ pub enum AtomNodes {
 }
 
 impl AtomNodes {
-  pub fn new<T>(atom_header: AtomHeader, file: &mut T) -> Result<AtomNodes>
-  where T: Read + Seek {
-    if let Ok(k) = ContainerAtoms::new(atom_header, file) {
-      Ok(AtomNodes::Container(k))
-    } else {
-      Ok(AtomNodes::Atom(Atoms::new(atom_header, file)?))
+  pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<AtomNodes> {
+    if ctx.skips(atom_header.four_cc()) {
+      return Ok(AtomNodes::Atom(Atoms::UnknownAtom(UnknownAtom::skipped(atom_header))));
+    }
+    match ContainerAtoms::try_new(atom_header, file, ctx)? {
+      Some(container) => Ok(AtomNodes::Container(container)),
+      None => Ok(AtomNodes::Atom(Atoms::new(atom_header, file, ctx)?)),
     }
   }
   pub fn is_container(&self) -> bool {
-    if let AtomNodes::Container(_) = self {
-      true
-    } else {
-      false
+    matches!(self, AtomNodes::Container(_))
+  }
+  /// The leaf atom, when this is not a container.
+  pub fn as_atom(&self) -> Option<&Atoms> {
+    match self {
+      AtomNodes::Atom(atom) => Some(atom),
+      AtomNodes::Container(_) => None,
+    }
+  }
+  /// The container and its children, when this is one.
+  pub fn as_container(&self) -> Option<&dyn Container> {
+    match self {
+      AtomNodes::Container(container) => Some(container),
+      AtomNodes::Atom(_) => None,
+    }
+  }
+  /// The file type atom, when this is one.
+  ///
+  /// ```
+  /// let results = media_atoms::Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+  /// let ftyp = results.find("ftyp").and_then(|node| node.as_ftyp()).unwrap();
+  /// assert_eq!(ftyp.major_brand(), "isom");
+  /// assert!(ftyp.compatible_brands().iter().any(|brand| *brand == "avc1"));
+  /// ```
+  pub fn as_ftyp(&self) -> Option<&FtypAtom> {
+    match self {
+      AtomNodes::Atom(Atoms::Ftyp(ftyp)) => Some(ftyp),
+      _ => None,
+    }
+  }
+  /// The movie header atom, when this is one.
+  ///
+  /// ```
+  /// let results = media_atoms::Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+  /// let mvhd = results.find("moov/mvhd").and_then(|node| node.as_mvhd()).unwrap();
+  /// assert_eq!(1000, mvhd.time_scale());
+  /// ```
+  pub fn as_mvhd(&self) -> Option<&MvhdAtom> {
+    match self {
+      AtomNodes::Atom(Atoms::Mvhd(mvhd)) => Some(mvhd),
+      _ => None,
+    }
+  }
+  /// The track header atom, when this is one.
+  pub fn as_tkhd(&self) -> Option<&TkhdAtom> {
+    match self {
+      AtomNodes::Atom(Atoms::Tkhd(tkhd)) => Some(tkhd),
+      _ => None,
+    }
+  }
+  /// The media header atom, when this is one.
+  pub fn as_mdhd(&self) -> Option<&MdhdAtom> {
+    match self {
+      AtomNodes::Atom(Atoms::Mdhd(mdhd)) => Some(mdhd),
+      _ => None,
+    }
+  }
+  /// The handler reference atom, when this is one.
+  pub fn as_hdlr(&self) -> Option<&HdlrAtom> {
+    match self {
+      AtomNodes::Atom(Atoms::Hdlr(hdlr)) => Some(hdlr),
+      _ => None,
+    }
+  }
+  /// The sample description atom, when this is one.
+  pub fn as_stsd(&self) -> Option<&StsdAtom> {
+    match self {
+      AtomNodes::Atom(Atoms::Stsd(stsd)) => Some(stsd),
+      _ => None,
+    }
+  }
+  /// The movie atom, when this is one.
+  pub fn as_moov(&self) -> Option<&MoovAtom> {
+    match self {
+      AtomNodes::Container(ContainerAtoms::Moov(moov)) => Some(moov),
+      _ => None,
+    }
+  }
+  /// The track atom, when this is one.
+  pub fn as_trak(&self) -> Option<&TrakAtom> {
+    match self {
+      AtomNodes::Container(ContainerAtoms::Trak(trak)) => Some(trak),
+      _ => None,
+    }
+  }
+  /// The fields the atom decodes, by name, in the order the JSON output lists them, for tools
+  /// that handle every type of atom alike. The header fields every atom has are left out, as
+  /// are the children of containers.
+  ///
+  /// ```
+  /// use media_atoms::FieldValue;
+  /// let results = media_atoms::Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+  /// let fields = results.find("moov/mvhd").unwrap().fields();
+  /// assert!(fields.contains(&(String::from("time_scale"), FieldValue::Unsigned(1000))));
+  /// ```
+  pub fn fields(&self) -> Vec<(String, crate::FieldValue)> {
+    crate::json::node_fields(self)
+  }
+  /// The result of the custom handler that parsed this atom, if there was one and it returned a
+  /// `T`.
+  pub fn as_custom<T: 'static>(&self) -> Option<&T> {
+    match self {
+      AtomNodes::Atom(Atoms::Custom(atom)) => atom.value::<T>(),
+      _ => None,
+    }
+  }
+  /// Visits this node and every node below it, depth first, parents before their children, with
+  /// a mutable reference so the tree can be edited in place.
+  pub fn walk_mut<F>(&mut self, mut f: F) where F: FnMut(&mut AtomNodes) {
+    fn visit(node: &mut AtomNodes, f: &mut dyn FnMut(&mut AtomNodes)) {
+      f(node);
+      if let AtomNodes::Container(atom) = node {
+        for child in atom.children_mut() {
+          visit(child, f);
+        }
+      }
+    }
+    visit(self, &mut f);
+  }
+  /// Visits this node and every node below it, depth first, parents before their children.
+  pub fn walk<'a, F>(&'a self, mut f: F) where F: FnMut(&'a AtomNodes) {
+    fn visit<'a>(node: &'a AtomNodes, f: &mut dyn FnMut(&'a AtomNodes)) {
+      f(node);
+      if let AtomNodes::Container(atom) = node {
+        for child in atom.children() {
+          visit(child, f);
+        }
+      }
+    }
+    visit(self, &mut f);
+  }
+  /// This node and every node below it in document order, the same order as [`AtomNodes::walk`].
+  pub fn iter(&self) -> AtomIter<'_> {
+    AtomIter::new(std::slice::from_ref(self))
+  }
+  /// The number of nodes [`AtomNodes::iter`] yields, this one included.
+  pub fn atom_count(&self) -> usize {
+    match self {
+      AtomNodes::Container(atom) => 1 + atom.children().iter().map(AtomNodes::atom_count).sum::<usize>(),
+      AtomNodes::Atom(_) => 1,
+    }
+  }
+  /// The number of levels in this subtree, 1 for a leaf atom or an empty container.
+  pub fn depth(&self) -> usize {
+    match self {
+      AtomNodes::Container(atom) => 1 + atom.children().iter().map(AtomNodes::depth).max().unwrap_or_default(),
+      AtomNodes::Atom(_) => 1,
+    }
+  }
+  /// A stable hash of the shape of this subtree: atom types, sizes, header sizes and where each
+  /// child sits relative to its parent. Absolute offsets and payloads are left out, so identical
+  /// structures hash equal wherever they are located, within a file or across files.
+  pub fn structural_hash(&self) -> u64 {
+    fn hash_bytes(hash: u64, bytes: &[u8]) -> u64 {
+      bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+    }
+    fn hash_node(node: &AtomNodes, relative_location: u64, hash: u64) -> u64 {
+      let mut hash = hash_bytes(hash, node.atom_type().as_bytes());
+      hash = hash_bytes(hash, &node.atom_size().to_be_bytes());
+      hash = hash_bytes(hash, &node.header_size().to_be_bytes());
+      hash = hash_bytes(hash, &relative_location.to_be_bytes());
+      if let AtomNodes::Container(atom) = node {
+        hash = hash_bytes(hash, &(atom.children().len() as u64).to_be_bytes());
+        for child in atom.children() {
+          hash = hash_node(child, child.atom_location().wrapping_sub(node.atom_location()), hash);
+        }
+      }
+      hash
+    }
+    hash_node(self, 0, 0xcbf29ce484222325)
+  }
+}
+
+/// Iterates over a list of nodes and everything below them in document order, parents before
+/// their children. The number of nodes is counted up front, so the iterator knows its exact
+/// length.
+#[derive(Debug, Clone)]
+pub struct AtomIter<'a> {
+  stack: Vec<std::slice::Iter<'a, AtomNodes>>,
+  remaining: usize,
+}
+
+impl<'a> AtomIter<'a> {
+  pub fn new(nodes: &'a [AtomNodes]) -> AtomIter<'a> {
+    AtomIter { stack: vec![nodes.iter()], remaining: nodes.iter().map(AtomNodes::atom_count).sum() }
+  }
+}
+
+impl<'a> Iterator for AtomIter<'a> {
+  type Item = &'a AtomNodes;
+
+  fn next(&mut self) -> Option<&'a AtomNodes> {
+    loop {
+      match self.stack.last_mut()?.next() {
+        Some(node) => {
+          if let AtomNodes::Container(atom) = node {
+            self.stack.push(atom.children().iter());
+          }
+          self.remaining -= 1;
+          return Some(node);
+        }
+        None => {
+          self.stack.pop();
+        }
+      }
     }
   }
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl ExactSizeIterator for AtomIter<'_> {}
+
+#[test]
+fn should_iterate_in_the_same_order_as_walk() {
+  let mut file = std::fs::File::open("resources/tests/trak.mp4").unwrap();
+  let header = AtomHeader::new(&mut file).unwrap();
+  let trak = AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap();
+  let mut walked = Vec::new();
+  trak.walk(|node| walked.push(node.atom_location()));
+  let iterated: Vec<u64> = trak.iter().map(|node| node.atom_location()).collect();
+  assert_eq!(walked, iterated);
+  assert_eq!(trak.atom_count(), trak.iter().len());
 }
 
 impl AtomLike for AtomNodes {
@@ -200,48 +1014,303 @@ pub mod containers {
   use super::*;
 
   impl AtomNodes {
-    fn parse_children<T>(container_header: AtomHeader, file: &mut T) -> Result<Vec<AtomNodes>>
-    where T: Read + Seek {
+    fn parse_children(container_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<Vec<AtomNodes>> {
+      AtomNodes::parse_children_with(container_header, file, ctx, AtomNodes::new)
+    }
+    /// Parses the children of a container with `parse`, for containers whose children are told
+    /// apart by the container rather than by their types.
+    fn parse_children_with(container_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext,
+                           parse: fn(AtomHeader, &mut dyn ReadSeek, &ParseContext) -> Result<AtomNodes>) -> Result<Vec<AtomNodes>> {
+      let container_end = container_header.end_location()?;
+      let payload_start = AtomNodes::first_child_start(&container_header, file)?;
+      let _nesting = ctx.nest(&container_header)?;
       let mut children = Vec::new();
+      let mut slack = None;
+      // The file is kept at `position`, so the children are walked without asking where it is.
+      let mut position = payload_start;
       loop {
-        let child_header = AtomHeader::new(file)?;
+        if position >= container_end {
+          break;
+        }
+        // Fewer than 8 bytes left can not hold another child, so they are slack.
+        if container_end - position < 8 {
+          slack = Some(position);
+          break;
+        }
+        ctx.check_child_count(&container_header, children.len())?;
+        let child_header = match AtomHeader::new_within(file, Some(container_end)) {
+          // Bytes that do not start with a four-CC, such as zero padding, are not another child.
+          Err(ParseError::Misaligned { .. }) if !ctx.options().strict => {
+            slack = Some(position);
+            break;
+          }
+          header => header?,
+        };
+        if child_header.atom_size() < child_header.header_size() as u64 {
+          return Err(ParseError::InvalidChildSize { atom_type: child_header.four_cc(),
+                                                    offset: child_header.atom_location(),
+                                                    size: child_header.atom_size(), container_end: None });
+        }
+        let child_end = child_header.end_location()?;
+        // A child can't be parsed past the end of its container without reading the bytes of
+        // the container's siblings, so it is left out and the rest of the container is slack.
+        if child_end > container_end {
+          let err = ParseError::InvalidChildSize { atom_type: child_header.four_cc(), offset: position,
+                                                   size: child_header.atom_size(), container_end: Some(container_end) };
+          if ctx.options().strict {
+            return Err(err);
+          }
+          ctx.child_dropped(container_header.atom_type(), position, err);
+          slack = Some(position);
+          break;
+        }
         children.push(child_header);
-        file.seek(SeekFrom::Start(child_header.atom_location() + child_header.atom_size()))?;
-        if child_header.atom_location() + child_header.atom_size() >= container_header.atom_size() {
+        // An atom that is only a header already has the file at the next one.
+        if child_end != child_header.atom_location() + child_header.header_size() as u64 {
+          file.seek(SeekFrom::Start(child_end))?;
+        }
+        position = child_end;
+        if child_end >= container_end {
           break;
         }
       }
-      Ok(children.iter().map(|x| {
-        AtomNodes::new(*x, file)
-      }).filter(|x| { x.is_ok() } )
-        .map(|x| {x.unwrap()}).collect())
+      let mut layout = ctx.layout_check(container_header.atom_type(), payload_start, container_end);
+      let mut parallel = ctx.parse_tracks(&container_header, &children);
+      let mut nodes = Vec::new();
+      for (index, header) in children.into_iter().enumerate() {
+        let mark = ctx.warning_mark();
+        let node = match parallel.get_mut(index).and_then(Option::take) {
+          Some(parsed) => ctx.parsed_in_parallel(&container_header, parsed),
+          None => parse(header, file, ctx),
+        };
+        let node = match node {
+          Ok(node) => Some(node),
+          Err(err @ ParseError::LimitExceeded { .. }) => return Err(err),
+          Err(err) if ctx.options().strict => return Err(err),
+          Err(err) => {
+            ctx.child_dropped(container_header.atom_type(), header.atom_location(), err);
+            None
+          }
+        };
+        ctx.child_parsed(&mut layout, mark, node.as_ref());
+        nodes.extend(node);
+      }
+      if let Some(location) = slack {
+        let slack = AtomNodes::Atom(Atoms::Slack { location, size: container_end - location });
+        ctx.child_parsed(&mut layout, ctx.warning_mark(), Some(&slack));
+        nodes.push(slack);
+      }
+      ctx.layout_checked(layout);
+      Ok(nodes)
     }
   }
-  #[derive(Debug, Clone)]
-  pub enum ContainerAtoms {
-    Root(RootAtom),
-    Moov(MoovAtom),
-    Trak(TrakAtom),
+  impl AtomNodes {
+    /// Seeks to the first child of a container, which normally starts at the current position,
+    /// right after the header. A header size recorded wrongly, such as 8 for a header with an
+    /// extended size, puts the children 8 bytes off, so when the current position does not hold
+    /// an atom header that fits in the container the other header size is tried.
+    fn first_child_start(container_header: &AtomHeader, file: &mut dyn ReadSeek) -> Result<u64> {
+      let start = file.stream_position()?;
+      let container_end = container_header.end_location()?;
+      let mut fits_at = |location: u64| -> Result<bool> {
+        file.seek(SeekFrom::Start(location))?;
+        Ok(matches!(AtomHeader::new_within(file, Some(container_end)).map(|header| header.end_location()),
+                    Ok(Ok(end)) if end <= container_end))
+      };
+      let alternate = match container_header.header_size() {
+        8 => start.checked_add(8),
+        16 => start.checked_sub(8),
+        _ => None,
+      };
+      let location = match alternate {
+        Some(alternate) if !fits_at(start)? && alternate < container_end && fits_at(alternate)? => alternate,
+        _ => start,
+      };
+      file.seek(SeekFrom::Start(location))?;
+      Ok(location)
+    }
   }
 
-  impl ContainerAtoms {
-    pub fn new<T>(header: AtomHeader, file: &mut T) -> Result<ContainerAtoms>
-      where T: Read + Seek {
-      match header.atom_type() {
-        "root" => Ok(ContainerAtoms::Root(RootAtom::new(file, header.atom_size())?)),
-        "moov" => Ok(ContainerAtoms::Moov(MoovAtom::new(header.into(), file)?)),
-        "trak" => Ok(ContainerAtoms::Trak(TrakAtom::new(header.into(), file)?)),
-        _ => Err(ParseError::NotAContainer)
+  #[test]
+  fn should_find_the_children_of_a_container_with_an_extended_size_header() {
+    let mut bytes = vec![0, 0, 0, 1];
+    bytes.extend_from_slice(b"moov");
+    bytes.extend_from_slice(&40u64.to_be_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 24]);
+    bytes.extend_from_slice(b"free");
+    bytes.extend_from_slice(&[0; 16]);
+    let children = |header: AtomHeader, file: &mut std::io::Cursor<Vec<u8>>| {
+      match ContainerAtoms::new(header, file, &ParseContext::default()).unwrap() {
+        ContainerAtoms::Moov(moov) => moov.children().iter().map(|child| (child.atom_type().to_string(), child.atom_location()))
+          .collect::<Vec<(String, u64)>>(),
+        other => panic!("expected moov, got {:?}", other),
       }
-    }
+    };
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert_eq!(16, header.header_size());
+    assert_eq!(vec![(String::from("free"), 16)], children(header, &mut file));
+
+    // The same container with its header size recorded as 8 bytes.
+    let header = AtomHeader::synthesized(b"moov", 40, 0);
+    assert_eq!(8, header.header_size());
+    assert_eq!(vec![(String::from("free"), 16)], children(header, &mut file));
   }
 
-  impl AtomLike for ContainerAtoms {
-    fn atom_size(&self) -> u64 {
-      match self {
+  #[test]
+  fn should_locate_the_children_of_an_extended_size_container_after_its_header() {
+    let mut bytes = vec![0, 0, 0, 1];
+    bytes.extend_from_slice(b"udta");
+    bytes.extend_from_slice(&44u64.to_be_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 12]);
+    bytes.extend_from_slice(b"free");
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&[0, 0, 0, 16]);
+    bytes.extend_from_slice(b"skip");
+    bytes.extend_from_slice(&[0; 8]);
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert!(header.uses_extended_size());
+    let udta = AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap();
+    let children = match &udta {
+      AtomNodes::Container(udta) => udta.children().iter().map(|child| (child.atom_type(), child.atom_location(), child.atom_size()))
+        .collect::<Vec<(&str, u64, u64)>>(),
+      other => panic!("expected udta, got {:?}", other),
+    };
+    assert_eq!(vec![("free", 16, 12), ("skip", 28, 16)], children);
+  }
+
+  #[test]
+  fn should_keep_children_with_bad_sizes_inside_their_container() {
+    // An edts of `size` holding `payload`, followed by a sibling that must never be read as a child.
+    let parse = |size: u32, payload: &[u8]| {
+      let mut bytes = size.to_be_bytes().to_vec();
+      bytes.extend_from_slice(b"edts");
+      bytes.extend_from_slice(payload);
+      bytes.extend_from_slice(&[0, 0, 0, 8]);
+      bytes.extend_from_slice(b"skip");
+      let mut file = std::io::Cursor::new(bytes);
+      let header = AtomHeader::new(&mut file).unwrap();
+      ContainerAtoms::new(header, &mut file, &ParseContext::default()).map(|edts| {
+        edts.children().iter().map(|child| (child.atom_type().to_string(), child.atom_location(), child.atom_size()))
+          .collect::<Vec<(String, u64, u64)>>()
+      })
+    };
+    let free = |size: u32, len: usize| {
+      let mut bytes = size.to_be_bytes().to_vec();
+      bytes.extend_from_slice(b"free");
+      bytes.resize(len, 0);
+      bytes
+    };
+
+    let fitting = [free(16, 16), free(8, 8)].concat();
+    assert_eq!(vec![(String::from("free"), 8, 16), (String::from("free"), 24, 8)], parse(32, &fitting).unwrap());
+
+    assert_eq!(vec![(String::from("free"), 8, 24)], parse(32, &free(0, 24)).unwrap());
+
+    // A child that runs past the end of the container is left out, and its bytes are slack.
+    let overrun = parse(24, &free(100, 16)).unwrap();
+    assert_eq!(vec![(String::from("slack"), 8, 16)], overrun);
+    let mut file = std::io::Cursor::new([24u32.to_be_bytes().to_vec(), b"edts".to_vec(), free(100, 16)].concat());
+    let header = AtomHeader::new(&mut file).unwrap();
+    let strict = ParseContext::from(crate::ParseOptions { strict: true, ..Default::default() });
+    match ContainerAtoms::new(header, &mut file, &strict) {
+      Err(err @ ParseError::InvalidChildSize { offset: 8, size: 100, container_end: Some(24), .. }) =>
+        assert_eq!("free at 8 (0x8): size 100 runs past the end of its container at 24", err.to_string()),
+      other => panic!("expected InvalidChildSize, got {:?}", other.map(|edts| edts.to_string())),
+    }
+
+    // The 5 bytes after the child are slack, as they can not hold a header.
+    assert_eq!(vec![(String::from("free"), 8, 8), (String::from("slack"), 16, 5)],
+               parse(21, &[free(8, 8), vec![0; 5]].concat()).unwrap());
+
+    match parse(24, &free(4, 16)) {
+      Err(ParseError::InvalidChildSize { atom_type, offset: 8, size: 4, container_end: None }) => assert_eq!(atom_type, "free"),
+      other => panic!("expected InvalidChildSize, got {:?}", other),
+    }
+  }
+
+  #[derive(Debug, Clone)]
+  pub enum ContainerAtoms {
+    Root(RootAtom),
+    Moov(MoovAtom),
+    Trak(TrakAtom),
+    Mdia(MdiaAtom),
+    Minf(MinfAtom),
+    Stbl(StblAtom),
+    Edts(EdtsAtom),
+    Udta(UdtaAtom),
+    Meta(MetaAtom),
+    Tapt(TaptAtom),
+    Iprp(IprpAtom),
+    Dinf(DinfAtom),
+    Moof(MoofAtom),
+    Traf(TrafAtom),
+    Gmhd(GmhdAtom),
+    Tref(TrefAtom),
+    Generic(GenericAtom),
+  }
+
+  impl ContainerAtoms {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<ContainerAtoms> {
+      ContainerAtoms::try_new(header, file, ctx)?
+        .ok_or_else(|| ParseError::parse_failed(&header, "not a container type"))
+    }
+    /// Parses the atom of `header` when it is of a container type, and returns `None` for every
+    /// other type so it can be parsed as a leaf.
+    pub(crate) fn try_new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext)
+      -> Result<Option<ContainerAtoms>> {
+      let container = match header.atom_type() {
+        "moov" => MoovAtom::new(header, file, ctx).map(ContainerAtoms::Moov),
+        "trak" => TrakAtom::new(header, file, ctx).map(ContainerAtoms::Trak),
+        "mdia" => MdiaAtom::new(header, file, ctx).map(ContainerAtoms::Mdia),
+        "minf" => MinfAtom::new(header, file, ctx).map(ContainerAtoms::Minf),
+        "stbl" => StblAtom::new(header, file, ctx).map(ContainerAtoms::Stbl),
+        "edts" => EdtsAtom::new(header, file, ctx).map(ContainerAtoms::Edts),
+        "udta" => UdtaAtom::new(header, file, ctx).map(ContainerAtoms::Udta),
+        "meta" => MetaAtom::new(header, file, ctx).map(ContainerAtoms::Meta),
+        "tapt" => TaptAtom::new(header, file, ctx).map(ContainerAtoms::Tapt),
+        "iprp" => IprpAtom::new(header, file, ctx).map(ContainerAtoms::Iprp),
+        "dinf" => DinfAtom::new(header, file, ctx).map(ContainerAtoms::Dinf),
+        "moof" => MoofAtom::new(header, file, ctx).map(ContainerAtoms::Moof),
+        "traf" => TrafAtom::new(header, file, ctx).map(ContainerAtoms::Traf),
+        "gmhd" => GmhdAtom::new(header, file, ctx).map(ContainerAtoms::Gmhd),
+        "tref" => TrefAtom::new(header, file, ctx).map(ContainerAtoms::Tref),
+        _ if ctx.is_container(header.four_cc()) => GenericAtom::new(header, file, ctx).map(ContainerAtoms::Generic),
+        _ => return Ok(None),
+      };
+      container.map(Some)
+    }
+    /// The bytes between the header and the first child, such as the version and flags of a full
+    /// atom container.
+    pub fn prefix_size(&self) -> u64 {
+      match self {
+        ContainerAtoms::Meta(atom) if atom.full_atom().is_some() => 4,
+        _ => 0,
+      }
+    }
+  }
+
+  impl AtomLike for ContainerAtoms {
+    fn atom_size(&self) -> u64 {
+      match self {
         ContainerAtoms::Root(atom) => atom.atom_size(),
         ContainerAtoms::Moov(atom) => atom.atom_size(),
         ContainerAtoms::Trak(atom) => atom.atom_size(),
+        ContainerAtoms::Mdia(atom) => atom.atom_size(),
+        ContainerAtoms::Minf(atom) => atom.atom_size(),
+        ContainerAtoms::Stbl(atom) => atom.atom_size(),
+        ContainerAtoms::Edts(atom) => atom.atom_size(),
+        ContainerAtoms::Udta(atom) => atom.atom_size(),
+        ContainerAtoms::Meta(atom) => atom.atom_size(),
+        ContainerAtoms::Tapt(atom) => atom.atom_size(),
+        ContainerAtoms::Iprp(atom) => atom.atom_size(),
+        ContainerAtoms::Dinf(atom) => atom.atom_size(),
+        ContainerAtoms::Moof(atom) => atom.atom_size(),
+        ContainerAtoms::Traf(atom) => atom.atom_size(),
+        ContainerAtoms::Gmhd(atom) => atom.atom_size(),
+        ContainerAtoms::Tref(atom) => atom.atom_size(),
+        ContainerAtoms::Generic(atom) => atom.atom_size(),
       }
     }
 
@@ -250,6 +1319,20 @@ pub mod containers {
         ContainerAtoms::Root(atom) => atom.atom_type(),
         ContainerAtoms::Moov(atom) => atom.atom_type(),
         ContainerAtoms::Trak(atom) => atom.atom_type(),
+        ContainerAtoms::Mdia(atom) => atom.atom_type(),
+        ContainerAtoms::Minf(atom) => atom.atom_type(),
+        ContainerAtoms::Stbl(atom) => atom.atom_type(),
+        ContainerAtoms::Edts(atom) => atom.atom_type(),
+        ContainerAtoms::Udta(atom) => atom.atom_type(),
+        ContainerAtoms::Meta(atom) => atom.atom_type(),
+        ContainerAtoms::Tapt(atom) => atom.atom_type(),
+        ContainerAtoms::Iprp(atom) => atom.atom_type(),
+        ContainerAtoms::Dinf(atom) => atom.atom_type(),
+        ContainerAtoms::Moof(atom) => atom.atom_type(),
+        ContainerAtoms::Traf(atom) => atom.atom_type(),
+        ContainerAtoms::Gmhd(atom) => atom.atom_type(),
+        ContainerAtoms::Tref(atom) => atom.atom_type(),
+        ContainerAtoms::Generic(atom) => atom.atom_type(),
       }
     }
 
@@ -258,6 +1341,20 @@ pub mod containers {
         ContainerAtoms::Root(atom) => atom.atom_location(),
         ContainerAtoms::Moov(atom) => atom.atom_location(),
         ContainerAtoms::Trak(atom) => atom.atom_location(),
+        ContainerAtoms::Mdia(atom) => atom.atom_location(),
+        ContainerAtoms::Minf(atom) => atom.atom_location(),
+        ContainerAtoms::Stbl(atom) => atom.atom_location(),
+        ContainerAtoms::Edts(atom) => atom.atom_location(),
+        ContainerAtoms::Udta(atom) => atom.atom_location(),
+        ContainerAtoms::Meta(atom) => atom.atom_location(),
+        ContainerAtoms::Tapt(atom) => atom.atom_location(),
+        ContainerAtoms::Iprp(atom) => atom.atom_location(),
+        ContainerAtoms::Dinf(atom) => atom.atom_location(),
+        ContainerAtoms::Moof(atom) => atom.atom_location(),
+        ContainerAtoms::Traf(atom) => atom.atom_location(),
+        ContainerAtoms::Gmhd(atom) => atom.atom_location(),
+        ContainerAtoms::Tref(atom) => atom.atom_location(),
+        ContainerAtoms::Generic(atom) => atom.atom_location(),
       }
     }
 
@@ -266,6 +1363,20 @@ pub mod containers {
         ContainerAtoms::Root(atom) => atom.header_size(),
         ContainerAtoms::Moov(atom) => atom.header_size(),
         ContainerAtoms::Trak(atom) => atom.header_size(),
+        ContainerAtoms::Mdia(atom) => atom.header_size(),
+        ContainerAtoms::Minf(atom) => atom.header_size(),
+        ContainerAtoms::Stbl(atom) => atom.header_size(),
+        ContainerAtoms::Edts(atom) => atom.header_size(),
+        ContainerAtoms::Udta(atom) => atom.header_size(),
+        ContainerAtoms::Meta(atom) => atom.header_size(),
+        ContainerAtoms::Tapt(atom) => atom.header_size(),
+        ContainerAtoms::Iprp(atom) => atom.header_size(),
+        ContainerAtoms::Dinf(atom) => atom.header_size(),
+        ContainerAtoms::Moof(atom) => atom.header_size(),
+        ContainerAtoms::Traf(atom) => atom.header_size(),
+        ContainerAtoms::Gmhd(atom) => atom.header_size(),
+        ContainerAtoms::Tref(atom) => atom.header_size(),
+        ContainerAtoms::Generic(atom) => atom.header_size(),
       }
     }
   }
@@ -276,6 +1387,41 @@ pub mod containers {
         ContainerAtoms::Root(atom) => atom.children(),
         ContainerAtoms::Moov(atom) => atom.children(),
         ContainerAtoms::Trak(atom) => atom.children(),
+        ContainerAtoms::Mdia(atom) => atom.children(),
+        ContainerAtoms::Minf(atom) => atom.children(),
+        ContainerAtoms::Stbl(atom) => atom.children(),
+        ContainerAtoms::Edts(atom) => atom.children(),
+        ContainerAtoms::Udta(atom) => atom.children(),
+        ContainerAtoms::Meta(atom) => atom.children(),
+        ContainerAtoms::Tapt(atom) => atom.children(),
+        ContainerAtoms::Iprp(atom) => atom.children(),
+        ContainerAtoms::Dinf(atom) => atom.children(),
+        ContainerAtoms::Moof(atom) => atom.children(),
+        ContainerAtoms::Traf(atom) => atom.children(),
+        ContainerAtoms::Gmhd(atom) => atom.children(),
+        ContainerAtoms::Tref(atom) => atom.children(),
+        ContainerAtoms::Generic(atom) => atom.children(),
+      }
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      match self {
+        ContainerAtoms::Root(atom) => atom.children_mut(),
+        ContainerAtoms::Moov(atom) => atom.children_mut(),
+        ContainerAtoms::Trak(atom) => atom.children_mut(),
+        ContainerAtoms::Mdia(atom) => atom.children_mut(),
+        ContainerAtoms::Minf(atom) => atom.children_mut(),
+        ContainerAtoms::Stbl(atom) => atom.children_mut(),
+        ContainerAtoms::Edts(atom) => atom.children_mut(),
+        ContainerAtoms::Udta(atom) => atom.children_mut(),
+        ContainerAtoms::Meta(atom) => atom.children_mut(),
+        ContainerAtoms::Tapt(atom) => atom.children_mut(),
+        ContainerAtoms::Iprp(atom) => atom.children_mut(),
+        ContainerAtoms::Dinf(atom) => atom.children_mut(),
+        ContainerAtoms::Moof(atom) => atom.children_mut(),
+        ContainerAtoms::Traf(atom) => atom.children_mut(),
+        ContainerAtoms::Gmhd(atom) => atom.children_mut(),
+        ContainerAtoms::Tref(atom) => atom.children_mut(),
+        ContainerAtoms::Generic(atom) => atom.children_mut(),
       }
     }
     fn set_children(&mut self, children: Vec<AtomNodes> ){
@@ -283,35 +1429,53 @@ pub mod containers {
         ContainerAtoms::Root(atom) => atom.set_children(children),
         ContainerAtoms::Moov(atom) => atom.set_children(children),
         ContainerAtoms::Trak(atom) => atom.set_children(children),
+        ContainerAtoms::Mdia(atom) => atom.set_children(children),
+        ContainerAtoms::Minf(atom) => atom.set_children(children),
+        ContainerAtoms::Stbl(atom) => atom.set_children(children),
+        ContainerAtoms::Edts(atom) => atom.set_children(children),
+        ContainerAtoms::Udta(atom) => atom.set_children(children),
+        ContainerAtoms::Meta(atom) => atom.set_children(children),
+        ContainerAtoms::Tapt(atom) => atom.set_children(children),
+        ContainerAtoms::Iprp(atom) => atom.set_children(children),
+        ContainerAtoms::Dinf(atom) => atom.set_children(children),
+        ContainerAtoms::Moof(atom) => atom.set_children(children),
+        ContainerAtoms::Traf(atom) => atom.set_children(children),
+        ContainerAtoms::Gmhd(atom) => atom.set_children(children),
+        ContainerAtoms::Tref(atom) => atom.set_children(children),
+        ContainerAtoms::Generic(atom) => atom.set_children(children),
       }
     }
   }
   impl std::fmt::Display for ContainerAtoms {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
       match self {
-        ContainerAtoms::Root(atom) => writeln!(f, "/" ),
-        ContainerAtoms::Moov(atom) => writeln!(f, "{}", atom),
-        ContainerAtoms::Trak(atom) => writeln!(f, "{}", atom),
+        ContainerAtoms::Root(_) => writeln!(f, "/" )?,
+        ContainerAtoms::Moov(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Trak(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Mdia(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Minf(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Stbl(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Edts(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Udta(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Meta(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Tapt(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Iprp(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Dinf(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Moof(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Traf(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Gmhd(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Tref(atom) => writeln!(f, "{}", atom)?,
+        ContainerAtoms::Generic(atom) => writeln!(f, "{}", atom)?,
       };
-      let mut num_children = self.children().len();
-      for node in self.children() {
-        if num_children == 1 {
-          write!(f, "┗ ")?;
-        } else {
-          write!(f, "├ ")?;
-        }
-        writeln!(f, "{}", node)?;
-        num_children -= 1;
-      }
-      Ok(())
+      crate::parse_state::print_siblings(f, self.children(), "", None, false)
     }
   }
 
   #[test]
   fn test_container() {
-    let mut file = fs::File::open("resources/tests/moov.mp4").unwrap();
+    let mut file = std::fs::File::open("resources/tests/moov.mp4").unwrap();
     let header = AtomHeader::new(&mut file).unwrap();
-    let container = ContainerAtoms::new(header, &mut file).unwrap();
+    let container = ContainerAtoms::new(header, &mut file, &ParseContext::default()).unwrap();
     match &container {
       ContainerAtoms::Moov(atom) => {
         let children = atom.children();
@@ -328,24 +1492,53 @@ pub mod containers {
     children: Vec<AtomNodes>,
   }
   impl RootAtom {
-    pub fn new<T>(file: &mut T, file_size: u64) -> Result<RootAtom>
-    where T: Read + Seek {
+    pub fn new(file: &mut dyn ReadSeek, file_size: u64, ctx: &ParseContext) -> Result<RootAtom> {
       let mut res =  RootAtom{file_size, ..Default::default()};
-      let mut file_size = file_size;
-      file.seek(SeekFrom::Start(0));
-      loop {
-        if 0 == file_size {
-          break;
+      let mut location = 0;
+      file.rewind()?;
+      let mut layout = ctx.layout_check(res.atom_type(), 0, file_size);
+      let _nesting = ctx.nest(&res)?;
+      while location < file_size {
+        ctx.check_child_count(&res, res.children.len())?;
+        let header = AtomHeader::new_within(file, Some(file_size)).and_then(|header| match header {
+          _ if header.atom_size() < header.header_size() as u64 =>
+            Err(ParseError::InvalidChildSize { atom_type: header.four_cc(), offset: location, size: header.atom_size(),
+                                               container_end: None }),
+          header => Ok(header),
+        });
+        let header = match header {
+          // Like the end of a container, bytes after the last atom that are too few for a header,
+          // don't start with one or have a size too small for theirs are kept as slack.
+          Err(err @ (ParseError::TruncatedHeader { .. } | ParseError::Misaligned { .. } | ParseError::InvalidChildSize { .. }))
+            if !ctx.options().strict => {
+            ctx.child_dropped(res.atom_type(), location, err);
+            let slack = AtomNodes::Atom(Atoms::Slack { location, size: file_size - location });
+            ctx.child_parsed(&mut layout, ctx.warning_mark(), Some(&slack));
+            res.children.push(slack);
+            break;
+          }
+          header => header?,
+        };
+        let mark = ctx.warning_mark();
+        let atom = AtomNodes::new(header, file, ctx).map_err(|err| match header.end_location() {
+          Ok(end) if end <= file_size => err,
+          _ => ParseError::AtomPastEndOfFile { atom_type: header.four_cc(), offset: header.atom_location(),
+                                               size: header.atom_size(), file_size },
+        })?;
+        ctx.child_parsed(&mut layout, mark, Some(&atom));
+        if let AtomNodes::Atom(Atoms::Ftyp(ftyp)) = &atom {
+          ctx.check_brand(ftyp)?;
         }
-        let header = AtomHeader::new(file)?;
-        let atom = AtomNodes::new(header, file)?;
-        file_size -= atom.atom_size();
-        assert_eq!(atom.atom_size(), header.atom_size());
-        file.seek(SeekFrom::Start((atom.atom_location() + atom.atom_size() ) as u64))?;
+        location = header.end_location()?;
+        file.seek(SeekFrom::Start(location))?;
         res.children.push(atom);
       }
+      ctx.layout_checked(layout);
       Ok(res)
     }
+    pub(crate) fn from_children(file_size: u64, children: Vec<AtomNodes>) -> RootAtom {
+      RootAtom { file_size, children }
+    }
   }
 
   impl AtomLike for RootAtom {
@@ -369,8 +1562,11 @@ pub mod containers {
     fn children(&self) -> &Vec<AtomNodes> {
       &self.children
     }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
 
-    fn set_children(&mut self, children: Vec<AtomNodes>) -> () {
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
       self.children = children;
     }
   }
@@ -385,16 +1581,114 @@ pub mod containers {
   pub struct MoovAtom {
     atom_header: AtomHeader,
     children: Vec<AtomNodes>,
+    decompressed: Option<AtomHeader>,
   }
 
   impl MoovAtom {
-    pub fn new<T>(atom_header: AtomHeader, file: &mut T) -> Result<MoovAtom>
-      where T: Read + Seek {
-      let mut location = atom_header.atom_location() + atom_header.header_size() as u64;
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<MoovAtom> {
+      let location = atom_header.atom_location() + atom_header.header_size() as u64;
       file.seek(SeekFrom::Start(location))?;
-      let children = AtomNodes::parse_children(atom_header, file)?;
-      Ok(MoovAtom {atom_header, children})
+      let mut children = AtomNodes::parse_children(atom_header, file, ctx)?;
+      let mut decompressed = None;
+      if let Some(index) = children.iter().position(|child| child.atom_type() == "cmov") {
+        let cmov = AtomHeader::new_from(&children[index]);
+        match decompress_movie(cmov, file, ctx) {
+          Ok(movie) => {
+            children.splice(index..=index, movie.children);
+            decompressed = Some(movie.atom_header);
+          }
+          Err(err) if !ctx.options().strict =>
+            children[index] = AtomNodes::Atom(Atoms::UnknownAtom(UnknownAtom::failed(cmov, err.to_string()))),
+          Err(err) => return Err(err),
+        }
+      }
+      Ok(MoovAtom {atom_header, children, decompressed})
+    }
+    /// The header of the movie that was decompressed from a `cmov` child, which the children come
+    /// from. Their locations are offsets into the decompressed movie.
+    pub fn decompressed(&self) -> Option<&AtomHeader> { self.decompressed.as_ref() }
+    /// The tracks of the movie, in file order.
+    pub fn tracks(&self) -> Vec<&TrakAtom> {
+      self.children.iter().filter_map(|child| match child {
+        AtomNodes::Container(ContainerAtoms::Trak(trak)) => Some(trak),
+        _ => None,
+      }).collect()
+    }
+  }
+
+  #[test]
+  fn should_list_the_tracks_of_a_movie() {
+    let mut file = std::fs::File::open("resources/tests/moov.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let moov = match ContainerAtoms::new(header, &mut file, &ParseContext::default()).unwrap() {
+      ContainerAtoms::Moov(moov) => moov,
+      other => panic!("expected moov, got {:?}", other),
+    };
+    let tracks: Vec<(u64, Vec<&str>)> = moov.tracks().iter()
+      .map(|trak| (trak.atom_location(), trak.children().iter().map(|child| child.atom_type()).collect())).collect();
+    assert_eq!(vec![(116, vec!["tkhd", "edts", "mdia"]), (432097, vec!["tkhd", "edts", "mdia"])], tracks);
+    let handlers: Vec<&str> = moov.tracks().iter().filter_map(|trak| {
+      let mdia = trak.children().iter().find(|child| child.atom_type() == "mdia")?;
+      crate::parse_state::find_atom(mdia, |atom| match atom { Atoms::Hdlr(hdlr) => Some(hdlr), _ => None })
+    }).map(|hdlr| hdlr.component_subtype()).collect();
+    assert_eq!(vec!["vide", "soun"], handlers);
+  }
+
+  /// The children of the movie compressed in `cmov`, a compressed movie atom holding a `dcom` that
+  /// names the compression and a `cmvd` with the size of the movie and the compressed movie. The
+  /// children are parsed from the decompressed movie, so their locations are offsets into it
+  /// rather than into the file. [`MoovAtom::new`] keeps a `cmov` this fails for as an unknown atom
+  /// that failed to parse, except in strict mode.
+  fn decompress_movie(cmov: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<MoovAtom> {
+    let max_size = ctx.max_atom_read();
+    let payload = cmov.read_payload_with_limit(file, max_size)?;
+    let mut bytes = payload.as_slice();
+    let (mut compression, mut data) = (None, None);
+    while bytes.len() >= 8 {
+      let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+      if size < 8 || size > bytes.len() {
+        return Err(ParseError::parse_failed(&cmov, format!("a child of size {} does not fit", size)));
+      }
+      match &bytes[4..8] {
+        b"dcom" if size >= 12 => compression = Some(FourCC::new([bytes[8], bytes[9], bytes[10], bytes[11]])),
+        b"cmvd" => data = Some(&bytes[8..size]),
+        _ => {}
+      }
+      bytes = &bytes[size..];
+    }
+    let compression = compression.ok_or(ParseError::MissingAtom { atom_type: FourCC::from("dcom"),
+                                                                  needed_for: "a compressed movie" })?;
+    let mut data = data.ok_or(ParseError::MissingAtom { atom_type: FourCC::from("cmvd"),
+                                                        needed_for: "a compressed movie" })?;
+    let size = read_u32(&mut data)? as u64;
+    if size > max_size {
+      return Err(ParseError::AtomTooLarge { atom_type: cmov.four_cc(), offset: cmov.atom_location(), size, limit: max_size });
+    }
+    let movie = inflate_movie(&cmov, compression, data, size)?;
+    if movie.len() as u64 != size {
+      return Err(ParseError::parse_failed(&cmov, format!("the movie decompressed to {} bytes instead of {}",
+                                                         movie.len(), size)));
+    }
+    let mut movie = std::io::Cursor::new(movie);
+    let header = AtomHeader::new_within(&mut movie, Some(size))?;
+    if header.atom_type() != "moov" {
+      return Err(ParseError::parse_failed(&cmov, format!("the compressed movie is a {} rather than a moov",
+                                                         header.atom_type())));
     }
+    MoovAtom::new(header, &mut movie, &ctx.in_memory())
+  }
+
+  #[cfg(feature = "flate2")]
+  fn inflate_movie(cmov: &AtomHeader, compression: FourCC, data: &[u8], size: u64) -> Result<Vec<u8>> {
+    if compression != "zlib" {
+      return Err(ParseError::UnsupportedCompression { compression, offset: cmov.atom_location() });
+    }
+    crate::gzip::decompress_zlib(data, size)
+  }
+
+  #[cfg(not(feature = "flate2"))]
+  fn inflate_movie(cmov: &AtomHeader, compression: FourCC, _data: &[u8], _size: u64) -> Result<Vec<u8>> {
+    Err(ParseError::UnsupportedCompression { compression, offset: cmov.atom_location() })
   }
 
   impl AtomLike for MoovAtom {
@@ -419,8 +1713,11 @@ pub mod containers {
     fn children(&self) -> &Vec<AtomNodes> {
       self.children.as_ref()
     }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
 
-    fn set_children(&mut self, children: Vec<AtomNodes>) -> () {
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
       self.children = children;
     }
   }
@@ -433,15 +1730,51 @@ pub mod containers {
 
   #[test]
   fn can_parse_a_moov_atom() {
-    let mut file = fs::File::open("resources/tests/moov.mp4").unwrap();
+    let mut file = std::fs::File::open("resources/tests/moov.mp4").unwrap();
     let header = AtomHeader::new(&mut file).unwrap();
-    let atom = MoovAtom::new(header, &mut file).unwrap();
+    let atom = MoovAtom::new(header, &mut file, &ParseContext::default()).unwrap();
     for child in &atom.children {
       println!("{}", child);
     }
     assert!(atom.children.len() > 1);
   }
 
+  #[test]
+  fn an_extended_child_size_that_overflows_is_an_error() {
+    let mut bytes = vec![0, 0, 0, 24];
+    bytes.extend_from_slice(b"moov");
+    bytes.extend_from_slice(&[0, 0, 0, 1]);
+    bytes.extend_from_slice(b"free");
+    bytes.extend_from_slice(&u64::MAX.to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    match MoovAtom::new(header, &mut file, &ParseContext::default()) {
+      Err(ParseError::AtomParseFailed { atom_type, offset: 8, .. }) => assert_eq!(atom_type, "free"),
+      ref other => panic!("expected AtomParseFailed, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn a_container_with_too_many_children_is_an_error() {
+    let mut bytes = vec![0, 0, 0, 88];
+    bytes.extend_from_slice(b"moov");
+    for _ in 0..10 {
+      bytes.extend_from_slice(&[0, 0, 0, 8]);
+      bytes.extend_from_slice(b"free");
+    }
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    use crate::parse_state::Limit;
+    let ctx = ParseContext::default().with_max_children(4);
+    match MoovAtom::new(header, &mut file, &ctx) {
+      Err(ParseError::LimitExceeded { limit: Limit::ChildrenPerContainer, max: 4, atom_type, offset: 0 }) =>
+        assert_eq!(atom_type, "moov"),
+      ref other => panic!("expected LimitExceeded, got {:?}", other),
+    }
+    let moov = MoovAtom::new(header, &mut file, &ParseContext::default().with_max_children(10)).unwrap();
+    assert_eq!(10, moov.children().len());
+  }
+
   #[derive(Debug, Default, Clone)]
   pub struct TrakAtom {
     header: AtomHeader,
@@ -449,20 +1782,30 @@ pub mod containers {
   }
 
   impl TrakAtom {
-    pub fn new<T>(header: AtomHeader, file: &mut T) -> Result<TrakAtom>
-    where T: Read + Seek {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<TrakAtom> {
       let mut result = TrakAtom{header, ..Default::default()};
       file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
-      result.set_children(AtomNodes::parse_children(header, file)?);
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
       Ok(result)
     }
+    /// The tracks this track refers to, by the type of the reference, from its `tref`. Empty
+    /// when it has none.
+    pub fn references(&self) -> Vec<(FourCC, Vec<u32>)> {
+      self.children.iter().find_map(|node| match node {
+        AtomNodes::Container(ContainerAtoms::Tref(tref)) => Some(tref.references()),
+        _ => None,
+      }).unwrap_or_default()
+    }
   }
 
   impl Container for TrakAtom {
     fn children(&self) -> &Vec<AtomNodes> {
       &self.children
     }
-    fn set_children(&mut self, children: Vec<AtomNodes>) -> () {
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
       self.children = children;
     }
   }
@@ -478,487 +1821,5047 @@ pub mod containers {
       write!(f, "Trak: {}", self.header)
     }
   }
-}
 
-pub mod leaves {
-  use super::*;
-
-  #[derive(Debug, Clone)]
-  pub enum Atoms {
-    Ftyp(FtypAtom),
-    Free(FreeAtom),
-    Wide(WideAtom),
-    Mdat(MdatAtom),
-    UnknownAtom(AtomHeader),
+  /// The tref atom is the [track reference atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25614).
+  /// It links its track to others, such as the text track holding its chapters or the timecode
+  /// track it is timed by. Each child is a [`TrackReferenceTypeAtom`] whatever its type.
+  #[derive(Debug, Default, Clone)]
+  pub struct TrefAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
   }
 
-  impl Atoms {
-    pub fn new<T>(atom_header: AtomHeader, file: &mut T) -> Result<Atoms>
-      where T: Read + Seek {
-      match atom_header.atom_type() {
-        "ftyp" => Ok(Atoms::Ftyp(FtypAtom::new(atom_header, file)?)),
-        "free" => Ok(Atoms::Free(FreeAtom::new(atom_header)?)),
-        "wide" => Ok(Atoms::Wide(WideAtom::new(atom_header)?)),
-        "mdat" => Ok(Atoms::Mdat(MdatAtom::new(atom_header)?)),
-        _ => Ok(Atoms::UnknownAtom(atom_header))
+  impl TrefAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<TrefAtom> {
+      let mut result = TrefAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children_with(header, file, ctx, TrefAtom::parse_reference)?);
+      Ok(result)
+    }
+    fn parse_reference(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<AtomNodes> {
+      match TrackReferenceTypeAtom::new(header.with_read_limit(ctx.max_atom_read()), file) {
+        Ok(reference) => Ok(AtomNodes::Atom(Atoms::TrackReference(reference))),
+        Err(err) if !ctx.options().strict => Ok(AtomNodes::Atom(Atoms::UnknownAtom(UnknownAtom::failed(header, err.to_string())))),
+        Err(err) => Err(err),
       }
     }
+    /// The type of each reference and the tracks it refers to, in the order they are in.
+    pub fn references(&self) -> Vec<(FourCC, Vec<u32>)> {
+      self.children.iter().filter_map(|node| match node {
+        AtomNodes::Atom(Atoms::TrackReference(reference)) => Some((reference.reference_type(), reference.track_ids().to_vec())),
+        _ => None,
+      }).collect()
+    }
   }
 
-  impl AtomLike for Atoms {
-    fn atom_size(&self) -> u64 {
-      match self {
-        Atoms::Ftyp(atom) => atom.atom_size(),
-        Atoms::Free(atom) => atom.atom_size(),
-        Atoms::Wide(atom) => atom.atom_size(),
-        Atoms::Mdat(atom) => atom.atom_size(),
-        Atoms::UnknownAtom(atom) => atom.atom_size(),
-      }
+  impl Container for TrefAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
     }
-
-    fn atom_type(&self) -> &str {
-      match self {
-        Atoms::Ftyp(atom) => atom.atom_type(),
-        Atoms::Free(atom) => atom.atom_type(),
-        Atoms::Wide(atom) => atom.atom_type(),
-        Atoms::Mdat(atom) => atom.atom_type(),
-        Atoms::UnknownAtom(atom) => atom.atom_type(),
-      }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
     }
-
-    fn atom_location(&self) -> u64 {
-      match self {
-        Atoms::Ftyp(atom) => atom.atom_location(),
-        Atoms::Free(atom) => atom.atom_location(),
-        Atoms::Wide(atom) => atom.atom_location(),
-        Atoms::Mdat(atom) => atom.atom_location(),
-        Atoms::UnknownAtom(atom) => atom.atom_location(),
-      }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
     }
+  }
+  impl AtomLike for TrefAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
 
-    fn header_size(&self) -> u32 {
-      match self {
-        Atoms::Ftyp(atom) => atom.header_size(),
-        Atoms::Free(atom) => atom.header_size(),
-        Atoms::Wide(atom) => atom.header_size(),
-        Atoms::Mdat(atom) => atom.header_size(),
-        Atoms::UnknownAtom(atom) => atom.header_size(),
+  impl std::fmt::Display for TrefAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Tref: {}", self.header)?;
+      for (reference_type, track_ids) in self.references() {
+        let track_ids: Vec<String> = track_ids.iter().map(u32::to_string).collect();
+        write!(f, ", {}: [{}]", reference_type, track_ids.join(", "))?;
       }
+      Ok(())
     }
   }
 
-  impl std::fmt::Display for Atoms {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-      match self {
-        Atoms::Ftyp(atom) => {
-          write!(f, "{}", atom);
-          Ok(())
-        },
-        Atoms::Free(atom) => {
-          write!(f, "{}", atom);
-          Ok(())
-        },
-        Atoms::Wide(atom) => {
-          write!(f, "{}", atom);
-          Ok(())
-        },
-        Atoms::Mdat(atom) => {
-          write!(f, "{}", atom);
-          Ok(())
-        },
-        Atoms::UnknownAtom(atom) => {
-          write!(f, "{}", atom);
-          Ok(())
-        },
-      }
+  #[test]
+  fn should_link_a_chapter_track_to_the_video_track() {
+    let results = crate::Parser::from_path("resources/tests/chapters.mp4").unwrap().parse();
+    let traks: Vec<&TrakAtom> = results.find_all("moov/trak").into_iter().filter_map(|node| match node {
+      AtomNodes::Container(ContainerAtoms::Trak(trak)) => Some(trak),
+      _ => None,
+    }).collect();
+    assert_eq!(2, traks.len());
+    assert!(traks[0].references().is_empty());
+    assert_eq!(vec![(FourCC::from("chap"), vec![1])], traks[1].references());
+    match results.find("moov/trak/tref") {
+      Some(AtomNodes::Container(ContainerAtoms::Tref(tref))) =>
+        assert_eq!("Tref: type: tref, size: 20, location: 382, chap: [1]", tref.to_string()),
+      other => panic!("expected tref, got {:?}", other),
     }
   }
 
-/// The Ftyp Atom is the [file type compatibility atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap1/qtff1.html#//apple_ref/doc/uid/TP40000939-CH203-CJBCBIFF).
-/// Allows the reader to determine whether this a type of file that the reader understands. When a
-/// file is compatible with more than one specificatio, the fiel type atom lists all the
-/// compatible types and inidicates the preferred brand, or best use, among the compatible types.
+  /// The mdia atom is the [media atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-SW1).
+  /// It describes and defines a track's media type and sample data.
   #[derive(Debug, Default, Clone)]
-  pub struct FtypAtom {
-    atom_header: AtomHeader,
-    major_brand: u32,
-    minor_version: u32,
-    compatible_brands: Vec<u32>,
+  pub struct MdiaAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
   }
 
-  impl FtypAtom {
-    pub fn new<T>(atom_header: AtomHeader, file: &mut T) -> Result<FtypAtom>
-      where T: Read + Seek {
-      let mut buf = atom_header.read_atom(file)?;
-
-      if buf.len() >= atom_header.atom_size() as usize {
-        let mut atom = FtypAtom { atom_header, ..Default::default() };
-        let start_offset = atom_header.header_size as usize;
-        let mut bytes: [u8; 4] = [0; 4];
-        bytes.clone_from_slice(&buf[(start_offset)..(start_offset + std::mem::size_of::<u32>())]);
-        atom.major_brand = u32::from_be_bytes(bytes);
-        bytes.clone_from_slice(&buf[start_offset + 4..start_offset + 8]);
-        atom.minor_version = u32::from_be_bytes(bytes);
-        let bytes_left = atom.atom_header.atom_size() -
-          atom.atom_header.header_size() as u64 -
-          2 * std::mem::size_of::<u32>() as u64;
-        let num_u32 = bytes_left / std::mem::size_of::<u32>() as u64;
-        let start_offset = atom.header_size() + 2 * std::mem::size_of::<u32>() as u32;
-        for i in 0..num_u32 as u32 {
-          let start_offset = (start_offset + i * 4) as usize;
-          bytes.clone_from_slice(&buf[start_offset..(start_offset + std::mem::size_of::<u32>() as usize)]);
-          atom.compatible_brands.push(u32::from_be_bytes(bytes));
-        }
-        Ok(atom)
-      } else {
-        Err(ParseError::AtomParseFailed(String::from(atom_header.atom_type())))
-      }
+  impl MdiaAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<MdiaAtom> {
+      let mut result = MdiaAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
     }
   }
 
-  impl AtomLike for FtypAtom {
-    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
-    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
-    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
-    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  impl Container for MdiaAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for MdiaAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
   }
 
-  impl std::fmt::Display for FtypAtom {
+  impl std::fmt::Display for MdiaAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-      write!(f, "Ftyp - Major: {}, Minor: {}, Compatible = [",
-             String::from_utf8_lossy(&self.major_brand.to_be_bytes()).to_string(),
-             String::from_utf8_lossy(&self.minor_version.to_be_bytes()).to_string())?;
-      for item in &self.compatible_brands {
-        write!(f, "{},", String::from_utf8_lossy(&item.to_be_bytes()).to_string())?;
-      }
-      write!(f, "]")?;
-      Ok(())
+      write!(f, "Mdia: {}", self.header)
     }
   }
 
-  #[test]
-  fn test_read_of_ftyp() {
-    let mut file = std::fs::File::open("resources/tests/ftyp.mp4").unwrap();
-    let header = AtomHeader::new(&mut file).unwrap();
-    let atom = header.read_atom(&mut file).unwrap();
-    assert_eq!(header.atom_size() as usize, atom.len());
+  /// The minf atom is the [media information atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25638).
+  /// It stores handler-specific information that maps media time to media data.
+  #[derive(Debug, Default, Clone)]
+  pub struct MinfAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
   }
 
-  #[derive(Debug, Default, Clone, Copy)]
-  pub struct WideAtom {
-    atom_header: AtomHeader,
+  impl MinfAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<MinfAtom> {
+      let mut result = MinfAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
   }
 
-  impl WideAtom {
-    /// Creates a wide atom given a wide atom header. There is nothing to read from the file since
-  /// all the data is in the header
-    pub fn new(atom_header: AtomHeader) -> Result<WideAtom>
-    {
-      Ok(WideAtom { atom_header })
+  impl Container for MinfAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
     }
   }
-
-  impl AtomLike for WideAtom {
-    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
-    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
-    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
-    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  impl AtomLike for MinfAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
   }
 
-  impl std::fmt::Display for WideAtom {
+  impl std::fmt::Display for MinfAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-      write!(f, "Wide: {}", self.atom_header);
-      Ok(())
+      write!(f, "Minf: {}", self.header)
     }
   }
 
-  #[derive(Debug, Default, Clone, Copy)]
-  pub struct FreeAtom {
-    atom_header: AtomHeader,
+  /// The stbl atom is the [sample table atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-BBCBFHFB).
+  /// It holds the atoms that map media samples to chunks, sizes and file offsets.
+  #[derive(Debug, Default, Clone)]
+  pub struct StblAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
   }
 
-  impl FreeAtom {
-    pub fn new(atom_header: AtomHeader) -> Result<FreeAtom> {
-      Ok(FreeAtom { atom_header })
+  impl StblAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<StblAtom> {
+      let mut result = StblAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
     }
   }
 
-  impl AtomLike for FreeAtom {
-    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
-    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
-    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
-    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  impl Container for StblAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for StblAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
   }
 
-  impl std::fmt::Display for FreeAtom {
+  impl std::fmt::Display for StblAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Stbl: {}", self.header)
+    }
+  }
+
+  /// The edts atom is the [edit atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25679).
+  /// It holds the edit list that maps the track's media onto the movie timeline.
+  #[derive(Debug, Default, Clone)]
+  pub struct EdtsAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl EdtsAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<EdtsAtom> {
+      let mut result = EdtsAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+  }
+
+  impl Container for EdtsAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for EdtsAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for EdtsAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Edts: {}", self.header)
+    }
+  }
+
+  /// The tapt atom is the [track aperture mode dimensions atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-SW15).
+  /// It holds the clean, production and encoded apertures of a video track, which differ from
+  /// each other for anamorphic content.
+  #[derive(Debug, Default, Clone)]
+  pub struct TaptAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl TaptAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<TaptAtom> {
+      let mut result = TaptAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+  }
+
+  impl Container for TaptAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for TaptAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for TaptAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Tapt: {}", self.header)
+    }
+  }
+
+  /// The dinf atom is the [data information atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25680).
+  /// It holds the `dref` data reference atom, which says where the media data of a track lives.
+  #[derive(Debug, Default, Clone)]
+  pub struct DinfAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl DinfAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<DinfAtom> {
+      let mut result = DinfAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+  }
+
+  impl Container for DinfAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for DinfAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for DinfAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Dinf: {}", self.header)
+    }
+  }
+
+  /// The gmhd atom is the [base media information header atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html)
+  /// that starts the `minf` of tracks such as timecode and text. It holds a `gmin` atom and, for
+  /// text tracks, a `text` atom.
+  #[derive(Debug, Default, Clone)]
+  pub struct GmhdAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl GmhdAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<GmhdAtom> {
+      let mut result = GmhdAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+  }
+
+  impl Container for GmhdAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for GmhdAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for GmhdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Gmhd: {}", self.header)
+    }
+  }
+
+  /// An atom of a type the parse was told holds nothing but child atoms, see
+  /// [`ParseOptions::containers`](crate::ParseOptions::containers).
+  #[derive(Debug, Default, Clone)]
+  pub struct GenericAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl GenericAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<GenericAtom> {
+      let mut result = GenericAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+  }
+
+  impl Container for GenericAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for GenericAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for GenericAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Container: {}", self.header)
+    }
+  }
+
+  /// The moof atom is the movie fragment atom (ISO/IEC 14496-12). Fragmented files follow the
+  /// `moov` with one `moof` and `mdat` pair per fragment, the `moof` describing the samples of
+  /// its `mdat`.
+  #[derive(Debug, Default, Clone)]
+  pub struct MoofAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl MoofAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<MoofAtom> {
+      let mut result = MoofAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+    /// The sequence number from the `mfhd` atom, which counts up from fragment to fragment.
+    pub fn sequence_number(&self) -> Option<u32> {
+      self.children.iter().find_map(|child| match child {
+        AtomNodes::Atom(Atoms::Mfhd(mfhd)) => Some(mfhd.sequence_number()),
+        _ => None,
+      })
+    }
+  }
+
+  impl Container for MoofAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for MoofAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for MoofAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Moof: {}", self.header)
+    }
+  }
+
+  /// The traf atom is the track fragment atom (ISO/IEC 14496-12). It holds a `tfhd` naming the
+  /// track and the `trun` runs of samples the fragment adds to it.
+  #[derive(Debug, Default, Clone)]
+  pub struct TrafAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl TrafAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<TrafAtom> {
+      let mut result = TrafAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+  }
+
+  impl Container for TrafAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for TrafAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for TrafAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Traf: {}", self.header)
+    }
+  }
+
+  /// The iprp atom is the HEIF item properties atom (ISO/IEC 23008-12). It holds the `ipco`
+  /// property container and the `ipma` table associating properties with items.
+  #[derive(Debug, Default, Clone)]
+  pub struct IprpAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl IprpAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<IprpAtom> {
+      let mut result = IprpAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+  }
+
+  impl Container for IprpAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for IprpAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for IprpAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Iprp: {}", self.header)
+    }
+  }
+
+  /// The udta atom is the [user data atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-BBCCFFGD).
+  /// It carries metadata about the movie or track it is in.
+  #[derive(Debug, Default, Clone)]
+  pub struct UdtaAtom {
+    header: AtomHeader,
+    children: Vec<AtomNodes>,
+  }
+
+  impl UdtaAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<UdtaAtom> {
+      let mut result = UdtaAtom{header, ..Default::default()};
+      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64))?;
+      let mut children = AtomNodes::parse_children(header, file, ctx)?;
+      for child in children.iter_mut() {
+        if let AtomNodes::Atom(Atoms::UnknownAtom(unknown)) = child {
+          if *unknown.reason() == UnknownReason::Unrecognized {
+            let header = *unknown.header();
+            *child = AtomNodes::Atom(match VendorDataAtom::new(header.with_read_limit(ctx.max_atom_read()), file) {
+              Ok(vendor) => Atoms::Vendor(vendor),
+              Err(err) => Atoms::UnknownAtom(UnknownAtom::failed(header, err.to_string())),
+            });
+          }
+        }
+      }
+      result.set_children(children);
+      Ok(result)
+    }
+  }
+
+  impl Container for UdtaAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for UdtaAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for UdtaAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Udta: {}", self.header)
+    }
+  }
+
+  /// The meta atom is the [metadata atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/Metadata/Metadata.html#//apple_ref/doc/uid/TP40000939-CH1-SW10).
+  /// MPEG-4 files write it as a full atom, with a version and flags before its children, while
+  /// QuickTime files start the children straight after the header.
+  #[derive(Debug, Default, Clone)]
+  pub struct MetaAtom {
+    header: AtomHeader,
+    full_atom: Option<FullAtom>,
+    children: Vec<AtomNodes>,
+  }
+
+  impl MetaAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<MetaAtom> {
+      let mut result = MetaAtom{header, ..Default::default()};
+      let payload_start = header.atom_location() + header.header_size() as u64;
+      file.seek(SeekFrom::Start(payload_start))?;
+      // The first child of a QuickTime meta atom has a non-zero size where a full atom has its
+      // version and flags, which are always zero.
+      if read_u32(file)? == 0 {
+        result.full_atom = Some(FullAtom::default());
+      } else {
+        file.seek(SeekFrom::Start(payload_start))?;
+      }
+      result.set_children(AtomNodes::parse_children(header, file, ctx)?);
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> Option<&FullAtom> { self.full_atom.as_ref() }
+  }
+
+  impl Container for MetaAtom {
+    fn children(&self) -> &Vec<AtomNodes> {
+      &self.children
+    }
+    fn children_mut(&mut self) -> &mut Vec<AtomNodes> {
+      &mut self.children
+    }
+    fn set_children(&mut self, children: Vec<AtomNodes>) {
+      self.children = children;
+    }
+  }
+  impl AtomLike for MetaAtom {
+    fn atom_size(&self) -> u64 { self.header.atom_size() }
+    fn atom_type(&self) -> &str { self.header.atom_type() }
+    fn atom_location(&self) -> u64 { self.header.atom_location() }
+    fn header_size(&self) -> u32 { self.header.header_size() }
+  }
+
+  impl std::fmt::Display for MetaAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Meta: {}", self.header)
+    }
+  }
+
+  #[test]
+  fn can_reach_the_sample_tables_of_a_track() {
+    let mut file = std::fs::File::open("resources/tests/trak.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let trak = AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap();
+    let mut path = Vec::new();
+    trak.walk(|node| if node.is_container() { path.push(node.atom_type().to_string()) });
+    assert_eq!(vec!["trak", "edts", "mdia", "minf", "dinf", "stbl"], path);
+  }
+
+  #[test]
+  fn should_parse_the_media_information_of_every_track() {
+    let mut file = std::fs::File::open("resources/tests/moov.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let moov = AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap();
+    let mut minf_children = Vec::new();
+    moov.walk(|node| if let AtomNodes::Container(ContainerAtoms::Minf(minf)) = node {
+      minf_children.push(minf.children().iter().map(|child| (child.atom_type(), child.is_container())).collect::<Vec<_>>());
+    });
+    assert_eq!(vec![vec![("vmhd", false), ("dinf", true), ("stbl", true)],
+                    vec![("smhd", false), ("dinf", true), ("stbl", true)]], minf_children);
+  }
+}
+
+pub mod leaves {
+  use super::*;
+
+  #[derive(Debug, Clone)]
+  pub enum Atoms {
+    Ftyp(FtypAtom),
+    Free(FreeAtom),
+    Wide(WideAtom),
+    Mdat(MdatAtom),
+    Stco(StcoAtom),
+    Co64(Co64Atom),
+    Stsd(StsdAtom),
+    Saiz(SaizAtom),
+    Saio(SaioAtom),
+    Mvhd(MvhdAtom),
+    Tkhd(TkhdAtom),
+    Stsz(StszAtom),
+    Mdhd(MdhdAtom),
+    Hdlr(HdlrAtom),
+    Stts(SttsAtom),
+    Elst(ElstAtom),
+    Ilst(IlstAtom),
+    Ctts(CttsAtom),
+    Aperture(ApertureAtom),
+    Pitm(PitmAtom),
+    Iref(IrefAtom),
+    Ipco(IpcoAtom),
+    Ipma(IpmaAtom),
+    Stsc(StscAtom),
+    Mfhd(MfhdAtom),
+    Tfhd(TfhdAtom),
+    Trun(TrunAtom),
+    TrackReference(TrackReferenceTypeAtom),
+    Dref(DrefAtom),
+    Vmhd(VmhdAtom),
+    Smhd(SmhdAtom),
+    Gmin(GminAtom),
+    Stss(StssAtom),
+    Uuid(UuidAtom),
+    Chpl(ChplAtom),
+    Vendor(VendorDataAtom),
+    Custom(CustomAtom),
+    UnknownAtom(UnknownAtom),
+    /// Bytes after the last child of a container that can not be an atom, too few to hold a
+    /// header or not starting with one, such as padding. They are kept so that every byte of a
+    /// file is accounted for.
+    Slack { location: u64, size: u64 },
+  }
+
+  impl Atoms {
+    /// Parses the leaf atom described by `atom_header`. An atom of a known type that fails to
+    /// parse is kept as an [`UnknownAtom`] that records why, rather than failing its parent.
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<Atoms> {
+      match Atoms::parse(atom_header, file, ctx) {
+        Err(err) if !ctx.options().strict => Ok(Atoms::UnknownAtom(UnknownAtom::failed(atom_header, err.to_string()))),
+        atom => atom,
+      }
+    }
+    /// Parses like [`Atoms::new`] but returns the error. A leaf parser that runs out of file
+    /// reports it as [`ParseError::TruncatedAtom`] with the bytes that were there.
+    fn parse(atom_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<Atoms> {
+      match Atoms::parse_known(atom_header, file, ctx) {
+        Err(ParseError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+          let file_size = file.seek(SeekFrom::End(0))?;
+          let present = file_size.saturating_sub(atom_header.atom_location()).min(atom_header.atom_size());
+          Err(atom_header.truncated(present))
+        }
+        atom => atom,
+      }
+    }
+    fn parse_known(atom_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<Atoms> {
+      // Free space and media data are never read into memory.
+      let max_atom_read = ctx.max_atom_read();
+      if atom_header.atom_size() > max_atom_read && !matches!(atom_header.atom_type(), "free" | "wide" | "mdat") {
+        return Err(ParseError::AtomTooLarge { atom_type: atom_header.four_cc(), offset: atom_header.atom_location(),
+                                              size: atom_header.atom_size(), limit: max_atom_read });
+      }
+      let atom_header = atom_header.with_read_limit(max_atom_read);
+      if let Some(handler) = ctx.handler(atom_header.atom_type()) {
+        let buf = atom_header.read_atom(file)?;
+        let value = handler(&atom_header, &buf[(atom_header.header_size() as usize)..])?;
+        return Ok(Atoms::Custom(CustomAtom { atom_header, value: value.into() }));
+      }
+      match atom_header.atom_type() {
+        "ftyp" => Ok(Atoms::Ftyp(FtypAtom::new(atom_header, file)?)),
+        "free" => Ok(Atoms::Free(FreeAtom::new(atom_header)?)),
+        "wide" => Ok(Atoms::Wide(WideAtom::new(atom_header)?)),
+        "mdat" => Ok(Atoms::Mdat(MdatAtom::new(atom_header)?)),
+        "stco" => Ok(Atoms::Stco(StcoAtom::new(atom_header, file)?)),
+        "co64" => Ok(Atoms::Co64(Co64Atom::new(atom_header, file)?)),
+        "stsd" => Ok(Atoms::Stsd(StsdAtom::new(atom_header, file)?)),
+        "saiz" => Ok(Atoms::Saiz(SaizAtom::new(atom_header, file)?)),
+        "saio" => Ok(Atoms::Saio(SaioAtom::new(atom_header, file)?)),
+        "mvhd" => Ok(Atoms::Mvhd(MvhdAtom::new(atom_header, file)?)),
+        "tkhd" => Ok(Atoms::Tkhd(TkhdAtom::new(atom_header, file)?)),
+        "stsz" => Ok(Atoms::Stsz(StszAtom::new(atom_header, file, ctx)?)),
+        "mdhd" => Ok(Atoms::Mdhd(MdhdAtom::new(atom_header, file)?)),
+        "hdlr" => Ok(Atoms::Hdlr(HdlrAtom::new(atom_header, file)?)),
+        "stts" => Ok(Atoms::Stts(SttsAtom::new(atom_header, file)?)),
+        "elst" => Ok(Atoms::Elst(ElstAtom::new(atom_header, file)?)),
+        "ilst" => Ok(Atoms::Ilst(IlstAtom::new(atom_header, file)?)),
+        "ctts" => Ok(Atoms::Ctts(CttsAtom::new(atom_header, file)?)),
+        "clef" | "prof" | "enof" => Ok(Atoms::Aperture(ApertureAtom::new(atom_header, file)?)),
+        "pitm" => Ok(Atoms::Pitm(PitmAtom::new(atom_header, file)?)),
+        "iref" => Ok(Atoms::Iref(IrefAtom::new(atom_header, file)?)),
+        "ipco" => Ok(Atoms::Ipco(IpcoAtom::new(atom_header, file)?)),
+        "ipma" => Ok(Atoms::Ipma(IpmaAtom::new(atom_header, file)?)),
+        "stsc" => Ok(Atoms::Stsc(StscAtom::new(atom_header, file)?)),
+        "mfhd" => Ok(Atoms::Mfhd(MfhdAtom::new(atom_header, file)?)),
+        "tfhd" => Ok(Atoms::Tfhd(TfhdAtom::new(atom_header, file)?)),
+        "trun" => Ok(Atoms::Trun(TrunAtom::new(atom_header, file)?)),
+        "dref" => Ok(Atoms::Dref(DrefAtom::new(atom_header, file)?)),
+        "vmhd" => Ok(Atoms::Vmhd(VmhdAtom::new(atom_header, file)?)),
+        "smhd" => Ok(Atoms::Smhd(SmhdAtom::new(atom_header, file)?)),
+        "gmin" => Ok(Atoms::Gmin(GminAtom::new(atom_header, file)?)),
+        "stss" => Ok(Atoms::Stss(StssAtom::new(atom_header, file)?)),
+        "uuid" => Ok(Atoms::Uuid(UuidAtom::new(atom_header)?)),
+        "chpl" => Ok(Atoms::Chpl(ChplAtom::new(atom_header, file)?)),
+        _ => Ok(Atoms::UnknownAtom(UnknownAtom::new(atom_header)))
+      }
+    }
+  }
+
+  impl AtomLike for Atoms {
+    fn atom_size(&self) -> u64 {
+      match self {
+        Atoms::Ftyp(atom) => atom.atom_size(),
+        Atoms::Free(atom) => atom.atom_size(),
+        Atoms::Wide(atom) => atom.atom_size(),
+        Atoms::Mdat(atom) => atom.atom_size(),
+        Atoms::Stco(atom) => atom.atom_size(),
+        Atoms::Co64(atom) => atom.atom_size(),
+        Atoms::Stsd(atom) => atom.atom_size(),
+        Atoms::Saiz(atom) => atom.atom_size(),
+        Atoms::Saio(atom) => atom.atom_size(),
+        Atoms::Mvhd(atom) => atom.atom_size(),
+        Atoms::Tkhd(atom) => atom.atom_size(),
+        Atoms::Stsz(atom) => atom.atom_size(),
+        Atoms::Mdhd(atom) => atom.atom_size(),
+        Atoms::Hdlr(atom) => atom.atom_size(),
+        Atoms::Stts(atom) => atom.atom_size(),
+        Atoms::Elst(atom) => atom.atom_size(),
+        Atoms::Ilst(atom) => atom.atom_size(),
+        Atoms::Ctts(atom) => atom.atom_size(),
+        Atoms::Aperture(atom) => atom.atom_size(),
+        Atoms::Pitm(atom) => atom.atom_size(),
+        Atoms::Iref(atom) => atom.atom_size(),
+        Atoms::Ipco(atom) => atom.atom_size(),
+        Atoms::Ipma(atom) => atom.atom_size(),
+        Atoms::Stsc(atom) => atom.atom_size(),
+        Atoms::Mfhd(atom) => atom.atom_size(),
+        Atoms::Tfhd(atom) => atom.atom_size(),
+        Atoms::Trun(atom) => atom.atom_size(),
+        Atoms::TrackReference(atom) => atom.atom_size(),
+        Atoms::Dref(atom) => atom.atom_size(),
+        Atoms::Vmhd(atom) => atom.atom_size(),
+        Atoms::Smhd(atom) => atom.atom_size(),
+        Atoms::Gmin(atom) => atom.atom_size(),
+        Atoms::Vendor(atom) => atom.atom_size(),
+        Atoms::Stss(atom) => atom.atom_size(),
+        Atoms::Uuid(atom) => atom.atom_size(),
+        Atoms::Chpl(atom) => atom.atom_size(),
+        Atoms::Custom(atom) => atom.atom_size(),
+        Atoms::UnknownAtom(atom) => atom.atom_size(),
+        Atoms::Slack { size, .. } => *size,
+      }
+    }
+
+    fn atom_type(&self) -> &str {
+      match self {
+        Atoms::Ftyp(atom) => atom.atom_type(),
+        Atoms::Free(atom) => atom.atom_type(),
+        Atoms::Wide(atom) => atom.atom_type(),
+        Atoms::Mdat(atom) => atom.atom_type(),
+        Atoms::Stco(atom) => atom.atom_type(),
+        Atoms::Co64(atom) => atom.atom_type(),
+        Atoms::Stsd(atom) => atom.atom_type(),
+        Atoms::Saiz(atom) => atom.atom_type(),
+        Atoms::Saio(atom) => atom.atom_type(),
+        Atoms::Mvhd(atom) => atom.atom_type(),
+        Atoms::Tkhd(atom) => atom.atom_type(),
+        Atoms::Stsz(atom) => atom.atom_type(),
+        Atoms::Mdhd(atom) => atom.atom_type(),
+        Atoms::Hdlr(atom) => atom.atom_type(),
+        Atoms::Stts(atom) => atom.atom_type(),
+        Atoms::Elst(atom) => atom.atom_type(),
+        Atoms::Ilst(atom) => atom.atom_type(),
+        Atoms::Ctts(atom) => atom.atom_type(),
+        Atoms::Aperture(atom) => atom.atom_type(),
+        Atoms::Pitm(atom) => atom.atom_type(),
+        Atoms::Iref(atom) => atom.atom_type(),
+        Atoms::Ipco(atom) => atom.atom_type(),
+        Atoms::Ipma(atom) => atom.atom_type(),
+        Atoms::Stsc(atom) => atom.atom_type(),
+        Atoms::Mfhd(atom) => atom.atom_type(),
+        Atoms::Tfhd(atom) => atom.atom_type(),
+        Atoms::Trun(atom) => atom.atom_type(),
+        Atoms::TrackReference(atom) => atom.atom_type(),
+        Atoms::Dref(atom) => atom.atom_type(),
+        Atoms::Vmhd(atom) => atom.atom_type(),
+        Atoms::Smhd(atom) => atom.atom_type(),
+        Atoms::Gmin(atom) => atom.atom_type(),
+        Atoms::Vendor(atom) => atom.atom_type(),
+        Atoms::Stss(atom) => atom.atom_type(),
+        Atoms::Uuid(atom) => atom.atom_type(),
+        Atoms::Chpl(atom) => atom.atom_type(),
+        Atoms::Custom(atom) => atom.atom_type(),
+        Atoms::UnknownAtom(atom) => atom.atom_type(),
+        Atoms::Slack { .. } => "slack",
+      }
+    }
+
+    fn atom_location(&self) -> u64 {
+      match self {
+        Atoms::Ftyp(atom) => atom.atom_location(),
+        Atoms::Free(atom) => atom.atom_location(),
+        Atoms::Wide(atom) => atom.atom_location(),
+        Atoms::Mdat(atom) => atom.atom_location(),
+        Atoms::Stco(atom) => atom.atom_location(),
+        Atoms::Co64(atom) => atom.atom_location(),
+        Atoms::Stsd(atom) => atom.atom_location(),
+        Atoms::Saiz(atom) => atom.atom_location(),
+        Atoms::Saio(atom) => atom.atom_location(),
+        Atoms::Mvhd(atom) => atom.atom_location(),
+        Atoms::Tkhd(atom) => atom.atom_location(),
+        Atoms::Stsz(atom) => atom.atom_location(),
+        Atoms::Mdhd(atom) => atom.atom_location(),
+        Atoms::Hdlr(atom) => atom.atom_location(),
+        Atoms::Stts(atom) => atom.atom_location(),
+        Atoms::Elst(atom) => atom.atom_location(),
+        Atoms::Ilst(atom) => atom.atom_location(),
+        Atoms::Ctts(atom) => atom.atom_location(),
+        Atoms::Aperture(atom) => atom.atom_location(),
+        Atoms::Pitm(atom) => atom.atom_location(),
+        Atoms::Iref(atom) => atom.atom_location(),
+        Atoms::Ipco(atom) => atom.atom_location(),
+        Atoms::Ipma(atom) => atom.atom_location(),
+        Atoms::Stsc(atom) => atom.atom_location(),
+        Atoms::Mfhd(atom) => atom.atom_location(),
+        Atoms::Tfhd(atom) => atom.atom_location(),
+        Atoms::Trun(atom) => atom.atom_location(),
+        Atoms::TrackReference(atom) => atom.atom_location(),
+        Atoms::Dref(atom) => atom.atom_location(),
+        Atoms::Vmhd(atom) => atom.atom_location(),
+        Atoms::Smhd(atom) => atom.atom_location(),
+        Atoms::Gmin(atom) => atom.atom_location(),
+        Atoms::Vendor(atom) => atom.atom_location(),
+        Atoms::Stss(atom) => atom.atom_location(),
+        Atoms::Uuid(atom) => atom.atom_location(),
+        Atoms::Chpl(atom) => atom.atom_location(),
+        Atoms::Custom(atom) => atom.atom_location(),
+        Atoms::UnknownAtom(atom) => atom.atom_location(),
+        Atoms::Slack { location, .. } => *location,
+      }
+    }
+
+    fn header_size(&self) -> u32 {
+      match self {
+        Atoms::Ftyp(atom) => atom.header_size(),
+        Atoms::Free(atom) => atom.header_size(),
+        Atoms::Wide(atom) => atom.header_size(),
+        Atoms::Mdat(atom) => atom.header_size(),
+        Atoms::Stco(atom) => atom.header_size(),
+        Atoms::Co64(atom) => atom.header_size(),
+        Atoms::Stsd(atom) => atom.header_size(),
+        Atoms::Saiz(atom) => atom.header_size(),
+        Atoms::Saio(atom) => atom.header_size(),
+        Atoms::Mvhd(atom) => atom.header_size(),
+        Atoms::Tkhd(atom) => atom.header_size(),
+        Atoms::Stsz(atom) => atom.header_size(),
+        Atoms::Mdhd(atom) => atom.header_size(),
+        Atoms::Hdlr(atom) => atom.header_size(),
+        Atoms::Stts(atom) => atom.header_size(),
+        Atoms::Elst(atom) => atom.header_size(),
+        Atoms::Ilst(atom) => atom.header_size(),
+        Atoms::Ctts(atom) => atom.header_size(),
+        Atoms::Aperture(atom) => atom.header_size(),
+        Atoms::Pitm(atom) => atom.header_size(),
+        Atoms::Iref(atom) => atom.header_size(),
+        Atoms::Ipco(atom) => atom.header_size(),
+        Atoms::Ipma(atom) => atom.header_size(),
+        Atoms::Stsc(atom) => atom.header_size(),
+        Atoms::Mfhd(atom) => atom.header_size(),
+        Atoms::Tfhd(atom) => atom.header_size(),
+        Atoms::Trun(atom) => atom.header_size(),
+        Atoms::TrackReference(atom) => atom.header_size(),
+        Atoms::Dref(atom) => atom.header_size(),
+        Atoms::Vmhd(atom) => atom.header_size(),
+        Atoms::Smhd(atom) => atom.header_size(),
+        Atoms::Gmin(atom) => atom.header_size(),
+        Atoms::Vendor(atom) => atom.header_size(),
+        Atoms::Stss(atom) => atom.header_size(),
+        Atoms::Uuid(atom) => atom.header_size(),
+        Atoms::Chpl(atom) => atom.header_size(),
+        Atoms::Custom(atom) => atom.header_size(),
+        Atoms::UnknownAtom(atom) => atom.header_size(),
+        Atoms::Slack { .. } => 0,
+      }
+    }
+  }
+
+  impl std::fmt::Display for Atoms {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      match self {
+        Atoms::Ftyp(atom) => write!(f, "{}", atom),
+        Atoms::Free(atom) => write!(f, "{}", atom),
+        Atoms::Wide(atom) => write!(f, "{}", atom),
+        Atoms::Mdat(atom) => write!(f, "{}", atom),
+        Atoms::Stco(atom) => write!(f, "{}", atom),
+        Atoms::Co64(atom) => write!(f, "{}", atom),
+        Atoms::Stsd(atom) => write!(f, "{}", atom),
+        Atoms::Saiz(atom) => write!(f, "{}", atom),
+        Atoms::Saio(atom) => write!(f, "{}", atom),
+        Atoms::Mvhd(atom) => write!(f, "{}", atom),
+        Atoms::Tkhd(atom) => write!(f, "{}", atom),
+        Atoms::Stsz(atom) => write!(f, "{}", atom),
+        Atoms::Mdhd(atom) => write!(f, "{}", atom),
+        Atoms::Hdlr(atom) => write!(f, "{}", atom),
+        Atoms::Stts(atom) => write!(f, "{}", atom),
+        Atoms::Elst(atom) => write!(f, "{}", atom),
+        Atoms::Ilst(atom) => write!(f, "{}", atom),
+        Atoms::Ctts(atom) => write!(f, "{}", atom),
+        Atoms::Aperture(atom) => write!(f, "{}", atom),
+        Atoms::Pitm(atom) => write!(f, "{}", atom),
+        Atoms::Iref(atom) => write!(f, "{}", atom),
+        Atoms::Ipco(atom) => write!(f, "{}", atom),
+        Atoms::Ipma(atom) => write!(f, "{}", atom),
+        Atoms::Stsc(atom) => write!(f, "{}", atom),
+        Atoms::Mfhd(atom) => write!(f, "{}", atom),
+        Atoms::Tfhd(atom) => write!(f, "{}", atom),
+        Atoms::Trun(atom) => write!(f, "{}", atom),
+        Atoms::TrackReference(atom) => write!(f, "{}", atom),
+        Atoms::Dref(atom) => write!(f, "{}", atom),
+        Atoms::Vmhd(atom) => write!(f, "{}", atom),
+        Atoms::Smhd(atom) => write!(f, "{}", atom),
+        Atoms::Gmin(atom) => write!(f, "{}", atom),
+        Atoms::Vendor(atom) => write!(f, "{}", atom),
+        Atoms::Stss(atom) => write!(f, "{}", atom),
+        Atoms::Uuid(atom) => write!(f, "{}", atom),
+        Atoms::Chpl(atom) => write!(f, "{}", atom),
+        Atoms::Custom(atom) => write!(f, "{}", atom),
+        Atoms::UnknownAtom(atom) => write!(f, "{}", atom),
+        Atoms::Slack { size, .. } => write!(f, "slack ({} bytes)", size),
+      }
+    }
+  }
+
+  /// Why an atom was kept as an [`UnknownAtom`].
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub enum UnknownReason {
+    /// The parser has no support for the atom type.
+    Unrecognized,
+    /// The atom type is supported but its contents could not be parsed.
+    ParseFailed(String),
+    /// The parse was told not to read atoms of the type, see
+    /// [`ParseOptions::skip_types`](crate::ParseOptions::skip_types).
+    Skipped,
+  }
+
+  /// A leaf atom parsed by a handler registered with [`ParseContext::with_handler`]. The handler's
+  /// result is kept type erased, see [`AtomNodes::as_custom`].
+  #[derive(Clone)]
+  pub struct CustomAtom {
+    atom_header: AtomHeader,
+    value: std::sync::Arc<dyn std::any::Any + Send + Sync>,
+  }
+
+  impl CustomAtom {
+    pub fn header(&self) -> &AtomHeader { &self.atom_header }
+    /// The handler's result, if it is a `T`.
+    pub fn value<T: 'static>(&self) -> Option<&T> { self.value.downcast_ref::<T>() }
+  }
+
+  impl std::fmt::Debug for CustomAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "CustomAtom{{ atom_header: {:?} }}", self.atom_header)
+    }
+  }
+
+  impl AtomLike for CustomAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for CustomAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Custom: {}", self.atom_header)
+    }
+  }
+
+  #[test]
+  fn a_registered_handler_result_can_be_recovered() {
+    #[derive(Debug, PartialEq)]
+    struct VendorInfo {
+      version: u32,
+    }
+    let ctx = ParseContext::default().with_handler("XVND", |_, payload| {
+      Ok(VendorInfo { version: read_u32(&mut &payload[..])? })
+    });
+    let mut bytes = vec![0, 0, 0, 12];
+    bytes.extend_from_slice(b"XVND");
+    bytes.extend_from_slice(&7u32.to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let node = AtomNodes::new(header, &mut file, &ctx).unwrap();
+    assert_eq!(Some(&VendorInfo { version: 7 }), node.as_custom::<VendorInfo>());
+    assert_eq!(None, node.as_custom::<u32>());
+
+    let unhandled = AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap();
+    assert_eq!(None, unhandled.as_custom::<VendorInfo>());
+  }
+
+  /// A leaf atom the parser could not interpret. Only its header is kept.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct UnknownAtom {
+    atom_header: AtomHeader,
+    reason: UnknownReason,
+  }
+
+  impl UnknownAtom {
+    pub fn new(atom_header: AtomHeader) -> UnknownAtom {
+      UnknownAtom { atom_header, reason: UnknownReason::Unrecognized }
+    }
+    pub fn failed(atom_header: AtomHeader, reason: String) -> UnknownAtom {
+      UnknownAtom { atom_header, reason: UnknownReason::ParseFailed(reason) }
+    }
+    pub fn skipped(atom_header: AtomHeader) -> UnknownAtom {
+      UnknownAtom { atom_header, reason: UnknownReason::Skipped }
+    }
+    pub fn header(&self) -> &AtomHeader { &self.atom_header }
+    /// The four character code of the atom type.
+    pub fn four_cc(&self) -> FourCC { self.atom_header.atom_type }
+    pub fn reason(&self) -> &UnknownReason { &self.reason }
+  }
+
+  impl AtomLike for UnknownAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for UnknownAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      match &self.reason {
+        UnknownReason::Unrecognized => write!(f, "Unknown: {}", self.atom_header),
+        UnknownReason::ParseFailed(reason) => write!(f, "Unknown: {}, failed to parse: {}", self.atom_header, reason),
+        UnknownReason::Skipped => write!(f, "Unknown: {}, skipped", self.atom_header),
+      }
+    }
+  }
+
+  /// A `uuid` atom, the extension ISO/IEC 14496-12 makes for types outside the four-CC space. Its
+  /// real type is the UUID after the header, see [`AtomHeader::extended_type`]. Cameras keep
+  /// proprietary metadata in them, such as Canon's `CNTH` thumbnails and Sony's `PROF` profile.
+  /// The payload is left in the file.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct UuidAtom {
+    atom_header: AtomHeader,
+    uuid: [u8; 16],
+  }
+
+  impl UuidAtom {
+    pub fn new(atom_header: AtomHeader) -> Result<UuidAtom> {
+      let uuid = atom_header.extended_type()
+        .ok_or_else(|| ParseError::parse_failed(&atom_header, "the header has no UUID"))?;
+      Ok(UuidAtom { atom_header, uuid })
+    }
+    /// The UUID that identifies what the atom holds.
+    pub fn uuid(&self) -> [u8; 16] { self.uuid }
+    /// The UUID in the canonical hyphenated form.
+    pub fn uuid_string(&self) -> String { uuid_string(&self.uuid) }
+    /// The number of bytes after the header and its UUID.
+    pub fn payload_size(&self) -> u64 {
+      self.atom_header.atom_size().saturating_sub(self.atom_header.header_size() as u64)
+    }
+  }
+
+  impl AtomLike for UuidAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for UuidAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Uuid: {}, uuid: {}, payload: {} bytes", self.atom_header, self.uuid_string(), self.payload_size())
+    }
+  }
+
+  /// One chapter of a [`ChplAtom`].
+  #[derive(Debug, Default, Clone, PartialEq, Eq)]
+  pub struct ChplEntry {
+    /// When the chapter starts, in units of 100 nanoseconds.
+    pub start_time: u64,
+    pub title: String,
+  }
+
+  /// The chpl atom is the chapter list Nero writes into the `udta` of the movie, which players
+  /// read as well as chapter tracks. Each chapter is a start time and a title of up to 255 bytes
+  /// of UTF-8.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct ChplAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    entries: Vec<ChplEntry>,
+  }
+
+  impl ChplAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<ChplAtom> {
+      let mut reader = AtomReader::new(atom_header, file)?;
+      let full_atom = reader.read_full_atom()?;
+      // Version 1 has four reserved bytes before the count.
+      if full_atom.version() > 0 {
+        reader.skip(4)?;
+      }
+      let count = reader.read_u8()?;
+      let mut entries = Vec::with_capacity(count as usize);
+      for _ in 0..count {
+        let start_time = reader.read_u64()?;
+        let len = reader.read_u8()?;
+        let title = String::from_utf8_lossy(reader.read_bytes(len as usize)?).into_owned();
+        entries.push(ChplEntry { start_time, title });
+      }
+      Ok(ChplAtom { atom_header, full_atom, entries })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn chapter_count(&self) -> usize { self.entries.len() }
+    pub fn entries(&self) -> &[ChplEntry] { &self.entries }
+  }
+
+  impl AtomLike for ChplAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for ChplAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Chpl: {}, chapters: {}", self.atom_header, self.entries.len())
+    }
+  }
+
+  #[test]
+  fn should_read_a_nero_chapter_list() {
+    let mut payload = vec![1, 0, 0, 0, 0, 0, 0, 0, 2];
+    payload.extend_from_slice(&0u64.to_be_bytes());
+    payload.push(5);
+    payload.extend_from_slice(b"Intro");
+    payload.extend_from_slice(&15_000_000u64.to_be_bytes());
+    payload.push(7);
+    payload.extend_from_slice("Caf\u{e9} 2".as_bytes());
+    let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(b"chpl");
+    bytes.extend_from_slice(&payload);
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let chpl = ChplAtom::new(header, &mut file).unwrap();
+    assert_eq!(1, chpl.full_atom().version());
+    assert_eq!(vec![ChplEntry { start_time: 0, title: "Intro".to_string() },
+                    ChplEntry { start_time: 15_000_000, title: "Caf\u{e9} 2".to_string() }], chpl.entries());
+    assert_eq!("Chpl: type: chpl, size: 47, location: 0, chapters: 2", chpl.to_string());
+
+    // A title that runs past the end of the atom is a truncated field.
+    let mut file = std::io::Cursor::new(file.into_inner()[..40].to_vec());
+    file.get_mut()[3] = 40;
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert!(matches!(ChplAtom::new(header, &mut file), Err(ParseError::TruncatedField { .. })));
+  }
+
+  /// A child of a [user data atom](crate::containers::UdtaAtom) the parser has no support for.
+  /// Cameras keep their firmware version, serial number and settings in these, such as the
+  /// `FIRM`, `LENS` and `CAME` atoms and the `GPMF` telemetry of GoPro cameras. The payload is
+  /// kept as is.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct VendorDataAtom {
+    atom_header: AtomHeader,
+    payload: Vec<u8>,
+  }
+
+  impl VendorDataAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<VendorDataAtom> {
+      let buf = atom_header.read_atom(file)?;
+      Ok(VendorDataAtom { atom_header, payload: buf[(atom_header.header_size() as usize)..].to_vec() })
+    }
+    /// The four character code of the atom type.
+    pub fn four_cc(&self) -> FourCC { self.atom_header.atom_type }
+    /// The bytes after the atom header.
+    pub fn payload(&self) -> &[u8] { &self.payload }
+    /// The payload as text when it is printable UTF-8, without the NUL padding some cameras
+    /// write after it.
+    pub fn as_utf8(&self) -> Option<&str> {
+      let end = self.payload.iter().rposition(|x| *x != 0).map_or(0, |x| x + 1);
+      let text = std::str::from_utf8(&self.payload[..end]).ok()?;
+      (!text.is_empty() && !text.chars().any(char::is_control)).then_some(text)
+    }
+  }
+
+  impl AtomLike for VendorDataAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for VendorDataAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      match self.as_utf8() {
+        Some(text) => write!(f, "Vendor: {}, {}: \"{}\"", self.atom_header, self.four_cc(), text),
+        None => write!(f, "Vendor: {}, {}: {} bytes", self.atom_header, self.four_cc(), self.payload.len()),
+      }
+    }
+  }
+
+  #[test]
+  fn an_unrecognized_vendor_atom_reports_its_four_cc() {
+    let mut bytes = vec![0, 0, 0, 12];
+    bytes.extend_from_slice(b"XVND");
+    bytes.extend_from_slice(&[1, 2, 3, 4]);
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    match Atoms::new(header, &mut file, &ParseContext::default()).unwrap() {
+      Atoms::UnknownAtom(atom) => {
+        assert_eq!(*b"XVND", atom.four_cc().raw());
+        assert_eq!(&UnknownReason::Unrecognized, atom.reason());
+      }
+      other => panic!("expected an unknown atom, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn should_keep_the_payload_of_vendor_atoms_under_udta() {
+    let results = crate::Parser::from_path("resources/tests/gopro_udta.mp4").unwrap().parse();
+    let vendor = results.vendor_metadata();
+    let types: Vec<String> = vendor.iter().map(|(four_cc, _)| four_cc.to_string()).collect();
+    assert_eq!(vec!["FIRM", "LENS", "CAME", "GPMF"], types);
+
+    let firm = vendor[0].1;
+    assert_eq!(Some("HD9.01.01.72.00"), firm.as_utf8());
+    assert_eq!(16, firm.payload().len());
+    assert_eq!("Vendor: type: FIRM, size: 24, location: 40, FIRM: \"HD9.01.01.72.00\"", firm.to_string());
+
+    let came = vendor[2].1;
+    assert_eq!(None, came.as_utf8());
+    assert_eq!([0x8a, 0x3c, 0x00, 0x1f], came.payload()[..4]);
+    assert_eq!("Vendor: type: CAME, size: 24, location: 88, CAME: 16 bytes", came.to_string());
+    assert_eq!(None, vendor[3].1.as_utf8());
+    assert_eq!(20, vendor[3].1.payload().len());
+  }
+
+  #[test]
+  fn a_known_atom_that_fails_to_parse_is_unknown_with_a_reason() {
+    // An stco that claims 4 entries but holds none.
+    let mut bytes = vec![0, 0, 0, 16];
+    bytes.extend_from_slice(b"stco");
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 4]);
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    match Atoms::new(header, &mut file, &ParseContext::default()).unwrap() {
+      Atoms::UnknownAtom(atom) => {
+        assert_eq!(*b"stco", atom.four_cc().raw());
+        assert!(matches!(atom.reason(), UnknownReason::ParseFailed(_)));
+        assert!(atom.to_string().contains("failed to parse"));
+      }
+      other => panic!("expected an unknown atom, got {:?}", other),
+    }
+  }
+
+/// The Ftyp Atom is the [file type compatibility atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap1/qtff1.html#//apple_ref/doc/uid/TP40000939-CH203-CJBCBIFF).
+/// Allows the reader to determine whether this a type of file that the reader understands. When a
+/// file is compatible with more than one specificatio, the fiel type atom lists all the
+/// compatible types and inidicates the preferred brand, or best use, among the compatible types.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct FtypAtom {
+    atom_header: AtomHeader,
+    major_brand: FourCC,
+    minor_version: u32,
+    compatible_brands: Vec<FourCC>,
+  }
+
+  impl FtypAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<FtypAtom> {
+      let buf = atom_header.read_payload(file)?;
+      let mut bytes = buf.as_slice();
+      if bytes.len() < 8 {
+        return Err(ParseError::parse_failed(&atom_header, format!("size {} is too small for its brands", atom_header.atom_size())));
+      }
+      if bytes.len() % 4 != 0 {
+        return Err(ParseError::parse_failed(&atom_header, format!("{} bytes of brands are not a whole number of brands",
+                                                                  bytes.len() - 8)));
+      }
+      let major_brand = FourCC::new(read_u32(&mut bytes)?.to_be_bytes());
+      let minor_version = read_u32(&mut bytes)?;
+      let compatible_brands = bytes.chunks_exact(4).map(|brand| FourCC::new([brand[0], brand[1], brand[2], brand[3]])).collect();
+      Ok(FtypAtom { atom_header, major_brand, minor_version, compatible_brands })
+    }
+    /// The brand of the specification the file conforms to best, such as `isom` or `qt  `.
+    pub fn major_brand(&self) -> FourCC { self.major_brand }
+    pub fn minor_version(&self) -> u32 { self.minor_version }
+    /// The brands of the other specifications the file conforms to.
+    pub fn compatible_brands(&self) -> &[FourCC] { &self.compatible_brands }
+    pub(crate) fn is_major_brand(&self, brand: &[u8; 4]) -> bool {
+      self.major_brand.raw() == *brand
+    }
+    /// Whether `brand` is the major brand or one of the compatible brands.
+    pub(crate) fn has_brand(&self, brand: &[u8; 4]) -> bool {
+      self.is_major_brand(brand) || self.compatible_brands.iter().any(|x| x.raw() == *brand)
+    }
+    /// Whether the file declares it conforms to `brand`, such as `isom` or `qt  `, as its major
+    /// brand or one of its compatible brands. Brands shorter than four characters are padded with
+    /// spaces.
+    pub fn is_compatible_with(&self, brand: &str) -> bool {
+      brand.chars().count() <= 4 && self.has_brand(&four_cc_from_str(brand))
+    }
+    /// Starts a new file type atom that is not read from a file.
+    pub fn builder() -> FtypAtomBuilder { FtypAtomBuilder::default() }
+  }
+
+  /// Builds an [`FtypAtom`] from scratch, for instance to write it in place of the one a file
+  /// has. Brands shorter than four characters are padded with spaces.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct FtypAtomBuilder {
+    major_brand: FourCC,
+    minor_version: u32,
+    compatible_brands: Vec<FourCC>,
+  }
+
+  impl FtypAtomBuilder {
+    pub fn major_brand(mut self, brand: &str) -> Self {
+      self.major_brand = FourCC::new(four_cc_from_str(brand));
+      self
+    }
+    pub fn minor_version(mut self, minor_version: u32) -> Self {
+      self.minor_version = minor_version;
+      self
+    }
+    /// Adds `brands` to the compatible brands.
+    pub fn compatible(mut self, brands: &[&str]) -> Self {
+      self.compatible_brands.extend(brands.iter().map(|brand| FourCC::new(four_cc_from_str(brand))));
+      self
+    }
+    pub fn build(self) -> FtypAtom {
+      let atom_size = 16 + 4 * self.compatible_brands.len() as u64;
+      FtypAtom { atom_header: AtomHeader::synthesized(b"ftyp", atom_size, 0), major_brand: self.major_brand,
+                 minor_version: self.minor_version, compatible_brands: self.compatible_brands }
+    }
+  }
+
+  impl AtomLike for FtypAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  #[test]
+  fn an_ftyp_declaring_more_brands_than_present_is_an_error() {
+    let mut bytes = vec![0, 0, 0, 32];
+    bytes.extend_from_slice(b"ftypisom");
+    bytes.extend_from_slice(&[0, 0, 2, 0]);
+    bytes.extend_from_slice(b"isomiso2");
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    match FtypAtom::new(header, &mut file) {
+      Err(ParseError::TruncatedAtom { atom_type, offset: 0, expected: 32, got: 24 }) => assert_eq!(atom_type, "ftyp"),
+      ref other => panic!("expected TruncatedAtom, got {:?}", other),
+    }
+
+    let mut file = std::io::Cursor::new(b"\0\0\0\x0cftypisom".to_vec());
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert!(matches!(FtypAtom::new(header, &mut file), Err(ParseError::AtomParseFailed { .. })));
+  }
+
+  #[test]
+  fn an_ftyp_is_compatible_with_its_major_and_compatible_brands() {
+    let mut file = std::fs::File::open("resources/tests/ftyp.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let ftyp = FtypAtom::new(header, &mut file).unwrap();
+    assert!(ftyp.is_compatible_with("isom"));
+    assert!(ftyp.is_compatible_with("mp41"));
+    assert!(!ftyp.is_compatible_with("qt  "));
+    assert!(!ftyp.is_compatible_with("isom2"));
+  }
+
+  #[test]
+  fn should_read_the_brands_of_an_ftyp() {
+    let mut file = std::fs::File::open("resources/tests/ftyp.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let ftyp = FtypAtom::new(header, &mut file).unwrap();
+    assert_eq!(ftyp.major_brand(), "isom");
+    assert_eq!(512, ftyp.minor_version());
+    let brands: Vec<&str> = ftyp.compatible_brands().iter().map(FourCC::as_str).collect();
+    assert_eq!(vec!["isom", "iso2", "avc1", "mp41"], brands);
+
+    let mut file = std::io::Cursor::new(b"\0\0\0\x14ftypqt  \x20\x05\x03\0qt  ".to_vec());
+    let header = AtomHeader::new(&mut file).unwrap();
+    let ftyp = FtypAtom::new(header, &mut file).unwrap();
+    assert_eq!(ftyp.major_brand(), "qt  ");
+    assert_eq!(0x2005_0300, ftyp.minor_version());
+    assert!(ftyp.is_compatible_with("qt"));
+    assert!(!ftyp.is_compatible_with("isom"));
+  }
+
+  /// A reader that returns at most 3 bytes per read, like a pipe or a socket might.
+  #[cfg(test)]
+  struct Trickle<R>(R);
+
+  #[cfg(test)]
+  impl<R: Read> Read for Trickle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      let len = buf.len().min(3);
+      self.0.read(&mut buf[..len])
+    }
+  }
+
+  #[cfg(test)]
+  impl<R: Seek> Seek for Trickle<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> { self.0.seek(pos) }
+  }
+
+  #[test]
+  fn should_read_atoms_from_a_reader_that_returns_a_few_bytes_at_a_time() {
+    let bytes = std::fs::read("resources/tests/ftyp.mp4").unwrap();
+    let mut file = Trickle(std::io::Cursor::new(bytes.clone()));
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert_eq!(bytes, header.read_atom(&mut file).unwrap());
+    assert_eq!(&bytes[8..], header.read_payload(&mut file).unwrap().as_slice());
+    let ftyp = FtypAtom::new(header, &mut file).unwrap();
+    assert_eq!(ftyp.major_brand(), "isom");
+    assert_eq!(4, ftyp.compatible_brands().len());
+
+    let mut file = Trickle(std::io::Cursor::new(bytes[..27].to_vec()));
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert!(matches!(header.read_payload(&mut file), Err(ParseError::TruncatedAtom { expected: 32, got: 27, .. })));
+  }
+
+  #[test]
+  fn should_read_payloads_up_to_the_limit_of_the_parse() {
+    let mut file = std::fs::File::open("resources/tests/ftyp.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert!(matches!(header.read_payload_with_limit(&mut file, 16), Err(ParseError::AtomTooLarge { size: 32, limit: 16, .. })));
+    let limited = header.with_read_limit(16);
+    assert_eq!(header, limited);
+    assert!(matches!(limited.read_payload(&mut file), Err(ParseError::AtomTooLarge { limit: 16, .. })));
+    assert!(matches!(limited.read_atom(&mut file), Err(ParseError::AtomTooLarge { limit: 16, .. })));
+    assert_eq!(24, header.with_read_limit(32).read_payload(&mut file).unwrap().len());
+
+    // A compressed movie is read within the limit of the parse as well.
+    let moov = |strict: bool| {
+      let mut file = std::fs::File::open("resources/tests/cmov.mp4").unwrap();
+      let ftyp = AtomHeader::new(&mut file).unwrap();
+      file.seek(SeekFrom::Start(ftyp.atom_size())).unwrap();
+      let header = AtomHeader::new(&mut file).unwrap();
+      let options = crate::ParseOptions { max_atom_read: Some(1024), strict, ..Default::default() };
+      MoovAtom::new(header, &mut file, &ParseContext::from(options))
+    };
+    assert!(matches!(moov(true), Err(ParseError::AtomTooLarge { limit: 1024, .. })));
+    match moov(false).unwrap().children().as_slice() {
+      [AtomNodes::Atom(Atoms::UnknownAtom(cmov))] =>
+        assert!(matches!(cmov.reason(), UnknownReason::ParseFailed(reason) if reason.ends_with("over the 1024 bytes that are read into memory"))),
+      other => panic!("expected an unknown cmov, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn should_read_the_features_of_a_prfl() {
+    let mut bytes = vec![0, 0, 0, 28];
+    bytes.extend_from_slice(b"prfl");
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    bytes.extend_from_slice(&[0, 0, 0, 2]);
+    bytes.extend_from_slice(b"mtyp");
+    bytes.extend_from_slice(&[0, 0, 0, 7]);
+    let mut file = Trickle(std::io::Cursor::new(bytes.clone()));
+    let header = AtomHeader::new(&mut file).unwrap();
+    let prfl = PrflAtom::new(header, &mut file).unwrap();
+    assert_eq!(1, prfl.num_features());
+    assert_eq!(2, prfl.features()[0].part_id());
+    assert_eq!("mtyp", prfl.features()[0].feature_code());
+    assert_eq!(7, prfl.features()[0].feature_value());
+
+    bytes.truncate(24);
+    let mut file = Trickle(std::io::Cursor::new(bytes));
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert!(matches!(PrflAtom::new(header, &mut file), Err(ParseError::TruncatedAtom { expected: 28, got: 24, .. })));
+  }
+
+  #[test]
+  fn an_ftyp_with_part_of_a_brand_is_an_error() {
+    let mut file = std::io::Cursor::new(b"\0\0\0\x13ftypisom\0\0\0\0mp4".to_vec());
+    let header = AtomHeader::new(&mut file).unwrap();
+    match FtypAtom::new(header, &mut file) {
+      Err(err @ ParseError::AtomParseFailed { .. }) =>
+        assert_eq!("ftyp at 0 (0x0): 3 bytes of brands are not a whole number of brands", err.to_string()),
+      other => panic!("expected AtomParseFailed, got {:?}", other),
+    }
+  }
+
+  impl std::fmt::Display for FtypAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Ftyp - Major: {}, Minor: {}, Compatible = [", self.major_brand,
+             String::from_utf8_lossy(&self.minor_version.to_be_bytes()))?;
+      for item in &self.compatible_brands {
+        write!(f, "{},", item)?;
+      }
+      write!(f, "]")?;
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_read_of_ftyp() {
+    let mut file = std::fs::File::open("resources/tests/ftyp.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = header.read_atom(&mut file).unwrap();
+    assert_eq!(header.atom_size() as usize, atom.len());
+  }
+
+  #[derive(Debug, Default, Clone, Copy, PartialEq)]
+  pub struct WideAtom {
+    atom_header: AtomHeader,
+  }
+
+  impl WideAtom {
+    /// Creates a wide atom given a wide atom header. There is nothing to read from the file since
+  /// all the data is in the header
+    pub fn new(atom_header: AtomHeader) -> Result<WideAtom>
+    {
+      Ok(WideAtom { atom_header })
+    }
+  }
+
+  impl AtomLike for WideAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for WideAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Wide: {}", self.atom_header)
+    }
+  }
+
+  #[derive(Debug, Default, Clone, Copy, PartialEq)]
+  pub struct FreeAtom {
+    atom_header: AtomHeader,
+  }
+
+  impl FreeAtom {
+    pub fn new(atom_header: AtomHeader) -> Result<FreeAtom> {
+      Ok(FreeAtom { atom_header })
+    }
+    /// Starts a new free atom that is not read from a file.
+    pub fn builder() -> FreeAtomBuilder { FreeAtomBuilder { atom_size: 8 } }
+    /// Changes the recorded size of the free space, for instance before the tree is written out.
+    pub fn set_atom_size(&mut self, size: u64) {
+      self.atom_header.atom_size = size;
+    }
+    /// Whether an atom of `size` bytes can be written over this free space. Any space left over
+    /// must be big enough to hold the header of a trailing free atom.
+    pub fn can_hold(&self, size: u64) -> bool {
+      size == self.atom_size() || size.saturating_add(8) <= self.atom_size()
+    }
+    /// Overwrites this free region in `file` with the bytes of a complete atom, followed by a
+    /// free atom covering whatever space remains. Only the headers are written, the contents of
+    /// the remaining padding are left as they were.
+    pub fn overwrite_with<T>(&self, file: &mut T, atom: &[u8]) -> Result<()>
+    where T: Write + Seek {
+      let size = atom.len() as u64;
+      if !self.can_hold(size) {
+        return Err(ParseError::NotEnoughSpace { offset: self.atom_location(), needed: size,
+                                                available: self.atom_size() });
+      }
+      file.seek(SeekFrom::Start(self.atom_location()))?;
+      file.write_all(atom)?;
+      let remaining = self.atom_size() - size;
+      if remaining > u32::MAX as u64 {
+        file.write_all(&1u32.to_be_bytes())?;
+        file.write_all(b"free")?;
+        file.write_all(&remaining.to_be_bytes())?;
+      } else if remaining > 0 {
+        file.write_all(&(remaining as u32).to_be_bytes())?;
+        file.write_all(b"free")?;
+      }
+      Ok(())
+    }
+  }
+
+  /// Builds a [`FreeAtom`] from scratch, such as padding to leave room for a later edit.
+  #[derive(Debug, Clone, Copy, PartialEq)]
+  pub struct FreeAtomBuilder {
+    atom_size: u64,
+  }
+
+  impl FreeAtomBuilder {
+    /// The size of the atom, header included. Anything under the 8 bytes of a header is rounded up.
+    pub fn size(mut self, atom_size: u64) -> Self {
+      self.atom_size = atom_size.max(8);
+      self
+    }
+    pub fn build(self) -> FreeAtom {
+      FreeAtom { atom_header: AtomHeader::synthesized(b"free", self.atom_size, 0) }
+    }
+  }
+
+  impl AtomLike for FreeAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  #[test]
+  fn should_overwrite_a_free_region_and_leave_trailing_padding() {
+    let mut bytes = std::fs::read("resources/tests/ftyp.mp4").unwrap();
+    bytes.extend_from_slice(&[0, 0, 0, 64]);
+    bytes.extend_from_slice(b"free");
+    bytes.resize(32 + 64, 0xAA);
+    bytes.extend_from_slice(&[0, 0, 0, 16]);
+    bytes.extend_from_slice(b"mdat");
+    bytes.resize(32 + 64 + 16, 0);
+    let mut file = std::io::Cursor::new(bytes);
+
+    file.seek(SeekFrom::Start(32)).unwrap();
+    let free = FreeAtom::new(AtomHeader::new(&mut file).unwrap()).unwrap();
+    assert!(free.can_hold(64));
+    assert!(free.can_hold(56));
+    assert!(!free.can_hold(60));
+    assert!(!free.can_hold(65));
+
+    let mut injected = vec![0, 0, 0, 24];
+    injected.extend_from_slice(b"blob");
+    injected.resize(24, 0x11);
+    free.overwrite_with(&mut file, &injected).unwrap();
+    assert!(free.overwrite_with(&mut file, &[0; 60]).is_err());
+
+    let len = file.get_ref().len() as u64;
+    let root = RootAtom::new(&mut file, len, &ParseContext::default()).unwrap();
+    let layout: Vec<(&str, u64)> = root.children().iter()
+      .map(|x| (x.atom_type(), x.atom_size())).collect();
+    assert_eq!(vec![("ftyp", 32), ("blob", 24), ("free", 40), ("mdat", 16)], layout);
+  }
+
+  impl std::fmt::Display for FreeAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
       write!(f, "Free: {}", self.atom_header)?;
       Ok(())
     }
   }
 
-  #[derive(Debug, Default, Clone, Copy)]
-  pub struct MdatAtom {
+  #[derive(Debug, Default, Clone, Copy, PartialEq)]
+  pub struct MdatAtom {
+    atom_header: AtomHeader,
+  }
+
+  impl MdatAtom {
+    pub fn new(atom_header: AtomHeader) -> Result<MdatAtom> {
+      Ok(MdatAtom { atom_header })
+    }
+    /// Absolute offset of the media data, just after the header.
+    pub fn payload_offset(&self) -> u64 { self.atom_location() + self.header_size() as u64 }
+    pub fn payload_size(&self) -> u64 { self.atom_size().saturating_sub(self.header_size() as u64) }
+    /// A reader over exactly the media data, read from `source` as it is consumed rather than
+    /// held in memory.
+    pub fn payload_reader<'a, R: Read + Seek>(&self, source: &'a mut R) -> Result<impl Read + 'a> {
+      source.seek(SeekFrom::Start(self.payload_offset()))?;
+      Ok(source.take(self.payload_size()))
+    }
+  }
+
+  #[test]
+  fn should_stream_exactly_the_media_data() {
+    let bytes = std::fs::read("resources/tests/mdat_to_eof.mp4").unwrap();
+    let mut bytes_with_trailer = bytes.clone();
+    bytes_with_trailer.extend_from_slice(&[0xff; 8]);
+    let mut file = std::io::Cursor::new(bytes_with_trailer);
+    file.seek(SeekFrom::Start(32)).unwrap();
+    let mdat = MdatAtom::new(AtomHeader::new_within(&mut file, Some(104)).unwrap()).unwrap();
+    assert_eq!((40, 64), (mdat.payload_offset(), mdat.payload_size()));
+    let mut payload = Vec::new();
+    mdat.payload_reader(&mut file).unwrap().read_to_end(&mut payload).unwrap();
+    assert_eq!(&bytes[40..], &payload[..]);
+  }
+
+  #[test]
+  fn should_refuse_to_read_a_huge_atom_into_memory() {
+    let mut bytes = vec![0, 0, 0, 1];
+    bytes.extend_from_slice(b"mdat");
+    bytes.extend_from_slice(&(1u64 << 40).to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    match header.read_atom(&mut file) {
+      Err(ParseError::AtomTooLarge { atom_type, offset: 0, size, limit: DEFAULT_MAX_ATOM_SIZE }) =>
+        assert_eq!(("mdat", 1 << 40), (atom_type.as_str(), size)),
+      other => panic!("expected AtomTooLarge, got {:?}", other),
+    }
+    assert!(matches!(header.read_atom_with_limit(&mut file, 8), Err(ParseError::AtomTooLarge { limit: 8, .. })));
+  }
+
+  impl AtomLike for MdatAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for MdatAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "mdia: {}", self.atom_header)?;
+      Ok(())
+    }
+  }
+
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct PrflAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    num_features: u32,
+    features: Vec<FeatureEntry>
+  }
+
+  impl PrflAtom {
+    pub fn new(header: AtomHeader, file: &mut dyn ReadSeek) -> Result<PrflAtom> {
+      let mut result = PrflAtom{atom_header: header, ..Default::default() };
+      let mut reader = AtomReader::new(header, file)?;
+      result.full_atom = reader.read_full_atom()?;
+      result.num_features = reader.read_u32()?;
+
+      for _ in 0..result.num_features {
+        result.features.push(FeatureEntry::new(&mut reader)?);
+      }
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> &FullAtom{ &self.full_atom }
+    pub fn num_features(&self) -> u32 { self.num_features }
+    pub fn features(&self) -> &Vec<FeatureEntry> { &self.features }
+  }
+
+  impl AtomLike for PrflAtom {
+    fn atom_size(&self) -> u64 {
+      self.atom_header.atom_size()
+    }
+
+    fn atom_type(&self) -> &str {
+      self.atom_header.atom_type()
+    }
+
+    fn atom_location(&self) -> u64 {
+      self.atom_header.atom_location()
+    }
+
+    fn header_size(&self) -> u32 {
+      self.atom_header.header_size()
+    }
+  }
+  impl std::fmt::Display for PrflAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Prfl: num_features: {}", self.num_features)
+    }
+  }
+
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct FeatureEntry {
+    part_id: u32,
+    feature_code: [u8;4],
+    feature_value: u32,
+  }
+
+  impl FeatureEntry {
+    pub(crate) fn new(reader: &mut AtomReader) -> Result<FeatureEntry> {
+      Ok(FeatureEntry { part_id: reader.read_u32()?, feature_code: reader.read_fourcc()?.raw(),
+                        feature_value: reader.read_u32()? })
+    }
+    pub fn part_id(&self) -> u32 { self.part_id }
+    pub fn feature_code(&self) -> &str { std::str::from_utf8(&self.feature_code).unwrap_or("????") }
+    pub fn feature_value(&self) -> u32 { self.feature_value }
+  }
+  impl std::fmt::Display for FeatureEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "part_id: {:X}, (code, value): ({}, {})",
+             self.part_id(), self.feature_code(), self.feature_value())
+    }
+  }
+
+  /// Seconds from the QuickTime epoch, midnight on January 1 1904, to the Unix epoch.
+  const SECONDS_FROM_1904_TO_1970: i64 = 2_082_844_800;
+
+  /// The time a QuickTime timestamp, in seconds since 1904, stands for. Timestamps before 1970
+  /// come before the Unix epoch.
+  fn time_since_1904(seconds: u64) -> std::time::SystemTime {
+    let unix = seconds as i128 - SECONDS_FROM_1904_TO_1970 as i128;
+    let offset = std::time::Duration::from_secs(unix.unsigned_abs() as u64);
+    if unix < 0 { std::time::UNIX_EPOCH - offset } else { std::time::UNIX_EPOCH + offset }
+  }
+
+  /// A transformation matrix, which maps a point (x, y) to (a x + c y + tx, b x + d y + ty) when
+  /// `u` and `v` are 0 and `w` is 1 as usual.
+  #[derive(Debug, Default, Clone, Copy, PartialEq)]
+  pub struct TransformMatrix {
+    pub a: f64,
+    pub b: f64,
+    pub u: f64,
+    pub c: f64,
+    pub d: f64,
+    pub v: f64,
+    pub tx: f64,
+    pub ty: f64,
+    pub w: f64,
+  }
+
+  impl TransformMatrix {
+    /// Decodes the 36 byte form stored in `mvhd` and `tkhd`, nine 32-bit fixed point values in
+    /// row order. `u`, `v` and `w` are 2.30 fixed point, the rest 16.16.
+    pub fn from_bytes(bytes: &[u8]) -> Option<TransformMatrix> {
+      if bytes.len() < 36 {
+        return None;
+      }
+      let value = |i: usize, fraction_bits: i32| {
+        i32::from_be_bytes([bytes[4 * i], bytes[4 * i + 1], bytes[4 * i + 2], bytes[4 * i + 3]]) as f64
+          / 2f64.powi(fraction_bits)
+      };
+      Some(TransformMatrix { a: value(0, 16), b: value(1, 16), u: value(2, 30), c: value(3, 16), d: value(4, 16),
+                             v: value(5, 30), tx: value(6, 16), ty: value(7, 16), w: value(8, 30) })
+    }
+  }
+
+  /// The mvhd atom is the [movie header atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-BBCGFGJG).
+  /// It holds the time scale and duration of the movie. Version 1 stores the times and the
+  /// duration as 64-bit values.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct MvhdAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    creation_time: u64,
+    modification_time: u64,
+    time_scale: u32,
+    duration: u64,
+    preferred_rate: u32,
+    preferred_vol: u16,
+    matrix: Vec<u8>,
+    preview_time: u32,
+    preview_duration: u32,
+    poster_time: u32,
+    selection_time: u32,
+    selection_duration: u32,
+    current_time: u32,
+    next_track_id: u32
+  }
+
+  impl MvhdAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<MvhdAtom> {
+      let mut reader = AtomReader::new(atom_header, file)?;
+      let mut result = MvhdAtom { atom_header, full_atom: reader.read_full_atom()?, ..Default::default() };
+      let version = result.full_atom.version();
+      result.creation_time = reader.read_versioned(version)?;
+      result.modification_time = reader.read_versioned(version)?;
+      result.time_scale = reader.read_u32()?;
+      result.duration = reader.read_versioned(version)?;
+      result.preferred_rate = reader.read_u32()?;
+      result.preferred_vol = reader.read_u16()?;
+      reader.skip(10)?;
+      result.matrix = reader.read_bytes(36)?.to_vec();
+      result.preview_time = reader.read_u32()?;
+      result.preview_duration = reader.read_u32()?;
+      result.poster_time = reader.read_u32()?;
+      result.selection_time = reader.read_u32()?;
+      result.selection_duration = reader.read_u32()?;
+      result.current_time = reader.read_u32()?;
+      result.next_track_id = reader.read_u32()?;
+      Ok(result)
+    }
+
+    pub fn header(&self) -> &AtomHeader { &self.atom_header}
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// Seconds since midnight on January 1 1904.
+    pub fn creation_time(&self) -> u64 { self.creation_time }
+    pub fn creation_datetime(&self) -> std::time::SystemTime { time_since_1904(self.creation_time) }
+    pub fn modification_time(&self) -> u64 { self.modification_time}
+    pub fn modification_datetime(&self) -> std::time::SystemTime { time_since_1904(self.modification_time) }
+    pub fn time_scale(&self) -> u32 { self.time_scale }
+    /// The duration of the movie in time scale units.
+    pub fn duration(&self) -> u64 { self.duration }
+    /// The duration of the movie in seconds, 0 when there is no time scale.
+    pub fn duration_seconds(&self) -> f64 {
+      if self.time_scale == 0 { 0.0 } else { self.duration as f64 / self.time_scale as f64 }
+    }
+    /// The preferred playback rate as a 16.16 fixed point value.
+    pub fn preferred_rate(&self) -> u32 { self.preferred_rate}
+    pub fn preferred_rate_f32(&self) -> f32 { self.preferred_rate as f32 / 65536.0 }
+    /// The preferred volume as an 8.8 fixed point value.
+    pub fn preferred_vol(&self) -> u16 { self.preferred_vol }
+    pub fn preferred_vol_f32(&self) -> f32 { self.preferred_vol as f32 / 256.0 }
+    pub fn matrix(&self) -> &Vec<u8> { &self.matrix }
+    pub fn matrix_values(&self) -> TransformMatrix { TransformMatrix::from_bytes(&self.matrix).unwrap_or_default() }
+    pub fn preview_time(&self) -> u32 { self.preview_time }
+    pub fn preview_duration(&self) -> u32 { self.preview_duration}
+    pub fn poster_time(&self) -> u32 { self.poster_time }
+    pub fn selection_time(&self) -> u32 { self.selection_time }
+    pub fn selection_duration(&self) -> u32 { self.selection_duration }
+    pub fn current_time(&self) -> u32 {self.current_time}
+    pub fn next_track_id(&self) -> u32 {self.next_track_id}
+  }
+  impl AtomLike for MvhdAtom {
+    fn atom_size(&self) -> u64 {
+      self.header().atom_size()
+    }
+
+    fn atom_type(&self) -> &str {
+      self.header().atom_type()
+    }
+
+    fn atom_location(&self) -> u64 {
+      self.header().atom_location()
+    }
+
+    fn header_size(&self) -> u32 {
+      self.header().header_size()
+    }
+  }
+
+  impl std::fmt::Display for MvhdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Mvhd: {}, duration: {:.3}s, rate: {}", self.header(), self.duration_seconds(), self.preferred_rate_f32())
+    }
+  }
+
+  #[test]
+  fn should_read_an_mvhd_atom() {
+    let mut file = std::fs::File::open("resources/tests/mvhd.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = MvhdAtom::new(header, &mut file).unwrap();
+    assert_eq!(108, atom.atom_size());
+    assert_eq!(0, atom.full_atom().version());
+    assert_eq!(0, atom.full_atom().flags());
+    assert_eq!(2082844800, atom.creation_time());
+    assert_eq!(2082844800, atom.modification_time());
+    assert_eq!(1000, atom.time_scale());
+    assert_eq!(973753, atom.duration());
+    assert_eq!(3, atom.next_track_id());
+    assert_eq!(std::time::UNIX_EPOCH, atom.creation_datetime());
+    assert_eq!(973.753, atom.duration_seconds());
+    assert_eq!((1.0, 1.0), (atom.preferred_rate_f32(), atom.preferred_vol_f32()));
+    let identity = TransformMatrix { a: 1.0, d: 1.0, w: 1.0, ..Default::default() };
+    assert_eq!(identity, atom.matrix_values());
+    assert_eq!("Mvhd: type: mvhd, size: 108, location: 0, duration: 973.753s, rate: 1", atom.to_string());
+  }
+
+  #[test]
+  fn should_say_which_field_of_a_short_mvhd_is_cut_short() {
+    let mut bytes = std::fs::read("resources/tests/mvhd.mp4").unwrap();
+    bytes.truncate(30);
+    bytes[..4].copy_from_slice(&30u32.to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let err = MvhdAtom::new(header, &mut file).unwrap_err();
+    assert!(matches!(err, ParseError::TruncatedField { offset: 28, expected: 4, got: 2, .. }), "{:?}", err);
+  }
+
+  #[test]
+  fn should_read_a_version_1_mvhd_atom() {
+    let mut bytes = vec![0, 0, 0, 120];
+    bytes.extend_from_slice(b"mvhd");
+    bytes.extend_from_slice(&[1, 0, 0, 0]);
+    bytes.extend_from_slice(&1_000_000_000u64.to_be_bytes());
+    bytes.extend_from_slice(&(1u64 << 33).to_be_bytes());
+    bytes.extend_from_slice(&90000u32.to_be_bytes());
+    bytes.extend_from_slice(&(1u64 << 34).to_be_bytes());
+    bytes.extend_from_slice(&0x0002_8000u32.to_be_bytes());
+    bytes.extend_from_slice(&0x0080u16.to_be_bytes());
+    bytes.extend_from_slice(&[0; 10]);
+    for value in [0i32, 0x10000, 0, -0x10000, 0, 0, 0x140000, 0, 0x4000_0000] {
+      bytes.extend_from_slice(&value.to_be_bytes());
+    }
+    bytes.extend_from_slice(&[0; 24]);
+    bytes.extend_from_slice(&7u32.to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = MvhdAtom::new(header, &mut file).unwrap();
+    assert_eq!(1, atom.full_atom().version());
+    assert_eq!((1 << 33, 1 << 34), (atom.modification_time(), atom.duration()));
+    assert_eq!((1u64 << 34) as f64 / 90000.0, atom.duration_seconds());
+    // 1935-09-09, before the Unix epoch.
+    let before_1970 = std::time::UNIX_EPOCH.duration_since(atom.creation_datetime()).unwrap();
+    assert_eq!(1_082_844_800, before_1970.as_secs());
+    assert_eq!((2.5, 0.5), (atom.preferred_rate_f32(), atom.preferred_vol_f32()));
+    let rotated = TransformMatrix { b: 1.0, c: -1.0, tx: 20.0, w: 1.0, ..Default::default() };
+    assert_eq!(rotated, atom.matrix_values());
+    assert_eq!(7, atom.next_track_id());
+  }
+
+  #[test]
+  fn should_report_an_mvhd_atom_cut_short() {
+    let mut bytes = std::fs::read("resources/tests/mvhd.mp4").unwrap();
+    bytes.truncate(50);
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    match Atoms::parse(header, &mut file, &ParseContext::default()) {
+      Err(ParseError::TruncatedAtom { atom_type, offset: 0, expected: 108, got: 50 }) => assert_eq!(atom_type, "mvhd"),
+      other => panic!("expected TruncatedAtom, got {:?}", other),
+    }
+  }
+  /// The stco atom is the [chunk offset atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-BBCGAEBF).
+  /// It stores the absolute file offset of every chunk of media data in a track as 32-bit values.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct StcoAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    chunk_offsets: Vec<u32>,
+  }
+
+  impl StcoAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<StcoAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut chunk_offsets = Vec::with_capacity((num_entries as usize).min(bytes.len() / 4));
+      for _ in 0..num_entries {
+        chunk_offsets.push(read_u32(&mut bytes)?);
+      }
+      Ok(StcoAtom { atom_header, full_atom, chunk_offsets })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn chunk_offsets(&self) -> &[u32] { &self.chunk_offsets }
+    pub fn chunk_offsets_mut(&mut self) -> &mut Vec<u32> { &mut self.chunk_offsets }
+  }
+
+  impl ChunkOffsetTable for StcoAtom {
+    fn iter_offsets(&self) -> Box<dyn Iterator<Item = u64> + '_> { Box::new(self.chunk_offsets.iter().map(|x| *x as u64)) }
+    fn len(&self) -> usize { self.chunk_offsets.len() }
+  }
+
+  impl AtomLike for StcoAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for StcoAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Stco: {}, chunks: {}", self.atom_header, self.chunk_offsets.len())
+    }
+  }
+
+  /// The stsz atom is the [sample size atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25710).
+  /// It holds either one size shared by every sample or a table with the size of each sample.
+  /// When the parse skips sample tables only the sample count is kept.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct StszAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    sample_size: u32,
+    sample_count: u32,
+    sample_sizes: Vec<u32>,
+  }
+
+  impl StszAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek, ctx: &ParseContext) -> Result<StszAtom> {
+      file.seek(SeekFrom::Start(atom_header.atom_location() + atom_header.header_size() as u64))?;
+      let full_atom = FullAtom::new(file)?;
+      let sample_size = read_u32(file)?;
+      let sample_count = read_u32(file)?;
+      let mut result = StszAtom { atom_header, full_atom, sample_size, sample_count, ..Default::default() };
+      if sample_size == 0 && !ctx.skip_sample_tables() {
+        let table_size = sample_count as u64 * 4;
+        if table_size > atom_header.atom_size().saturating_sub(atom_header.header_size() as u64 + 12) {
+          return Err(ParseError::parse_failed(&atom_header, format!("{} samples do not fit in {} bytes",
+                                                                    sample_count, atom_header.atom_size())));
+        }
+        let mut buf = vec![0; table_size as usize];
+        file.read_exact(&mut buf)?;
+        result.sample_sizes = buf.chunks_exact(4).map(|x| u32::from_be_bytes([x[0], x[1], x[2], x[3]])).collect();
+      }
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// The size shared by every sample, or 0 when each sample has its own size.
+    pub fn sample_size(&self) -> u32 { self.sample_size }
+    pub fn sample_count(&self) -> u32 { self.sample_count }
+    /// The size of each sample. Empty when the samples share one size or the table was skipped.
+    pub fn sample_sizes(&self) -> &[u32] { &self.sample_sizes }
+  }
+
+  impl AtomLike for StszAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for StszAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Stsz: {}, samples: {}", self.atom_header, self.sample_count)
+    }
+  }
+
+  #[test]
+  fn skipping_sample_tables_keeps_only_the_stsz_count() {
+    let sample_count: u32 = 1_000_000;
+    let mut bytes = (20 + sample_count * 4).to_be_bytes().to_vec();
+    bytes.extend_from_slice(b"stsz");
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(&sample_count.to_be_bytes());
+    for sample in 0..sample_count {
+      bytes.extend_from_slice(&(sample % 4096).to_be_bytes());
+    }
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+
+    let start = std::time::Instant::now();
+    let full = StszAtom::new(header, &mut file, &ParseContext::default()).unwrap();
+    let full_time = start.elapsed();
+    let start = std::time::Instant::now();
+    let skipped = StszAtom::new(header, &mut file, &ParseContext::default().with_skip_sample_tables(true)).unwrap();
+    let skipped_time = start.elapsed();
+
+    assert_eq!(sample_count as usize, full.sample_sizes().len());
+    assert_eq!(4095, full.sample_sizes()[4095]);
+    assert_eq!(sample_count, skipped.sample_count());
+    assert!(skipped.sample_sizes().is_empty());
+    assert!(skipped_time < full_time, "skipped: {:?}, full: {:?}", skipped_time, full_time);
+  }
+
+  /// One run of the sample-to-chunk table: from `first_chunk` on, counting from 1, every chunk
+  /// holds `samples_per_chunk` samples described by the entry at `sample_description_index` of
+  /// `stsd`, until the first chunk of the next run.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+  pub struct SampleToChunkEntry {
+    pub first_chunk: u32,
+    pub samples_per_chunk: u32,
+    pub sample_description_index: u32,
+  }
+
+  /// The stsc atom is the [sample-to-chunk atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25706).
+  /// It maps the samples of a track to the chunks that hold them.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct StscAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    entries: Vec<SampleToChunkEntry>,
+  }
+
+  impl StscAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<StscAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut entries = Vec::with_capacity((num_entries as usize).min(bytes.len() / 12));
+      for _ in 0..num_entries {
+        entries.push(SampleToChunkEntry {
+          first_chunk: read_u32(&mut bytes)?,
+          samples_per_chunk: read_u32(&mut bytes)?,
+          sample_description_index: read_u32(&mut bytes)?,
+        });
+      }
+      Ok(StscAtom { atom_header, full_atom, entries })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn entries(&self) -> &Vec<SampleToChunkEntry> { &self.entries }
+  }
+
+  impl AtomLike for StscAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for StscAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Stsc: {}, entries: {}", self.atom_header, self.entries.len())
+    }
+  }
+
+  #[test]
+  fn should_read_the_sample_to_chunk_tables_of_the_sample() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(198605)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let video = StscAtom::new(header, &mut file).unwrap();
+    assert_eq!(2, video.entries().len());
+    assert_eq!(SampleToChunkEntry { first_chunk: 1, samples_per_chunk: 3, sample_description_index: 1 },
+               video.entries()[0]);
+    file.seek(SeekFrom::Start(432545)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let audio = StscAtom::new(header, &mut file).unwrap();
+    assert_eq!(25503, audio.entries().len());
+    assert!(audio.entries().windows(2).all(|x| x[0].first_chunk < x[1].first_chunk));
+  }
+
+  /// The co64 atom is the 64-bit variant of the chunk offset atom, used when media data lives
+  /// beyond the first 4 GiB of a file.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct Co64Atom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    chunk_offsets: Vec<u64>,
+  }
+
+  impl Co64Atom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<Co64Atom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut chunk_offsets = Vec::with_capacity((num_entries as usize).min(bytes.len() / 8));
+      for _ in 0..num_entries {
+        chunk_offsets.push(read_u64(&mut bytes)?);
+      }
+      Ok(Co64Atom { atom_header, full_atom, chunk_offsets })
+    }
+    /// A co64 atom holding `chunk_offsets`, for instance to replace an stco atom whose offsets no
+    /// longer fit in 32 bits.
+    pub fn from_offsets(full_atom: FullAtom, chunk_offsets: Vec<u64>, atom_location: u64) -> Co64Atom {
+      let atom_size = 16 + 8 * chunk_offsets.len() as u64;
+      Co64Atom { atom_header: AtomHeader::synthesized(b"co64", atom_size, atom_location), full_atom, chunk_offsets }
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn chunk_offsets(&self) -> &[u64] { &self.chunk_offsets }
+    pub fn chunk_offsets_mut(&mut self) -> &mut Vec<u64> { &mut self.chunk_offsets }
+  }
+
+  impl ChunkOffsetTable for Co64Atom {
+    fn iter_offsets(&self) -> Box<dyn Iterator<Item = u64> + '_> { Box::new(self.chunk_offsets.iter().copied()) }
+    fn len(&self) -> usize { self.chunk_offsets.len() }
+  }
+
+  impl AtomLike for Co64Atom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for Co64Atom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Co64: {}, chunks: {}", self.atom_header, self.chunk_offsets.len())
+    }
+  }
+
+  #[test]
+  fn should_read_the_chunk_offsets_of_an_stco_atom() {
+    let mut file = std::fs::File::open("resources/tests/trak.mp4").unwrap();
+    file.seek(SeekFrom::Start(315245)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = StcoAtom::new(header, &mut file).unwrap();
+    assert_eq!(29180, atom.len());
+    assert_eq!(0xf9ceb, atom.offsets()[0]);
+  }
+
+  #[test]
+  fn should_iterate_over_chunk_offsets_without_collecting_them() {
+    let mut file = std::fs::File::open("resources/tests/trak.mp4").unwrap();
+    file.seek(SeekFrom::Start(315245)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let stco = StcoAtom::new(header, &mut file).unwrap();
+    let co64 = Co64Atom::from_offsets(stco.full_atom().clone(), stco.offsets(), 0);
+    for table in [&stco as &dyn ChunkOffsetTable, &co64] {
+      assert_eq!(table.len(), table.iter_offsets().count());
+      assert_eq!(stco.chunk_offsets().iter().map(|x| *x as u64).sum::<u64>(), table.iter_offsets().sum::<u64>());
+      assert_eq!(Some(0xf9ceb), table.iter_offsets().next());
+    }
+  }
+
+  /// The stsd atom is the [sample description atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25691).
+  /// It holds one entry per encoding used by the samples of a track. Each entry starts with a
+  /// common header naming the data format (`avc1`, `mp4a`, ...) followed by media specific fields.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct StsdAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    entries: Vec<SampleDescription>,
+  }
+
+  impl StsdAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<StsdAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut entries = Vec::new();
+      for _ in 0..num_entries {
+        let mut size = [0; 4];
+        size.clone_from_slice(bytes.get(0..4).ok_or_else(|| {
+          ParseError::parse_failed(&atom_header, "a sample description is cut short")
+        })?);
+        let size = u32::from_be_bytes(size) as usize;
+        let entry = bytes.get(0..size).ok_or_else(|| {
+          ParseError::parse_failed(&atom_header, format!("a sample description of size {} is cut short", size))
+        })?;
+        let location = atom_header.atom_location() + (buf.len() - bytes.len()) as u64;
+        entries.push(SampleDescription::new_at(entry, location)?);
+        bytes = &bytes[size..];
+      }
+      Ok(StsdAtom { atom_header, full_atom, entries })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn entries(&self) -> &Vec<SampleDescription> { &self.entries }
+  }
+
+  impl AtomLike for StsdAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for StsdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Stsd: {}, entries: [", self.atom_header)?;
+      for entry in &self.entries {
+        write!(f, "{},", entry)?;
+      }
+      write!(f, "]")
+    }
+  }
+
+  /// Data formats whose sample description follows the sound sample description layout.
+  const AUDIO_FORMATS: [&str; 16] = ["mp4a", "ac-3", "ec-3", "alac", "Opus", "fLaC", "sowt", "twos",
+    "lpcm", "in24", "in32", "fl32", "fl64", "raw ", "ulaw", "alaw"];
+
+  /// Data formats whose sample description follows the video sample description layout.
+  const VIDEO_FORMATS: [&str; 20] = ["avc1", "avc3", "hvc1", "hev1", "mp4v", "jpeg", "mjpa", "mjpb",
+    "apcn", "apch", "apcs", "apco", "ap4h", "ap4x", "vp08", "vp09", "av01", "2vuy", "s263", "h263"];
+
+  /// One entry of an [`StsdAtom`].
+  #[derive(Debug, Clone, PartialEq)]
+  pub enum SampleDescription {
+    Video(VideoSampleEntry),
+    Audio(AudioSampleEntry),
+    Timecode(TmcdSampleDescription),
+    Other(SampleEntry),
+  }
+
+  impl SampleDescription {
+    /// Parses an entry from its bytes, starting at the entry's size field. The locations of its
+    /// extension atoms are relative to the start of the entry.
+    pub fn new(bytes: &[u8]) -> Result<SampleDescription> {
+      SampleDescription::new_at(bytes, 0)
+    }
+    /// Parses an entry that starts at `location` in the file.
+    pub fn new_at(bytes: &[u8], location: u64) -> Result<SampleDescription> {
+      let entry_bytes = bytes;
+      let mut bytes = bytes;
+      let entry = SampleEntry::new(&mut bytes)?;
+      let extensions_at = |bytes: &[u8]| {
+        let offset = entry_bytes.len() - bytes.len();
+        read_extension_headers(bytes, location + offset as u64)
+      };
+      if VIDEO_FORMATS.contains(&entry.data_format()) {
+        let mut video = VideoSampleEntry::new(entry, &mut bytes)?;
+        video.extensions = extensions_at(bytes);
+        if let Some(avcc) = video.extensions.iter().find(|header| header.atom_type() == "avcC") {
+          video.avc_configuration = Some(AvcCAtom::from_payload(*avcc, extension_payload(entry_bytes, location, avcc))?);
+        }
+        Ok(SampleDescription::Video(video))
+      } else if AUDIO_FORMATS.contains(&entry.data_format()) {
+        let mut audio = AudioSampleEntry::new(entry, &mut bytes)?;
+        audio.extensions = extensions_at(bytes);
+        if let Some(esds) = audio.extensions.iter().find(|header| header.atom_type() == "esds") {
+          audio.esds = Some(EsdsAtom::from_payload(*esds, extension_payload(entry_bytes, location, esds))?);
+        }
+        Ok(SampleDescription::Audio(audio))
+      } else if entry.data_format() == "tmcd" {
+        let mut timecode = TmcdSampleDescription::new(entry, &mut bytes)?;
+        timecode.extensions = extensions_at(bytes);
+        Ok(SampleDescription::Timecode(timecode))
+      } else {
+        Ok(SampleDescription::Other(entry))
+      }
+    }
+    /// The headers of the atoms that follow the fixed fields of the entry, such as `avcC` or
+    /// `esds`. Entries of unknown formats have no known layout, so this is empty for them.
+    pub fn extensions(&self) -> &[AtomHeader] {
+      match self {
+        SampleDescription::Video(video) => &video.extensions,
+        SampleDescription::Audio(audio) => &audio.extensions,
+        SampleDescription::Timecode(timecode) => &timecode.extensions,
+        SampleDescription::Other(_) => &[],
+      }
+    }
+    pub fn entry(&self) -> &SampleEntry {
+      match self {
+        SampleDescription::Video(video) => video.entry(),
+        SampleDescription::Audio(audio) => audio.entry(),
+        SampleDescription::Timecode(timecode) => timecode.entry(),
+        SampleDescription::Other(entry) => entry,
+      }
+    }
+  }
+
+  impl std::fmt::Display for SampleDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      match self {
+        SampleDescription::Video(video) => write!(f, "{}", video),
+        SampleDescription::Audio(audio) => write!(f, "{}", audio),
+        SampleDescription::Timecode(timecode) => write!(f, "{}", timecode),
+        SampleDescription::Other(entry) => write!(f, "{}", entry),
+      }
+    }
+  }
+
+  /// Timecode counts frames that are dropped from the count to keep it in step with the clock.
+  const TIMECODE_DROP_FRAME: u32 = 0x1;
+  /// Timecode wraps around after 24 hours.
+  const TIMECODE_24_HOUR_MAX: u32 = 0x2;
+  /// Timecode can be negative.
+  const TIMECODE_NEGATIVE_TIMES_OK: u32 = 0x4;
+  /// The samples count something other than frames.
+  const TIMECODE_COUNTER: u32 = 0x8;
+
+  /// The [timecode sample description](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap3/qtff3.html#//apple_ref/doc/uid/TP40000939-CH205-BBCGDEBA)
+  /// of a timecode track. Each sample of the track is the frame number the timecode starts
+  /// counting from, see [`crate::timecode_for_track`].
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct TmcdSampleDescription {
+    entry: SampleEntry,
+    flags: u32,
+    time_scale: u32,
+    frame_duration: u32,
+    number_of_frames: u8,
+    extensions: Vec<AtomHeader>,
+  }
+
+  impl TmcdSampleDescription {
+    pub fn new(entry: SampleEntry, file: &mut dyn Read) -> Result<TmcdSampleDescription> {
+      read_u32(file)?;
+      let result = TmcdSampleDescription {
+        entry,
+        flags: read_u32(file)?,
+        time_scale: read_u32(file)?,
+        frame_duration: read_u32(file)?,
+        number_of_frames: read_u8(file)?,
+        extensions: Vec::new(),
+      };
+      read_u8(file)?;
+      Ok(result)
+    }
+    pub fn entry(&self) -> &SampleEntry { &self.entry }
+    pub fn flags(&self) -> u32 { self.flags }
+    pub fn is_drop_frame(&self) -> bool { self.flags & TIMECODE_DROP_FRAME != 0 }
+    pub fn is_24_hour_max(&self) -> bool { self.flags & TIMECODE_24_HOUR_MAX != 0 }
+    pub fn allows_negative_times(&self) -> bool { self.flags & TIMECODE_NEGATIVE_TIMES_OK != 0 }
+    pub fn is_counter(&self) -> bool { self.flags & TIMECODE_COUNTER != 0 }
+    pub fn time_scale(&self) -> u32 { self.time_scale }
+    /// How long a frame lasts, in time scale units.
+    pub fn frame_duration(&self) -> u32 { self.frame_duration }
+    /// The frames in a second of timecode, 30 for 29.97 frames a second.
+    pub fn number_of_frames(&self) -> u8 { self.number_of_frames }
+    /// The frame rate, 0 when the frames have no duration.
+    pub fn frame_rate(&self) -> f64 {
+      if self.frame_duration == 0 { 0.0 } else { self.time_scale as f64 / self.frame_duration as f64 }
+    }
+  }
+
+  impl std::fmt::Display for TmcdSampleDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "{} {:.2} fps, frames: {}", self.entry, self.frame_rate(), self.number_of_frames)?;
+      if self.is_drop_frame() {
+        write!(f, ", drop frame")?;
+      }
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn should_read_a_timecode_sample_description() {
+    let mut bytes = vec![0, 0, 0, 34];
+    bytes.extend_from_slice(b"tmcd");
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 3]);
+    bytes.extend_from_slice(&30000u32.to_be_bytes());
+    bytes.extend_from_slice(&1001u32.to_be_bytes());
+    bytes.extend_from_slice(&[30, 0]);
+    let timecode = match SampleDescription::new(&bytes).unwrap() {
+      SampleDescription::Timecode(timecode) => timecode,
+      other => panic!("expected a timecode description, got {:?}", other),
+    };
+    assert_eq!((30000, 1001, 30), (timecode.time_scale(), timecode.frame_duration(), timecode.number_of_frames()));
+    assert!(timecode.is_drop_frame() && timecode.is_24_hour_max());
+    assert!(!timecode.allows_negative_times() && !timecode.is_counter());
+    assert_eq!("tmcd 29.97 fps, frames: 30, drop frame", timecode.to_string());
+    assert!(matches!(SampleDescription::new(&bytes[..30]), Err(ParseError::IoError(_))));
+  }
+
+  /// The headers of the atoms packed one after another in `bytes`, which start at `location`.
+  /// Stops at the first one that is not a well formed atom.
+  fn read_extension_headers(bytes: &[u8], location: u64) -> Vec<AtomHeader> {
+    let mut headers = Vec::new();
+    let mut offset = 0;
+    while bytes.len() - offset >= 8 {
+      let mut cursor = std::io::Cursor::new(&bytes[offset..]);
+      match AtomHeader::new(&mut cursor) {
+        Ok(mut header) if header.atom_size >= header.header_size as u64
+          && header.atom_size <= (bytes.len() - offset) as u64 => {
+          header.atom_location += location + offset as u64;
+          offset += header.atom_size as usize;
+          headers.push(header);
+        }
+        _ => break,
+      }
+    }
+    headers
+  }
+
+  /// The bytes after the header of `extension`, one of the extensions read from `entry` at
+  /// `location` by [`read_extension_headers`].
+  fn extension_payload<'a>(entry: &'a [u8], location: u64, extension: &AtomHeader) -> &'a [u8] {
+    let start = (extension.atom_location - location) as usize;
+    &entry[start + extension.header_size as usize..start + extension.atom_size as usize]
+  }
+
+  /// The header shared by every sample description entry.
+  #[derive(Debug, Default, Clone, Copy, PartialEq)]
+  pub struct SampleEntry {
+    size: u32,
+    data_format: [u8;4],
+    data_reference_index: u16,
+  }
+
+  impl SampleEntry {
+    pub fn new(file: &mut dyn Read) -> Result<SampleEntry> {
+      let mut result = SampleEntry { size: read_u32(file)?, ..Default::default() };
+      file.read_exact(&mut result.data_format)?;
+      let mut reserved = [0; 6];
+      file.read_exact(&mut reserved)?;
+      result.data_reference_index = read_u16(file)?;
+      Ok(result)
+    }
+    pub fn size(&self) -> u32 { self.size }
+    pub fn data_format(&self) -> &str { std::str::from_utf8(&self.data_format).unwrap_or("????") }
+    pub fn data_reference_index(&self) -> u16 { self.data_reference_index }
+  }
+
+  impl std::fmt::Display for SampleEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "{}", self.data_format())
+    }
+  }
+
+  /// Sample description of a video track.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct VideoSampleEntry {
+    entry: SampleEntry,
+    version: u16,
+    revision_level: u16,
+    vendor: u32,
+    temporal_quality: u32,
+    spatial_quality: u32,
+    width: u16,
+    height: u16,
+    horizontal_resolution: u32,
+    vertical_resolution: u32,
+    data_size: u32,
+    frame_count: u16,
+    compressor_name: [u8;32],
+    depth: u16,
+    color_table_id: u16,
+    extensions: Vec<AtomHeader>,
+    avc_configuration: Option<AvcCAtom>,
+  }
+
+  impl VideoSampleEntry {
+    pub fn new(entry: SampleEntry, file: &mut dyn Read) -> Result<VideoSampleEntry> {
+      let mut result = VideoSampleEntry {
+        entry,
+        version: read_u16(file)?,
+        revision_level: read_u16(file)?,
+        vendor: read_u32(file)?,
+        temporal_quality: read_u32(file)?,
+        spatial_quality: read_u32(file)?,
+        width: read_u16(file)?,
+        height: read_u16(file)?,
+        horizontal_resolution: read_u32(file)?,
+        vertical_resolution: read_u32(file)?,
+        data_size: read_u32(file)?,
+        frame_count: read_u16(file)?,
+        ..Default::default()
+      };
+      file.read_exact(&mut result.compressor_name)?;
+      result.depth = read_u16(file)?;
+      result.color_table_id = read_u16(file)?;
+      Ok(result)
+    }
+    pub fn entry(&self) -> &SampleEntry { &self.entry }
+    pub fn version(&self) -> u16 { self.version }
+    pub fn revision_level(&self) -> u16 { self.revision_level }
+    pub fn vendor(&self) -> u32 { self.vendor }
+    pub fn temporal_quality(&self) -> u32 { self.temporal_quality }
+    pub fn spatial_quality(&self) -> u32 { self.spatial_quality }
+    /// Encoded width and height in pixels.
+    pub fn dimensions(&self) -> (u16, u16) { (self.width, self.height) }
+    /// Horizontal resolution in pixels per inch, decoded from 16.16 fixed point.
+    pub fn horizontal_resolution(&self) -> f64 { self.horizontal_resolution as f64 / 65536.0 }
+    /// Vertical resolution in pixels per inch, decoded from 16.16 fixed point.
+    pub fn vertical_resolution(&self) -> f64 { self.vertical_resolution as f64 / 65536.0 }
+    pub fn data_size(&self) -> u32 { self.data_size }
+    pub fn frame_count(&self) -> u16 { self.frame_count }
+    /// The compressor name, stored as a Pascal string in a 32 byte field.
+    pub fn compressor_name(&self) -> String {
+      let len = (self.compressor_name[0] as usize).min(31);
+      String::from_utf8_lossy(&self.compressor_name[1..=len]).to_string()
+    }
+    pub fn depth(&self) -> u16 { self.depth }
+    pub fn color_table_id(&self) -> u16 { self.color_table_id }
+    /// The `avcC` atom of an H.264 sample description.
+    pub fn avc_configuration(&self) -> Option<&AvcCAtom> { self.avc_configuration.as_ref() }
+  }
+
+  impl std::fmt::Display for VideoSampleEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "{} {}x{}, depth: {}", self.entry, self.width, self.height, self.depth)?;
+      if self.compressor_name[0] > 0 {
+        write!(f, ", compressor: {}", self.compressor_name())?;
+      }
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn should_give_the_offset_of_a_sample_description_table_that_is_cut_short() {
+    let mut bytes = std::fs::read("resources/tests/moov.mp4").unwrap();
+    assert_eq!(b"stsd", &bytes[413..417]);
+    bytes[425..429].copy_from_slice(&0x1000u32.to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    file.seek(SeekFrom::Start(409)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let err = StsdAtom::new(header, &mut file).unwrap_err();
+    match &err {
+      ParseError::AtomParseFailed { atom_type, offset: 409, .. } => assert_eq!(*atom_type, "stsd"),
+      other => panic!("expected AtomParseFailed, got {:?}", other),
+    }
+    assert_eq!("stsd at 409 (0x199): a sample description of size 4096 is cut short", err.to_string());
+  }
+
+  #[test]
+  fn should_read_the_video_fields_of_an_avc_sample_description() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(441)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = StsdAtom::new(header, &mut file).unwrap();
+    match &atom.entries()[0] {
+      SampleDescription::Video(video) => {
+        assert_eq!("avc1", video.entry().data_format());
+        assert_eq!((960, 540), video.dimensions());
+        assert_eq!(24, video.depth());
+        assert_eq!(72.0, video.horizontal_resolution());
+        assert_eq!(1, video.frame_count());
+        assert_eq!("", video.compressor_name());
+      }
+      other => panic!("expected a video entry, got {:?}", other),
+    }
+    let extensions: Vec<(&str, u64)> = atom.entries()[0].extensions().iter()
+      .map(|header| (header.atom_type(), header.atom_location())).collect();
+    assert_eq!(vec![("avcC", 441 + 16 + 86)], extensions);
+    file.seek(SeekFrom::Start(extensions[0].1 + 4)).unwrap();
+    let mut four_cc = [0; 4];
+    file.read_exact(&mut four_cc).unwrap();
+    assert_eq!(b"avcC", &four_cc);
+  }
+
+  #[test]
+  fn should_read_the_compressor_name_of_a_video_sample_description() {
+    let mut entry = vec![0, 0, 0, 86];
+    entry.extend_from_slice(b"avc1");
+    entry.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    entry.extend_from_slice(&[0; 16]);
+    entry.extend_from_slice(&1920u16.to_be_bytes());
+    entry.extend_from_slice(&1080u16.to_be_bytes());
+    entry.extend_from_slice(&[0, 0x48, 0, 0, 0, 0x48, 0, 0, 0, 0, 0, 0, 0, 1]);
+    let mut name = [0u8; 32];
+    name[0] = 10;
+    name[1..11].clone_from_slice(b"AVC Coding");
+    entry.extend_from_slice(&name);
+    entry.extend_from_slice(&[0, 0x18, 0xff, 0xff]);
+    match SampleDescription::new(&entry).unwrap() {
+      SampleDescription::Video(video) => {
+        assert_eq!((1920, 1080), video.dimensions());
+        assert_eq!("AVC Coding", video.compressor_name());
+        assert_eq!(24, video.depth());
+      }
+      other => panic!("expected a video entry, got {:?}", other),
+    }
+  }
+
+  /// Sample description of a sound track, covering the fixed fields of the version 0 sound
+  /// sample description and those QuickTime adds in
+  /// [versions 1 and 2](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap3/qtff3.html#//apple_ref/doc/uid/TP40000939-CH205-SW1).
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct AudioSampleEntry {
+    entry: SampleEntry,
+    version: u16,
+    revision_level: u16,
+    vendor: u32,
+    channel_count: u16,
+    sample_size: u16,
+    compression_id: u16,
+    packet_size: u16,
+    sample_rate: u32,
+    v1: Option<SoundDescriptionV1>,
+    v2: Option<SoundDescriptionV2>,
+    extensions: Vec<AtomHeader>,
+    esds: Option<EsdsAtom>,
+  }
+
+  /// The fields a version 1 sound sample description adds, which describe how compressed audio
+  /// is packed.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+  pub struct SoundDescriptionV1 {
+    pub samples_per_packet: u32,
+    pub bytes_per_packet: u32,
+    pub bytes_per_frame: u32,
+    pub bytes_per_sample: u32,
+  }
+
+  /// The fields of a version 2 sound sample description, which replace the version 0 ones with
+  /// room for sample rates above 65535 Hz and any number of channels. The version 0 fields are
+  /// then set to fixed values.
+  #[derive(Debug, Default, Clone, Copy, PartialEq)]
+  pub struct SoundDescriptionV2 {
+    /// The size of the sample description up to its extension atoms.
+    pub size_of_struct_only: u32,
+    pub sample_rate: f64,
+    pub channel_count: u32,
+    /// The bits of each channel of an uncompressed sample, 0 for compressed audio.
+    pub const_bits_per_channel: u32,
+    /// Flags that depend on the format, such as whether LPCM samples are floats or big endian.
+    pub format_specific_flags: u32,
+    /// The bytes of every packet when they are all the same size, otherwise 0.
+    pub const_bytes_per_audio_packet: u32,
+    /// The frames of every packet when they all hold the same number, otherwise 0.
+    pub const_lpcm_frames_per_audio_packet: u32,
+  }
+
+  impl AudioSampleEntry {
+    pub fn new(entry: SampleEntry, file: &mut dyn Read) -> Result<AudioSampleEntry> {
+      let mut audio = AudioSampleEntry {
+        entry,
+        version: read_u16(file)?,
+        revision_level: read_u16(file)?,
+        vendor: read_u32(file)?,
+        channel_count: read_u16(file)?,
+        sample_size: read_u16(file)?,
+        compression_id: read_u16(file)?,
+        packet_size: read_u16(file)?,
+        sample_rate: read_u32(file)?,
+        ..Default::default()
+      };
+      match audio.version {
+        1 => audio.v1 = Some(SoundDescriptionV1 { samples_per_packet: read_u32(file)?, bytes_per_packet: read_u32(file)?,
+                                                  bytes_per_frame: read_u32(file)?, bytes_per_sample: read_u32(file)? }),
+        2 => {
+          let size_of_struct_only = read_u32(file)?;
+          let sample_rate = f64::from_bits(read_u64(file)?);
+          let channel_count = read_u32(file)?;
+          // Always 0x7f000000.
+          read_u32(file)?;
+          audio.v2 = Some(SoundDescriptionV2 { size_of_struct_only, sample_rate, channel_count,
+                                               const_bits_per_channel: read_u32(file)?,
+                                               format_specific_flags: read_u32(file)?,
+                                               const_bytes_per_audio_packet: read_u32(file)?,
+                                               const_lpcm_frames_per_audio_packet: read_u32(file)? });
+        }
+        _ => (),
+      }
+      Ok(audio)
+    }
+    pub fn entry(&self) -> &SampleEntry { &self.entry }
+    pub fn version(&self) -> u16 { self.version }
+    pub fn revision_level(&self) -> u16 { self.revision_level }
+    pub fn vendor(&self) -> u32 { self.vendor }
+    /// The channel count field of the version 0 fields, always 3 in a version 2 description, see
+    /// [`AudioSampleEntry::channels`].
+    pub fn channel_count(&self) -> u16 { self.channel_count }
+    /// The number of channels, whatever the version of the description.
+    pub fn channels(&self) -> u32 {
+      self.v2.map_or(u32::from(self.channel_count), |v2| v2.channel_count)
+    }
+    /// The bits of each sample of a channel, from the version 2 fields when there are any.
+    pub fn bits_per_sample(&self) -> u16 {
+      self.v2.map_or(self.sample_size, |v2| v2.const_bits_per_channel.min(u32::from(u16::MAX)) as u16)
+    }
+    pub fn compression_id(&self) -> u16 { self.compression_id }
+    pub fn packet_size(&self) -> u16 { self.packet_size }
+    /// Sample rate in whole Hz, see [`AudioSampleEntry::sample_rate_f64`].
+    pub fn sample_rate(&self) -> u32 { self.sample_rate_f64() as u32 }
+    /// Sample rate in Hz, whatever the version of the description: the 16.16 fixed point field
+    /// of versions 0 and 1, or the 64-bit float of version 2.
+    pub fn sample_rate_f64(&self) -> f64 {
+      self.v2.map_or(f64::from(self.sample_rate) / 65536.0, |v2| v2.sample_rate)
+    }
+    /// The raw 16.16 fixed point sample rate, 1 Hz in a version 2 description.
+    pub fn sample_rate_fixed(&self) -> u32 { self.sample_rate }
+    /// The fields of a version 1 description.
+    pub fn v1(&self) -> Option<&SoundDescriptionV1> { self.v1.as_ref() }
+    /// The fields of a version 2 description.
+    pub fn v2(&self) -> Option<&SoundDescriptionV2> { self.v2.as_ref() }
+    /// The `esds` atom of an MPEG-4 audio sample description, such as AAC.
+    pub fn esds(&self) -> Option<&EsdsAtom> { self.esds.as_ref() }
+  }
+
+  impl std::fmt::Display for AudioSampleEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "{} {} Hz, channels: {}, bits: {}", self.entry, self.sample_rate(),
+             self.channels(), self.bits_per_sample())
+    }
+  }
+
+  #[test]
+  fn should_read_every_version_of_a_sound_sample_description() {
+    // An lpcm description of `version`, with the version 0 `channels` and 16.16 `sample_rate`,
+    // followed by `extra` and an extension atom.
+    let description = |version: u16, channels: u16, sample_rate: u32, extra: &[u8]| {
+      let mut bytes = vec![0, 0, 0, 0];
+      bytes.extend_from_slice(b"lpcm");
+      bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+      bytes.extend_from_slice(&version.to_be_bytes());
+      bytes.extend_from_slice(&[0; 6]);
+      bytes.extend_from_slice(&channels.to_be_bytes());
+      bytes.extend_from_slice(&16u16.to_be_bytes());
+      bytes.extend_from_slice(&[0xff, 0xfe, 0, 0]);
+      bytes.extend_from_slice(&sample_rate.to_be_bytes());
+      bytes.extend_from_slice(extra);
+      bytes.extend_from_slice(&[0, 0, 0, 8]);
+      bytes.extend_from_slice(b"chan");
+      let size = bytes.len() as u32;
+      bytes[..4].copy_from_slice(&size.to_be_bytes());
+      match SampleDescription::new(&bytes).unwrap() {
+        SampleDescription::Audio(audio) => audio,
+        other => panic!("expected an audio entry, got {:?}", other),
+      }
+    };
+    let extension_types = |audio: &AudioSampleEntry| audio.extensions.iter().map(|header| header.atom_type().to_string())
+      .collect::<Vec<String>>();
+
+    let v0 = description(0, 2, 48000 << 16, &[]);
+    assert_eq!((48000.0, 48000, 2, 16), (v0.sample_rate_f64(), v0.sample_rate(), v0.channels(), v0.bits_per_sample()));
+    assert!(v0.v1().is_none() && v0.v2().is_none());
+    assert_eq!(vec!["chan"], extension_types(&v0));
+
+    let fields: Vec<u8> = [1024u32, 768, 4, 2].iter().flat_map(|x| x.to_be_bytes()).collect();
+    let v1 = description(1, 2, 44100 << 16, &fields);
+    assert_eq!((44100.0, 2), (v1.sample_rate_f64(), v1.channels()));
+    assert_eq!(Some(&SoundDescriptionV1 { samples_per_packet: 1024, bytes_per_packet: 768, bytes_per_frame: 4,
+                                          bytes_per_sample: 2 }), v1.v1());
+    assert_eq!(vec!["chan"], extension_types(&v1));
+
+    let mut fields = 72u32.to_be_bytes().to_vec();
+    fields.extend_from_slice(&96000f64.to_bits().to_be_bytes());
+    fields.extend([8u32, 0x7f00_0000, 24, 0xc, 24, 1].iter().flat_map(|x| x.to_be_bytes()));
+    let v2 = description(2, 3, 1 << 16, &fields);
+    assert_eq!((96000.0, 96000, 8, 24), (v2.sample_rate_f64(), v2.sample_rate(), v2.channels(), v2.bits_per_sample()));
+    assert_eq!((3, 1 << 16), (v2.channel_count(), v2.sample_rate_fixed()));
+    let fields = v2.v2().unwrap();
+    assert_eq!((72, 0xc, 24, 1), (fields.size_of_struct_only, fields.format_specific_flags,
+                                  fields.const_bytes_per_audio_packet, fields.const_lpcm_frames_per_audio_packet));
+    assert_eq!(vec!["chan"], extension_types(&v2));
+    assert_eq!("lpcm 96000 Hz, channels: 8, bits: 24", v2.to_string());
+  }
+
+  #[test]
+  fn should_read_the_audio_fields_of_an_aac_sample_description() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(432418)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = StsdAtom::new(header, &mut file).unwrap();
+    assert_eq!(1, atom.entries().len());
+    match &atom.entries()[0] {
+      SampleDescription::Audio(audio) => {
+        assert_eq!("mp4a", audio.entry().data_format());
+        assert_eq!(1, audio.entry().data_reference_index());
+        assert_eq!(44100, audio.sample_rate());
+        assert_eq!(2, audio.channel_count());
+        assert_eq!(16, audio.bits_per_sample());
+      }
+      other => panic!("expected an audio entry, got {:?}", other),
+    }
+    let extensions: Vec<&str> = atom.entries()[0].extensions().iter().map(|header| header.atom_type()).collect();
+    assert_eq!(vec!["esds"], extensions);
+  }
+
+  /// The avcC atom is the AVC decoder configuration record from ISO/IEC 14496-15, found after the
+  /// fields of `avc1` and `avc3` sample descriptions. It holds the parameter sets a decoder needs
+  /// before the first sample.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct AvcCAtom {
+    atom_header: AtomHeader,
+    configuration_version: u8,
+    profile_idc: u8,
+    profile_compatibility: u8,
+    level_idc: u8,
+    nal_length_size: u8,
+    sps: Vec<Vec<u8>>,
+    pps: Vec<Vec<u8>>,
+  }
+
+  impl AvcCAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<AvcCAtom> {
+      AvcCAtom::from_payload(atom_header, &atom_header.read_payload(file)?)
+    }
+    /// Parses the configuration record in `payload`, the bytes after the header.
+    pub(crate) fn from_payload(atom_header: AtomHeader, payload: &[u8]) -> Result<AvcCAtom> {
+      let mut bytes = payload;
+      let mut result = AvcCAtom {
+        atom_header,
+        configuration_version: read_u8(&mut bytes)?,
+        profile_idc: read_u8(&mut bytes)?,
+        profile_compatibility: read_u8(&mut bytes)?,
+        level_idc: read_u8(&mut bytes)?,
+        nal_length_size: (read_u8(&mut bytes)? & 0x3) + 1,
+        ..Default::default()
+      };
+      let sps_count = read_u8(&mut bytes)? & 0x1f;
+      result.sps = AvcCAtom::parameter_sets(&atom_header, &mut bytes, sps_count)?;
+      let pps_count = read_u8(&mut bytes)?;
+      result.pps = AvcCAtom::parameter_sets(&atom_header, &mut bytes, pps_count)?;
+      Ok(result)
+    }
+    fn parameter_sets(atom_header: &AtomHeader, bytes: &mut &[u8], count: u8) -> Result<Vec<Vec<u8>>> {
+      (0..count).map(|_| {
+        let len = read_u16(bytes)? as usize;
+        let set = bytes.get(..len).ok_or_else(|| {
+          ParseError::parse_failed(atom_header, format!("a parameter set of {} bytes is cut short", len))
+        })?;
+        *bytes = &bytes[len..];
+        Ok(set.to_vec())
+      }).collect()
+    }
+    pub fn configuration_version(&self) -> u8 { self.configuration_version }
+    pub fn profile_idc(&self) -> u8 { self.profile_idc }
+    /// The constraint set flags, which narrow the profile down, such as to constrained baseline.
+    pub fn profile_compatibility(&self) -> u8 { self.profile_compatibility }
+    pub fn level_idc(&self) -> u8 { self.level_idc }
+    /// How many bytes give the length of each NAL unit in the samples, 1, 2 or 4.
+    pub fn nal_length_size(&self) -> u8 { self.nal_length_size }
+    /// The sequence parameter sets, without their length fields.
+    pub fn sps(&self) -> &[Vec<u8>] { &self.sps }
+    /// The picture parameter sets, without their length fields.
+    pub fn pps(&self) -> &[Vec<u8>] { &self.pps }
+    /// The name of the profile, such as `High`, when it is one of the H.264 profiles.
+    pub fn profile_name(&self) -> Option<&'static str> {
+      match self.profile_idc {
+        66 if self.profile_compatibility & 0x40 != 0 => Some("Constrained Baseline"),
+        66 => Some("Baseline"),
+        77 => Some("Main"),
+        88 => Some("Extended"),
+        100 => Some("High"),
+        110 => Some("High 10"),
+        122 => Some("High 4:2:2"),
+        244 => Some("High 4:4:4 Predictive"),
+        44 => Some("CAVLC 4:4:4 Intra"),
+        _ => None,
+      }
+    }
+    /// The level as it is usually written, `3.1` for a `level_idc` of 31.
+    pub fn level_name(&self) -> String {
+      format!("{}.{}", self.level_idc / 10, self.level_idc % 10)
+    }
+  }
+
+  impl AtomLike for AvcCAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for AvcCAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "AvcC: {}, ", self.atom_header)?;
+      match self.profile_name() {
+        Some(name) => write!(f, "{} profile", name)?,
+        None => write!(f, "profile {}", self.profile_idc)?,
+      }
+      write!(f, ", level {}, sps: {}, pps: {}", self.level_name(), self.sps.len(), self.pps.len())
+    }
+  }
+
+  /// The AudioSpecificConfig from ISO/IEC 14496-3 that an [`EsdsAtom`] of MPEG-4 audio carries.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+  pub struct AudioSpecificConfig {
+    object_type: u8,
+    sampling_frequency_index: u8,
+    sampling_frequency: u32,
+    channel_configuration: u8,
+  }
+
+  /// The sampling frequencies that a sampling frequency index below 13 stands for.
+  const SAMPLING_FREQUENCIES: [u32; 13] = [96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000,
+    12000, 11025, 8000, 7350];
+
+  impl AudioSpecificConfig {
+    pub fn new(bytes: &[u8]) -> Option<AudioSpecificConfig> {
+      let mut bits = BitReader { bytes, position: 0 };
+      let mut object_type = bits.read(5)? as u8;
+      if object_type == 31 {
+        object_type = 32 + bits.read(6)? as u8;
+      }
+      let sampling_frequency_index = bits.read(4)? as u8;
+      let sampling_frequency = match SAMPLING_FREQUENCIES.get(sampling_frequency_index as usize) {
+        Some(frequency) => *frequency,
+        None if sampling_frequency_index == 15 => bits.read(24)?,
+        None => 0,
+      };
+      let channel_configuration = bits.read(4)? as u8;
+      Some(AudioSpecificConfig { object_type, sampling_frequency_index, sampling_frequency, channel_configuration })
+    }
+    /// The audio object type, 2 for AAC LC.
+    pub fn object_type(&self) -> u8 { self.object_type }
+    pub fn sampling_frequency_index(&self) -> u8 { self.sampling_frequency_index }
+    /// The sampling frequency in Hz, 0 for a reserved index.
+    pub fn sampling_frequency(&self) -> u32 { self.sampling_frequency }
+    /// The channel configuration, the number of channels for 1 to 6, 0 when the channels are
+    /// described elsewhere.
+    pub fn channel_configuration(&self) -> u8 { self.channel_configuration }
+    /// The name of the audio object type, when it is one of the common ones.
+    pub fn object_type_name(&self) -> Option<&'static str> {
+      match self.object_type {
+        1 => Some("AAC Main"),
+        2 => Some("AAC LC"),
+        3 => Some("AAC SSR"),
+        4 => Some("AAC LTP"),
+        5 => Some("HE-AAC"),
+        23 => Some("AAC LD"),
+        29 => Some("HE-AAC v2"),
+        39 => Some("AAC ELD"),
+        _ => None,
+      }
+    }
+  }
+
+  impl std::fmt::Display for AudioSpecificConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      match self.object_type_name() {
+        Some(name) => write!(f, "{}", name)?,
+        None => write!(f, "object type {}", self.object_type)?,
+      }
+      write!(f, ", {} Hz, channels: {}", self.sampling_frequency, self.channel_configuration)
+    }
+  }
+
+  /// Reads fields that are not whole bytes, most significant bit first.
+  struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+  }
+
+  impl BitReader<'_> {
+    fn read(&mut self, bits: usize) -> Option<u32> {
+      let mut value = 0;
+      for _ in 0..bits {
+        let byte = self.bytes.get(self.position / 8)?;
+        value = (value << 1) | ((byte >> (7 - self.position % 8)) & 1) as u32;
+        self.position += 1;
+      }
+      Some(value)
+    }
+  }
+
+  /// The descriptor tags an [`EsdsAtom`] is made of.
+  const ES_DESCRIPTOR_TAG: u8 = 3;
+  const DECODER_CONFIG_DESCRIPTOR_TAG: u8 = 4;
+  const DECODER_SPECIFIC_INFO_TAG: u8 = 5;
+
+  /// The object type indication of MPEG-4 audio, whose decoder specific info is an
+  /// [`AudioSpecificConfig`].
+  const MPEG4_AUDIO: u8 = 0x40;
+
+  /// The esds atom is the elementary stream descriptor atom from ISO/IEC 14496-14, found after the
+  /// fields of `mp4a` sample descriptions. It nests descriptors of MPEG-4 systems down to the
+  /// decoder specific info, which for AAC is the AudioSpecificConfig.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct EsdsAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    es_id: u16,
+    object_type_indication: u8,
+    stream_type: u8,
+    buffer_size: u32,
+    max_bitrate: u32,
+    average_bitrate: u32,
+    decoder_specific_info: Vec<u8>,
+  }
+
+  impl EsdsAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<EsdsAtom> {
+      EsdsAtom::from_payload(atom_header, &atom_header.read_payload(file)?)
+    }
+    /// Parses the descriptors in `payload`, the bytes after the header.
+    pub(crate) fn from_payload(atom_header: AtomHeader, payload: &[u8]) -> Result<EsdsAtom> {
+      let mut bytes = payload;
+      let mut result = EsdsAtom { atom_header, full_atom: FullAtom::new(&mut bytes)?, ..Default::default() };
+      let mut es = result.descriptor(&mut bytes, ES_DESCRIPTOR_TAG)?;
+      result.es_id = read_u16(&mut es)?;
+      let flags = read_u8(&mut es)?;
+      if flags & 0x80 != 0 {
+        read_u16(&mut es)?;
+      }
+      if flags & 0x40 != 0 {
+        let url_length = read_u8(&mut es)? as usize;
+        es = es.get(url_length..).ok_or_else(|| ParseError::parse_failed(&atom_header, "the URL is cut short"))?;
+      }
+      if flags & 0x20 != 0 {
+        read_u16(&mut es)?;
+      }
+      let mut config = result.descriptor(&mut es, DECODER_CONFIG_DESCRIPTOR_TAG)?;
+      result.object_type_indication = read_u8(&mut config)?;
+      result.stream_type = read_u8(&mut config)? >> 2;
+      let mut buffer_size = [0; 4];
+      config.read_exact(&mut buffer_size[1..])?;
+      result.buffer_size = u32::from_be_bytes(buffer_size);
+      result.max_bitrate = read_u32(&mut config)?;
+      result.average_bitrate = read_u32(&mut config)?;
+      if !config.is_empty() {
+        result.decoder_specific_info = result.descriptor(&mut config, DECODER_SPECIFIC_INFO_TAG)?.to_vec();
+      }
+      Ok(result)
+    }
+    /// Reads the descriptor with `tag` at the start of `bytes`, skipping any others before it, and
+    /// moves `bytes` past it. Descriptor lengths take one to four bytes, seven bits at a time, with
+    /// the top bit set on all but the last.
+    fn descriptor<'a>(&self, bytes: &mut &'a [u8], tag: u8) -> Result<&'a [u8]> {
+      loop {
+        let found = read_u8(bytes).map_err(|_| {
+          ParseError::parse_failed(&self.atom_header, format!("there is no descriptor with tag {}", tag))
+        })?;
+        let mut len = 0usize;
+        for _ in 0..4 {
+          let byte = read_u8(bytes)?;
+          len = (len << 7) | (byte & 0x7f) as usize;
+          if byte & 0x80 == 0 {
+            break;
+          }
+        }
+        let body = bytes.get(..len).ok_or_else(|| {
+          ParseError::parse_failed(&self.atom_header, format!("a descriptor of {} bytes with tag {} is cut short", len, found))
+        })?;
+        *bytes = &bytes[len..];
+        if found == tag {
+          return Ok(body);
+        }
+      }
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn es_id(&self) -> u16 { self.es_id }
+    /// The codec of the stream, 0x40 for MPEG-4 audio such as AAC.
+    pub fn object_type_indication(&self) -> u8 { self.object_type_indication }
+    /// The kind of stream, 5 for audio and 4 for video.
+    pub fn stream_type(&self) -> u8 { self.stream_type }
+    /// The size of the decoding buffer in bytes.
+    pub fn buffer_size(&self) -> u32 { self.buffer_size }
+    pub fn max_bitrate(&self) -> u32 { self.max_bitrate }
+    pub fn average_bitrate(&self) -> u32 { self.average_bitrate }
+    /// The configuration of the decoder, as it is stored.
+    pub fn decoder_specific_info(&self) -> &[u8] { &self.decoder_specific_info }
+    /// The decoded AudioSpecificConfig, for MPEG-4 audio.
+    pub fn audio_specific_config(&self) -> Option<AudioSpecificConfig> {
+      if self.object_type_indication == MPEG4_AUDIO {
+        AudioSpecificConfig::new(&self.decoder_specific_info)
+      } else {
+        None
+      }
+    }
+  }
+
+  impl AtomLike for EsdsAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for EsdsAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Esds: {}, ", self.atom_header)?;
+      match self.audio_specific_config() {
+        Some(config) => write!(f, "{}", config),
+        None => write!(f, "object type: {:#04x}", self.object_type_indication),
+      }
+    }
+  }
+
+  #[test]
+  fn should_read_the_avc_configuration_of_an_h264_sample_description() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(441)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = StsdAtom::new(header, &mut file).unwrap();
+    let avcc = match &atom.entries()[0] {
+      SampleDescription::Video(video) => video.avc_configuration().unwrap().clone(),
+      other => panic!("expected a video entry, got {:?}", other),
+    };
+    assert_eq!(543, avcc.atom_location());
+    assert_eq!(100, avcc.profile_idc());
+    assert_eq!(Some("High"), avcc.profile_name());
+    assert_eq!(31, avcc.level_idc());
+    assert_eq!(4, avcc.nal_length_size());
+    assert_eq!(vec![25], avcc.sps().iter().map(Vec::len).collect::<Vec<_>>());
+    assert_eq!(&[0x67, 0x64, 0x00, 0x1f], &avcc.sps()[0][..4]);
+    assert_eq!(vec![vec![0x68, 0xeb, 0xe2, 0x4b, 0x22, 0xc0]], avcc.pps());
+    assert_eq!("AvcC: type: avcC, size: 50, location: 543, High profile, level 3.1, sps: 1, pps: 1", avcc.to_string());
+  }
+
+  #[test]
+  fn should_read_the_audio_specific_config_of_an_aac_sample_description() {
+    // The descriptors of this esds have lengths in the four byte form, 0x80 0x80 0x80 0x22.
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(432418)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let atom = StsdAtom::new(header, &mut file).unwrap();
+    let esds = match &atom.entries()[0] {
+      SampleDescription::Audio(audio) => audio.esds().unwrap().clone(),
+      other => panic!("expected an audio entry, got {:?}", other),
+    };
+    assert_eq!(0x40, esds.object_type_indication());
+    assert_eq!(5, esds.stream_type());
+    assert_eq!(&[0x12, 0x10], esds.decoder_specific_info());
+    let config = esds.audio_specific_config().unwrap();
+    assert_eq!((2, 44100, 2), (config.object_type(), config.sampling_frequency(), config.channel_configuration()));
+    assert_eq!("Esds: type: esds, size: 51, location: 432470, AAC LC, 44100 Hz, channels: 2", esds.to_string());
+
+    // Lengths in the one byte form, AAC LC at 48 kHz in stereo.
+    let mut bytes = vec![0, 0, 0, 39];
+    bytes.extend_from_slice(b"esds");
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0x03, 25, 0, 1, 0]);
+    bytes.extend_from_slice(&[0x04, 17, 0x40, 0x15, 0, 0x18, 0, 0, 0x01, 0xf4, 0, 0, 0x01, 0xf4, 0]);
+    bytes.extend_from_slice(&[0x05, 2, 0x11, 0x90, 0x06, 1, 2]);
+    let mut file = std::io::Cursor::new(bytes.clone());
+    let header = AtomHeader::new(&mut file).unwrap();
+    let esds = EsdsAtom::new(header, &mut file).unwrap();
+    assert_eq!(1, esds.es_id());
+    assert_eq!(0x1800, esds.buffer_size());
+    assert_eq!((128000, 128000), (esds.max_bitrate(), esds.average_bitrate()));
+    let config = esds.audio_specific_config().unwrap();
+    assert_eq!(Some("AAC LC"), config.object_type_name());
+    assert_eq!((3, 48000, 2), (config.sampling_frequency_index(), config.sampling_frequency(), config.channel_configuration()));
+
+    // The decoder config claims more bytes than the stream descriptor holds.
+    bytes[18] = 40;
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let err = EsdsAtom::new(header, &mut file).unwrap_err();
+    assert_eq!("esds at 0 (0x0): a descriptor of 40 bytes with tag 4 is cut short", err.to_string());
+  }
+
+  /// The saiz atom is the sample auxiliary information sizes atom from ISO/IEC 14496-12. Together
+  /// with `saio` it locates per-sample data such as the initialization vectors of encrypted
+  /// samples.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct SaizAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    aux_info_type: Option<(u32, u32)>,
+    default_sample_info_size: u8,
+    sample_count: u32,
+    sample_info_sizes: Vec<u8>,
+  }
+
+  impl SaizAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<SaizAtom> {
+      let mut reader = AtomReader::new(atom_header, file)?;
+      let mut result = SaizAtom { atom_header, full_atom: reader.read_full_atom()?, ..Default::default() };
+      if result.full_atom.flags() & 1 != 0 {
+        result.aux_info_type = Some((reader.read_u32()?, reader.read_u32()?));
+      }
+      result.default_sample_info_size = reader.read_u8()?;
+      result.sample_count = reader.read_u32()?;
+      if result.default_sample_info_size == 0 {
+        result.sample_info_sizes = reader.read_bytes(result.sample_count as usize)?.to_vec();
+      }
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// The aux_info_type and aux_info_type_parameter, when present.
+    pub fn aux_info_type(&self) -> Option<(u32, u32)> { self.aux_info_type }
+    pub fn default_sample_info_size(&self) -> u8 { self.default_sample_info_size }
+    pub fn sample_count(&self) -> u32 { self.sample_count }
+    /// Size of the auxiliary information of the sample at `index`, counting from 0.
+    pub fn sample_info_size(&self, index: usize) -> Option<u8> {
+      if index >= self.sample_count as usize {
+        None
+      } else if self.default_sample_info_size != 0 {
+        Some(self.default_sample_info_size)
+      } else {
+        self.sample_info_sizes.get(index).copied()
+      }
+    }
+  }
+
+  impl AtomLike for SaizAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for SaizAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Saiz: {}, samples: {}", self.atom_header, self.sample_count)
+    }
+  }
+
+  /// The saio atom is the sample auxiliary information offsets atom. Each offset locates the
+  /// auxiliary information of a run of samples, either a chunk or a track fragment run.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct SaioAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    aux_info_type: Option<(u32, u32)>,
+    offsets: Vec<u64>,
+  }
+
+  impl SaioAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<SaioAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let mut result = SaioAtom { atom_header, full_atom: FullAtom::new(&mut bytes)?, ..Default::default() };
+      if result.full_atom.flags() & 1 != 0 {
+        result.aux_info_type = Some((read_u32(&mut bytes)?, read_u32(&mut bytes)?));
+      }
+      let num_entries = read_u32(&mut bytes)?;
+      for _ in 0..num_entries {
+        let offset = if result.full_atom.version() == 0 { read_u32(&mut bytes)? as u64 } else { read_u64(&mut bytes)? };
+        result.offsets.push(offset);
+      }
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// The aux_info_type and aux_info_type_parameter, when present.
+    pub fn aux_info_type(&self) -> Option<(u32, u32)> { self.aux_info_type }
+    pub fn offsets(&self) -> &Vec<u64> { &self.offsets }
+  }
+
+  impl AtomLike for SaioAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for SaioAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Saio: {}, offsets: {}", self.atom_header, self.offsets.len())
+    }
+  }
+
+  /// The tkhd atom is the [track header atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25550).
+  /// It specifies the characteristics of a single track, including its id, duration and
+  /// dimensions. Version 1 stores the times and duration as 64-bit values.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct TkhdAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    creation_time: u64,
+    modification_time: u64,
+    track_id: u32,
+    duration: u64,
+    layer: u16,
+    alternate_group: u16,
+    volume: u16,
+    matrix: Vec<u8>,
+    track_width: u32,
+    track_height: u32,
+  }
+
+  impl TkhdAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<TkhdAtom> {
+      let mut reader = AtomReader::new(atom_header, file)?;
+      let mut result = TkhdAtom { atom_header, full_atom: reader.read_full_atom()?, ..Default::default() };
+      let version = result.full_atom.version();
+      result.creation_time = reader.read_versioned(version)?;
+      result.modification_time = reader.read_versioned(version)?;
+      result.track_id = reader.read_u32()?;
+      reader.skip(4)?;
+      result.duration = reader.read_versioned(version)?;
+      reader.skip(8)?;
+      result.layer = reader.read_u16()?;
+      result.alternate_group = reader.read_u16()?;
+      result.volume = reader.read_u16()?;
+      reader.skip(2)?;
+      result.matrix = reader.read_bytes(36)?.to_vec();
+      result.track_width = reader.read_u32()?;
+      result.track_height = reader.read_u32()?;
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn creation_time(&self) -> u64 { self.creation_time }
+    pub fn modification_time(&self) -> u64 { self.modification_time }
+    pub fn track_id(&self) -> u32 { self.track_id }
+    pub fn duration(&self) -> u64 { self.duration }
+    pub fn layer(&self) -> u16 { self.layer }
+    pub fn alternate_group(&self) -> u16 { self.alternate_group }
+    pub fn volume(&self) -> u16 { self.volume }
+    pub fn matrix(&self) -> &Vec<u8> { &self.matrix }
+    /// Track width as a 16.16 fixed point value.
+    pub fn width_fixed(&self) -> u32 { self.track_width }
+    /// Track height as a 16.16 fixed point value.
+    pub fn height_fixed(&self) -> u32 { self.track_height }
+    pub fn width_f32(&self) -> f32 { self.track_width as f32 / 65536.0 }
+    pub fn height_f32(&self) -> f32 { self.track_height as f32 / 65536.0 }
+  }
+
+  impl AtomLike for TkhdAtom {
+    fn atom_size(&self) -> u64 {
+      self.atom_header.atom_size()
+    }
+
+    fn atom_type(&self) -> &str {
+      self.atom_header.atom_type()
+    }
+
+    fn atom_location(&self) -> u64 {
+      self.atom_header.atom_location()
+    }
+
+    fn header_size(&self) -> u32 {
+      self.atom_header.header_size()
+    }
+  }
+
+  impl std::fmt::Display for TkhdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Tkhd: {}, track_id: {}", self.atom_header, self.track_id)
+    }
+  }
+
+  #[test]
+  fn should_read_the_track_headers_of_the_sample() {
+    let mut file = std::fs::File::open("resources/tests/trak.mp4").unwrap();
+    file.seek(SeekFrom::Start(8)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let tkhd = TkhdAtom::new(header, &mut file).unwrap();
+    assert_eq!("tkhd", tkhd.atom_type());
+    assert_eq!(0, tkhd.full_atom().version());
+    assert_eq!(1, tkhd.track_id());
+    assert_eq!(960 << 16, tkhd.width_fixed());
+    assert_eq!(540 << 16, tkhd.height_fixed());
+    assert_eq!(960.0, tkhd.width_f32());
+  }
+
+  #[test]
+  fn should_read_a_version_1_track_header() {
+    let mut bytes = vec![0, 0, 0, 104];
+    bytes.extend_from_slice(b"tkhd");
+    bytes.extend_from_slice(&[1, 0, 0, 3]);
+    bytes.extend_from_slice(&0x1_0000_0001u64.to_be_bytes());
+    bytes.extend_from_slice(&0x1_0000_0002u64.to_be_bytes());
+    bytes.extend_from_slice(&7u32.to_be_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&0x2_0000_0000u64.to_be_bytes());
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(&[0, 0, 0, 1, 0x01, 0x00, 0, 0]);
+    bytes.extend_from_slice(&[0; 36]);
+    bytes.extend_from_slice(&(1280u32 << 16 | 0x8000).to_be_bytes());
+    bytes.extend_from_slice(&(720u32 << 16).to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let tkhd = TkhdAtom::new(header, &mut file).unwrap();
+    assert_eq!(1, tkhd.full_atom().version());
+    assert_eq!((0x1_0000_0001, 0x1_0000_0002), (tkhd.creation_time(), tkhd.modification_time()));
+    assert_eq!(7, tkhd.track_id());
+    assert_eq!(0x2_0000_0000, tkhd.duration());
+    assert_eq!((0, 1, 0x0100), (tkhd.layer(), tkhd.alternate_group(), tkhd.volume()));
+    assert_eq!((1280.5, 720.0), (tkhd.width_f32(), tkhd.height_f32()));
+  }
+
+  /// One of the children of a [`TrefAtom`]. Its type is the [type of the
+  /// reference](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-BBCHCAAI),
+  /// such as `chap` or `tmcd`, and it holds the IDs of the tracks referred to.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct TrackReferenceTypeAtom {
+    atom_header: AtomHeader,
+    track_ids: Vec<u32>,
+  }
+
+  impl TrackReferenceTypeAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<TrackReferenceTypeAtom> {
+      let payload = atom_header.read_payload(file)?;
+      if payload.len() % 4 != 0 {
+        return Err(ParseError::parse_failed(&atom_header, format!("{} bytes are not a whole number of track IDs", payload.len())));
+      }
+      // A track ID of 0 is an unused entry.
+      let track_ids = payload.chunks_exact(4).map(|id| u32::from_be_bytes([id[0], id[1], id[2], id[3]]))
+        .filter(|id| *id != 0).collect();
+      Ok(TrackReferenceTypeAtom { atom_header, track_ids })
+    }
+    pub fn reference_type(&self) -> FourCC { self.atom_header.four_cc() }
+    pub fn track_ids(&self) -> &[u32] { &self.track_ids }
+  }
+
+  impl AtomLike for TrackReferenceTypeAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for TrackReferenceTypeAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "TrackReference: {}, track_ids: {:?}", self.atom_header, self.track_ids)
+    }
+  }
+
+  #[test]
+  fn should_leave_out_the_unused_entries_of_a_track_reference() {
+    let reference = |payload: &[u8]| {
+      let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+      bytes.extend_from_slice(b"tmcd");
+      bytes.extend_from_slice(payload);
+      let mut file = std::io::Cursor::new(bytes);
+      let header = AtomHeader::new(&mut file).unwrap();
+      TrackReferenceTypeAtom::new(header, &mut file)
+    };
+    let tmcd = reference(&[0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 5]).unwrap();
+    assert_eq!((FourCC::from("tmcd"), &[3, 5][..]), (tmcd.reference_type(), tmcd.track_ids()));
+    assert_eq!("TrackReference: type: tmcd, size: 20, location: 0, track_ids: [3, 5]", tmcd.to_string());
+    assert!(reference(&[]).unwrap().track_ids().is_empty());
+    assert!(matches!(reference(&[0, 0, 1]), Err(ParseError::AtomParseFailed { .. })));
+  }
+
+  /// The mdhd atom is the [media header atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25615).
+  /// It holds the time scale and duration of a track's media. Version 1 stores the times and
+  /// duration as 64-bit values.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct MdhdAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    creation_time: u64,
+    modification_time: u64,
+    time_scale: u32,
+    duration: u64,
+    language: u16,
+    quality: u16,
+  }
+
+  impl MdhdAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<MdhdAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let mut result = MdhdAtom { atom_header, full_atom: FullAtom::new(&mut bytes)?, ..Default::default() };
+      let version = result.full_atom.version();
+      result.creation_time = read_versioned(&mut bytes, version)?;
+      result.modification_time = read_versioned(&mut bytes, version)?;
+      result.time_scale = read_u32(&mut bytes)?;
+      result.duration = read_versioned(&mut bytes, version)?;
+      result.language = read_u16(&mut bytes)?;
+      result.quality = read_u16(&mut bytes)?;
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn creation_time(&self) -> u64 { self.creation_time }
+    pub fn modification_time(&self) -> u64 { self.modification_time }
+    /// The number of time units that pass per second in the track's media.
+    pub fn time_scale(&self) -> u32 { self.time_scale }
+    /// The duration of the media in time scale units.
+    pub fn duration(&self) -> u64 { self.duration }
+    /// The packed language code as stored: three 5-bit letters of an ISO 639-2/T code, or a
+    /// Macintosh language code below 0x400.
+    pub fn language_code(&self) -> u16 { self.language }
+    /// The ISO 639-2/T language of the track, such as `eng`, or `und` when it is undetermined.
+    /// Macintosh language codes are only decoded for English, code 0.
+    pub fn language(&self) -> String {
+      match self.language {
+        0 => String::from("eng"),
+        code if code < 0x400 => String::from("und"),
+        code => [10, 5, 0].iter().map(|shift| char::from(((code >> shift) & 0x1f) as u8 + 0x60)).collect(),
+      }
+    }
+    pub fn quality(&self) -> u16 { self.quality }
+  }
+
+  impl AtomLike for MdhdAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for MdhdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Mdhd: {}, time_scale: {}, duration: {}, language: {}", self.atom_header, self.time_scale, self.duration,
+             self.language())
+    }
+  }
+
+  #[test]
+  fn should_decode_the_language_of_a_track() {
+    let mdhd = |version: u8, language: u16| {
+      let times = if version == 1 { 8 } else { 4 };
+      let mut bytes = ((8 + 4 + 3 * times + 4 + 4) as u32).to_be_bytes().to_vec();
+      bytes.extend_from_slice(b"mdhd");
+      bytes.extend_from_slice(&[version, 0, 0, 0]);
+      bytes.extend(std::iter::repeat_n(0, 2 * times));
+      bytes.extend_from_slice(&600u32.to_be_bytes());
+      bytes.extend(std::iter::repeat_n(0, times - 2));
+      bytes.extend_from_slice(&1200u16.to_be_bytes());
+      bytes.extend_from_slice(&language.to_be_bytes());
+      bytes.extend_from_slice(&[0, 0]);
+      let mut file = std::io::Cursor::new(bytes);
+      let header = AtomHeader::new(&mut file).unwrap();
+      MdhdAtom::new(header, &mut file).unwrap()
+    };
+    // "eng" packs to ((e - 0x60) << 10) | ((n - 0x60) << 5) | (g - 0x60).
+    let english = mdhd(0, 0x15c7);
+    assert_eq!(("eng", 600, 1200), (english.language().as_str(), english.time_scale(), english.duration()));
+    let undetermined = mdhd(1, 0x55c4);
+    assert_eq!(("und", 600, 1200), (undetermined.language().as_str(), undetermined.time_scale(), undetermined.duration()));
+    assert_eq!("eng", mdhd(0, 0).language());
+
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(292)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    assert_eq!("und", MdhdAtom::new(header, &mut file).unwrap().language());
+  }
+
+  /// The vmhd atom is the [video media information header atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html).
+  /// It starts the `minf` of a video track and says how the video is drawn.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct VmhdAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    graphics_mode: u16,
+    opcolor: [u16; 3],
+  }
+
+  impl VmhdAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<VmhdAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let graphics_mode = read_u16(&mut bytes)?;
+      let opcolor = [read_u16(&mut bytes)?, read_u16(&mut bytes)?, read_u16(&mut bytes)?];
+      Ok(VmhdAtom { atom_header, full_atom, graphics_mode, opcolor })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// The transfer mode used to draw the video, 0 for copy.
+    pub fn graphics_mode(&self) -> u16 { self.graphics_mode }
+    /// The red, green and blue used by the graphics modes that blend with a color.
+    pub fn opcolor(&self) -> [u16; 3] { self.opcolor }
+  }
+
+  impl AtomLike for VmhdAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for VmhdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Vmhd: {}, graphics mode: {}, opcolor: {:?}", self.atom_header, self.graphics_mode, self.opcolor)
+    }
+  }
+
+  /// The smhd atom is the [sound media information header atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html).
+  /// It starts the `minf` of a sound track and holds the balance across the speakers.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct SmhdAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    balance: i16,
+  }
+
+  impl SmhdAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<SmhdAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      Ok(SmhdAtom { atom_header, full_atom, balance: read_u16(&mut bytes)? as i16 })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// The balance as stored, an 8.8 fixed point number.
+    pub fn balance(&self) -> i16 { self.balance }
+    /// The balance from -1.0, all left, through 0.0, centered, to 1.0, all right.
+    pub fn balance_f32(&self) -> f32 { self.balance as f32 / 256.0 }
+  }
+
+  impl AtomLike for SmhdAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for SmhdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Smhd: {}, balance: {}", self.atom_header, self.balance_f32())
+    }
+  }
+
+  /// The gmin atom is the [base media info atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html)
+  /// of a `gmhd`, used by tracks such as timecode and text that are neither video nor sound.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct GminAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    graphics_mode: u16,
+    opcolor: [u16; 3],
+    balance: i16,
+  }
+
+  impl GminAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<GminAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let graphics_mode = read_u16(&mut bytes)?;
+      let opcolor = [read_u16(&mut bytes)?, read_u16(&mut bytes)?, read_u16(&mut bytes)?];
+      let balance = read_u16(&mut bytes)? as i16;
+      Ok(GminAtom { atom_header, full_atom, graphics_mode, opcolor, balance })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn graphics_mode(&self) -> u16 { self.graphics_mode }
+    pub fn opcolor(&self) -> [u16; 3] { self.opcolor }
+    /// The balance as stored, an 8.8 fixed point number.
+    pub fn balance(&self) -> i16 { self.balance }
+    pub fn balance_f32(&self) -> f32 { self.balance as f32 / 256.0 }
+  }
+
+  impl AtomLike for GminAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for GminAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Gmin: {}, graphics mode: {}, opcolor: {:?}, balance: {}", self.atom_header, self.graphics_mode,
+             self.opcolor, self.balance_f32())
+    }
+  }
+
+  #[test]
+  fn should_read_the_media_information_headers_of_the_video_and_sound_tracks() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(377)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    match Atoms::new(header, &mut file, &ParseContext::default()).unwrap() {
+      Atoms::Vmhd(vmhd) => {
+        assert_eq!(1, vmhd.full_atom().flags());
+        assert_eq!((0, [0, 0, 0]), (vmhd.graphics_mode(), vmhd.opcolor()));
+      }
+      other => panic!("expected vmhd, got {:?}", other),
+    }
+    file.seek(SeekFrom::Start(432358)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    match Atoms::new(header, &mut file, &ParseContext::default()).unwrap() {
+      Atoms::Smhd(smhd) => {
+        assert_eq!(0.0, smhd.balance_f32());
+        assert!(smhd.to_string().ends_with("balance: 0"));
+      }
+      other => panic!("expected smhd, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn should_read_the_base_media_info_of_a_gmhd() {
+    let gmin = [vec![0, 0, 0, 0], 64u16.to_be_bytes().to_vec(), vec![0x80, 0, 0x80, 0, 0x80, 0],
+                (-128i16).to_be_bytes().to_vec(), vec![0, 0]].concat();
+    let mut gmhd = ((8 + 8 + gmin.len()) as u32).to_be_bytes().to_vec();
+    gmhd.extend_from_slice(b"gmhd");
+    gmhd.extend_from_slice(&((8 + gmin.len()) as u32).to_be_bytes());
+    gmhd.extend_from_slice(b"gmin");
+    gmhd.extend_from_slice(&gmin);
+    let mut file = std::io::Cursor::new(gmhd);
+    let header = AtomHeader::new(&mut file).unwrap();
+    match AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap() {
+      AtomNodes::Container(ContainerAtoms::Gmhd(gmhd)) => match &gmhd.children()[..] {
+        [AtomNodes::Atom(Atoms::Gmin(gmin))] => {
+          assert_eq!((64, [0x8000; 3]), (gmin.graphics_mode(), gmin.opcolor()));
+          assert_eq!(-0.5, gmin.balance_f32());
+        }
+        other => panic!("expected a single gmin, got {:?}", other),
+      },
+      other => panic!("expected gmhd, got {:?}", other),
+    }
+  }
+
+  /// The hdlr atom is the [handler reference atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-BBCIBHFD).
+  /// Under `mdia` its subtype names the kind of media in the track, such as `vide` or `soun`.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct HdlrAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    component_type: [u8; 4],
+    component_subtype: [u8; 4],
+    manufacturer: [u8; 4],
+    flags: u32,
+    flags_mask: u32,
+    name: String,
+  }
+
+  impl HdlrAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<HdlrAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let mut result = HdlrAtom { atom_header, full_atom: FullAtom::new(&mut bytes)?, ..Default::default() };
+      bytes.read_exact(&mut result.component_type)?;
+      bytes.read_exact(&mut result.component_subtype)?;
+      let reserved = bytes.get(12..).ok_or_else(|| ParseError::parse_failed(&atom_header, "the component name is missing"))?;
+      bytes.read_exact(&mut result.manufacturer)?;
+      result.flags = read_u32(&mut bytes)?;
+      result.flags_mask = read_u32(&mut bytes)?;
+      // QuickTime writes the name as a Pascal string, sometimes padded with zeros, ISO files as a
+      // null terminated one.
+      let name = match reserved.split_first() {
+        Some((&len, rest)) if (len as usize) < reserved.len() && rest[len as usize..].iter().all(|x| *x == 0) =>
+          &rest[..len as usize],
+        _ => reserved.split(|x| *x == 0).next().unwrap_or(&[]),
+      };
+      result.name = String::from_utf8_lossy(name).into_owned();
+      Ok(result)
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn component_type(&self) -> &str { std::str::from_utf8(&self.component_type).unwrap_or("") }
+    /// The handler type, `vide` for video and `soun` for sound tracks.
+    pub fn component_subtype(&self) -> &str { std::str::from_utf8(&self.component_subtype).unwrap_or("") }
+    /// The handler type, the component subtype of QuickTime files, such as `vide`, `soun` or
+    /// `meta`.
+    pub fn handler_type(&self) -> &str { self.component_subtype() }
+    /// Reserved in ISO files, where it is 0.
+    pub fn manufacturer(&self) -> &[u8; 4] { &self.manufacturer }
+    pub fn flags(&self) -> u32 { self.flags }
+    pub fn flags_mask(&self) -> u32 { self.flags_mask }
+    pub fn name(&self) -> &str { &self.name }
+  }
+
+  impl AtomLike for HdlrAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for HdlrAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Hdlr: {}, subtype: {}, name: {}", self.atom_header, self.component_subtype(), self.name)
+    }
+  }
+
+  #[test]
+  fn should_read_the_media_header_and_handler_of_the_video_track() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(292)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let mdhd = MdhdAtom::new(header, &mut file).unwrap();
+    assert_eq!(30000, mdhd.time_scale());
+    assert_eq!(0x01bdba2e, mdhd.duration());
+    file.seek(SeekFrom::Start(mdhd.atom_location() + mdhd.atom_size())).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let hdlr = HdlrAtom::new(header, &mut file).unwrap();
+    assert_eq!("vide", hdlr.component_subtype());
+    assert_eq!("VideoHandler", hdlr.name());
+    assert_eq!("vide", hdlr.handler_type());
+  }
+
+  #[test]
+  fn should_read_the_pascal_string_name_of_a_quicktime_handler() {
+    let name = b"Apple Sound Media Handler";
+    let mut bytes = ((8 + 24 + 1 + name.len()) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(b"hdlr");
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(b"mhlrsounappl");
+    bytes.extend_from_slice(&0x4000_0000u32.to_be_bytes());
+    bytes.extend_from_slice(&0x0001_0047u32.to_be_bytes());
+    bytes.push(name.len() as u8);
+    bytes.extend_from_slice(name);
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let hdlr = HdlrAtom::new(header, &mut file).unwrap();
+    assert_eq!(("mhlr", "soun"), (hdlr.component_type(), hdlr.handler_type()));
+    assert_eq!(b"appl", hdlr.manufacturer());
+    assert_eq!((0x4000_0000, 0x0001_0047), (hdlr.flags(), hdlr.flags_mask()));
+    assert_eq!("Apple Sound Media Handler", hdlr.name());
+  }
+
+  #[test]
+  fn should_leave_out_the_padding_after_the_pascal_string_name_of_a_handler() {
+    let hdlr = |name: &[u8]| {
+      let mut bytes = ((8 + 24 + name.len()) as u32).to_be_bytes().to_vec();
+      bytes.extend_from_slice(b"hdlr");
+      bytes.extend_from_slice(&[0; 4]);
+      bytes.extend_from_slice(b"mhlrvideappl");
+      bytes.extend_from_slice(&[0; 8]);
+      bytes.extend_from_slice(name);
+      let mut file = std::io::Cursor::new(bytes);
+      let header = AtomHeader::new(&mut file).unwrap();
+      HdlrAtom::new(header, &mut file).unwrap().name().to_string()
+    };
+    assert_eq!("Apple Video Media Handler", hdlr(b"\x19Apple Video Media Handler\0\0\0"));
+    assert_eq!("", hdlr(b"\0\0\0\0"));
+    // A null terminated name is not mistaken for a Pascal string.
+    assert_eq!("VideoHandler", hdlr(b"VideoHandler\0"));
+  }
+
+  /// One run of the time-to-sample table: `sample_count` consecutive samples that each last
+  /// `sample_delta` time scale units.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+  pub struct TimeToSampleEntry {
+    pub sample_count: u32,
+    pub sample_delta: u32,
+  }
+
+  /// The stts atom is the [time-to-sample atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-BBCGFJII).
+  /// It stores the duration of every sample, run length encoded.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct SttsAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    entries: Vec<TimeToSampleEntry>,
+  }
+
+  impl SttsAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<SttsAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut entries = Vec::with_capacity((num_entries as usize).min(bytes.len() / 8));
+      for _ in 0..num_entries {
+        entries.push(TimeToSampleEntry { sample_count: read_u32(&mut bytes)?, sample_delta: read_u32(&mut bytes)? });
+      }
+      Ok(SttsAtom { atom_header, full_atom, entries })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn entries(&self) -> &Vec<TimeToSampleEntry> { &self.entries }
+    pub fn sample_count(&self) -> u64 { self.entries.iter().map(|x| x.sample_count as u64).sum() }
+    /// The sum of all sample durations, in time scale units.
+    pub fn total_duration(&self) -> u64 {
+      self.entries.iter().map(|x| x.sample_count as u64 * x.sample_delta as u64).sum()
+    }
+    /// Whether the samples do not all last the same time.
+    pub fn is_variable(&self) -> bool {
+      self.entries.iter().any(|x| x.sample_delta != self.entries[0].sample_delta)
+    }
+  }
+
+  impl AtomLike for SttsAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for SttsAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Stts: {}, entries: {}", self.atom_header, self.entries.len())
+    }
+  }
+
+  /// The clef, prof and enof atoms are the [track clean aperture, production aperture and encoded
+  /// pixels dimensions atoms](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-SW16)
+  /// of a `tapt` atom. They share a layout: a width and a height in 16.16 fixed point.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct ApertureAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    width: u32,
+    height: u32,
+  }
+
+  impl ApertureAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<ApertureAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      Ok(ApertureAtom { atom_header, full_atom, width: read_u32(&mut bytes)?, height: read_u32(&mut bytes)? })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// Whether this is the clean aperture, the part of the picture meant to be shown.
+    pub fn is_clean_aperture(&self) -> bool { self.atom_type() == "clef" }
+    /// Width and height in pixels, decoded from 16.16 fixed point.
+    pub fn dimensions(&self) -> (f64, f64) { (self.width as f64 / 65536.0, self.height as f64 / 65536.0) }
+  }
+
+  impl AtomLike for ApertureAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for ApertureAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      let (width, height) = self.dimensions();
+      write!(f, "Aperture: {}, {}x{}", self.atom_header, width, height)
+    }
+  }
+
+  /// One run of the composition offset table: `sample_count` consecutive samples that are each
+  /// presented `sample_offset` time scale units after they are decoded.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+  pub struct CompositionOffsetEntry {
+    pub sample_count: u32,
+    /// Only version 1 atoms can have negative offsets.
+    pub sample_offset: i64,
+  }
+
+  /// The ctts atom is the [composition offset atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-SW19).
+  /// Tracks with B-frames decode samples out of presentation order, and this holds how far each
+  /// sample's presentation lags its decoding.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct CttsAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    entries: Vec<CompositionOffsetEntry>,
+  }
+
+  impl CttsAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<CttsAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut entries = Vec::with_capacity((num_entries as usize).min(bytes.len() / 8));
+      for _ in 0..num_entries {
+        let sample_count = read_u32(&mut bytes)?;
+        let offset = read_u32(&mut bytes)?;
+        let sample_offset = if full_atom.version() == 1 { offset as i32 as i64 } else { offset as i64 };
+        entries.push(CompositionOffsetEntry { sample_count, sample_offset });
+      }
+      Ok(CttsAtom { atom_header, full_atom, entries })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn entries(&self) -> &Vec<CompositionOffsetEntry> { &self.entries }
+  }
+
+  impl AtomLike for CttsAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for CttsAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Ctts: {}, entries: {}", self.atom_header, self.entries.len())
+    }
+  }
+
+  #[test]
+  fn should_read_the_composition_offsets_of_the_video_track() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(1117)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let ctts = CttsAtom::new(header, &mut file).unwrap();
+    assert_eq!(24684, ctts.entries().len());
+    assert_eq!(CompositionOffsetEntry { sample_count: 1, sample_offset: 3003 }, ctts.entries()[1]);
+  }
+
+  #[test]
+  fn should_read_negative_composition_offsets_of_version_1() {
+    let mut bytes = vec![0, 0, 0, 32];
+    bytes.extend_from_slice(b"ctts");
+    bytes.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 2]);
+    bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0x03, 0xe9]);
+    bytes.extend_from_slice(&[0, 0, 0, 2, 0xff, 0xff, 0xfc, 0x17]);
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let ctts = CttsAtom::new(header, &mut file).unwrap();
+    assert_eq!(vec![CompositionOffsetEntry { sample_count: 1, sample_offset: 1001 },
+                    CompositionOffsetEntry { sample_count: 2, sample_offset: -1001 }], *ctts.entries());
+  }
+
+  /// The stss atom is the [sync sample atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25701).
+  /// It lists the samples that can be decoded without the ones before them, the key frames a
+  /// player can seek to. A track without one has only sync samples, so an empty table, where
+  /// no sample is a sync sample, is not the same as a missing one.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct StssAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    sync_samples: Vec<u32>,
+  }
+
+  impl StssAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<StssAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut sync_samples = Vec::with_capacity((num_entries as usize).min(bytes.len() / 4));
+      for _ in 0..num_entries {
+        sync_samples.push(read_u32(&mut bytes)?);
+      }
+      Ok(StssAtom { atom_header, full_atom, sync_samples })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// The numbers of the sync samples in increasing order, counting the first sample as 1.
+    pub fn sync_samples(&self) -> &[u32] { &self.sync_samples }
+    /// Whether sample `sample_number`, counting from 1, is a sync sample.
+    pub fn is_sync_sample(&self, sample_number: u32) -> bool {
+      self.sync_samples.binary_search(&sample_number).is_ok()
+    }
+  }
+
+  impl AtomLike for StssAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for StssAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Stss: {}, sync samples: {}", self.atom_header, self.sync_samples.len())
+    }
+  }
+
+  #[test]
+  fn should_read_the_sync_samples_of_the_video_track() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(617)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let stss = StssAtom::new(header, &mut file).unwrap();
+    assert_eq!(121, stss.sync_samples().len());
+    assert_eq!(1, stss.sync_samples()[0]);
+    assert!(stss.is_sync_sample(1));
+    assert!(!stss.is_sync_sample(2));
+    assert!(stss.to_string().ends_with("sync samples: 121"));
+
+    // An empty table has no sync samples at all.
+    let mut file = std::io::Cursor::new(vec![0, 0, 0, 16, b's', b't', b's', b's', 0, 0, 0, 0, 0, 0, 0, 0]);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let stss = StssAtom::new(header, &mut file).unwrap();
+    assert!(stss.sync_samples().is_empty());
+    assert!(!stss.is_sync_sample(1));
+  }
+
+  /// The flag of a data reference whose media data is in the same file as the movie.
+  const DATA_REFERENCE_SELF_CONTAINED: u32 = 0x000001;
+
+  /// One entry of a `dref` atom, saying where some of a track's media data is, such as a `url `
+  /// entry with a URL or an `alis` entry with a Mac OS alias record.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct DataReference {
+    reference_type: FourCC,
+    size: u64,
+    full_atom: FullAtom,
+    data: Vec<u8>,
+  }
+
+  impl DataReference {
+    pub fn reference_type(&self) -> FourCC { self.reference_type }
+    /// The size of the entry, its header included.
+    pub fn size(&self) -> u64 { self.size }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    /// The bytes after the version and flags.
+    pub fn data(&self) -> &[u8] { &self.data }
+    /// Whether the media data is in the file the movie is in, in which case the entry has no data.
+    pub fn is_self_contained(&self) -> bool { self.full_atom.flags() & DATA_REFERENCE_SELF_CONTAINED != 0 }
+    /// The location a `url ` entry points to, when it has one and it is valid UTF-8.
+    pub fn url(&self) -> Option<&str> {
+      if self.reference_type != "url " {
+        return None;
+      }
+      let end = self.data.iter().position(|x| *x == 0).unwrap_or(self.data.len());
+      std::str::from_utf8(&self.data[..end]).ok().filter(|url| !url.is_empty())
+    }
+  }
+
+  /// The dref atom is the [data reference atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25686).
+  /// Sample descriptions refer to its entries by index to say where their media data is, which for
+  /// a reference movie can be outside of the file.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct DrefAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    entries: Vec<DataReference>,
+  }
+
+  impl DrefAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<DrefAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut entries = Vec::new();
+      for (reference_type, mut payload) in split_atoms(&atom_header, bytes)?.into_iter().take(num_entries as usize) {
+        let size = payload.len() as u64 + 8;
+        let full_atom = FullAtom::new(&mut payload)?;
+        entries.push(DataReference { reference_type: FourCC::new(reference_type), size, full_atom, data: payload.to_vec() });
+      }
+      Ok(DrefAtom { atom_header, full_atom, entries })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn entries(&self) -> &[DataReference] { &self.entries }
+    /// Whether every entry has its media data in this file, so the file plays on its own.
+    pub fn is_self_contained(&self) -> bool {
+      self.entries.iter().all(DataReference::is_self_contained)
+    }
+  }
+
+  impl AtomLike for DrefAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for DrefAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Dref: {}, entries: {}, self-contained: {}", self.atom_header, self.entries.len(),
+             self.is_self_contained())
+    }
+  }
+
+  #[test]
+  fn should_find_the_media_data_of_a_self_contained_file_in_the_file() {
+    let results = crate::Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let mut drefs = Vec::new();
+    results.nodes().walk(|node| if let AtomNodes::Atom(Atoms::Dref(dref)) = node { drefs.push(dref.clone()) });
+    assert_eq!(2, drefs.len());
+    for dref in &drefs {
+      assert!(dref.is_self_contained());
+      assert_eq!(1, dref.entries().len());
+      assert_eq!(dref.entries()[0].reference_type(), "url ");
+      assert_eq!(12, dref.entries()[0].size());
+      assert_eq!(None, dref.entries()[0].url());
+    }
+  }
+
+  #[test]
+  fn should_read_the_url_of_media_data_outside_of_the_file() {
+    let url = b"http://example.com/media.mov\0";
+    let mut bytes = vec![0, 0, 0, 0];
+    bytes.extend_from_slice(b"dref");
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2]);
+    bytes.extend_from_slice(&(12 + url.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(b"url \0\0\0\0");
+    bytes.extend_from_slice(url);
+    bytes.extend_from_slice(&[0, 0, 0, 12]);
+    bytes.extend_from_slice(b"url \0\0\0\x01");
+    let size = bytes.len() as u32;
+    bytes[..4].copy_from_slice(&size.to_be_bytes());
+    let mut file = std::io::Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let dref = match Atoms::new(header, &mut file, &ParseContext::default()).unwrap() {
+      Atoms::Dref(dref) => dref,
+      other => panic!("expected dref, got {:?}", other),
+    };
+    let entries: Vec<(u64, bool, Option<&str>)> = dref.entries().iter()
+      .map(|x| (x.size(), x.is_self_contained(), x.url())).collect();
+    assert_eq!(vec![(41, false, Some("http://example.com/media.mov")), (12, true, None)], entries);
+    assert!(!dref.is_self_contained());
+    assert!(dref.to_string().ends_with("entries: 2, self-contained: false"));
+  }
+
+  /// One edit of an edit list. A `media_time` of -1 marks an empty edit, which delays the start
+  /// of the track's media.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+  pub struct EditListEntry {
+    /// Duration of the edit in movie time scale units.
+    pub segment_duration: u64,
+    /// Where the edit starts in the media, in media time scale units.
+    pub media_time: i64,
+    /// Playback rate of the edit as a 16.16 fixed point value.
+    pub media_rate: i32,
+  }
+
+  impl EditListEntry {
+    /// Whether the edit presents nothing for its duration, delaying whatever follows.
+    pub fn is_empty_edit(&self) -> bool { self.media_time == -1 }
+  }
+
+  /// The elst atom is the [edit list atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html#//apple_ref/doc/uid/TP40000939-CH204-25682).
+  /// Its edits describe which parts of the media are presented and when. Version 1 stores the
+  /// duration and media time as 64-bit values.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct ElstAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    entries: Vec<EditListEntry>,
+  }
+
+  impl ElstAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<ElstAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut entries = Vec::with_capacity((num_entries as usize).min(bytes.len() / 12));
+      for _ in 0..num_entries {
+        let segment_duration = read_versioned(&mut bytes, full_atom.version())?;
+        let media_time = if full_atom.version() == 1 {
+          read_u64(&mut bytes)? as i64
+        } else {
+          read_u32(&mut bytes)? as i32 as i64
+        };
+        let media_rate = read_u32(&mut bytes)? as i32;
+        entries.push(EditListEntry { segment_duration, media_time, media_rate });
+      }
+      Ok(ElstAtom { atom_header, full_atom, entries })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn entries(&self) -> &[EditListEntry] { &self.entries }
+    /// The time the edits take on the movie timeline, in movie time scale units.
+    pub fn presentation_duration(&self) -> u64 {
+      self.entries.iter().map(|x| x.segment_duration).sum()
+    }
+    /// How long the track starts after the movie does, the duration of the empty edits before its
+    /// first media, in movie time scale units.
+    pub fn initial_delay(&self) -> u64 {
+      self.entries.iter().take_while(|x| x.is_empty_edit()).map(|x| x.segment_duration).sum()
+    }
+  }
+
+  impl AtomLike for ElstAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for ElstAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Elst: {}, edits: {}", self.atom_header, self.entries.len())
+    }
+  }
+
+  #[test]
+  fn should_read_the_edit_list_of_the_video_track() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(256)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let elst = ElstAtom::new(header, &mut file).unwrap();
+    assert_eq!(&vec![EditListEntry { segment_duration: 973707, media_time: 2002, media_rate: 0x10000 }], elst.entries());
+    assert_eq!(0, elst.initial_delay());
+  }
+
+  #[test]
+  fn should_delay_a_track_that_starts_with_an_empty_edit() {
+    let mut file = std::fs::File::open("resources/tests/edts_empty_edit.mp4").unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let elst = match AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap() {
+      AtomNodes::Container(ContainerAtoms::Edts(edts)) => match &edts.children()[..] {
+        [AtomNodes::Atom(Atoms::Elst(elst))] => elst.clone(),
+        other => panic!("expected a single elst, got {:?}", other),
+      },
+      other => panic!("expected edts, got {:?}", other),
+    };
+    assert_eq!(1, elst.full_atom().version());
+    assert!(elst.entries()[0].is_empty_edit());
+    assert_eq!(EditListEntry { segment_duration: 90000, media_time: 3003, media_rate: 0x10000 }, elst.entries()[1]);
+    assert!(!elst.entries()[1].is_empty_edit());
+    assert_eq!(1500, elst.initial_delay());
+    assert_eq!(91500, elst.presentation_duration());
+  }
+
+  /// The mfhd atom is the movie fragment header atom (ISO/IEC 14496-12).
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct MfhdAtom {
     atom_header: AtomHeader,
+    full_atom: FullAtom,
+    sequence_number: u32,
   }
 
-  impl MdatAtom {
-    pub fn new(atom_header: AtomHeader) -> Result<MdatAtom> {
-      Ok(MdatAtom { atom_header })
+  impl MfhdAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<MfhdAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      Ok(MfhdAtom { atom_header, full_atom, sequence_number: read_u32(&mut bytes)? })
     }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn sequence_number(&self) -> u32 { self.sequence_number }
   }
 
-  impl AtomLike for MdatAtom {
+  impl AtomLike for MfhdAtom {
     fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
     fn atom_type(&self) -> &str { self.atom_header.atom_type() }
     fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
     fn header_size(&self) -> u32 { self.atom_header.header_size() }
   }
 
-  impl std::fmt::Display for MdatAtom {
+  impl std::fmt::Display for MfhdAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-      write!(f, "mdia: {}", self.atom_header)?;
-      Ok(())
+      write!(f, "Mfhd: {}, sequence_number: {}", self.atom_header, self.sequence_number)
     }
   }
 
-  #[derive(Debug, Default, Clone)]
-  pub struct PrflAtom {
+  /// The tfhd atom is the track fragment header atom (ISO/IEC 14496-12). It names the track of a
+  /// `traf` and may set defaults for the samples of its runs. Which optional fields are stored
+  /// is given by the flags, and fields that are not stored are `None`.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct TfhdAtom {
     atom_header: AtomHeader,
     full_atom: FullAtom,
-    num_features: u32,
-    features: Vec<FeatureEntry>
+    track_id: u32,
+    base_data_offset: Option<u64>,
+    sample_description_index: Option<u32>,
+    default_sample_duration: Option<u32>,
+    default_sample_size: Option<u32>,
+    default_sample_flags: Option<u32>,
   }
 
-  impl PrflAtom {
-    pub fn new<T>(header: AtomHeader, file: &mut T) -> Result<PrflAtom>
-    where T: Read + Seek {
-      let mut result = PrflAtom{atom_header: header, ..Default::default() };
-      file.seek(SeekFrom::Start(header.atom_location()))?;
+  impl TfhdAtom {
+    const BASE_DATA_OFFSET_PRESENT: u32 = 0x01;
+    const SAMPLE_DESCRIPTION_INDEX_PRESENT: u32 = 0x02;
+    const DEFAULT_SAMPLE_DURATION_PRESENT: u32 = 0x08;
+    const DEFAULT_SAMPLE_SIZE_PRESENT: u32 = 0x10;
+    const DEFAULT_SAMPLE_FLAGS_PRESENT: u32 = 0x20;
+    const DURATION_IS_EMPTY: u32 = 0x01_0000;
+    const DEFAULT_BASE_IS_MOOF: u32 = 0x02_0000;
+
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<TfhdAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let flags = full_atom.flags();
+      let track_id = read_u32(&mut bytes)?;
+      let optional_u32 = |flag: u32, bytes: &mut &[u8]| -> Result<Option<u32>> {
+        if flags & flag != 0 { read_u32(bytes).map(Some) } else { Ok(None) }
+      };
+      let base_data_offset = if flags & TfhdAtom::BASE_DATA_OFFSET_PRESENT != 0 { Some(read_u64(&mut bytes)?) } else { None };
+      Ok(TfhdAtom {
+        atom_header, full_atom, track_id, base_data_offset,
+        sample_description_index: optional_u32(TfhdAtom::SAMPLE_DESCRIPTION_INDEX_PRESENT, &mut bytes)?,
+        default_sample_duration: optional_u32(TfhdAtom::DEFAULT_SAMPLE_DURATION_PRESENT, &mut bytes)?,
+        default_sample_size: optional_u32(TfhdAtom::DEFAULT_SAMPLE_SIZE_PRESENT, &mut bytes)?,
+        default_sample_flags: optional_u32(TfhdAtom::DEFAULT_SAMPLE_FLAGS_PRESENT, &mut bytes)?,
+      })
+    }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn track_id(&self) -> u32 { self.track_id }
+    /// The absolute offset data offsets in the fragment are relative to, when stored.
+    pub fn base_data_offset(&self) -> Option<u64> { self.base_data_offset }
+    pub fn sample_description_index(&self) -> Option<u32> { self.sample_description_index }
+    pub fn default_sample_duration(&self) -> Option<u32> { self.default_sample_duration }
+    pub fn default_sample_size(&self) -> Option<u32> { self.default_sample_size }
+    pub fn default_sample_flags(&self) -> Option<u32> { self.default_sample_flags }
+    /// Whether the fragment adds no samples to the track, only time.
+    pub fn duration_is_empty(&self) -> bool { self.full_atom.flags() & TfhdAtom::DURATION_IS_EMPTY != 0 }
+    /// Whether data offsets without a base data offset are relative to the start of the `moof`.
+    pub fn default_base_is_moof(&self) -> bool { self.full_atom.flags() & TfhdAtom::DEFAULT_BASE_IS_MOOF != 0 }
+  }
 
-      let mut bytes = header.read_atom(file)?;
-      let mut bytes = &bytes[(header.header_size() as usize) ..];
-      result.full_atom = FullAtom::new(&mut bytes)?;
-      let mut tmp: [u8;4] = [0;4];
-      bytes.read(&mut tmp);
-      result.num_features = u32::from_be_bytes(tmp);
+  impl AtomLike for TfhdAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for TfhdAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Tfhd: {}, track_id: {}", self.atom_header, self.track_id)
+    }
+  }
+
+  /// One sample of a track run. Each field is only stored when the flags of the `trun` say so,
+  /// otherwise the sample takes the default from the `tfhd` or `trex`.
+  #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+  pub struct TrackRunSample {
+    pub duration: Option<u32>,
+    pub size: Option<u32>,
+    pub flags: Option<u32>,
+    /// Signed in version 1 runs.
+    pub composition_time_offset: Option<i64>,
+  }
+
+  /// The trun atom is the track fragment run atom (ISO/IEC 14496-12). It describes a run of
+  /// samples stored one after another in the `mdat` of the fragment.
+  #[derive(Debug, Default, Clone, PartialEq)]
+  pub struct TrunAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    data_offset: Option<i32>,
+    first_sample_flags: Option<u32>,
+    sample_count: u32,
+    samples: Vec<TrackRunSample>,
+  }
+
+  impl TrunAtom {
+    const DATA_OFFSET_PRESENT: u32 = 0x001;
+    const FIRST_SAMPLE_FLAGS_PRESENT: u32 = 0x004;
+    const SAMPLE_DURATION_PRESENT: u32 = 0x100;
+    const SAMPLE_SIZE_PRESENT: u32 = 0x200;
+    const SAMPLE_FLAGS_PRESENT: u32 = 0x400;
+    const SAMPLE_COMPOSITION_TIME_OFFSET_PRESENT: u32 = 0x800;
 
-      for i in 0..result.num_features {
-        result.features.push(FeatureEntry::new(&mut bytes)?);
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<TrunAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let flags = full_atom.flags();
+      let present = |flag: u32| flags & flag != 0;
+      let sample_count = read_u32(&mut bytes)?;
+      let data_offset = if present(TrunAtom::DATA_OFFSET_PRESENT) { Some(read_u32(&mut bytes)? as i32) } else { None };
+      let first_sample_flags = if present(TrunAtom::FIRST_SAMPLE_FLAGS_PRESENT) { Some(read_u32(&mut bytes)?) } else { None };
+      let sample_fields = [TrunAtom::SAMPLE_DURATION_PRESENT, TrunAtom::SAMPLE_SIZE_PRESENT, TrunAtom::SAMPLE_FLAGS_PRESENT,
+                           TrunAtom::SAMPLE_COMPOSITION_TIME_OFFSET_PRESENT];
+      let sample_size = 4 * sample_fields.iter().filter(|flag| present(**flag)).count();
+      // A run without per-sample fields has nothing to read for its samples, however many it has.
+      let (capacity, per_sample) = match bytes.len().checked_div(sample_size) {
+        Some(capacity) => ((sample_count as usize).min(capacity), sample_count),
+        None => (0, 0),
+      };
+      let mut samples = Vec::with_capacity(capacity);
+      for _ in 0..per_sample {
+        let mut field = |flag: u32| -> Result<Option<u32>> {
+          if present(flag) { read_u32(&mut bytes).map(Some) } else { Ok(None) }
+        };
+        let duration = field(TrunAtom::SAMPLE_DURATION_PRESENT)?;
+        let size = field(TrunAtom::SAMPLE_SIZE_PRESENT)?;
+        let sample_flags = field(TrunAtom::SAMPLE_FLAGS_PRESENT)?;
+        let composition_time_offset = field(TrunAtom::SAMPLE_COMPOSITION_TIME_OFFSET_PRESENT)?.map(|offset| {
+          if full_atom.version() == 0 { offset as i64 } else { offset as i32 as i64 }
+        });
+        samples.push(TrackRunSample { duration, size, flags: sample_flags, composition_time_offset });
       }
-      Ok(result)
+      Ok(TrunAtom { atom_header, full_atom, data_offset, first_sample_flags, sample_count, samples })
     }
-    pub fn full_atom(&self) -> &FullAtom{ &self.full_atom }
-    pub fn num_features(&self) -> u32 { self.num_features }
-    pub fn features(&self) -> &Vec<FeatureEntry> { &self.features }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn sample_count(&self) -> u32 { self.sample_count }
+    /// Where the run's data starts, relative to the base data offset of the fragment.
+    pub fn data_offset(&self) -> Option<i32> { self.data_offset }
+    /// Flags that replace those of the first sample, usually to mark it as a sync sample.
+    pub fn first_sample_flags(&self) -> Option<u32> { self.first_sample_flags }
+    /// The per-sample fields of the run, one entry per sample. Empty when the flags say the
+    /// samples have none, as every sample then takes the defaults of the fragment.
+    pub fn samples(&self) -> &[TrackRunSample] { &self.samples }
   }
 
-  impl AtomLike for PrflAtom {
-    fn atom_size(&self) -> u64 {
-      self.atom_header.atom_size()
+  impl AtomLike for TrunAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for TrunAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Trun: {}, samples: {}", self.atom_header, self.sample_count)
     }
+  }
 
-    fn atom_type(&self) -> &str {
-      self.atom_header.atom_type()
+  #[test]
+  fn should_read_the_optional_fields_of_track_fragments() {
+    let mut file = std::fs::File::open("resources/tests/fragmented.mp4").unwrap();
+    let mut read = |location: u64| {
+      file.seek(SeekFrom::Start(location)).unwrap();
+      let header = AtomHeader::new(&mut file).unwrap();
+      Atoms::new(header, &mut file, &ParseContext::default()).unwrap()
+    };
+    match (read(212), read(232)) {
+      (Atoms::Tfhd(tfhd), Atoms::Trun(trun)) => {
+        assert_eq!((1, Some(1000), None), (tfhd.track_id(), tfhd.default_sample_duration(), tfhd.default_sample_size()));
+        assert_eq!(None, tfhd.base_data_offset());
+        assert!(tfhd.default_base_is_moof() && !tfhd.duration_is_empty());
+        assert_eq!((Some(96), None), (trun.data_offset(), trun.first_sample_flags()));
+        assert_eq!(&[TrackRunSample { duration: Some(1001), size: Some(5), ..Default::default() },
+                     TrackRunSample { duration: Some(999), size: Some(3), ..Default::default() }], trun.samples());
+      }
+      other => panic!("expected tfhd and trun, got {:?}", other),
+    }
+    match (read(316), read(344)) {
+      (Atoms::Tfhd(tfhd), Atoms::Trun(trun)) => {
+        assert_eq!((Some(4), Some(0x0101_0000)), (tfhd.default_sample_size(), tfhd.default_sample_flags()));
+        assert_eq!(None, tfhd.sample_description_index());
+        assert_eq!((1, 3), (trun.full_atom().version(), trun.sample_count()));
+        assert_eq!((Some(104), Some(0x0200_0000)), (trun.data_offset(), trun.first_sample_flags()));
+        let offsets: Vec<Option<i64>> = trun.samples().iter().map(|x| x.composition_time_offset).collect();
+        assert_eq!(vec![Some(2000), Some(-1000), Some(0)], offsets);
+        assert!(trun.samples().iter().all(|x| x.duration.is_none() && x.size.is_none() && x.flags.is_none()));
+      }
+      other => panic!("expected tfhd and trun, got {:?}", other),
     }
+  }
 
-    fn atom_location(&self) -> u64 {
-      self.atom_header.atom_location()
+  #[test]
+  fn should_count_the_samples_of_a_run_without_reading_fields_it_does_not_have() {
+    let trun = |flags: u32| {
+      let mut bytes = vec![0, 0, 0, 16];
+      bytes.extend_from_slice(b"trun");
+      bytes.extend_from_slice(&flags.to_be_bytes());
+      bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+      let mut file = std::io::Cursor::new(bytes);
+      let header = AtomHeader::new(&mut file).unwrap();
+      TrunAtom::new(header, &mut file)
+    };
+    let run = trun(0).unwrap();
+    assert_eq!(u32::MAX, run.sample_count());
+    assert!(run.samples().is_empty());
+    assert_eq!("Trun: type: trun, size: 16, location: 0, samples: 4294967295", run.to_string());
+    // With a field per sample, the run ends long before its samples do.
+    assert!(matches!(trun(TrunAtom::SAMPLE_SIZE_PRESENT), Err(ParseError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof));
+  }
+
+  /// The country and language a metadata value is written for, from the locale field of its
+  /// `data` atom. Values that are not localized have the default locale, with both set to 0.
+  #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+  pub struct Locale {
+    pub country: u16,
+    pub language: u16,
+  }
+
+  /// The value of a `data` atom, decoded according to its well-known type.
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub enum MetadataValue {
+    Text(String),
+    Integer(i64),
+    Jpeg(Vec<u8>),
+    Png(Vec<u8>),
+    /// A value of a type that is not decoded, such as the implicit type of `trkn`.
+    Binary { type_indicator: u32, data: Vec<u8> },
+  }
+
+  impl MetadataValue {
+    fn new(type_indicator: u32, data: &[u8]) -> MetadataValue {
+      let integer = |signed: bool| -> Option<i64> {
+        if data.is_empty() || data.len() > 8 {
+          return None;
+        }
+        let value = data.iter().fold(0u64, |value, byte| value << 8 | *byte as u64);
+        let shift = 64 - 8 * data.len() as u32;
+        Some(if signed { ((value << shift) as i64) >> shift } else { value as i64 })
+      };
+      let decoded = match type_indicator {
+        1 => String::from_utf8(data.to_vec()).ok().map(MetadataValue::Text),
+        2 => {
+          let units: Vec<u16> = data.chunks_exact(2).map(|x| u16::from_be_bytes([x[0], x[1]])).collect();
+          String::from_utf16(&units).ok().map(MetadataValue::Text)
+        }
+        13 => Some(MetadataValue::Jpeg(data.to_vec())),
+        14 => Some(MetadataValue::Png(data.to_vec())),
+        21 => integer(true).map(MetadataValue::Integer),
+        22 => integer(false).map(MetadataValue::Integer),
+        _ => None,
+      };
+      decoded.unwrap_or_else(|| MetadataValue::Binary { type_indicator, data: data.to_vec() })
+    }
+    pub fn as_text(&self) -> Option<&str> {
+      match self {
+        MetadataValue::Text(text) => Some(text),
+        _ => None,
+      }
+    }
+    pub fn as_image(&self) -> Option<(ImageFormat, &[u8])> {
+      match self {
+        MetadataValue::Jpeg(data) => Some((ImageFormat::Jpeg, data)),
+        MetadataValue::Png(data) => Some((ImageFormat::Png, data)),
+        _ => None,
+      }
     }
+  }
 
-    fn header_size(&self) -> u32 {
-      self.atom_header.header_size()
+  /// The format of an image stored in a `data` atom, such as cover art.
+  #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+  pub enum ImageFormat {
+    Jpeg,
+    Png,
+  }
+
+  /// One value of a metadata item with the locale it is for.
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub struct MetadataEntry {
+    pub locale: Locale,
+    pub value: MetadataValue,
+  }
+
+  /// A metadata item of an `ilst` atom, such as the `©nam` title, with every value its `data`
+  /// atoms hold.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct MetadataItem {
+    key: String,
+    entries: Vec<MetadataEntry>,
+  }
+
+  impl MetadataItem {
+    /// The item's atom type, or `----:<mean>:<name>` for a free-form item.
+    pub fn key(&self) -> &str { &self.key }
+    pub fn entries(&self) -> &Vec<MetadataEntry> { &self.entries }
+  }
+
+  /// Splits `bytes` into the type and payload of each atom in it.
+  fn split_atoms<'a>(atom_header: &AtomHeader, mut bytes: &'a [u8]) -> Result<Vec<([u8; 4], &'a [u8])>> {
+    let mut atoms = Vec::new();
+    while bytes.len() >= 8 {
+      let size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+      if size < 8 || size > bytes.len() {
+        return Err(ParseError::parse_failed(atom_header,
+          format!("a child declared size {} but only {} bytes present", size, bytes.len())));
+      }
+      atoms.push(([bytes[4], bytes[5], bytes[6], bytes[7]], &bytes[8..size]));
+      bytes = &bytes[size..];
     }
+    Ok(atoms)
   }
-  impl std::fmt::Display for PrflAtom {
+
+  /// The ilst atom is the [metadata item list atom](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/Metadata/Metadata.html#//apple_ref/doc/uid/TP40000939-CH1-SW25)
+  /// used by iTunes style metadata. Each item holds one `data` atom per locale it is written for.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct IlstAtom {
+    atom_header: AtomHeader,
+    items: Vec<MetadataItem>,
+  }
+
+  impl IlstAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<IlstAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut items = Vec::new();
+      for (item_type, payload) in split_atoms(&atom_header, &buf[(atom_header.header_size() as usize)..])? {
+        let mut key = FourCC::new(item_type).to_string();
+        let mut free_form = Vec::new();
+        let mut entries = Vec::new();
+        for (child_type, mut child) in split_atoms(&atom_header, payload)? {
+          match &child_type {
+            b"mean" | b"name" => {
+              FullAtom::new(&mut child)?;
+              free_form.push(String::from_utf8_lossy(child).into_owned());
+            }
+            b"data" => {
+              let type_indicator = read_u32(&mut child)?;
+              let country = read_u16(&mut child)?;
+              let language = read_u16(&mut child)?;
+              entries.push(MetadataEntry {
+                locale: Locale { country, language },
+                value: MetadataValue::new(type_indicator & 0x00ff_ffff, child),
+              });
+            }
+            _ => {}
+          }
+        }
+        if &item_type == b"----" {
+          key = std::iter::once(key).chain(free_form).collect::<Vec<_>>().join(":");
+        }
+        items.push(MetadataItem { key, entries });
+      }
+      Ok(IlstAtom { atom_header, items })
+    }
+    pub fn items(&self) -> &Vec<MetadataItem> { &self.items }
+  }
+
+  impl AtomLike for IlstAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for IlstAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-      write!(f, "Prfl: num_features: {}", self.num_features)
+      write!(f, "Ilst: {}, items: {}", self.atom_header, self.items.len())
     }
   }
 
-  #[derive(Debug, Default, Clone)]
-  pub struct FeatureEntry {
-    part_id: u32,
-    feature_code: [u8;4],
-    feature_value: u32,
+  #[test]
+  fn should_read_the_encoder_from_the_item_list() {
+    let mut file = std::fs::File::open("resources/tests/sample.mp4").unwrap();
+    file.seek(SeekFrom::Start(1023150)).unwrap();
+    let header = AtomHeader::new(&mut file).unwrap();
+    let ilst = IlstAtom::new(header, &mut file).unwrap();
+    let item = &ilst.items()[0];
+    assert_eq!("©too", item.key());
+    let encoder = MetadataEntry { locale: Locale::default(), value: MetadataValue::Text("Lavf53.29.100".to_string()) };
+    assert_eq!(&vec![encoder], item.entries());
   }
 
-  impl FeatureEntry {
-    pub fn new<T>(file: &mut T) -> Result<FeatureEntry> where T: Read {
-      let mut buf: [u8;4] = [0;4];
-      let mut result = FeatureEntry{..Default::default()};
-      file.read(&mut buf)?;
-      result.part_id = u32::from_be_bytes(buf);
+  /// Reads an item id, which is 16 bits wide in version 0 full atoms and 32 bits otherwise.
+  fn read_item_id(bytes: &mut &[u8], version: u8) -> Result<u32> {
+    if version == 0 { read_u16(bytes).map(u32::from) } else { read_u32(bytes) }
+  }
 
-      file.read(&mut result.feature_code)?;
-      file.read(&mut buf)?;
-      result.feature_value = u32::from_be_bytes(buf);
+  /// The pitm atom is the HEIF primary item atom (ISO/IEC 14496-12). It names the item that is
+  /// the image to show, as opposed to its thumbnails and the tiles it is derived from.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct PitmAtom {
+    atom_header: AtomHeader,
+    full_atom: FullAtom,
+    item_id: u32,
+  }
 
-      Ok(result)
+  impl PitmAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<PitmAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let item_id = read_item_id(&mut bytes, full_atom.version())?;
+      Ok(PitmAtom { atom_header, full_atom, item_id })
     }
-    pub fn part_id(&self) -> u32 { self.part_id }
-    pub fn feature_code(&self) -> &str { std::str::from_utf8(&self.feature_code ).unwrap() }
-    pub fn feature_value(&self) -> u32 { self.feature_value }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn item_id(&self) -> u32 { self.item_id }
   }
-  impl std::fmt::Display for FeatureEntry {
+
+  impl AtomLike for PitmAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for PitmAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-      write!(f, "part_id: {:X}, (code, value): ({}, {})",
-             self.part_id(), self.feature_code(), self.feature_value())
+      write!(f, "Pitm: {}, item: {}", self.atom_header, self.item_id)
     }
   }
 
-  #[derive(Debug, Default, Clone)]
-  pub struct MvhdAtom {
+  /// References of one type from an item to other items, such as a `thmb` reference from a
+  /// thumbnail to the image it is a thumbnail of.
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub struct ItemReference {
+    pub reference_type: String,
+    pub from_item_id: u32,
+    pub to_item_ids: Vec<u32>,
+  }
+
+  /// The iref atom is the HEIF item reference atom (ISO/IEC 14496-12).
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct IrefAtom {
     atom_header: AtomHeader,
     full_atom: FullAtom,
-    creation_time: u32,
-    modification_time: u32,
-    time_scale: u32,
-    duration: u32,
-    preferred_rate: u32,
-    preferred_vol: u16,
-    matrix: Vec<u8>,
-    preview_time: u32,
-    preview_duration: u32,
-    poster_time: u32,
-    selection_time: u32,
-    selection_duration: u32,
-    current_time: u32,
-    next_track_id: u32
+    references: Vec<ItemReference>,
   }
 
-  impl MvhdAtom {
-    pub fn new<T>(header: AtomHeader, file: &mut T) -> Result<MvhdAtom> where T: Read + Seek {
-      let mut result = MvhdAtom{atom_header: header, ..Default::default()};
-      result.matrix.resize(36, 0);
-      file.seek(SeekFrom::Start(header.atom_location() + header.header_size() as u64) )?;
-      result.full_atom = FullAtom::new(file)?;
-
-      let mut tmp:[u8;4] = [0;4];
-      file.read(&mut tmp)?;
-      result.creation_time = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.modification_time = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.time_scale = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.duration = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.preferred_rate = u32::from_be_bytes(tmp);
-
-      let mut tmp:[u8;2] = [0;2];
-      file.read(&mut tmp)?;
-      result.preferred_vol = u16::from_be_bytes(tmp);
-      file.read(&mut result.matrix);
-
-      let mut tmp:[u8;4] = [0;4];
-      file.read(&mut tmp)?;
-      result.preview_time = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.preview_duration = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.poster_time = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.selection_time = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.selection_duration = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.current_time = u32::from_be_bytes(tmp);
-      file.read(&mut tmp)?;
-      result.next_track_id = u32::from_be_bytes(tmp);
-      Ok(result)
+  impl IrefAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<IrefAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let mut references = Vec::new();
+      for (reference_type, mut payload) in split_atoms(&atom_header, bytes)? {
+        let from_item_id = read_item_id(&mut payload, full_atom.version())?;
+        let count = read_u16(&mut payload)?;
+        let to_item_ids = (0..count).map(|_| read_item_id(&mut payload, full_atom.version()))
+          .collect::<Result<Vec<u32>>>()?;
+        references.push(ItemReference {
+          reference_type: FourCC::new(reference_type).to_string(), from_item_id, to_item_ids,
+        });
+      }
+      Ok(IrefAtom { atom_header, full_atom, references })
     }
-
-    pub fn header(&self) -> &AtomHeader { &self.atom_header}
     pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
-    pub fn creation_time(&self) -> u32 { self.creation_time }
-    pub fn modification_time(&self) -> u32 { self.modification_time}
-    pub fn time_scale(&self) -> u32 { self.time_scale }
-    pub fn duration(&self) -> u32 { self.duration }
-    pub fn preferred_rate(&self) -> u32 { self.preferred_rate}
-    pub fn preferred_vol(&self) -> u16 { self.preferred_vol }
-    pub fn matrix(&self) -> &Vec<u8> { &self.matrix }
-    pub fn preview_time(&self) -> u32 { self.preview_time }
-    pub fn preview_duration(&self) -> u32 { self.preview_duration}
-    pub fn poster_time(&self) -> u32 { self.poster_time }
-    pub fn selection_time(&self) -> u32 { self.selection_time }
-    pub fn selection_duration(&self) -> u32 { self.selection_duration }
-    pub fn current_time(&self) -> u32 {self.current_time}
-    pub fn next_track_id(&self) -> u32 {self.next_track_id}
+    pub fn references(&self) -> &Vec<ItemReference> { &self.references }
   }
-  impl AtomLike for MvhdAtom {
-    fn atom_size(&self) -> u64 {
-      self.header().atom_size()
-    }
 
-    fn atom_type(&self) -> &str {
-      self.header().atom_type()
+  impl AtomLike for IrefAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for IrefAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Iref: {}, references: {}", self.atom_header, self.references.len())
     }
+  }
 
-    fn atom_location(&self) -> u64 {
-      self.header().atom_location()
+  /// A property of a HEIF item, from the `ipco` atom.
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub enum ItemProperty {
+    /// `ispe`: the width and height of the image in pixels.
+    ImageSpatialExtents { width: u32, height: u32 },
+    /// `irot`: rotation to apply when showing the image, in degrees anti-clockwise.
+    Rotation { degrees: u16 },
+    /// `imir`: mirroring to apply when showing the image, about the vertical axis (0) or the
+    /// horizontal axis (1).
+    Mirror { axis: u8 },
+    /// A property that is not decoded, such as a codec configuration.
+    Other { property_type: String },
+  }
+
+  impl ItemProperty {
+    fn new(property_type: &[u8; 4], mut bytes: &[u8]) -> Result<ItemProperty> {
+      Ok(match property_type {
+        b"ispe" => {
+          FullAtom::new(&mut bytes)?;
+          ItemProperty::ImageSpatialExtents { width: read_u32(&mut bytes)?, height: read_u32(&mut bytes)? }
+        }
+        b"irot" => ItemProperty::Rotation { degrees: (read_u8(&mut bytes)? & 0x3) as u16 * 90 },
+        b"imir" => ItemProperty::Mirror { axis: read_u8(&mut bytes)? & 0x1 },
+        _ => ItemProperty::Other { property_type: FourCC::new(*property_type).to_string() },
+      })
     }
+  }
 
-    fn header_size(&self) -> u32 {
-      self.header().header_size()
+  /// The ipco atom is the HEIF item property container atom (ISO/IEC 23008-12). Items refer to
+  /// its properties by their 1-based position.
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct IpcoAtom {
+    atom_header: AtomHeader,
+    properties: Vec<ItemProperty>,
+  }
+
+  impl IpcoAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<IpcoAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let properties = split_atoms(&atom_header, &buf[(atom_header.header_size() as usize)..])?.iter()
+        .map(|(property_type, payload)| ItemProperty::new(property_type, payload))
+        .collect::<Result<Vec<ItemProperty>>>()?;
+      Ok(IpcoAtom { atom_header, properties })
     }
+    pub fn properties(&self) -> &Vec<ItemProperty> { &self.properties }
   }
 
-  impl std::fmt::Display for MvhdAtom {
+  impl AtomLike for IpcoAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
+
+  impl std::fmt::Display for IpcoAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-      write!(f, "Mvhd: {}", self.header())
+      write!(f, "Ipco: {}, properties: {}", self.atom_header, self.properties.len())
     }
   }
 
-  #[test]
-  fn should_read_an_mvhd_atom() {
-    let mut file = fs::File::open("resources/tests/mvhd.mp4").unwrap();
-    let header = AtomHeader::new(&mut file).unwrap();
-    let atom = MvhdAtom::new(header, &mut file).unwrap();
-    assert_eq!(108, atom.atom_size());
-    assert_eq!(0, atom.full_atom().version());
-    assert_eq!(0, atom.full_atom().flags());
-    assert_eq!(2082844800, atom.creation_time());
-    assert_eq!(2082844800, atom.modification_time());
-    assert_eq!(1000, atom.time_scale());
-    assert_eq!(973753, atom.duration());
-
+  /// The properties associated with one item. Each association is the 1-based index of a property
+  /// in the `ipco` atom, and whether a reader must understand it to show the item.
+  #[derive(Debug, Clone, PartialEq, Eq)]
+  pub struct ItemPropertyAssociation {
+    pub item_id: u32,
+    pub properties: Vec<(bool, u16)>,
   }
-  #[derive(Debug, Default, Clone)]
-  struct TkhdAtom {
+
+  /// The ipma atom is the HEIF item property association atom (ISO/IEC 23008-12).
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct IpmaAtom {
     atom_header: AtomHeader,
     full_atom: FullAtom,
-    creation_time: u32,
-    modification_time: u32,
-    track_id: u32,
-    reserved1: u32,
-    duration: u32,
-    reserved2: u64,
-    layer: u16,
-    alternate_group: u16,
-    volume: u16,
-    reserved3: u16,
-    matrix: Vec<u8>,
-    track_width: u32,
-    track_height: u32,
+    associations: Vec<ItemPropertyAssociation>,
   }
 
-  impl AtomLike for TkhdAtom {
-    fn atom_size(&self) -> u64 {
-      self.atom_header.atom_size()
-    }
-
-    fn atom_type(&self) -> &str {
-      self.atom_header.atom_type()
+  impl IpmaAtom {
+    pub fn new(atom_header: AtomHeader, file: &mut dyn ReadSeek) -> Result<IpmaAtom> {
+      let buf = atom_header.read_atom(file)?;
+      let mut bytes = &buf[(atom_header.header_size() as usize)..];
+      let full_atom = FullAtom::new(&mut bytes)?;
+      let num_entries = read_u32(&mut bytes)?;
+      let mut associations = Vec::with_capacity((num_entries as usize).min(bytes.len() / 3));
+      for _ in 0..num_entries {
+        let item_id = read_item_id(&mut bytes, full_atom.version())?;
+        let count = read_u8(&mut bytes)?;
+        let mut properties = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+          // Flag 1 selects 15 bit property indices instead of 7 bit ones.
+          properties.push(if full_atom.flags() & 1 == 1 {
+            let value = read_u16(&mut bytes)?;
+            (value & 0x8000 != 0, value & 0x7fff)
+          } else {
+            let value = read_u8(&mut bytes)?;
+            (value & 0x80 != 0, (value & 0x7f) as u16)
+          });
+        }
+        associations.push(ItemPropertyAssociation { item_id, properties });
+      }
+      Ok(IpmaAtom { atom_header, full_atom, associations })
     }
+    pub fn full_atom(&self) -> &FullAtom { &self.full_atom }
+    pub fn associations(&self) -> &Vec<ItemPropertyAssociation> { &self.associations }
+  }
 
-    fn atom_location(&self) -> u64 {
-      self.atom_header.atom_location()
-    }
+  impl AtomLike for IpmaAtom {
+    fn atom_size(&self) -> u64 { self.atom_header.atom_size() }
+    fn atom_type(&self) -> &str { self.atom_header.atom_type() }
+    fn atom_location(&self) -> u64 { self.atom_header.atom_location() }
+    fn header_size(&self) -> u32 { self.atom_header.header_size() }
+  }
 
-    fn header_size(&self) -> u32 {
-      self.atom_header.header_size()
+  impl std::fmt::Display for IpmaAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+      write!(f, "Ipma: {}, items: {}", self.atom_header, self.associations.len())
     }
   }
 }
-
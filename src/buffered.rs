@@ -0,0 +1,107 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// How many bytes [`BufferedReader`] reads from the file at a time.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Buffers the reads of the parser, which reads many small headers and fields and seeks between
+/// every one of them. Unlike `std::io::BufReader`, whose `seek` always throws the buffer away, a
+/// seek that lands within the buffered bytes only moves the position in the buffer, so walking
+/// the children of a container costs one read of the file rather than a read and a seek per
+/// child. Reads at least as big as the buffer go straight to the file.
+pub(crate) struct BufferedReader<R> {
+  inner: R,
+  buf: Vec<u8>,
+  /// Where in the file `buf` starts. The file itself is at `start + filled`.
+  start: u64,
+  filled: usize,
+  /// Where in `buf` the next read starts.
+  position: usize,
+}
+
+impl<R: Read + Seek> BufferedReader<R> {
+  /// Buffers `inner` from where it is now.
+  pub(crate) fn new(mut inner: R) -> io::Result<BufferedReader<R>> {
+    let start = inner.stream_position()?;
+    Ok(BufferedReader { inner, buf: vec![0; BUFFER_SIZE], start, filled: 0, position: 0 })
+  }
+  fn offset(&self) -> u64 { self.start + self.position as u64 }
+  /// Empties the buffer for a file that is now at `offset`.
+  fn discard(&mut self, offset: u64) {
+    self.start = offset;
+    self.filled = 0;
+    self.position = 0;
+  }
+}
+
+impl<R: Read + Seek> Read for BufferedReader<R> {
+  fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+    if self.position == self.filled {
+      let offset = self.offset();
+      if out.len() >= self.buf.len() {
+        let read = self.inner.read(out)?;
+        self.discard(offset + read as u64);
+        return Ok(read);
+      }
+      self.filled = self.inner.read(&mut self.buf)?;
+      self.start = offset;
+      self.position = 0;
+    }
+    let read = out.len().min(self.filled - self.position);
+    out[..read].copy_from_slice(&self.buf[self.position..self.position + read]);
+    self.position += read;
+    Ok(read)
+  }
+}
+
+impl<R: Read + Seek> Seek for BufferedReader<R> {
+  fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+    let target = match pos {
+      SeekFrom::Start(offset) => offset,
+      SeekFrom::Current(delta) => self.offset().checked_add_signed(delta).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+      })?,
+      SeekFrom::End(_) => {
+        let offset = self.inner.seek(pos)?;
+        self.discard(offset);
+        return Ok(offset);
+      }
+    };
+    if (self.start..=self.start + self.filled as u64).contains(&target) {
+      self.position = (target - self.start) as usize;
+    } else {
+      self.inner.seek(SeekFrom::Start(target))?;
+      self.discard(target);
+    }
+    Ok(target)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  #[test]
+  fn should_read_and_seek_like_the_file_it_buffers() {
+    let bytes: Vec<u8> = (0..3 * BUFFER_SIZE as u32).map(|x| (x % 251) as u8).collect();
+    let mut plain = Cursor::new(bytes.clone());
+    let mut buffered = BufferedReader::new(Cursor::new(bytes)).unwrap();
+    let steps = [SeekFrom::Start(10), SeekFrom::Current(8), SeekFrom::Current(-12), SeekFrom::Start(BUFFER_SIZE as u64 - 3),
+                 SeekFrom::End(-5), SeekFrom::Start(0), SeekFrom::Current(BUFFER_SIZE as i64 + 1)];
+    for (index, step) in steps.iter().enumerate() {
+      assert_eq!(plain.seek(*step).unwrap(), buffered.seek(*step).unwrap());
+      for len in [8, 100, BUFFER_SIZE + 7] {
+        let (mut expected, mut got) = (vec![0; len], vec![0; len]);
+        let expected_len = plain.read(&mut expected).unwrap();
+        let mut got_len = 0;
+        // A read can come back short at the end of the buffer, as it can for a file.
+        while got_len < expected_len {
+          got_len += buffered.read(&mut got[got_len..expected_len]).unwrap();
+        }
+        assert_eq!(expected[..expected_len], got[..got_len], "step {}, length {}", index, len);
+        assert_eq!(plain.stream_position().unwrap(), buffered.stream_position().unwrap());
+      }
+    }
+    assert!(buffered.seek(SeekFrom::Current(-(4 * BUFFER_SIZE as i64))).is_err());
+  }
+}
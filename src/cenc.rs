@@ -0,0 +1,150 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use super::atoms::leaves::{SaioAtom, SaizAtom};
+use super::parse_state::{ParseError, Result};
+
+/// Computes the byte range of every sample's auxiliary information from a `saiz` and `saio`
+/// pair. This is how per-sample encryption data is found in common encryption files when no
+/// `senc` atom is present.
+///
+/// Offsets in `saio` are relative to `base_offset`, which is 0 for offsets under `moov` and the
+/// base data offset of the track fragment for offsets under `traf`. A single `saio` offset means
+/// the information for all samples is stored contiguously. Otherwise there is one offset per
+/// chunk (or track run) and `samples_per_entry` gives the number of samples each one covers.
+/// Information that would run past `file_size`, the size of the file it is read from, fails with
+/// [`ParseError::NotEnoughSpace`] before any range is computed.
+pub fn aux_info_ranges(saiz: &SaizAtom, saio: &SaioAtom, base_offset: u64, samples_per_entry: &[u32],
+                       file_size: u64) -> Result<Vec<Range<u64>>> {
+  let offsets = saio.offsets();
+  let counts: Vec<u32> = if offsets.len() == 1 {
+    vec![saiz.sample_count()]
+  } else if offsets.len() == samples_per_entry.len() {
+    samples_per_entry.to_vec()
+  } else {
+    return Err(ParseError::parse_failed(saio, format!(
+      "{} offsets but {} sample runs were given", offsets.len(), samples_per_entry.len())));
+  };
+  if counts.iter().map(|&count| count as u64).sum::<u64>() != saiz.sample_count() as u64 {
+    return Err(ParseError::parse_failed(saiz, format!(
+      "sample runs do not add up to the {} samples", saiz.sample_count())));
+  }
+
+  let no_size = |sample: usize| ParseError::parse_failed(saiz, format!("no size for sample {}", sample));
+  let overflows = || ParseError::parse_failed(saio, "an offset overflows");
+  let mut ranges = Vec::new();
+  let mut sample = 0;
+  for (offset, count) in offsets.iter().zip(counts) {
+    let mut start = base_offset.checked_add(*offset).ok_or_else(overflows)?;
+    let needed = match saiz.default_sample_info_size() {
+      0 => (sample..sample + count as usize)
+        .map(|index| saiz.sample_info_size(index).map(u64::from).ok_or_else(|| no_size(index)))
+        .sum::<Result<u64>>()?,
+      size => u64::from(count) * u64::from(size),
+    };
+    if start.checked_add(needed).is_none_or(|end| end > file_size) {
+      return Err(ParseError::NotEnoughSpace { offset: start, needed, available: file_size.saturating_sub(start) });
+    }
+    for _ in 0..count {
+      let size = saiz.sample_info_size(sample).ok_or_else(|| no_size(sample))?;
+      let end = start.checked_add(size as u64).ok_or_else(overflows)?;
+      ranges.push(start..end);
+      start = end;
+      sample += 1;
+    }
+  }
+  Ok(ranges)
+}
+
+/// Reads the auxiliary information at each of `ranges`, as computed by [`aux_info_ranges`].
+pub fn read_aux_info<T>(file: &mut T, ranges: &[Range<u64>]) -> Result<Vec<Vec<u8>>> where T: Read + Seek {
+  let file_size = file.seek(SeekFrom::End(0))?;
+  ranges.iter().map(|range| {
+    if range.end > file_size {
+      return Err(ParseError::NotEnoughSpace { offset: range.start, needed: range.end - range.start,
+                                              available: file_size.saturating_sub(range.start) });
+    }
+    let mut buf = vec![0; (range.end - range.start) as usize];
+    file.seek(SeekFrom::Start(range.start))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::atoms::AtomHeader;
+  use std::io::Cursor;
+
+  fn atom(atom_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(atom_type);
+    bytes.extend_from_slice(payload);
+    bytes
+  }
+
+  fn parse_saiz(payload: &[u8]) -> SaizAtom {
+    let mut file = Cursor::new(atom(b"saiz", payload));
+    let header = AtomHeader::new(&mut file).unwrap();
+    SaizAtom::new(header, &mut file).unwrap()
+  }
+
+  fn parse_saio(payload: &[u8]) -> SaioAtom {
+    let mut file = Cursor::new(atom(b"saio", payload));
+    let header = AtomHeader::new(&mut file).unwrap();
+    SaioAtom::new(header, &mut file).unwrap()
+  }
+
+  #[test]
+  fn should_compute_contiguous_ranges_from_a_single_offset() {
+    // version 0, no flags, default size 0, 3 samples of 8, 16 and 0 bytes.
+    let saiz = parse_saiz(&[0, 0, 0, 0, 0, 0, 0, 0, 3, 8, 16, 0]);
+    // version 0, one 32 bit offset of 1000.
+    let saio = parse_saio(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0x03, 0xe8]);
+    let ranges = aux_info_ranges(&saiz, &saio, 0, &[], 1024).unwrap();
+    assert_eq!(vec![1000..1008, 1008..1024, 1024..1024], ranges);
+    assert!(matches!(aux_info_ranges(&saiz, &saio, 0, &[], 1023),
+                     Err(ParseError::NotEnoughSpace { offset: 1000, needed: 24, available: 23 })));
+  }
+
+  #[test]
+  fn should_compute_ranges_per_chunk_from_64_bit_offsets() {
+    // flags 1 carries an aux_info_type of 'cenc', default size 16 for 3 samples.
+    let saiz = parse_saiz(&[0, 0, 0, 1, b'c', b'e', b'n', b'c', 0, 0, 0, 0, 16, 0, 0, 0, 3]);
+    assert_eq!(Some(16), saiz.sample_info_size(2));
+    assert_eq!(None, saiz.sample_info_size(3));
+    // version 1, two 64 bit offsets.
+    let mut payload = vec![1, 0, 0, 0, 0, 0, 0, 2];
+    payload.extend_from_slice(&0x1_0000_0000u64.to_be_bytes());
+    payload.extend_from_slice(&0x2000u64.to_be_bytes());
+    let saio = parse_saio(&payload);
+    let ranges = aux_info_ranges(&saiz, &saio, 0x10, &[2, 1], u64::MAX).unwrap();
+    assert_eq!(vec![0x1_0000_0010..0x1_0000_0020, 0x1_0000_0020..0x1_0000_0030, 0x2010..0x2020], ranges);
+    assert!(aux_info_ranges(&saiz, &saio, 0, &[3], u64::MAX).is_err());
+    assert!(aux_info_ranges(&saiz, &saio, 0, &[1, 1], u64::MAX).is_err());
+  }
+
+  #[test]
+  fn should_refuse_aux_info_that_does_not_fit_in_the_file() {
+    // default size 16 for 0xffffffff samples, at a single 32 bit offset of 8.
+    let saiz = parse_saiz(&[0, 0, 0, 0, 16, 0xff, 0xff, 0xff, 0xff]);
+    let saio = parse_saio(&[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 8]);
+    assert!(matches!(aux_info_ranges(&saiz, &saio, 0, &[], 1 << 20),
+                     Err(ParseError::NotEnoughSpace { offset: 8, needed: 0xf_ffff_fff0, .. })));
+    // A version 1 offset near the end of the offsets a file can have.
+    let saiz = parse_saiz(&[0, 0, 0, 0, 16, 0, 0, 0, 1]);
+    let mut payload = vec![1, 0, 0, 0, 0, 0, 0, 1];
+    payload.extend_from_slice(&(u64::MAX - 8).to_be_bytes());
+    let saio = parse_saio(&payload);
+    assert!(matches!(aux_info_ranges(&saiz, &saio, 0, &[], u64::MAX),
+                     Err(ParseError::NotEnoughSpace { needed: 16, available: 8, .. })));
+  }
+
+  #[test]
+  fn should_read_aux_info_bytes_from_the_file() {
+    let mut file = Cursor::new((0u8..32).collect::<Vec<u8>>());
+    let info = read_aux_info(&mut file, &[4..6, 30..32]).unwrap();
+    assert_eq!(vec![vec![4, 5], vec![30, 31]], info);
+    assert!(read_aux_info(&mut file, &[0..2, 30..40]).is_err());
+  }
+}
@@ -0,0 +1,145 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use super::atoms::{AtomNodes, FourCC};
+use super::atoms::containers::ContainerAtoms;
+use super::atoms::leaves::Atoms;
+use super::parse_state::{ParseError, ParseResults, Result};
+use super::sample_table::SampleTable;
+
+/// The most bytes of a sample of a chapter track that are read: its 16-bit length and the longest
+/// text that can follow it.
+const MAX_TEXT_SAMPLE_READ: u64 = 2 + u16::MAX as u64;
+
+/// A chapter marker of a movie, see [`chapters`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Chapter {
+  /// When the chapter starts, in seconds from the start of the movie.
+  pub start_seconds: f64,
+  pub title: String,
+}
+
+/// The chapters of the movie, from the Nero `chpl` atom of `moov/udta` when there is one, and
+/// otherwise from the samples of the text track a `chap` track reference points at, which are
+/// read from `source`, the file the results were parsed from. A movie with neither has no
+/// chapters. A chapter track without a sample table fails with the error of
+/// [`SampleTable::from_stbl`].
+pub fn chapters(results: &ParseResults, source: &mut (impl Read + Seek)) -> Result<Vec<Chapter>> {
+  if let Some(AtomNodes::Atom(Atoms::Chpl(chpl))) = results.find("moov/udta/chpl") {
+    return Ok(chpl.entries().iter().map(|entry| Chapter {
+      start_seconds: entry.start_time as f64 / 10_000_000.0,
+      title: entry.title.clone(),
+    }).collect());
+  }
+  let chap = FourCC::from("chap");
+  let track_id = results.find_all("moov/trak").into_iter().filter_map(AtomNodes::as_trak)
+    .flat_map(|trak| trak.references())
+    .find_map(|(reference_type, track_ids)| if reference_type == chap { track_ids.first().copied() } else { None });
+  let track_id = match track_id {
+    Some(track_id) => track_id,
+    None => return Ok(Vec::new()),
+  };
+  let missing = |atom_type| ParseError::MissingAtom { atom_type: FourCC::from(atom_type), needed_for: "the chapters" };
+  let track = results.track(track_id).ok_or_else(|| missing("trak"))?;
+  let mdhd = track.mdhd().ok_or_else(|| missing("mdhd"))?;
+  let stbl = track.node().iter().find(|node| matches!(node, AtomNodes::Container(ContainerAtoms::Stbl(_))))
+    .ok_or_else(|| missing("stbl"))?;
+  let table = SampleTable::from_stbl(stbl)?;
+  let mut chapters = Vec::new();
+  for index in 0..table.sample_count() {
+    let (offset, size) = match (table.sample_file_offset(index), table.sample_size(index)) {
+      (Some(offset), Some(size)) => (offset, size),
+      _ => break,
+    };
+    // Only the text is read, however big the sample says it is.
+    let size = u64::from(size).min(MAX_TEXT_SAMPLE_READ);
+    let mut sample = Vec::new();
+    source.seek(SeekFrom::Start(offset))?;
+    if source.take(size).read_to_end(&mut sample)? as u64 != size {
+      return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    chapters.push(Chapter {
+      start_seconds: table.sample_decode_time(index).unwrap_or(0) as f64 / f64::from(mdhd.time_scale().max(1)),
+      title: text_sample(&sample),
+    });
+  }
+  Ok(chapters)
+}
+
+/// The text of a sample of a QuickTime text track: a 16-bit length and that many bytes of text,
+/// UTF-16 when they start with a byte order mark and UTF-8 otherwise. Anything after the text,
+/// such as style atoms, is left out.
+fn text_sample(sample: &[u8]) -> String {
+  let len = match sample {
+    [high, low, ..] => usize::from(u16::from_be_bytes([*high, *low])),
+    _ => 0,
+  };
+  let text = &sample[sample.len().min(2)..sample.len().min(2 + len)];
+  let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|unit| from_bytes([unit[0], unit[1]])).collect();
+    String::from_utf16_lossy(&units)
+  };
+  match text {
+    [0xfe, 0xff, rest @ ..] => utf16(rest, u16::from_be_bytes),
+    [0xff, 0xfe, rest @ ..] => utf16(rest, u16::from_le_bytes),
+    [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+    _ => String::from_utf8_lossy(text).into_owned(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Parser;
+
+  #[test]
+  fn should_read_the_same_chapters_from_a_chapter_list_and_a_chapter_track() {
+    let expected = vec![
+      Chapter { start_seconds: 0.0, title: "Opening".to_string() },
+      Chapter { start_seconds: 62.5, title: "The Middle".to_string() },
+      Chapter { start_seconds: 185.0, title: "\u{c9}pilogue".to_string() },
+    ];
+    for path in ["resources/tests/chapters_chpl.mp4", "resources/tests/chapters_text.mp4"] {
+      let mut source = std::fs::File::open(path).unwrap();
+      let results = Parser::from_path(path).unwrap().parse();
+      assert_eq!(expected, chapters(&results, &mut source).unwrap(), "{}", path);
+    }
+
+    // A movie without either has no chapters.
+    let path = "resources/tests/sample.mp4";
+    let results = Parser::from_path(path).unwrap().parse();
+    assert!(chapters(&results, &mut std::fs::File::open(path).unwrap()).unwrap().is_empty());
+  }
+
+  #[test]
+  fn should_fail_for_a_chapter_track_whose_samples_can_not_be_read() {
+    let original = std::fs::read("resources/tests/chapters_text.mp4").unwrap();
+    let chapters_of = |bytes: Vec<u8>| {
+      let results = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+      chapters(&results, &mut std::io::Cursor::new(bytes))
+    };
+    // The stsc of the chapter track at 603, renamed, leaves it without a sample table.
+    let mut bytes = original.clone();
+    bytes[607..611].copy_from_slice(b"xxxx");
+    match chapters_of(bytes) {
+      Err(ParseError::MissingAtom { atom_type, needed_for: "a sample table" }) => assert_eq!(atom_type, "stsc"),
+      other => panic!("expected MissingAtom, got {:?}", other),
+    }
+    // The size of the last sample, in the stsz at 631, is read only as far as the file goes.
+    let mut bytes = original;
+    bytes[659..663].copy_from_slice(&u32::MAX.to_be_bytes());
+    match chapters_of(bytes) {
+      Err(ParseError::IoError(err)) => assert_eq!(io::ErrorKind::UnexpectedEof, err.kind()),
+      other => panic!("expected IoError, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn should_decode_the_text_of_a_text_sample() {
+    assert_eq!("Intro", text_sample(b"\0\x05Intro\0\0\0\x0cstyl"));
+    assert_eq!("Caf\u{e9}", text_sample(b"\0\x0a\xfe\xff\0C\0a\0f\0\xe9"));
+    assert_eq!("Caf\u{e9}", text_sample(b"\0\x0a\xff\xfeC\0a\0f\0\xe9\0"));
+    assert_eq!("Caf\u{e9}", text_sample(b"\0\x08\xef\xbb\xbfCaf\xc3\xa9"));
+    // A length past the end of the sample reads to the end.
+    assert_eq!("Int", text_sample(b"\0\x09Int"));
+    assert_eq!("", text_sample(b"\0"));
+  }
+}
@@ -0,0 +1,171 @@
+//! Comparison of the atom trees of two files, such as a file before and after a remux.
+use std::collections::HashMap;
+use std::fmt;
+use super::atoms::{AtomLike, AtomNodes, Container};
+use super::json::decoded_fields;
+use super::parse_state::ParseResults;
+
+/// How an atom differs between the two trees, see [`DiffEntry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+  /// The atom is only in the first tree. Its children are not listed.
+  OnlyInA { size: u64 },
+  /// The atom is only in the second tree. Its children are not listed.
+  OnlyInB { size: u64 },
+  /// The atom is in both trees, with a different value of `field`. The size of the atom is the
+  /// field `atom_size`, and the other fields are those the atom decodes, as in the JSON output.
+  Changed { field: String, a: String, b: String },
+}
+
+/// A difference between the atoms at `path` of the two trees. The path is the types of the atoms
+/// from the top level down, separated by slashes, such as `moov/trak[1]/tkhd`. An atom with
+/// siblings of the same type before it has its index among them after its type, so the atoms of
+/// the trees are matched up in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+  pub path: String,
+  pub difference: Difference,
+}
+
+/// The differences between two atom trees, see [`diff_trees`]. `Display` gives a report with a
+/// line starting with `-` for what is only in the first tree and `+` for what is only in the
+/// second.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiff {
+  entries: Vec<DiffEntry>,
+}
+
+impl TreeDiff {
+  /// The differences in document order, those of atoms that are only in the second tree after
+  /// those of their siblings in the first.
+  pub fn entries(&self) -> &[DiffEntry] { &self.entries }
+  /// Whether the trees have the same atoms, of the same sizes, decoding to the same values.
+  pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+  fn diff_siblings(&mut self, parent: &str, a: &[AtomNodes], b: &[AtomNodes]) {
+    let (a, b) = (keyed(parent, a), keyed(parent, b));
+    let b_paths: HashMap<&str, &AtomNodes> = b.iter().map(|(path, node)| (path.as_str(), *node)).collect();
+    let a_paths: HashMap<&str, &AtomNodes> = a.iter().map(|(path, node)| (path.as_str(), *node)).collect();
+    for (path, a_node) in &a {
+      match b_paths.get(path.as_str()) {
+        Some(b_node) => self.diff_nodes(path, a_node, b_node),
+        None => self.push(path, Difference::OnlyInA { size: a_node.atom_size() }),
+      }
+    }
+    for (path, b_node) in &b {
+      if !a_paths.contains_key(path.as_str()) {
+        self.push(path, Difference::OnlyInB { size: b_node.atom_size() });
+      }
+    }
+  }
+  fn diff_nodes(&mut self, path: &str, a: &AtomNodes, b: &AtomNodes) {
+    if a.atom_size() != b.atom_size() {
+      self.changed(path, "atom_size", a.atom_size().to_string(), b.atom_size().to_string());
+    }
+    match (a, b) {
+      (AtomNodes::Container(a), AtomNodes::Container(b)) => self.diff_siblings(path, a.children(), b.children()),
+      (AtomNodes::Atom(a), AtomNodes::Atom(b)) => {
+        let (a, b) = (decoded_fields(a), decoded_fields(b));
+        for (field, a_value) in &a {
+          let b_value = b.iter().find(|(name, _)| name == field).map(|(_, value)| value.as_str());
+          if b_value != Some(a_value.as_str()) {
+            self.changed(path, field, a_value.clone(), b_value.unwrap_or("none").to_string());
+          }
+        }
+        for (field, b_value) in b.iter().filter(|(name, _)| !a.iter().any(|(field, _)| field == name)) {
+          self.changed(path, field, "none".to_string(), b_value.clone());
+        }
+      }
+      (a, _) => {
+        let kind = |is_container| if is_container { "container" } else { "leaf" };
+        let a_is_container = matches!(a, AtomNodes::Container(_));
+        self.changed(path, "kind", kind(a_is_container).to_string(), kind(!a_is_container).to_string());
+      }
+    }
+  }
+  fn changed(&mut self, path: &str, field: &str, a: String, b: String) {
+    self.push(path, Difference::Changed { field: field.to_string(), a, b });
+  }
+  fn push(&mut self, path: &str, difference: Difference) {
+    self.entries.push(DiffEntry { path: path.to_string(), difference });
+  }
+}
+
+/// The paths of `nodes`, the children of the atom at `parent`, see [`DiffEntry`].
+fn keyed<'a>(parent: &str, nodes: &'a [AtomNodes]) -> Vec<(String, &'a AtomNodes)> {
+  let mut seen: HashMap<&str, usize> = HashMap::new();
+  nodes.iter().map(|node| {
+    let index = seen.entry(node.atom_type()).or_default();
+    let mut path = if parent.is_empty() { node.atom_type().to_string() } else { format!("{}/{}", parent, node.atom_type()) };
+    if *index > 0 {
+      path = format!("{}[{}]", path, index);
+    }
+    *index += 1;
+    (path, node)
+  }).collect()
+}
+
+/// Compares the atoms of `a` with those of `b`, matching them up by their paths. The locations
+/// of the atoms are not compared, as moving atoms around is what a remux does.
+pub fn diff_trees(a: &ParseResults, b: &ParseResults) -> TreeDiff {
+  let mut diff = TreeDiff::default();
+  diff.diff_siblings("", a.top_level(), b.top_level());
+  diff
+}
+
+impl fmt::Display for DiffEntry {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match &self.difference {
+      Difference::OnlyInA { size } => writeln!(f, "- {} ({} bytes)", self.path, size),
+      Difference::OnlyInB { size } => writeln!(f, "+ {} ({} bytes)", self.path, size),
+      Difference::Changed { field, a, b } => {
+        writeln!(f, "- {} {}: {}", self.path, field, a)?;
+        writeln!(f, "+ {} {}: {}", self.path, field, b)
+      }
+    }
+  }
+}
+
+impl fmt::Display for TreeDiff {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for entry in &self.entries {
+      write!(f, "{}", entry)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Parser;
+
+  #[test]
+  fn should_name_the_field_of_a_changed_brand() {
+    let bytes = std::fs::read("resources/tests/sample.mp4").unwrap();
+    let mut remuxed = bytes.clone();
+    remuxed[8..12].copy_from_slice(b"mp42");
+    let a = Parser::from_bytes_owned(bytes).unwrap().parse();
+    let b = Parser::from_bytes_owned(remuxed).unwrap().parse();
+    let diff = diff_trees(&a, &b);
+    let changed = Difference::Changed { field: "major_brand".to_string(), a: "\"isom\"".to_string(), b: "\"mp42\"".to_string() };
+    assert_eq!(&[DiffEntry { path: "ftyp".to_string(), difference: changed }], diff.entries());
+    assert_eq!("- ftyp major_brand: \"isom\"\n+ ftyp major_brand: \"mp42\"\n", diff.to_string());
+    assert!(diff_trees(&a, &a).is_empty());
+  }
+
+  #[test]
+  fn should_match_up_tracks_by_their_index() {
+    let bytes = std::fs::read("resources/tests/chapters.mp4").unwrap();
+    // The same movie without its second track, the chapter track.
+    let mut without_chapters = bytes[..282].to_vec();
+    without_chapters[20..24].copy_from_slice(&262u32.to_be_bytes());
+    let a = Parser::from_bytes_owned(bytes).unwrap().parse();
+    let b = Parser::from_bytes_owned(without_chapters).unwrap().parse();
+    let diff = diff_trees(&a, &b);
+    let paths: Vec<&str> = diff.entries().iter().map(|entry| entry.path.as_str()).collect();
+    assert_eq!(vec!["moov", "moov/trak[1]"], paths);
+    assert_eq!("- moov atom_size: 431\n+ moov atom_size: 262\n- moov/trak[1] (169 bytes)\n", diff.to_string());
+    assert_eq!("- moov atom_size: 262\n+ moov atom_size: 431\n+ moov/trak[1] (169 bytes)\n", diff_trees(&b, &a).to_string());
+  }
+}
@@ -0,0 +1,91 @@
+//! Decompression of gzip files ([RFC 1952](https://www.rfc-editor.org/rfc/rfc1952)) and of the
+//! zlib streams ([RFC 1950](https://www.rfc-editor.org/rfc/rfc1950)) of compressed QuickTime
+//! movies, with the `flate2` crate. A gzip stream can't be seeked, so the whole file is
+//! decompressed into memory.
+
+use std::io::{self, Read};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use super::parse_state::{ParseError, Result};
+
+/// Reads all of `decoder`, failing once the output grows past `max_size` bytes. `format` names
+/// the stream in errors.
+fn read_to_limit(decoder: impl Read, max_size: u64, format: &str) -> Result<Vec<u8>> {
+  let mut out = Vec::new();
+  decoder.take(max_size.saturating_add(1)).read_to_end(&mut out).map_err(|err| {
+    ParseError::IoError(io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", format, err)))
+  })?;
+  if out.len() as u64 > max_size {
+    return Err(ParseError::NotValidMediaFileSize(
+      format!("{}: decompressed size exceeds the limit of {} bytes", format, max_size)));
+  }
+  Ok(out)
+}
+
+/// Decompresses a gzip file, checking its CRC and length. Fails once the output grows past
+/// `max_size` bytes.
+pub(crate) fn decompress(data: &[u8], max_size: u64) -> Result<Vec<u8>> {
+  read_to_limit(GzDecoder::new(data), max_size, "gzip")
+}
+
+/// Decompresses a zlib stream, checking the Adler-32 checksum after the data. Fails once the
+/// output grows past `max_size` bytes.
+pub(crate) fn decompress_zlib(data: &[u8], max_size: u64) -> Result<Vec<u8>> {
+  read_to_limit(ZlibDecoder::new(data), max_size, "zlib")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Write;
+
+  #[test]
+  fn should_decompress_a_gzip_file() {
+    let compressed = std::fs::read("resources/tests/moov.mp4.gz").unwrap();
+    let original = std::fs::read("resources/tests/moov.mp4").unwrap();
+    assert!(decompress(&compressed, u64::MAX).unwrap() == original);
+  }
+
+  #[test]
+  fn should_stop_at_the_size_limit() {
+    let compressed = std::fs::read("resources/tests/moov.mp4.gz").unwrap();
+    match decompress(&compressed, 1024) {
+      Err(ParseError::NotValidMediaFileSize(_)) => {}
+      other => panic!("unexpected result {:?}", other.map(|x| x.len())),
+    }
+  }
+
+  #[test]
+  fn should_decompress_stored_blocks() {
+    // "abc" in a single stored block, written the way `gzip` stores incompressible data.
+    let mut data = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3, 1, 3, 0, 0xfc, 0xff];
+    data.extend_from_slice(b"abc");
+    let mut crc = flate2::Crc::new();
+    crc.update(b"abc");
+    data.extend_from_slice(&crc.sum().to_le_bytes());
+    data.extend_from_slice(&3u32.to_le_bytes());
+    assert_eq!(b"abc".to_vec(), decompress(&data, 3).unwrap());
+  }
+
+  #[test]
+  fn should_decompress_a_zlib_stream() {
+    // "abc" in a single stored block, after the header zlib writes by default.
+    let mut data = vec![0x78, 0x9c, 1, 3, 0, 0xfc, 0xff];
+    data.extend_from_slice(b"abc");
+    data.extend_from_slice(&0x024d_0127u32.to_be_bytes());
+    assert_eq!(b"abc".to_vec(), decompress_zlib(&data, 3).unwrap());
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    assert!(decompress_zlib(&data, 3).is_err());
+  }
+
+  #[test]
+  fn should_refuse_corrupt_streams() {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&[7; 1000]).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(vec![7; 1000], decompress(&compressed, 1000).unwrap());
+    assert!(decompress(&compressed[..compressed.len() - 4], 1000).is_err());
+    assert!(decompress(b"not gzip at all", 1000).is_err());
+    assert!(decompress_zlib(&compressed, 1000).is_err());
+  }
+}
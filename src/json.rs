@@ -0,0 +1,490 @@
+//! JSON output of the parse tree, for tools that read the structure rather than the drawn tree.
+//! Every atom has its header fields, containers their children and typed leaves the fields they
+//! decode. Sample tables are summarized by their entry counts to keep the output readable.
+use std::fmt::{self, Write};
+use super::atoms::{AtomHeader, AtomLike, AtomNodes, Container, FourCC, FullAtom};
+use super::atoms::leaves::{Atoms, ItemProperty, MetadataValue, SampleDescription};
+use super::parse_state::ParseResults;
+
+/// Conversion to a JSON document.
+pub trait ToJson {
+  fn to_json(&self) -> String;
+}
+
+impl ToJson for AtomHeader {
+  fn to_json(&self) -> String { header(self).to_string() }
+}
+
+impl ToJson for AtomNodes {
+  fn to_json(&self) -> String { node(self).to_string() }
+}
+
+impl ToJson for Atoms {
+  fn to_json(&self) -> String { leaf(self).to_string() }
+}
+
+impl ToJson for ParseResults {
+  /// The top-level atoms, and the error that stopped parsing if there was one.
+  fn to_json(&self) -> String {
+    let mut results = vec![("atoms", Json::Array(self.top_level().iter().map(node).collect()))];
+    if let Some(err) = self.error() {
+      results.push(("error", Json::from(err.to_string())));
+    }
+    Json::Object(results).to_string()
+  }
+}
+
+/// A JSON value. Object members keep the order they are added in.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+  Bool(bool),
+  Unsigned(u64),
+  Signed(i64),
+  Float(f64),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(&'static str, Json)>),
+}
+
+impl From<bool> for Json {
+  fn from(value: bool) -> Json { Json::Bool(value) }
+}
+impl From<u64> for Json {
+  fn from(value: u64) -> Json { Json::Unsigned(value) }
+}
+impl From<u32> for Json {
+  fn from(value: u32) -> Json { Json::Unsigned(value.into()) }
+}
+impl From<u16> for Json {
+  fn from(value: u16) -> Json { Json::Unsigned(value.into()) }
+}
+impl From<u8> for Json {
+  fn from(value: u8) -> Json { Json::Unsigned(value.into()) }
+}
+impl From<usize> for Json {
+  fn from(value: usize) -> Json { Json::Unsigned(value as u64) }
+}
+impl From<i64> for Json {
+  fn from(value: i64) -> Json { Json::Signed(value) }
+}
+impl From<i32> for Json {
+  fn from(value: i32) -> Json { Json::Signed(value.into()) }
+}
+impl From<f64> for Json {
+  fn from(value: f64) -> Json { Json::Float(value) }
+}
+impl From<f32> for Json {
+  fn from(value: f32) -> Json { Json::Float(value.into()) }
+}
+impl From<&str> for Json {
+  fn from(value: &str) -> Json { Json::String(value.to_string()) }
+}
+impl From<String> for Json {
+  fn from(value: String) -> Json { Json::String(value) }
+}
+impl From<FourCC> for Json {
+  fn from(value: FourCC) -> Json { Json::String(value.to_string()) }
+}
+impl From<&[u8]> for Json {
+  fn from(value: &[u8]) -> Json { Json::Array(value.iter().map(|x| Json::from(*x)).collect()) }
+}
+
+impl Json {
+  fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+    let items = |f: &mut fmt::Formatter, open: char, close: char, count: usize,
+                 item: &dyn Fn(&mut fmt::Formatter, usize) -> fmt::Result| -> fmt::Result {
+      if count == 0 {
+        return write!(f, "{}{}", open, close);
+      }
+      writeln!(f, "{}", open)?;
+      for i in 0..count {
+        write!(f, "{:1$}", "", indent + 2)?;
+        item(f, i)?;
+        writeln!(f, "{}", if i + 1 < count { "," } else { "" })?;
+      }
+      write!(f, "{:1$}{2}", "", indent, close)
+    };
+    match self {
+      Json::Bool(value) => write!(f, "{}", value),
+      Json::Unsigned(value) => write!(f, "{}", value),
+      Json::Signed(value) => write!(f, "{}", value),
+      Json::Float(value) if value.is_finite() => write!(f, "{}", value),
+      Json::Float(_) => write!(f, "null"),
+      Json::String(value) => write_string(f, value),
+      Json::Array(values) =>
+        items(f, '[', ']', values.len(), &|f, i| values[i].write(f, indent + 2)),
+      Json::Object(members) => items(f, '{', '}', members.len(), &|f, i| {
+        write_string(f, members[i].0)?;
+        write!(f, ": ")?;
+        members[i].1.write(f, indent + 2)
+      }),
+    }
+  }
+}
+
+impl Json {
+  /// The value on one line, for showing it inline.
+  fn compact(&self) -> String {
+    match self {
+      Json::Array(values) => format!("[{}]", values.iter().map(Json::compact).collect::<Vec<String>>().join(", ")),
+      Json::Object(members) => format!("{{{}}}", members.iter()
+        .map(|(name, value)| format!("{}: {}", Json::from(*name), value.compact())).collect::<Vec<String>>().join(", ")),
+      value => value.to_string(),
+    }
+  }
+}
+
+impl fmt::Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    self.write(f, 0)
+  }
+}
+
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+  f.write_char('"')?;
+  for c in value.chars() {
+    match c {
+      '"' => f.write_str("\\\"")?,
+      '\\' => f.write_str("\\\\")?,
+      '\n' => f.write_str("\\n")?,
+      '\r' => f.write_str("\\r")?,
+      '\t' => f.write_str("\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => f.write_char(c)?,
+    }
+  }
+  f.write_char('"')
+}
+
+fn header_fields(atom: &dyn AtomLike) -> Vec<(&'static str, Json)> {
+  vec![("atom_type", atom.atom_type().into()), ("atom_size", atom.atom_size().into()),
+       ("atom_location", atom.atom_location().into()), ("header_size", atom.header_size().into())]
+}
+
+fn header(atom: &AtomHeader) -> Json {
+  Json::Object(header_fields(atom))
+}
+
+fn full_atom_fields(full_atom: &FullAtom) -> Vec<(&'static str, Json)> {
+  vec![("version", full_atom.version().into()), ("flags", full_atom.flags().into())]
+}
+
+fn node(node: &AtomNodes) -> Json {
+  match node {
+    AtomNodes::Container(container) => {
+      let mut fields = header_fields(container);
+      fields.push(("children", Json::Array(container.children().iter().map(self::node).collect())));
+      Json::Object(fields)
+    }
+    AtomNodes::Atom(atom) => leaf(atom),
+  }
+}
+
+/// The value of a field an atom decodes, see [`AtomNodes::fields`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+  Bool(bool),
+  Unsigned(u64),
+  Signed(i64),
+  Float(f64),
+  Text(String),
+  List(Vec<FieldValue>),
+  /// A group of named fields, such as an entry of a table.
+  Fields(Vec<(String, FieldValue)>),
+}
+
+impl From<Json> for FieldValue {
+  fn from(value: Json) -> FieldValue {
+    match value {
+      Json::Bool(value) => FieldValue::Bool(value),
+      Json::Unsigned(value) => FieldValue::Unsigned(value),
+      Json::Signed(value) => FieldValue::Signed(value),
+      Json::Float(value) => FieldValue::Float(value),
+      Json::String(value) => FieldValue::Text(value),
+      Json::Array(values) => FieldValue::List(values.into_iter().map(FieldValue::from).collect()),
+      Json::Object(members) => FieldValue::Fields(members.into_iter()
+        .map(|(name, value)| (name.to_string(), value.into())).collect()),
+    }
+  }
+}
+
+/// The fields of `node` for [`AtomNodes::fields`].
+pub(crate) fn node_fields(node: &AtomNodes) -> Vec<(String, FieldValue)> {
+  match node {
+    AtomNodes::Atom(atom) => match leaf(atom) {
+      Json::Object(members) => members.into_iter().filter(|(name, _)| !is_header_field(name))
+        .map(|(name, value)| (name.to_string(), value.into())).collect(),
+      _ => Vec::new(),
+    },
+    AtomNodes::Container(_) => Vec::new(),
+  }
+}
+
+fn is_header_field(name: &str) -> bool {
+  matches!(name, "atom_type" | "atom_size" | "atom_location" | "header_size")
+}
+
+/// The fields a typed leaf decodes, with their values on one line, leaving out the header fields
+/// that every atom has.
+pub(crate) fn decoded_fields(atom: &Atoms) -> Vec<(&'static str, String)> {
+  match leaf(atom) {
+    Json::Object(members) => members.into_iter()
+      .filter(|(name, _)| !is_header_field(name))
+      .map(|(name, value)| (name, value.compact())).collect(),
+    _ => Vec::new(),
+  }
+}
+
+fn leaf(atom: &Atoms) -> Json {
+  let mut fields = header_fields(atom);
+  let entry_count = |count: usize| ("entry_count", Json::from(count));
+  match atom {
+    Atoms::Ftyp(ftyp) => {
+      fields.push(("major_brand", ftyp.major_brand().into()));
+      fields.push(("minor_version", ftyp.minor_version().into()));
+      fields.push(("compatible_brands", Json::Array(ftyp.compatible_brands().iter().map(|brand| Json::from(*brand)).collect())));
+    }
+    Atoms::Mvhd(mvhd) => {
+      fields.extend(full_atom_fields(mvhd.full_atom()));
+      fields.extend(vec![
+        ("creation_time", mvhd.creation_time().into()), ("modification_time", mvhd.modification_time().into()),
+        ("time_scale", mvhd.time_scale().into()), ("duration", mvhd.duration().into()),
+        ("preferred_rate", mvhd.preferred_rate().into()), ("preferred_volume", mvhd.preferred_vol().into()),
+        ("matrix", mvhd.matrix().as_slice().into()), ("preview_time", mvhd.preview_time().into()),
+        ("preview_duration", mvhd.preview_duration().into()), ("poster_time", mvhd.poster_time().into()),
+        ("selection_time", mvhd.selection_time().into()), ("selection_duration", mvhd.selection_duration().into()),
+        ("current_time", mvhd.current_time().into()), ("next_track_id", mvhd.next_track_id().into()),
+      ]);
+    }
+    Atoms::Tkhd(tkhd) => {
+      fields.extend(full_atom_fields(tkhd.full_atom()));
+      fields.extend(vec![
+        ("creation_time", tkhd.creation_time().into()), ("modification_time", tkhd.modification_time().into()),
+        ("track_id", tkhd.track_id().into()), ("duration", tkhd.duration().into()), ("layer", tkhd.layer().into()),
+        ("alternate_group", tkhd.alternate_group().into()), ("volume", tkhd.volume().into()),
+        ("matrix", tkhd.matrix().as_slice().into()), ("width", tkhd.width_f32().into()),
+        ("height", tkhd.height_f32().into()),
+      ]);
+    }
+    Atoms::Mdhd(mdhd) => {
+      fields.extend(full_atom_fields(mdhd.full_atom()));
+      fields.extend(vec![
+        ("creation_time", mdhd.creation_time().into()), ("modification_time", mdhd.modification_time().into()),
+        ("time_scale", mdhd.time_scale().into()), ("duration", mdhd.duration().into()),
+        ("language", mdhd.language().into()), ("quality", mdhd.quality().into()),
+      ]);
+    }
+    Atoms::Hdlr(hdlr) => {
+      fields.extend(full_atom_fields(hdlr.full_atom()));
+      fields.extend(vec![
+        ("component_type", hdlr.component_type().into()), ("handler_type", hdlr.handler_type().into()),
+        ("manufacturer", FourCC::new(*hdlr.manufacturer()).into()), ("name", hdlr.name().into()),
+      ]);
+    }
+    Atoms::Stsd(stsd) => {
+      fields.extend(full_atom_fields(stsd.full_atom()));
+      fields.push(("entries", Json::Array(stsd.entries().iter().map(sample_description).collect())));
+    }
+    Atoms::Stco(stco) => fields.push(entry_count(stco.chunk_offsets().len())),
+    Atoms::Co64(co64) => fields.push(entry_count(co64.chunk_offsets().len())),
+    Atoms::Stsc(stsc) => fields.push(entry_count(stsc.entries().len())),
+    Atoms::Stts(stts) => fields.push(entry_count(stts.entries().len())),
+    Atoms::Ctts(ctts) => fields.push(entry_count(ctts.entries().len())),
+    Atoms::Stss(stss) => fields.push(entry_count(stss.sync_samples().len())),
+    Atoms::Stsz(stsz) => {
+      fields.push(("sample_size", stsz.sample_size().into()));
+      fields.push(("sample_count", stsz.sample_count().into()));
+    }
+    Atoms::Saiz(saiz) => fields.push(("sample_count", saiz.sample_count().into())),
+    Atoms::Saio(saio) => fields.push(entry_count(saio.offsets().len())),
+    Atoms::Elst(elst) => {
+      fields.extend(full_atom_fields(elst.full_atom()));
+      fields.push(("entries", Json::Array(elst.entries().iter().map(|entry| Json::Object(vec![
+        ("segment_duration", entry.segment_duration.into()), ("media_time", entry.media_time.into()),
+        ("media_rate", entry.media_rate.into()),
+      ])).collect())));
+    }
+    Atoms::Ilst(ilst) => {
+      fields.push(("items", Json::Array(ilst.items().iter().map(|item| Json::Object(vec![
+        ("key", item.key().into()),
+        ("values", Json::Array(item.entries().iter().map(|entry| metadata_value(&entry.value)).collect())),
+      ])).collect())));
+    }
+    Atoms::Mfhd(mfhd) => fields.push(("sequence_number", mfhd.sequence_number().into())),
+    Atoms::Tfhd(tfhd) => {
+      fields.extend(full_atom_fields(tfhd.full_atom()));
+      fields.push(("track_id", tfhd.track_id().into()));
+      let optional = [("base_data_offset", tfhd.base_data_offset()),
+                      ("sample_description_index", tfhd.sample_description_index().map(u64::from)),
+                      ("default_sample_duration", tfhd.default_sample_duration().map(u64::from)),
+                      ("default_sample_size", tfhd.default_sample_size().map(u64::from)),
+                      ("default_sample_flags", tfhd.default_sample_flags().map(u64::from))];
+      fields.extend(optional.iter().filter_map(|(name, value)| value.map(|value| (*name, Json::from(value)))));
+    }
+    Atoms::Trun(trun) => {
+      fields.extend(full_atom_fields(trun.full_atom()));
+      fields.push(("sample_count", trun.sample_count().into()));
+      fields.extend(trun.data_offset().map(|offset| ("data_offset", Json::from(offset))));
+      fields.extend(trun.first_sample_flags().map(|flags| ("first_sample_flags", Json::from(flags))));
+    }
+    Atoms::Vmhd(vmhd) => {
+      fields.extend(full_atom_fields(vmhd.full_atom()));
+      fields.push(("graphics_mode", vmhd.graphics_mode().into()));
+      fields.push(("opcolor", Json::Array(vmhd.opcolor().iter().map(|x| Json::from(*x)).collect())));
+    }
+    Atoms::Smhd(smhd) => {
+      fields.extend(full_atom_fields(smhd.full_atom()));
+      fields.push(("balance", smhd.balance_f32().into()));
+    }
+    Atoms::Gmin(gmin) => {
+      fields.extend(full_atom_fields(gmin.full_atom()));
+      fields.push(("graphics_mode", gmin.graphics_mode().into()));
+      fields.push(("opcolor", Json::Array(gmin.opcolor().iter().map(|x| Json::from(*x)).collect())));
+      fields.push(("balance", gmin.balance_f32().into()));
+    }
+    Atoms::Uuid(uuid) => {
+      fields.push(("uuid", uuid.uuid_string().into()));
+      fields.push(("payload_size", uuid.payload_size().into()));
+    }
+    Atoms::Chpl(chpl) => {
+      fields.extend(full_atom_fields(chpl.full_atom()));
+      fields.push(("entries", Json::Array(chpl.entries().iter().map(|entry| Json::Object(vec![
+        ("start_time", entry.start_time.into()), ("title", entry.title.as_str().into()),
+      ])).collect())));
+    }
+    Atoms::Vendor(vendor) => match vendor.as_utf8() {
+      Some(text) => fields.push(("text", text.into())),
+      None => fields.push(("payload_size", vendor.payload().len().into())),
+    },
+    Atoms::TrackReference(reference) => {
+      fields.push(("track_ids", Json::Array(reference.track_ids().iter().map(|id| Json::from(*id)).collect())));
+    }
+    Atoms::Dref(dref) => {
+      fields.extend(full_atom_fields(dref.full_atom()));
+      fields.push(("self_contained", dref.is_self_contained().into()));
+      fields.push(("entries", Json::Array(dref.entries().iter().map(|entry| {
+        let mut fields = vec![("reference_type", entry.reference_type().into()), ("size", entry.size().into()),
+                              ("self_contained", entry.is_self_contained().into())];
+        fields.extend(entry.url().map(|url| ("url", Json::from(url))));
+        Json::Object(fields)
+      }).collect())));
+    }
+    Atoms::Aperture(aperture) => {
+      let (width, height) = aperture.dimensions();
+      fields.push(("width", width.into()));
+      fields.push(("height", height.into()));
+    }
+    Atoms::Pitm(pitm) => fields.push(("item_id", pitm.item_id().into())),
+    Atoms::Iref(iref) => {
+      fields.push(("references", Json::Array(iref.references().iter().map(|reference| Json::Object(vec![
+        ("reference_type", reference.reference_type.as_str().into()), ("from_item_id", reference.from_item_id.into()),
+        ("to_item_ids", Json::Array(reference.to_item_ids.iter().map(|x| Json::from(*x)).collect())),
+      ])).collect())));
+    }
+    Atoms::Ipco(ipco) => {
+      fields.push(("properties", Json::Array(ipco.properties().iter().map(item_property).collect())));
+    }
+    Atoms::Ipma(ipma) => {
+      fields.push(("associations", Json::Array(ipma.associations().iter().map(|association| Json::Object(vec![
+        ("item_id", association.item_id.into()),
+        ("properties", Json::Array(association.properties.iter().map(|(essential, index)| Json::Object(vec![
+          ("essential", (*essential).into()), ("index", (*index).into()),
+        ])).collect())),
+      ])).collect())));
+    }
+    Atoms::Free(_) | Atoms::Wide(_) | Atoms::Mdat(_) | Atoms::Custom(_) | Atoms::UnknownAtom(_) | Atoms::Slack { .. } => {}
+  }
+  Json::Object(fields)
+}
+
+fn sample_description(description: &SampleDescription) -> Json {
+  let entry = description.entry();
+  let mut fields = vec![("data_format", Json::from(entry.data_format())),
+                        ("data_reference_index", entry.data_reference_index().into())];
+  match description {
+    SampleDescription::Video(video) => {
+      let (width, height) = video.dimensions();
+      fields.extend(vec![("width", width.into()), ("height", height.into()), ("depth", video.depth().into()),
+                         ("compressor_name", video.compressor_name().into())]);
+      if let Some(avcc) = video.avc_configuration() {
+        fields.push(("avc_configuration", Json::Object(vec![
+          ("profile_idc", avcc.profile_idc().into()), ("level_idc", avcc.level_idc().into()),
+          ("nal_length_size", avcc.nal_length_size().into())])));
+      }
+    }
+    SampleDescription::Audio(audio) => {
+      fields.extend(vec![("channel_count", audio.channels().into()),
+                         ("bits_per_sample", audio.bits_per_sample().into()),
+                         ("sample_rate", audio.sample_rate().into())]);
+      if let Some(config) = audio.esds().and_then(|esds| esds.audio_specific_config()) {
+        fields.push(("audio_specific_config", Json::Object(vec![
+          ("object_type", config.object_type().into()), ("sampling_frequency", config.sampling_frequency().into()),
+          ("channel_configuration", config.channel_configuration().into())])));
+      }
+    }
+    SampleDescription::Timecode(timecode) => {
+      fields.extend(vec![("flags", timecode.flags().into()), ("drop_frame", timecode.is_drop_frame().into()),
+                         ("time_scale", timecode.time_scale().into()),
+                         ("frame_duration", timecode.frame_duration().into()),
+                         ("number_of_frames", timecode.number_of_frames().into())]);
+    }
+    SampleDescription::Other(_) => {}
+  }
+  fields.push(("extensions", Json::Array(description.extensions().iter().map(header).collect())));
+  Json::Object(fields)
+}
+
+fn metadata_value(value: &MetadataValue) -> Json {
+  match value {
+    MetadataValue::Text(text) => text.as_str().into(),
+    MetadataValue::Integer(integer) => (*integer).into(),
+    MetadataValue::Jpeg(data) | MetadataValue::Png(data) => data.as_slice().into(),
+    MetadataValue::Binary { data, .. } => data.as_slice().into(),
+  }
+}
+
+fn item_property(property: &ItemProperty) -> Json {
+  match property {
+    ItemProperty::ImageSpatialExtents { width, height } =>
+      Json::Object(vec![("property_type", "ispe".into()), ("width", (*width).into()), ("height", (*height).into())]),
+    ItemProperty::Rotation { degrees } =>
+      Json::Object(vec![("property_type", "irot".into()), ("degrees", (*degrees).into())]),
+    ItemProperty::Mirror { axis } =>
+      Json::Object(vec![("property_type", "imir".into()), ("axis", (*axis).into())]),
+    ItemProperty::Other { property_type } =>
+      Json::Object(vec![("property_type", property_type.as_str().into())]),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Parser;
+
+  #[test]
+  fn should_write_the_tree_with_its_decoded_fields() {
+    let json = Parser::from_path("resources/tests/sample.mp4").unwrap().parse().to_json();
+    assert!(json.starts_with("{\n  \"atoms\": [\n    {\n      \"atom_type\": \"ftyp\","), "{}", &json[..100]);
+    assert!(json.contains("\"major_brand\": \"isom\""));
+    assert!(json.contains("\"atom_type\": \"moov\",\n      \"atom_size\": 1023163,\n      \"atom_location\": 32,\n      \
+                           \"header_size\": 8,\n      \"children\": [\n        {\n          \"atom_type\": \"mvhd\","));
+    assert!(json.contains("\"time_scale\": 1000,"));
+    assert!(json.contains("\"matrix\": [\n            0,\n            1,"));
+    assert!(json.contains("\"key\": \"©too\",\n"));
+    assert!(json.contains("\"profile_idc\": 100,"));
+    assert!(json.contains("\"sampling_frequency\": 44100,"));
+    assert!(!json.contains("\"error\""));
+    assert_eq!(json.matches('{').count(), json.matches('}').count());
+  }
+
+  #[test]
+  fn should_escape_strings_and_write_unknown_atoms_as_their_header() {
+    assert_eq!("\"a \\\"b\\\"\\n\\u0001\"", Json::from("a \"b\"\n\u{1}").to_string());
+    assert_eq!("[]", Json::Array(Vec::new()).to_string());
+    let header = AtomHeader::synthesized(b"XVND", 16, 40);
+    let unknown = Atoms::UnknownAtom(crate::leaves::UnknownAtom::new(header));
+    assert_eq!(header.to_json(), unknown.to_json());
+    assert_eq!("{\n  \"atom_type\": \"XVND\",\n  \"atom_size\": 16,\n  \"atom_location\": 40,\n  \"header_size\": 8\n}",
+               unknown.to_json());
+  }
+}
@@ -7,22 +7,118 @@
 
 mod parse_state;
 mod atoms;
+mod buffered;
+mod chapters;
+mod sample_table;
+mod cenc;
+mod writer;
+mod timeline;
+mod timecode;
+mod track;
+mod validation;
+mod json;
+mod scan;
+mod stats;
+pub mod summary;
+pub mod diff;
+#[cfg(feature = "flate2")]
+mod gzip;
 
 pub use atoms::*;
-pub use parse_state::{ParseError, Result, ParseResults};
+pub use parse_state::{ParseError, ParseWarning, Result, ParseResults, Parser, ParseCursor, ParseContext, ParseOptions, ParseLimits, Limit, CustomHandler, Truncated};
+#[cfg(feature = "flate2")]
+pub use parse_state::DEFAULT_MAX_GZ_SIZE;
+#[cfg(feature = "colored")]
+pub use parse_state::color_enabled;
+pub use scan::HeaderIter;
+pub use chapters::{chapters, Chapter};
+pub use stats::FileStats;
+pub use sample_table::{SampleInfo, SampleTable, Samples};
+pub use timeline::{SampleTime, Timeline};
+pub use timecode::{timecode_for_track, Timecode};
+pub use track::Track;
+pub use validation::ValidationWarning;
+pub use json::{FieldValue, ToJson};
+pub use cenc::{aux_info_ranges, read_aux_info};
+pub use writer::{extract_atom, make_fast_start, serialized_size, shift_chunk_offsets, write_node, WriteAtom};
+pub use summary::{MediaType, MovieInfo, TrackInfo};
+pub use diff::{diff_trees, DiffEntry, Difference, TreeDiff};
 
-use parse_state::Parser;
 
 pub struct Config {
   filename: String,
+  options: ParseOptions,
+  threads: usize,
 }
 
 impl Config {
   pub fn new(filename: &str) -> Config{
-    Config{ filename: filename.to_string() }
+    Config{ filename: filename.to_string(), options: ParseOptions::default(), threads: 1 }
+  }
+  /// A config with the options set in the environment, see [`ParseOptions::from_env`].
+  pub fn from_env(filename: &str) -> Config {
+    Config::new(filename).with_options(ParseOptions::from_env())
+  }
+  pub fn with_options(mut self, options: ParseOptions) -> Config {
+    self.options = options;
+    self
+  }
+  pub fn options(&self) -> &ParseOptions { &self.options }
+  /// Replaces the limits on how deeply atoms nest and how many there are, see [`ParseLimits`].
+  pub fn with_limits(mut self, limits: ParseLimits) -> Config {
+    self.options.limits = limits;
+    self
+  }
+  /// Keep only the entry counts of large sample tables, see [`ParseContext::with_skip_sample_tables`].
+  pub fn skip_sample_tables(mut self, skip: bool) -> Config {
+    self.options.skip_sample_tables = skip;
+    self
+  }
+  /// Fail on the first atom that can't be parsed rather than keeping going with a warning, see
+  /// [`ParseOptions::strict`].
+  pub fn strict(mut self, strict: bool) -> Config {
+    self.options.strict = strict;
+    self
+  }
+  /// Keep atoms of the given types without reading them, see [`ParseOptions::skip_types`].
+  pub fn skip_types(mut self, atom_types: &[&str]) -> Config {
+    self.options.skip_types = atom_types.iter().map(|atom_type| FourCC::from(*atom_type)).collect();
+    self
+  }
+  /// Fail on leaf atoms bigger than `max_size` bytes, see [`ParseOptions::max_atom_read`].
+  pub fn max_atom_read(mut self, max_size: u64) -> Config {
+    self.options.max_atom_read = Some(max_size);
+    self
+  }
+  /// Parse atoms of the given types as containers of child atoms, see
+  /// [`ParseOptions::containers`].
+  pub fn containers(mut self, atom_types: &[&str]) -> Config {
+    self.options.containers = atom_types.iter().map(|atom_type| FourCC::from(*atom_type)).collect();
+    self
+  }
+  /// Parse the tracks of the movie on `threads` threads, see [`Parser::parse_parallel`]. The
+  /// default of 1 parses in turn.
+  pub fn with_threads(mut self, threads: usize) -> Config {
+    self.threads = threads;
+    self
+  }
+}
+/// Parses the file named by `config`, failing when the file can't be opened. Errors found while
+/// parsing are kept in the results.
+pub fn run(config: Config) -> Result<ParseResults> {
+  let mut parser = Parser::from_path(&config.filename)?.with_options(config.options);
+  if config.threads > 1 {
+    Ok(parser.with_threads(config.threads).parse_parallel())
+  } else {
+    Ok(parser.parse())
+  }
+}
+/// Parses the file named by `config` and summarizes its movie, failing when the file can't be
+/// parsed or has no movie in it.
+pub fn run_summary(config: Config) -> Result<MovieInfo> {
+  let results = run(config)?;
+  match MovieInfo::from_results(&results) {
+    Err(_) if results.error().is_some() => Err(results.into_error().unwrap()),
+    info => info,
   }
 }
-pub fn run(config: Config) -> ParseResults{
-  let mut parser = Parser::new(&config.filename).unwrap();
-  parser.parse()
-}
\ No newline at end of file
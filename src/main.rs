@@ -3,17 +3,235 @@ use std::env;
 use std::process;
 use media_atoms::*;
 
+/// Exit codes used by `--validate` and the other modes.
+const EXIT_USAGE: i32 = 1;
+const EXIT_WARNINGS: i32 = 2;
+const EXIT_UNPARSEABLE: i32 = 3;
+/// `--locate` found no atom holding the offset.
+const EXIT_NOT_FOUND: i32 = 4;
+
+fn usage(program: &str) -> ! {
+  eprintln!("Usage: {} [--validate | --json | --summary | --stats | --scan | --locate <offset> | --diff <other file> | --extract <atom path> [--out <file>]] <path to file>", program);
+  eprintln!("  --validate  check the file structure, exit 0 when clean, {} on warnings, {} when unparseable",
+            EXIT_WARNINGS, EXIT_UNPARSEABLE);
+  eprintln!("  --json      print the atoms as JSON instead of a tree");
+  eprintln!("  --summary   print the duration and tracks of the movie instead of a tree");
+  eprintln!("  --stats     print how many atoms of each type there are and how they cover the file");
+  eprintln!("  --scan      print the header of every atom without parsing any of them");
+  eprintln!("  --diff      print how the atoms of the file differ from those of another, exit {} when they do",
+            EXIT_WARNINGS);
+  eprintln!("  --locate    print the atoms holding the byte at an offset such as 0x12ab3400, exit {} when none does",
+            EXIT_NOT_FOUND);
+  eprintln!("  --extract   copy the bytes of the atom at a path such as moov/trak/mdia to --out, or to stdout");
+  process::exit(EXIT_USAGE);
+}
+
+/// Parses `filename` with the options set in the environment. A file that can't be opened gives
+/// results holding the error, which are printed like any other.
+fn parse(filename: &str) -> ParseResults {
+  run(Config::from_env(filename)).unwrap_or_else(|err| ParseResults::new(Err(err)))
+}
+
+/// Reports the atoms that were left out of the tree because they failed to parse.
+fn print_warnings(results: &ParseResults) {
+  for warning in results.warnings() {
+    eprintln!("warning: {}", warning);
+  }
+}
+
+fn validate(filename: &str) -> i32 {
+  let results = parse(filename);
+  if let Some(err) = results.error() {
+    eprintln!("error: {}", err);
+    return EXIT_UNPARSEABLE;
+  }
+  print_warnings(&results);
+  let warnings = results.validate();
+  for warning in &warnings {
+    eprintln!("warning: {}", warning);
+  }
+  if warnings.is_empty() && results.is_clean() { 0 } else { EXIT_WARNINGS }
+}
+
+/// Prints the headers of `filename`, indented by how deeply they nest.
+fn scan(filename: &str) -> i32 {
+  let mut parser = match Parser::from_path(filename) {
+    Ok(parser) => parser,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      return EXIT_UNPARSEABLE;
+    }
+  };
+  for header in parser.headers() {
+    match header {
+      Ok((depth, header)) => println!("{:indent$}{}", "", header, indent = depth * 2),
+      Err(err) => {
+        eprintln!("error: {}", err);
+        return EXIT_UNPARSEABLE;
+      }
+    }
+  }
+  0
+}
+
+/// Prints the atoms holding the byte at `offset` of `filename`, outermost first, and where the
+/// byte is in the innermost of them.
+fn locate(filename: &str, offset: u64) -> i32 {
+  let results = parse(filename);
+  if let Some(err) = results.error() {
+    eprintln!("error: {}", err);
+    return EXIT_UNPARSEABLE;
+  }
+  match results.atom_at_offset(offset) {
+    Some(chain) => {
+      let types: Vec<&str> = chain.iter().map(|node| node.atom_type()).collect();
+      let innermost = chain[chain.len() - 1];
+      println!("{} @ +{:#x}", types.join(" > "), offset - innermost.atom_location());
+      0
+    }
+    None => {
+      eprintln!("error: no atom of {} holds offset {:#x}", filename, offset);
+      EXIT_NOT_FOUND
+    }
+  }
+}
+
+/// Prints how the atoms of `filename` differ from those of `other`.
+fn diff(filename: &str, other: &str) -> i32 {
+  let (a, b) = (parse(filename), parse(other));
+  for (name, results) in [(filename, &a), (other, &b)] {
+    if let Some(err) = results.error() {
+      eprintln!("error: {}: {}", name, err);
+      return EXIT_UNPARSEABLE;
+    }
+  }
+  let diff = diff_trees(&a, &b);
+  if diff.is_empty() {
+    return 0;
+  }
+  println!("--- {}\n+++ {}", filename, other);
+  print!("{}", diff);
+  EXIT_WARNINGS
+}
+
+/// Reads an offset given in decimal or, with a leading `0x`, in hexadecimal.
+fn parse_offset(arg: &str) -> Option<u64> {
+  match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+    None => arg.parse().ok(),
+  }
+}
+
+/// Copies the atom at `path` in `filename` to `out`, or to stdout without one.
+fn extract(filename: &str, path: &str, out: Option<&String>) -> i32 {
+  let results = parse(filename);
+  let copied = match results.error() {
+    Some(err) => Err(ParseError::EditFailed(format!("{} could not be parsed: {}", filename, err))),
+    None => std::fs::File::open(filename).map_err(ParseError::from).and_then(|mut source| match out {
+      Some(out) => {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(out)?);
+        results.extract_atom(path, &mut source, &mut out)
+      }
+      None => results.extract_atom(path, &mut source, &mut std::io::stdout().lock()),
+    }),
+  };
+  match copied {
+    Ok(_) => 0,
+    Err(err) => {
+      eprintln!("error: {}", err);
+      EXIT_UNPARSEABLE
+    }
+  }
+}
+
 fn main() {
   let args: Vec<String> = env::args().collect();
 
-  if( args.len() != 2) {
-    eprintln!("Usage: {} <path to file>", args[0]);
-    process::exit(1);
+  let mut filename = None;
+  let mut validate_only = false;
+  let mut json = false;
+  let mut summary = false;
+  let mut scan_only = false;
+  let mut stats = false;
+  let mut extract_path = None;
+  let mut locate_offset = None;
+  let mut diff_with = None;
+  let mut out = None;
+  let mut rest = args[1..].iter();
+  while let Some(arg) = rest.next() {
+    match arg.as_str() {
+      "--validate" => validate_only = true,
+      "--json" => json = true,
+      "--summary" => summary = true,
+      "--scan" => scan_only = true,
+      "--stats" => stats = true,
+      "--extract" => extract_path = Some(rest.next().unwrap_or_else(|| usage(&args[0]))),
+      "--locate" => locate_offset = Some(rest.next().and_then(|arg| parse_offset(arg)).unwrap_or_else(|| usage(&args[0]))),
+      "--diff" => diff_with = Some(rest.next().unwrap_or_else(|| usage(&args[0]))),
+      "--out" => out = Some(rest.next().unwrap_or_else(|| usage(&args[0]))),
+      _ if filename.is_none() && !arg.starts_with("--") => filename = Some(arg),
+      _ => usage(&args[0]),
+    }
+  }
+  let filename = filename.unwrap_or_else(|| usage(&args[0]));
+  if [validate_only, json, summary, stats, scan_only, extract_path.is_some(), locate_offset.is_some(), diff_with.is_some()].iter().filter(|x| **x).count() > 1 {
+    usage(&args[0]);
+  }
+  if out.is_some() && extract_path.is_none() {
+    usage(&args[0]);
+  }
+  if let Some(path) = extract_path {
+    process::exit(extract(filename, path, out));
+  }
+  if scan_only {
+    process::exit(scan(filename));
+  }
+  if let Some(other) = diff_with {
+    process::exit(diff(filename, other));
+  }
+  if let Some(offset) = locate_offset {
+    process::exit(locate(filename, offset));
+  }
+  if validate_only {
+    process::exit(validate(filename));
+  }
+  if json {
+    let results = parse(filename);
+    print_warnings(&results);
+    println!("{}", results.to_json());
+    return;
+  }
+  if stats {
+    let results = parse(filename);
+    if let Some(err) = results.error() {
+      eprintln!("error: {}", err);
+      process::exit(EXIT_UNPARSEABLE);
+    }
+    print_warnings(&results);
+    print!("{}", results.statistics());
+    return;
+  }
+  if summary {
+    match run_summary(Config::from_env(filename)) {
+      Ok(info) => print!("{}", info),
+      Err(err) => {
+        eprintln!("error: {}", err);
+        process::exit(EXIT_UNPARSEABLE);
+      }
+    }
+    return;
   }
-  let filename = &args[1];
   println!("Will parse {}", filename);
 
-  let config = Config::new(filename);
-  let nodes = run(config);
+  let nodes = parse(filename);
+  print_warnings(&nodes);
+  #[cfg(feature = "colored")]
+  {
+    if let Err(err) = nodes.write_tree_colored(&mut std::io::stdout(), None, color_enabled()) {
+      eprintln!("error: {}", err);
+      process::exit(EXIT_USAGE);
+    }
+  }
+  #[cfg(not(feature = "colored"))]
   println!("{}", nodes)
 }
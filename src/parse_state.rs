@@ -1,18 +1,139 @@
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::fmt;
 use std::error;
-use super::atoms::{AtomLike, AtomHeader, AtomNodes, containers::ContainerAtoms};
-use std::io::{Read, SeekFrom, Seek};
-use std::borrow::{BorrowMut, Borrow};
-use crate::atoms::Container;
+use std::io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use super::atoms::{AtomLike, AtomHeader, FourCC, AtomIter, AtomNodes, ReadSeek, leaves::{Atoms, FtypAtom, MvhdAtom, TkhdAtom, MdhdAtom, SttsAtom, IlstAtom, Locale, MetadataValue, ImageFormat, SampleDescription, ItemProperty, VendorDataAtom}};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use crate::atoms::{type_matches, Container, DEFAULT_MAX_ATOM_SIZE};
+use crate::scan::HeaderIter;
+use crate::stats::FileStats;
+use crate::timeline::Timeline;
+use crate::track::Track;
+use crate::buffered::BufferedReader;
+use crate::validation::{LayoutCheck, ValidationWarning};
+use crate::atoms::containers::{ContainerAtoms, MoofAtom, RootAtom};
 
 #[derive(Debug)]
 pub enum ParseError {
   IoError(std::io::Error),
   NotValidMediaFileSize(String),
-  AtomParseFailed(String),
-  NotAContainer,
-  FailedToReadOutAtom(String, u64, usize),
+  /// The atom of `atom_type` at `offset` could not be parsed, for the given reason.
+  AtomParseFailed { atom_type: FourCC, offset: u64, reason: String },
+  /// Writing `needed` bytes at `offset` needs more than the `available` ones.
+  NotEnoughSpace { offset: u64, needed: u64, available: u64 },
+  /// The four-CC read at `offset` is not text, which usually means an earlier size was wrong and
+  /// parsing drifted out of alignment with the atoms.
+  Misaligned { offset: u64, four_cc: [u8; 4] },
+  /// A top-level atom that could not be parsed because it runs past the end of a file of
+  /// `file_size` bytes, usually because the file was cut short.
+  AtomPastEndOfFile { atom_type: FourCC, offset: u64, size: u64, file_size: u64 },
+  /// The file ended inside the header at `offset`, after `got` of the `expected` bytes.
+  TruncatedHeader { offset: u64, expected: u64, got: u64 },
+  /// The file ended inside the atom at `offset`, after `got` of the `expected` bytes.
+  TruncatedAtom { atom_type: FourCC, offset: u64, expected: u64, got: u64 },
+  /// The field at `offset` of an atom of `atom_type` needs `expected` bytes, but the payload of
+  /// the atom ends after `got` of them.
+  TruncatedField { atom_type: FourCC, atom_offset: u64, offset: u64, expected: u64, got: u64 },
+  /// A child atom whose size is smaller than its own header, or, with the end of its container
+  /// in `container_end`, runs past that end.
+  InvalidChildSize { atom_type: FourCC, offset: u64, size: u64, container_end: Option<u64> },
+  /// An atom that is bigger than `limit`, the most that is read into memory.
+  AtomTooLarge { atom_type: FourCC, offset: u64, size: u64, limit: u64 },
+  /// A parsed tree could not be edited or written out as asked, such as a fast start copy of a
+  /// file without a `moov` atom.
+  EditFailed(String),
+  /// Parsing the atom at `offset` went over one of the [`ParseLimits`], whose value was `max`.
+  LimitExceeded { limit: Limit, max: usize, atom_type: FourCC, offset: u64 },
+  /// The file has no atom of `atom_type`, which the operation `needed_for` can not do without.
+  MissingAtom { atom_type: FourCC, needed_for: &'static str },
+  /// The sample table atoms of the `stbl` at `offset` disagree, such as a sample size table with
+  /// a different number of samples than the time-to-sample table.
+  InconsistentSampleTable { offset: u64, reason: String },
+  /// The file type atom at `offset` has a major brand the parse was told is not supported, see
+  /// [`ParseContext::with_unsupported_brands`].
+  UnsupportedBrand { brand: FourCC, offset: u64 },
+  /// The compressed movie atom at `offset` is compressed in a way that can't be undone, anything
+  /// but `zlib`, or `zlib` without the `flate2` feature. Outside of strict mode the atom is kept
+  /// as an unknown atom that failed to parse instead.
+  UnsupportedCompression { compression: FourCC, offset: u64 },
+  /// Nothing was found at `path`, a path of atom types as taken by [`ParseResults::find`].
+  NoAtomAtPath(String),
+}
+
+/// One of the [`ParseLimits`], as reported by [`ParseError::LimitExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+  Depth,
+  ChildrenPerContainer,
+  TotalAtoms,
+}
+
+impl ParseError {
+  /// An [`ParseError::AtomParseFailed`] that carries the type and offset of `atom`.
+  pub fn parse_failed(atom: &dyn AtomLike, reason: impl Into<String>) -> ParseError {
+    ParseError::AtomParseFailed { atom_type: FourCC::from(atom.atom_type()), offset: atom.atom_location(),
+                                  reason: reason.into() }
+  }
+  /// The type of the atom the error is about, if it is about one.
+  pub fn atom_type(&self) -> Option<FourCC> {
+    match self {
+      ParseError::AtomParseFailed { atom_type, .. } | ParseError::AtomPastEndOfFile { atom_type, .. } |
+      ParseError::TruncatedAtom { atom_type, .. } | ParseError::TruncatedField { atom_type, .. } |
+      ParseError::InvalidChildSize { atom_type, .. } |
+      ParseError::AtomTooLarge { atom_type, .. } | ParseError::LimitExceeded { atom_type, .. } |
+      ParseError::MissingAtom { atom_type, .. } => Some(*atom_type),
+      _ => None,
+    }
+  }
+  /// The absolute offset in the file the error is about, if it is about a place in the file.
+  pub fn offset(&self) -> Option<u64> {
+    match self {
+      ParseError::AtomParseFailed { offset, .. } | ParseError::NotEnoughSpace { offset, .. } |
+      ParseError::Misaligned { offset, .. } | ParseError::AtomPastEndOfFile { offset, .. } |
+      ParseError::TruncatedHeader { offset, .. } | ParseError::TruncatedAtom { offset, .. } |
+      ParseError::TruncatedField { offset, .. } | ParseError::InvalidChildSize { offset, .. } | ParseError::AtomTooLarge { offset, .. } |
+      ParseError::LimitExceeded { offset, .. } | ParseError::InconsistentSampleTable { offset, .. } |
+      ParseError::UnsupportedBrand { offset, .. } | ParseError::UnsupportedCompression { offset, .. } => Some(*offset),
+      _ => None,
+    }
+  }
+}
+
+/// Shows a file offset in decimal and in hex, for comparing with a hex dump.
+struct Offset(u64);
+
+impl fmt::Display for Offset {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} (0x{:x})", self.0, self.0)
+  }
+}
+
+/// A child atom that could not be parsed and was left out of the tree, while its siblings were
+/// kept.
+#[derive(Debug)]
+pub struct ParseWarning {
+  parent: String,
+  offset: u64,
+  error: ParseError,
+}
+
+impl ParseWarning {
+  /// The type of the container the child was in.
+  pub fn parent(&self) -> &str { &self.parent }
+  /// Where the child starts in the file.
+  pub fn offset(&self) -> u64 { self.offset }
+  pub fn error(&self) -> &ParseError { &self.error }
+}
+
+impl fmt::Display for ParseWarning {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: left out the child at {}: {}", self.parent, Offset(self.offset), self.error)
+  }
 }
 
 impl fmt::Display for ParseError {
@@ -20,10 +141,47 @@ impl fmt::Display for ParseError {
     match self {
       ParseError::IoError(ref err) => write!(f, "{}", err),
       ParseError::NotValidMediaFileSize(ref reason) => write!(f, "{}", reason),
-      ParseError::AtomParseFailed(ref atom_type) => write!(f, "{}", atom_type),
-      ParseError::NotAContainer => Ok(()),
-      ParseError::FailedToReadOutAtom(atom_type, atom_size, read_size) =>
-        write!(f, "type: {}, size: {}, read out: {}", atom_type, atom_size, read_size),
+      ParseError::AtomParseFailed { atom_type, offset, reason } =>
+        write!(f, "{} at {}: {}", atom_type, Offset(*offset), reason),
+      ParseError::NotEnoughSpace { offset, needed, available } =>
+        write!(f, "at {}: {} bytes are needed but only {} are available", Offset(*offset), needed, available),
+      ParseError::Misaligned { offset, four_cc } =>
+        write!(f, "probable misalignment: {:02x?} at {} is not a four-CC", four_cc, Offset(*offset)),
+      ParseError::AtomTooLarge { atom_type, offset, size, limit } =>
+        write!(f, "{} at {}: size {} is over the {} bytes that are read into memory", atom_type, Offset(*offset),
+               size, limit),
+      ParseError::InvalidChildSize { atom_type, offset, size, container_end: None } =>
+        write!(f, "{} at {}: size {} is smaller than its header", atom_type, Offset(*offset), size),
+      ParseError::InvalidChildSize { atom_type, offset, size, container_end: Some(end) } =>
+        write!(f, "{} at {}: size {} runs past the end of its container at {}", atom_type, Offset(*offset), size, end),
+      ParseError::TruncatedHeader { offset, expected, got } =>
+        write!(f, "header at {}: truncated, expected {} bytes but got {}", Offset(*offset), expected, got),
+      ParseError::TruncatedAtom { atom_type, offset, expected, got } =>
+        write!(f, "{} at {}: truncated, expected {} bytes but got {}", atom_type, Offset(*offset), expected, got),
+      ParseError::TruncatedField { atom_type, atom_offset, offset, expected, got } =>
+        write!(f, "{} at {}: the field at {} needs {} bytes but only {} are left", atom_type, Offset(*atom_offset),
+               Offset(*offset), expected, got),
+      ParseError::AtomPastEndOfFile { atom_type, offset, size, file_size } =>
+        write!(f, "{} at {}: size {} runs past the end of the file at {}", atom_type, Offset(*offset), size,
+               file_size),
+      ParseError::EditFailed(ref reason) => write!(f, "{}", reason),
+      ParseError::LimitExceeded { limit, max, atom_type, offset } => {
+        let what = match limit {
+          Limit::Depth => "nested too deeply",
+          Limit::ChildrenPerContainer => "too many children",
+          Limit::TotalAtoms => "too many atoms in the file",
+        };
+        write!(f, "{} at {}: {}, the limit is {}", atom_type, Offset(*offset), what, max)
+      }
+      ParseError::MissingAtom { atom_type, needed_for } =>
+        write!(f, "there is no {} atom, which {} needs", atom_type, needed_for),
+      ParseError::InconsistentSampleTable { offset, reason } =>
+        write!(f, "stbl at {}: inconsistent sample tables: {}", Offset(*offset), reason),
+      ParseError::UnsupportedBrand { brand, offset } =>
+        write!(f, "ftyp at {}: the major brand {:?} is not supported", Offset(*offset), brand.as_str()),
+      ParseError::UnsupportedCompression { compression, offset } =>
+        write!(f, "cmov at {}: movies compressed with {:?} are not supported", Offset(*offset), compression.as_str()),
+      ParseError::NoAtomAtPath(ref path) => write!(f, "there is no atom at {}", path),
     }
   }
 }
@@ -46,191 +204,2484 @@ impl From<std::io::Error> for ParseError {
 pub type Result<T> = std::result::Result<T, ParseError>;
 
 const MIN_FILE_READ: u64 = 8;
+const DEFAULT_MAX_DEPTH: usize = 32;
+const DEFAULT_MAX_CHILDREN: usize = 100_000;
+const DEFAULT_MAX_TOTAL_ATOMS: usize = 500_000;
+/// The most bytes [`Parser::open_gz`] decompresses a file to.
+#[cfg(feature = "flate2")]
+pub const DEFAULT_MAX_GZ_SIZE: u64 = 1 << 30;
+
+/// Parses the payload of a leaf atom, the bytes after its header, into a value of the caller's
+/// choosing.
+pub type CustomHandler = Arc<dyn Fn(&AtomHeader, &[u8]) -> Result<Box<dyn Any + Send + Sync>> + Send + Sync>;
+
+/// Caps on how far a parse goes, so a corrupt or crafted file fails with
+/// [`ParseError::LimitExceeded`] instead of exhausting the stack or memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+  /// How deeply atoms may nest, top-level atoms being at depth 1.
+  pub max_depth: usize,
+  /// The most children a single container may have.
+  pub max_children_per_container: usize,
+  /// The most atoms in the whole tree.
+  pub max_total_atoms: usize,
+}
+
+impl std::default::Default for ParseLimits {
+  fn default() -> Self {
+    ParseLimits { max_depth: DEFAULT_MAX_DEPTH, max_children_per_container: DEFAULT_MAX_CHILDREN,
+                  max_total_atoms: DEFAULT_MAX_TOTAL_ATOMS }
+  }
+}
+
+/// The plain settings of a parse, with sensible defaults. Unlike [`ParseContext`] they can be
+/// built with struct update syntax or read from the environment.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+  pub limits: ParseLimits,
+  /// Record only the entry counts of the per-sample tables under `stbl`.
+  pub skip_sample_tables: bool,
+  /// Fail the parse on the first atom that can't be parsed, instead of keeping it as an unknown
+  /// atom or leaving it out of the tree with a warning.
+  pub strict: bool,
+  /// Keep atoms of these types as unknown atoms without reading them. Their children are skipped
+  /// as well.
+  pub skip_types: Vec<FourCC>,
+  /// The biggest leaf atom that is read into memory, [`DEFAULT_MAX_ATOM_SIZE`] when not
+  /// set. Bigger ones fail with [`ParseError::AtomTooLarge`], apart from `mdat` and free space,
+  /// which are never read.
+  pub max_atom_read: Option<u64>,
+  /// Types of atoms to parse as plain containers of child atoms, on top of those the parser knows.
+  pub containers: Vec<FourCC>,
+}
+
+impl ParseOptions {
+  /// The defaults, overridden by `MEDIA_ATOMS_MAX_DEPTH`, `MEDIA_ATOMS_MAX_CHILDREN`,
+  /// `MEDIA_ATOMS_MAX_ATOMS` and `MEDIA_ATOMS_SKIP_SAMPLE_TABLES` (`1` or `true`) when they are
+  /// set. Values that don't parse are ignored.
+  pub fn from_env() -> ParseOptions {
+    ParseOptions::from_vars(|name| std::env::var(name).ok())
+  }
+  fn from_vars<F>(var: F) -> ParseOptions where F: Fn(&str) -> Option<String> {
+    let mut options = ParseOptions::default();
+    let limit = |name| var(name).and_then(|x| x.trim().parse().ok());
+    if let Some(max_depth) = limit("MEDIA_ATOMS_MAX_DEPTH") {
+      options.limits.max_depth = max_depth;
+    }
+    if let Some(max_children) = limit("MEDIA_ATOMS_MAX_CHILDREN") {
+      options.limits.max_children_per_container = max_children;
+    }
+    if let Some(max_atoms) = limit("MEDIA_ATOMS_MAX_ATOMS") {
+      options.limits.max_total_atoms = max_atoms;
+    }
+    if let Some(skip) = var("MEDIA_ATOMS_SKIP_SAMPLE_TABLES") {
+      options.skip_sample_tables = matches!(skip.trim(), "1" | "true");
+    }
+    options
+  }
+}
+
+/// Settings shared by every atom constructor during a parse: the [`ParseOptions`] and any custom
+/// atom handlers. The limits guard against corrupt files that would otherwise make the parser
+/// spend unbounded time or memory.
+#[derive(Clone, Default)]
+pub struct ParseContext {
+  options: ParseOptions,
+  handlers: HashMap<String, CustomHandler>,
+  /// Layout warnings found while parsing, when [`Parser::parse_and_validate`] asked for them.
+  warnings: Option<Arc<Mutex<Vec<ValidationWarning>>>>,
+  /// Children left out of the tree because they failed to parse, when the parse collects them.
+  dropped: Option<Arc<Mutex<Vec<ParseWarning>>>>,
+  /// How far the parse has got, checked against the limits.
+  progress: Arc<ParseProgress>,
+  /// Set when the tracks of a movie are parsed on worker threads.
+  parallel: Option<Parallel>,
+  /// Major brands of files the parse gives up on as soon as their `ftyp` is read.
+  unsupported_brands: Vec<FourCC>,
+}
+
+/// Opens another handle on the file being parsed, for a worker thread to read from.
+type OpenSource = Arc<dyn Fn() -> io::Result<Box<dyn ReadSeek + Send>> + Send + Sync>;
+
+#[derive(Clone)]
+struct Parallel {
+  threads: usize,
+  open: OpenSource,
+}
+
+/// A track parsed on a worker thread, with the context it was parsed in, whose warnings and
+/// counts still have to be taken over.
+pub(crate) struct ParsedInParallel {
+  node: Result<AtomNodes>,
+  context: ParseContext,
+}
+
+#[derive(Debug, Default)]
+struct ParseProgress {
+  depth: AtomicUsize,
+  atoms: AtomicUsize,
+}
+
+/// Marks a container whose children are being parsed, one level deeper than its parent, until
+/// it is dropped.
+pub(crate) struct Nesting<'a>(&'a ParseProgress);
+
+impl Drop for Nesting<'_> {
+  fn drop(&mut self) {
+    self.0.depth.fetch_sub(1, Ordering::Relaxed);
+  }
+}
+
+impl fmt::Debug for ParseContext {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut handlers: Vec<&String> = self.handlers.keys().collect();
+    handlers.sort();
+    f.debug_struct("ParseContext")
+      .field("options", &self.options)
+      .field("handlers", &handlers)
+      .finish()
+  }
+}
+
+impl From<ParseOptions> for ParseContext {
+  fn from(options: ParseOptions) -> ParseContext {
+    ParseContext { options, handlers: HashMap::new(), warnings: None, dropped: None, progress: Default::default(),
+                   parallel: None, unsupported_brands: Vec::new() }
+  }
+}
+
+impl ParseContext {
+  pub fn options(&self) -> &ParseOptions { &self.options }
+  /// Replaces the limits on how deeply atoms nest and how many there are, see [`ParseLimits`].
+  pub fn with_limits(mut self, limits: ParseLimits) -> ParseContext {
+    self.options.limits = limits;
+    self
+  }
+  pub fn limits(&self) -> &ParseLimits { &self.options.limits }
+  /// Caps the number of children a single container may have. Defaults to 100,000.
+  pub fn with_max_children(mut self, max_children: usize) -> ParseContext {
+    self.options.limits.max_children_per_container = max_children;
+    self
+  }
+  pub fn max_children(&self) -> usize { self.options.limits.max_children_per_container }
+  /// Records only the entry counts of the per-sample tables under `stbl` instead of reading every
+  /// entry. Useful when only durations, codecs and metadata are needed.
+  pub fn with_skip_sample_tables(mut self, skip: bool) -> ParseContext {
+    self.options.skip_sample_tables = skip;
+    self
+  }
+  pub fn skip_sample_tables(&self) -> bool { self.options.skip_sample_tables }
+  /// Whether atoms of `atom_type` are left unread, see [`ParseOptions::skip_types`].
+  pub(crate) fn skips(&self, atom_type: FourCC) -> bool { self.options.skip_types.contains(&atom_type) }
+  /// Whether atoms of `atom_type` are parsed as plain containers, see [`ParseOptions::containers`].
+  pub(crate) fn is_container(&self, atom_type: FourCC) -> bool { self.options.containers.contains(&atom_type) }
+  pub(crate) fn max_atom_read(&self) -> u64 { self.options.max_atom_read.unwrap_or(DEFAULT_MAX_ATOM_SIZE) }
+  /// Parses leaf atoms of `atom_type` with `handler` instead of the built in parser. The result
+  /// can be recovered with [`AtomNodes::as_custom`]. A handler that fails leaves the atom as an
+  /// unknown atom carrying the error.
+  pub fn with_handler<T, F>(mut self, atom_type: &str, handler: F) -> ParseContext
+  where T: Any + Send + Sync, F: Fn(&AtomHeader, &[u8]) -> Result<T> + Send + Sync + 'static {
+    let handler: CustomHandler = Arc::new(move |header: &AtomHeader, payload: &[u8]| {
+      handler(header, payload).map(|value| Box::new(value) as Box<dyn Any + Send + Sync>)
+    });
+    self.handlers.insert(atom_type.to_string(), handler);
+    self
+  }
+  /// Fails the parse with [`ParseError::UnsupportedBrand`] as soon as the file type atom names
+  /// one of `brands`, such as `qt  ` or `heic`, as its major brand. Brands shorter than four
+  /// characters are padded with spaces.
+  pub fn with_unsupported_brands(mut self, brands: &[&str]) -> ParseContext {
+    self.unsupported_brands.extend(brands.iter().map(|brand| FourCC::from(*brand)));
+    self
+  }
+  /// Fails with [`ParseError::UnsupportedBrand`] when `ftyp` has a major brand the parse was told
+  /// not to support.
+  pub(crate) fn check_brand(&self, ftyp: &FtypAtom) -> Result<()> {
+    match self.unsupported_brands.contains(&ftyp.major_brand()) {
+      true => Err(ParseError::UnsupportedBrand { brand: ftyp.major_brand(), offset: ftyp.atom_location() }),
+      false => Ok(()),
+    }
+  }
+  pub(crate) fn handler(&self, atom_type: &str) -> Option<&CustomHandler> {
+    self.handlers.get(atom_type)
+  }
+  /// Checks that `container`, which already has `count` children, may have one more, and counts
+  /// that child towards the atoms of the file.
+  pub(crate) fn check_child_count(&self, container: &dyn AtomLike, count: usize) -> Result<()> {
+    let limits = &self.options.limits;
+    if count >= limits.max_children_per_container {
+      return Err(limit_exceeded(Limit::ChildrenPerContainer, limits.max_children_per_container, container));
+    }
+    if self.progress.atoms.fetch_add(1, Ordering::Relaxed) >= limits.max_total_atoms {
+      return Err(limit_exceeded(Limit::TotalAtoms, limits.max_total_atoms, container));
+    }
+    Ok(())
+  }
+  /// Goes one level deeper to parse the children of `container`, failing when they would nest
+  /// deeper than the limit. Top-level atoms are the children of the root.
+  pub(crate) fn nest(&self, container: &dyn AtomLike) -> Result<Nesting<'_>> {
+    let depth = self.progress.depth.fetch_add(1, Ordering::Relaxed) + 1;
+    let nesting = Nesting(&self.progress);
+    if depth > self.options.limits.max_depth {
+      return Err(limit_exceeded(Limit::Depth, self.options.limits.max_depth, container));
+    }
+    Ok(nesting)
+  }
+  /// Gets the context ready for a new parse, collecting the children left out of the tree and
+  /// counting atoms from zero.
+  fn collecting_dropped(mut self) -> ParseContext {
+    self.dropped = Some(Arc::new(Mutex::new(Vec::new())));
+    self.progress = Default::default();
+    self
+  }
+  fn take_dropped(&self) -> Vec<ParseWarning> {
+    self.dropped.as_ref().map(|dropped| std::mem::take(&mut *dropped.lock().unwrap())).unwrap_or_default()
+  }
+  /// Records that the child of `parent` at `offset` is left out of the tree because of `error`.
+  pub(crate) fn child_dropped(&self, parent: &str, offset: u64, error: ParseError) {
+    if let Some(dropped) = &self.dropped {
+      dropped.lock().unwrap().push(ParseWarning { parent: parent.to_string(), offset, error });
+    }
+  }
+  /// Parses the tracks of a movie on `threads` worker threads, each reading the file through a
+  /// handle from `open`.
+  fn in_parallel(mut self, threads: usize, open: OpenSource) -> ParseContext {
+    self.parallel = Some(Parallel { threads, open });
+    self
+  }
+  /// The same context for parsing atoms that are not read from the file, such as those of a
+  /// decompressed movie, which worker threads opening the file again would not find.
+  pub(crate) fn in_memory(&self) -> ParseContext {
+    ParseContext { parallel: None, ..self.clone() }
+  }
+  /// A context for parsing a child of the container being parsed on another thread. It collects
+  /// what this one does, separately, and starts counting atoms from zero at the current depth.
+  fn worker(&self) -> ParseContext {
+    ParseContext {
+      options: self.options.clone(),
+      handlers: self.handlers.clone(),
+      warnings: self.warnings.as_ref().map(|_| Default::default()),
+      dropped: self.dropped.as_ref().map(|_| Default::default()),
+      progress: Arc::new(ParseProgress { depth: AtomicUsize::new(self.progress.depth.load(Ordering::Relaxed)),
+                                         atoms: AtomicUsize::new(0) }),
+      parallel: None,
+      unsupported_brands: self.unsupported_brands.clone(),
+    }
+  }
+  /// Parses the `trak` children of the `moov` atom `container`, among the children at `headers`,
+  /// on worker threads when the parse is parallel. Gives what was parsed for each of the
+  /// headers, `None` for those left to be parsed in turn.
+  pub(crate) fn parse_tracks(&self, container: &AtomHeader, headers: &[AtomHeader]) -> Vec<Option<ParsedInParallel>> {
+    let parallel = match &self.parallel {
+      Some(parallel) if container.atom_type() == "moov" => parallel,
+      _ => return Vec::new(),
+    };
+    let tracks: Vec<usize> = (0..headers.len()).filter(|index| headers[*index].atom_type() == "trak").collect();
+    if tracks.len() < 2 || parallel.threads < 2 {
+      return Vec::new();
+    }
+    let parsed: Vec<Mutex<Option<ParsedInParallel>>> = headers.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+      for _ in 0..parallel.threads.min(tracks.len()) {
+        scope.spawn(|| {
+          while let Some(index) = tracks.get(next.fetch_add(1, Ordering::Relaxed)) {
+            // A track whose file can't be opened again is parsed in turn instead.
+            if let Ok(mut file) = (parallel.open)() {
+              let context = self.worker();
+              let node = AtomNodes::new(headers[*index], &mut *file, &context);
+              *parsed[*index].lock().unwrap() = Some(ParsedInParallel { node, context });
+            }
+          }
+        });
+      }
+    });
+    parsed.into_iter().map(|parsed| parsed.into_inner().unwrap()).collect()
+  }
+  /// Takes over a child of `container` parsed on a worker thread: its warnings, in the order a
+  /// parse in turn would have found them, and its atoms, which may go over the limit.
+  pub(crate) fn parsed_in_parallel(&self, container: &AtomHeader, parsed: ParsedInParallel) -> Result<AtomNodes> {
+    let ParsedInParallel { node, context } = parsed;
+    if let (Some(dropped), Some(worker)) = (&self.dropped, &context.dropped) {
+      dropped.lock().unwrap().append(&mut worker.lock().unwrap());
+    }
+    if let (Some(warnings), Some(worker)) = (&self.warnings, &context.warnings) {
+      warnings.lock().unwrap().append(&mut worker.lock().unwrap());
+    }
+    let atoms = context.progress.atoms.load(Ordering::Relaxed);
+    let max = self.options.limits.max_total_atoms;
+    if self.progress.atoms.fetch_add(atoms, Ordering::Relaxed) + atoms > max {
+      return Err(limit_exceeded(Limit::TotalAtoms, max, container));
+    }
+    node
+  }
+  fn collecting_warnings(mut self) -> ParseContext {
+    self.warnings = Some(Arc::new(Mutex::new(Vec::new())));
+    self
+  }
+  fn take_warnings(&self) -> Vec<ValidationWarning> {
+    self.warnings.as_ref().map(|warnings| std::mem::take(&mut *warnings.lock().unwrap())).unwrap_or_default()
+  }
+  /// Starts checking the layout of a container as its children are parsed, when warnings are
+  /// being collected.
+  pub(crate) fn layout_check(&self, container: &str, start: u64, end: u64) -> Option<LayoutCheck> {
+    self.warnings.as_ref().map(|_| LayoutCheck::new(container, start, end))
+  }
+  /// Where the warnings of the next child will start.
+  pub(crate) fn warning_mark(&self) -> usize {
+    self.warnings.as_ref().map(|warnings| warnings.lock().unwrap().len()).unwrap_or_default()
+  }
+  /// Records the warnings about `child` ahead of those its own children added since `mark`, the
+  /// order [`ParseResults::validate`] reports them in. A child that failed to parse is left out
+  /// of the tree, so its warnings are dropped.
+  pub(crate) fn child_parsed(&self, layout: &mut Option<LayoutCheck>, mark: usize, child: Option<&AtomNodes>) {
+    if let (Some(warnings), Some(layout)) = (&self.warnings, layout) {
+      let mut warnings = warnings.lock().unwrap();
+      match child {
+        Some(child) => drop(warnings.splice(mark..mark, layout.child(child))),
+        None => warnings.truncate(mark),
+      }
+    }
+  }
+  /// Records the unused bytes after the last child of a container.
+  pub(crate) fn layout_checked(&self, layout: Option<LayoutCheck>) {
+    if let (Some(warnings), Some(slack)) = (&self.warnings, layout.and_then(LayoutCheck::finish)) {
+      warnings.lock().unwrap().push(slack);
+    }
+  }
+}
+
+fn limit_exceeded(limit: Limit, max: usize, atom: &dyn AtomLike) -> ParseError {
+  ParseError::LimitExceeded { limit, max, atom_type: FourCC::from(atom.atom_type()), offset: atom.atom_location() }
+}
 
 #[derive(Debug)]
 pub struct ParseResults {
   results: Result<AtomNodes>,
+  truncated: Option<Truncated>,
+  warnings: Vec<ParseWarning>,
 }
 impl std::default::Default for ParseResults {
   fn default() -> Self {
-    ParseResults{results: Err(ParseError::NotAContainer) }
+    let root = AtomNodes::Container(ContainerAtoms::Root(RootAtom::default()));
+    ParseResults{results: Ok(root), truncated: None, warnings: Vec::new() }
   }
 }
 
+/// Marks results that stop short of the end of the file because of a byte limit, see
+/// [`Parser::parse_with_limit`].
+#[derive(Debug, Clone, Copy)]
+pub struct Truncated {
+  offset: u64,
+  partial_atom: Option<AtomHeader>,
+}
+
+impl Truncated {
+  /// Where the first top-level atom that was left out starts.
+  pub fn offset(&self) -> u64 { self.offset }
+  /// The header of the top-level atom that starts within the limit but ends past it, if any.
+  pub fn partial_atom(&self) -> Option<&AtomHeader> { self.partial_atom.as_ref() }
+}
+
 impl ParseResults {
   pub fn new(root: Result<AtomNodes>) -> ParseResults {
-   ParseResults{results: root}
+   ParseResults{results: root, truncated: None, warnings: Vec::new() }
+  }
+  /// The error that stopped parsing, taken out of the results.
+  pub(crate) fn into_error(self) -> Option<ParseError> {
+    self.results.err()
+  }
+  fn with_warnings(mut self, warnings: Vec<ParseWarning>) -> ParseResults {
+    self.warnings = warnings;
+    self
+  }
+  /// The children that were left out of the tree because they failed to parse.
+  pub fn warnings(&self) -> &[ParseWarning] { &self.warnings }
+  /// Whether the whole file parsed, without errors or children left out.
+  pub fn is_clean(&self) -> bool { self.results.is_ok() && self.warnings.is_empty() }
+  /// Set when parsing stopped at a byte limit before reaching the end of the file.
+  pub fn truncated(&self) -> Option<&Truncated> {
+    self.truncated.as_ref()
   }
   pub fn nodes(&self) -> &AtomNodes {
     self.results.as_ref().unwrap()
   }
-}
-impl fmt::Display for ParseResults {
-  fn fmt(&self, f: &mut std::fmt::Formatter) -> fmt::Result {
-    fn print_tree(f: &mut std::fmt::Formatter, node: &AtomNodes, depth:usize, is_last: bool) -> fmt::Result {
-      let prefix = if is_last { "\u{2517}" } else { "\u{2523}"};
-      let mut retval = Ok(());
-      match node {
-        AtomNodes::Container(atom) => {
-          retval = write!(f, "{:width$}", "", width = (2*depth));
-          retval = writeln!(f, "{} {}", prefix, AtomHeader::new_from(atom));
-          let mut stuff = 0;
-          let size = atom.children().len();
-          for child in atom.children() {
-            stuff += 1;
-            retval = print_tree(f, child, depth+1, (stuff == size));
-          }
-          retval
-        }
-        AtomNodes::Atom(atom) => {
-          retval = write!(f, "{:width$}", "", width = (2*depth));
-          writeln!(f, "{} {}", prefix, AtomHeader::new_from(atom))
-        }
+  pub fn nodes_mut(&mut self) -> &mut AtomNodes {
+    self.results.as_mut().unwrap()
+  }
+  /// The root of the tree, when the file could be parsed.
+  pub(crate) fn root(&self) -> Option<&AtomNodes> {
+    self.results.as_ref().ok()
+  }
+  /// The atoms at the top level of the file, in file order.
+  pub(crate) fn top_level(&self) -> &[AtomNodes] {
+    match &self.results {
+      Ok(AtomNodes::Container(root)) => root.children(),
+      _ => &[],
+    }
+  }
+  /// Every atom of the file in document order, parents before their children.
+  pub fn iter(&self) -> AtomIter<'_> {
+    AtomIter::new(self.top_level())
+  }
+  /// The number of atoms in the file, the number [`ParseResults::iter`] yields.
+  pub fn atom_count(&self) -> usize {
+    self.top_level().iter().map(AtomNodes::atom_count).sum()
+  }
+  /// The atoms at `path`, a slash separated list of atom types such as `moov/trak/mdia` that
+  /// starts at the top level. Each component matches every child with that type, so `moov/trak`
+  /// finds every track. A single type without slashes matches atoms of that type anywhere in the
+  /// file. Types match like [`FourCC::matches`], and a `uuid` atom also matches its UUID, such as
+  /// `moov/85c0b687-820f-11e0-8111-f4ce462b6a48`. Atoms are returned in document order.
+  pub fn find_all(&self, path: &str) -> Vec<&AtomNodes> {
+    if !path.contains('/') {
+      return self.iter().filter(|node| is_of_type(node, path)).collect();
+    }
+    let mut components = path.split('/');
+    let first = components.next().unwrap_or_default();
+    let mut nodes: Vec<&AtomNodes> = self.top_level().iter().filter(|node| is_of_type(node, first)).collect();
+    for component in components {
+      nodes = nodes.into_iter().flat_map(|node| match node {
+        AtomNodes::Container(atom) => atom.children().as_slice(),
+        AtomNodes::Atom(_) => &[],
+      }).filter(|node| is_of_type(node, component)).collect();
+    }
+    nodes
+  }
+  /// The first atom [`ParseResults::find_all`] finds at `path`.
+  pub fn find(&self, path: &str) -> Option<&AtomNodes> {
+    self.find_all(path).into_iter().next()
+  }
+  /// The atoms holding the byte at `offset` of the file, from the top-level atom down to the
+  /// innermost one, or `None` when no top-level atom holds it. A byte in the header of a
+  /// container, or between its children, ends the chain at the container. The children of a
+  /// compressed movie are not searched, as they are not at their locations in the file.
+  pub fn atom_at_offset(&self, offset: u64) -> Option<Vec<&AtomNodes>> {
+    let mut chain = Vec::new();
+    let mut nodes = self.top_level();
+    while let Some(node) = nodes.iter().find(|node| node.contains_offset(offset)) {
+      chain.push(node);
+      nodes = match node {
+        AtomNodes::Container(ContainerAtoms::Moov(moov)) if moov.decompressed().is_some() => &[],
+        AtomNodes::Container(container) => container.children(),
+        AtomNodes::Atom(_) => &[],
+      };
+    }
+    if chain.is_empty() { None } else { Some(chain) }
+  }
+  /// How deeply the atoms of the file nest, counting top-level atoms as depth 1. 0 for a file
+  /// that could not be parsed.
+  pub fn max_depth(&self) -> usize {
+    self.top_level().iter().map(AtomNodes::depth).max().unwrap_or_default()
+  }
+  /// The file type atom. Muxers are supposed to write it first but it is found wherever it is at
+  /// the top level.
+  pub fn ftyp(&self) -> Option<&FtypAtom> {
+    self.top_level().iter().find_map(|node| match node {
+      AtomNodes::Atom(Atoms::Ftyp(ftyp)) => Some(ftyp),
+      _ => None,
+    })
+  }
+  /// Whether the file type atom declares compatibility with `brand`, see
+  /// [`FtypAtom::is_compatible_with`]. A file without one is compatible with nothing.
+  pub fn is_compatible_with(&self, brand: &str) -> bool {
+    self.ftyp().is_some_and(|ftyp| ftyp.is_compatible_with(brand))
+  }
+  /// Whether the file has a file type atom. Raw captures and some streams start straight with
+  /// `mdat` or fragments instead; their atoms are still parsed.
+  pub fn has_ftyp(&self) -> bool {
+    self.ftyp().is_some()
+  }
+  /// The movie header atom.
+  pub fn mvhd(&self) -> Option<&MvhdAtom> {
+    let mut mvhd = None;
+    if let Ok(root) = &self.results {
+      root.walk(|node| if let AtomNodes::Atom(Atoms::Mvhd(atom)) = node { mvhd = mvhd.or(Some(atom)) });
+    }
+    mvhd
+  }
+  /// The track header atoms of every track, in file order.
+  pub fn track_headers(&self) -> Vec<&TkhdAtom> {
+    let mut headers = Vec::new();
+    if let Ok(root) = &self.results {
+      root.walk(|node| if let AtomNodes::Atom(Atoms::Tkhd(atom)) = node { headers.push(atom) });
+    }
+    headers
+  }
+  /// A track id that is safe to give a new track: one more than the largest existing track id, or
+  /// the movie header's next track id if that is larger.
+  pub fn allocate_track_id(&self) -> u32 {
+    let next_unused = self.track_headers().iter().map(|tkhd| tkhd.track_id().saturating_add(1)).max().unwrap_or(1);
+    next_unused.max(self.mvhd().map_or(0, |mvhd| mvhd.next_track_id()))
+  }
+  /// The `trak` containers, in file order.
+  fn tracks(&self) -> Vec<&AtomNodes> {
+    let mut tracks = Vec::new();
+    if let Ok(root) = &self.results {
+      root.walk(|node| if node.atom_type() == "trak" { tracks.push(node) });
+    }
+    tracks
+  }
+  fn track_node(&self, track_id: u32) -> Option<&AtomNodes> {
+    self.tracks().into_iter().find(|track| {
+      find_atom(track, |atom| match atom { Atoms::Tkhd(tkhd) => Some(tkhd), _ => None })
+        .is_some_and(|tkhd| tkhd.track_id() == track_id)
+    })
+  }
+  /// The track with `track_id`, with its headers and sample tables gathered together.
+  pub fn track(&self, track_id: u32) -> Option<Track<'_>> {
+    Track::new(self.track_node(track_id)?)
+  }
+  /// How long the track with `track_id` plays. Its edit list decides this when there is one: the
+  /// edits, including empty ones, are measured in the movie time scale. Without an edit list the
+  /// whole media is presented, as long as its media header says.
+  pub fn track_presentation_duration(&self, track_id: u32) -> Option<Duration> {
+    let track = self.track_node(track_id)?;
+    match find_atom(track, |atom| match atom { Atoms::Elst(elst) => Some(elst), _ => None }) {
+      Some(elst) => to_duration(elst.presentation_duration(), self.mvhd()?.time_scale()),
+      None => {
+        let mdhd = find_atom(track, |atom| match atom { Atoms::Mdhd(mdhd) => Some(mdhd), _ => None })?;
+        to_duration(mdhd.duration(), mdhd.time_scale())
       }
     }
-    if let Ok(res) = &self.results {
-      print_tree(f, res, 0, true)
+  }
+  /// The handler type of every track, such as `vide` or `soun`, in file order.
+  fn handler_types(&self) -> Vec<&str> {
+    self.tracks().into_iter().filter_map(|track| {
+      find_atom(track, |atom| match atom { Atoms::Hdlr(hdlr) => Some(hdlr), _ => None })
+    }).map(|hdlr| hdlr.component_subtype()).collect()
+  }
+  /// A best guess at the MIME type of the file, from the brands in its file type atom and, for
+  /// MPEG-4 files, whether it has any video tracks.
+  pub fn mime_type(&self) -> &'static str {
+    const HEIF_BRANDS: [&[u8; 4]; 6] = [b"heic", b"heix", b"heim", b"heis", b"mif1", b"msf1"];
+    const AUDIO_BRANDS: [&[u8; 4]; 3] = [b"M4A ", b"M4B ", b"M4P "];
+    let ftyp = match self.ftyp() {
+      Some(ftyp) => ftyp,
+      // Files from before the file type atom existed are QuickTime movies.
+      None if self.top_level().iter().any(|node| node.atom_type() == "moov") => return "video/quicktime",
+      None => return "application/octet-stream",
+    };
+    let handlers = self.handler_types();
+    let audio_only = !handlers.is_empty() && handlers.iter().all(|handler| *handler != "vide") &&
+      handlers.contains(&"soun");
+    if ftyp.is_major_brand(b"qt  ") {
+      "video/quicktime"
+    } else if ftyp.is_major_brand(b"avif") || ftyp.is_major_brand(b"avis") {
+      "image/avif"
+    } else if HEIF_BRANDS.iter().any(|brand| ftyp.is_major_brand(brand)) {
+      "image/heic"
+    } else if AUDIO_BRANDS.iter().any(|brand| ftyp.is_major_brand(brand)) || audio_only {
+      "audio/mp4"
+    } else if ftyp.has_brand(b"3g2a") {
+      "video/3gpp2"
+    } else if [b"3gp4", b"3gp5", b"3gp6"].iter().any(|brand| ftyp.is_major_brand(brand)) {
+      "video/3gpp"
     } else {
-      writeln!(f, "{}", self.results.as_ref().unwrap_err())
+      "video/mp4"
     }
   }
-}
-/*
-#[derive(Default, Debug)]
-struct ResultIterator<'a> {
-  result: &'a ParseResults,
-  current_node: Option<&'a AtomNodes>,
-  current_iter: Vec<dyn Iterator<Item=AtomNodes>>,
-}
-impl<'a> ResultIterator<'a> {
-  pub fn new(result: &ParseResults) -> ResultIterator {
-    ResultIterator{ result, current_node: Some(result.nodes()), current_iter: vec!() }
+  /// The first video track, the first track whose handler is `vide`.
+  fn video_track(&self) -> Option<&AtomNodes> {
+    self.tracks().into_iter().find(|track| {
+      find_atom(track, |atom| match atom { Atoms::Hdlr(hdlr) => Some(hdlr), _ => None })
+        .is_some_and(|hdlr| hdlr.component_subtype() == "vide")
+    })
+  }
+  fn video_timing(&self) -> Option<(&MdhdAtom, &SttsAtom)> {
+    let track = self.video_track()?;
+    let mdhd = find_atom(track, |atom| match atom { Atoms::Mdhd(mdhd) => Some(mdhd), _ => None })?;
+    let stts = find_atom(track, |atom| match atom { Atoms::Stts(stts) => Some(stts), _ => None })?;
+    Some((mdhd, stts))
+  }
+  /// The size the track with `track_id` is shown at, in pixels: its clean aperture when it has a
+  /// track aperture atom, otherwise the size in its track header, otherwise the coded size of its
+  /// first video sample description. Sizes with a zero side are passed over, so tracks without
+  /// pictures have no display dimensions.
+  pub fn display_dimensions(&self, track_id: u32) -> Option<(f64, f64)> {
+    let track = self.track_node(track_id)?;
+    let non_zero = |(width, height): (f64, f64)| if width > 0.0 && height > 0.0 { Some((width, height)) } else { None };
+    let clean_aperture = find_atom(track, |atom| match atom {
+      Atoms::Aperture(aperture) if aperture.is_clean_aperture() => Some(aperture),
+      _ => None,
+    }).and_then(|clef| non_zero(clef.dimensions()));
+    let track_header = || {
+      let tkhd = find_atom(track, |atom| match atom { Atoms::Tkhd(tkhd) => Some(tkhd), _ => None })?;
+      non_zero((tkhd.width_fixed() as f64 / 65536.0, tkhd.height_fixed() as f64 / 65536.0))
+    };
+    let coded = || {
+      let stsd = find_atom(track, |atom| match atom { Atoms::Stsd(stsd) => Some(stsd), _ => None })?;
+      stsd.entries().iter().find_map(|entry| match entry {
+        SampleDescription::Video(video) => non_zero((video.dimensions().0 as f64, video.dimensions().1 as f64)),
+        _ => None,
+      })
+    };
+    clean_aperture.or_else(track_header).or_else(coded)
+  }
+  /// The decode and composition time of every sample of the track with `track_id`.
+  pub fn timeline(&self, track_id: u32) -> Option<Timeline<'_>> {
+    let track = self.track_node(track_id)?;
+    let stts = find_atom(track, |atom| match atom { Atoms::Stts(stts) => Some(stts), _ => None })?;
+    let ctts = find_atom(track, |atom| match atom { Atoms::Ctts(ctts) => Some(ctts), _ => None });
+    Some(Timeline::new(stts, ctts))
+  }
+  /// The average frame rate of the first video track, computed from its time-to-sample table and
+  /// media time scale.
+  pub fn video_frame_rate(&self) -> Option<f64> {
+    let (mdhd, stts) = self.video_timing()?;
+    if stts.total_duration() == 0 {
+      return None;
+    }
+    Some(stts.sample_count() as f64 * mdhd.time_scale() as f64 / stts.total_duration() as f64)
+  }
+  /// Whether the frames of the first video track do not all last the same time, in which case
+  /// [`ParseResults::video_frame_rate`] is an average.
+  pub fn is_variable_frame_rate(&self) -> Option<bool> {
+    self.video_timing().map(|(_, stts)| stts.is_variable())
   }
-  fn next_container_item(&mut self, items: &mut Vec<dyn Iterator<Item=AtomNodes>>) -> Option<&AtomNodes>
-  {
-    if let Some(depth) = items.first() {
-      if let Some(retval) = depth.next() {
-        Some(&retval)
-      } else {
-        items.pop();
-        self.next_container_item(items)
+  /// The movie's iTunes style metadata item list, found in `moov/udta/meta/ilst`.
+  fn item_list(&self) -> Option<&IlstAtom> {
+    match self.find("moov/udta/meta/ilst")? {
+      AtomNodes::Atom(Atoms::Ilst(ilst)) => Some(ilst),
+      _ => None,
+    }
+  }
+
+  /// Every value of the movie's metadata items, by item key and then by the locale it is for.
+  pub fn metadata_localized(&self) -> BTreeMap<String, BTreeMap<Locale, MetadataValue>> {
+    let mut metadata = BTreeMap::new();
+    for item in self.item_list().map(|ilst| ilst.items().as_slice()).unwrap_or_default() {
+      let values: &mut BTreeMap<_, _> = metadata.entry(item.key().to_string()).or_default();
+      for entry in item.entries() {
+        values.entry(entry.locale).or_insert_with(|| entry.value.clone());
       }
-    } else {
-      None
     }
+    metadata
   }
-}
-impl<'a> Iterator for ResultIterator<'a> {
-  type Item = AtomNodes;
-  fn next(&mut self) -> Option<Self::Item> {
-    let mut retval = None;
-    if let Some(depth) = self.current_iter.first() {
-      self.current_node = depth.1.next();
+  /// One value for each of the movie's metadata items: the one with the default locale, or the
+  /// lowest locale when an item has only localized values.
+  pub fn metadata(&self) -> BTreeMap<String, MetadataValue> {
+    self.metadata_localized().into_iter().filter_map(|(key, mut values)| {
+      let value = values.remove(&Locale::default()).or_else(|| values.into_values().next())?;
+      Some((key, value))
+    }).collect()
+  }
+  /// The children of user data atoms the parser has no support for, anywhere in the file, such
+  /// as the firmware version and telemetry action cameras write. See [`VendorDataAtom`].
+  pub fn vendor_metadata(&self) -> Vec<(FourCC, &VendorDataAtom)> {
+    self.iter().filter_map(|node| match node {
+      AtomNodes::Atom(Atoms::Vendor(vendor)) => Some((vendor.four_cc(), vendor)),
+      _ => None,
+    }).collect()
+  }
+  /// The first image of the `covr` metadata item, or of `©art` for files that keep their artwork
+  /// there, with its format.
+  pub fn cover_art(&self) -> Option<(ImageFormat, Vec<u8>)> {
+    let items = self.item_list()?.items();
+    ["covr", "©art"].iter().find_map(|key| {
+      items.iter().filter(|item| item.key() == *key).flat_map(|item| item.entries())
+        .find_map(|entry| entry.value.as_image())
+    }).map(|(format, data)| (format, data.to_vec()))
+  }
+  /// The atoms of the file level `meta` atom, which describes the items of a HEIF image file.
+  fn item_atoms(&self) -> Vec<&Atoms> {
+    let mut atoms = Vec::new();
+    if let Some(meta) = self.top_level().iter().find(|node| node.atom_type() == "meta") {
+      meta.walk(|node| if let AtomNodes::Atom(atom) = node { atoms.push(atom) });
     }
-    if let Some(node) = self.current_node {
-      match node {
-        stuff @ AtomNodes::Container(atom) => {
-          let s = atom.children().iter();
-          let n = atom;
-          self.current_iter.push((n, s));
-        }
-        AtomNodes::Atom(atom) => {
-          retval = Some(atom)
+    atoms
+  }
+  /// The id of the HEIF item that is the image to show.
+  pub fn primary_item_id(&self) -> Option<u32> {
+    self.item_atoms().into_iter().find_map(|atom| match atom { Atoms::Pitm(pitm) => Some(pitm.item_id()), _ => None })
+  }
+  /// The references between HEIF items as (from item id, reference type, to item ids), such as
+  /// `(2, "thmb", [1])` for item 2 being a thumbnail of item 1.
+  pub fn item_references(&self) -> Vec<(u32, String, Vec<u32>)> {
+    self.item_atoms().into_iter().filter_map(|atom| match atom { Atoms::Iref(iref) => Some(iref), _ => None })
+      .flat_map(|iref| iref.references().iter())
+      .map(|reference| (reference.from_item_id, reference.reference_type.clone(), reference.to_item_ids.clone()))
+      .collect()
+  }
+  /// The properties of the HEIF item with `item_id`, such as its size and rotation, in the order
+  /// they are associated with it.
+  pub fn item_properties(&self, item_id: u32) -> Vec<ItemProperty> {
+    let atoms = self.item_atoms();
+    let ipco = atoms.iter().find_map(|atom| match atom { Atoms::Ipco(ipco) => Some(ipco), _ => None });
+    let ipma = atoms.iter().find_map(|atom| match atom { Atoms::Ipma(ipma) => Some(ipma), _ => None });
+    let (ipco, ipma) = match (ipco, ipma) {
+      (Some(ipco), Some(ipma)) => (ipco, ipma),
+      _ => return Vec::new(),
+    };
+    ipma.associations().iter().filter(|association| association.item_id == item_id)
+      .flat_map(|association| association.properties.iter())
+      // Index 0 means no property.
+      .filter_map(|(_, index)| ipco.properties().get((*index as usize).checked_sub(1)?).cloned())
+      .collect()
+  }
+  /// The movie fragments of a fragmented file, in file order.
+  pub fn fragments(&self) -> Vec<&MoofAtom> {
+    self.top_level().iter().filter_map(|node| match node {
+      AtomNodes::Container(ContainerAtoms::Moof(moof)) => Some(moof),
+      _ => None,
+    }).collect()
+  }
+  /// Whether the movie atom comes before the media data, so playback can start while the file
+  /// is still downloading.
+  pub fn is_fast_start(&self) -> bool {
+    let position = |atom_type| self.top_level().iter().position(|node| node.atom_type() == atom_type);
+    match (position("moov"), position("mdat")) {
+      (Some(moov), Some(mdat)) => moov < mdat,
+      (Some(_), None) => true,
+      _ => false,
+    }
+  }
+  /// Counts the atoms of the file by type and checks how they cover it, see [`FileStats`].
+  pub fn statistics(&self) -> FileStats {
+    FileStats::from_results(self)
+  }
+  /// Writes a fast start copy of `src`, the file these results were parsed from, to `out`, see
+  /// [`crate::make_fast_start`]. Returns the number of bytes written.
+  pub fn make_fast_start(&self, src: &mut dyn ReadSeek, out: &mut dyn Write) -> Result<u64> {
+    match &self.results {
+      Ok(root) => crate::writer::make_fast_start(root, src, out),
+      Err(err) => Err(ParseError::EditFailed(format!("fast start: the file could not be parsed: {}", err))),
+    }
+  }
+  /// Copies the bytes of the atom at `path`, as found by [`ParseResults::find`], from `source`,
+  /// the file these results were parsed from, to `out`, see [`crate::extract_atom`]. An empty
+  /// path or `/` copies the whole file. The atoms below a `moov` decompressed from a `cmov` are
+  /// not in the file, and fail with [`ParseError::EditFailed`]. Returns the number of bytes written.
+  pub fn extract_atom<W: Write>(&self, path: &str, source: &mut (impl Read + Seek), out: &mut W) -> Result<u64> {
+    let node = match (path, &self.results) {
+      ("" | "/", Ok(root)) => Some(root),
+      ("" | "/", Err(_)) => None,
+      _ => self.find(path),
+    };
+    let node = node.ok_or_else(|| ParseError::NoAtomAtPath(path.to_string()))?;
+    if self.in_compressed_movie(node) {
+      return Err(ParseError::EditFailed(format!("{} is in a compressed movie rather than in the file", path)));
+    }
+    crate::writer::extract_atom(node, source, out)
+  }
+  /// Whether `node` is one of the atoms below a `moov` decompressed from a `cmov`.
+  fn in_compressed_movie(&self, node: &AtomNodes) -> bool {
+    self.top_level().iter().any(|moov| match moov {
+      AtomNodes::Container(ContainerAtoms::Moov(atom)) if atom.decompressed().is_some() =>
+        atom.descendants().any(|child| std::ptr::eq(child, node)),
+      _ => false,
+    })
+  }
+  /// The tool that encoded the file, such as `Lavf58.76.100`, from the `©too` metadata item.
+  pub fn encoder(&self) -> Option<String> {
+    self.metadata().remove("©too")?.as_text().map(str::to_string)
+  }
+  /// The error that stopped parsing, if the file could not be parsed.
+  pub fn error(&self) -> Option<&ParseError> {
+    self.results.as_ref().err()
+  }
+  /// Checks the layout of the parsed tree and describes every problem found: gaps between
+  /// siblings, siblings that overlap, children running past the end of their parent and unused
+  /// bytes at the end of a container, a file type atom that is missing or not the first atom of
+  /// the file and a movie header whose next track id is already taken. A file that could not be parsed has no
+  /// warnings.
+  pub fn validate(&self) -> Vec<ValidationWarning> {
+    fn validate_container(node: &AtomNodes, warnings: &mut Vec<ValidationWarning>) {
+      if let AtomNodes::Container(atom) = node {
+        // The children of a compressed movie lie in the decompressed movie rather than the file.
+        let bounds: &dyn AtomLike = match atom {
+          ContainerAtoms::Moov(moov) => moov.decompressed().map_or(atom as &dyn AtomLike, |header| header as &dyn AtomLike),
+          _ => atom,
+        };
+        let start = bounds.atom_location() + bounds.header_size() as u64 + atom.prefix_size();
+        let mut layout = LayoutCheck::new(atom.atom_type(), start, bounds.atom_location() + bounds.atom_size());
+        for child in atom.children() {
+          warnings.extend(layout.child(child));
+          validate_container(child, warnings);
         }
+        warnings.extend(layout.finish());
+      }
+    }
+    let mut warnings = Vec::new();
+    if let Ok(root) = &self.results {
+      validate_container(root, &mut warnings);
+    }
+    warnings.extend(self.file_warnings());
+    warnings
+  }
+  /// The warnings of [`ParseResults::validate`] that are about the file as a whole rather than
+  /// the layout of a container.
+  fn file_warnings(&self) -> Vec<ValidationWarning> {
+    let mut warnings = Vec::new();
+    match (self.ftyp(), self.top_level().first()) {
+      (Some(ftyp), Some(first)) if first.atom_location() != ftyp.atom_location() =>
+        warnings.push(ValidationWarning::MisplacedFtyp { location: ftyp.atom_location() }),
+      (None, Some(first)) => warnings.push(ValidationWarning::MissingFtyp { first: first.atom_type().to_string() }),
+      _ => {}
+    }
+    if let (Some(mvhd), Some(track_id)) = (self.mvhd(), self.track_headers().iter().map(|tkhd| tkhd.track_id()).max()) {
+      if mvhd.next_track_id() <= track_id {
+        warnings.push(ValidationWarning::TrackIdInUse { next_track_id: mvhd.next_track_id(), track_id });
       }
     }
-    None
+    warnings
   }
 }
-*/
-pub struct Parser {
-  filename: String,
-  file: fs::File,
+/// `units` of `time_scale` as a duration, or `None` for a zero time scale.
+pub(crate) fn to_duration(units: u64, time_scale: u32) -> Option<Duration> {
+  if time_scale == 0 {
+    return None;
+  }
+  let nanos = units as u128 * 1_000_000_000 / time_scale as u128;
+  Some(Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32))
 }
 
-impl Parser {
-  pub fn new(filename: &str) -> Result<Parser> {
-    let file = fs::File::open(filename)?;
-    let meta = file.metadata()?;
-    if meta.len() > MIN_FILE_READ {
-      Ok(Parser { filename: String::from(filename), file })
-    } else {
-      Err(ParseError::NotValidMediaFileSize(String::from("Bad File Size")))
-    }
+/// The first leaf atom under `node`, in depth first order, that `select` picks out.
+pub(crate) fn find_atom<'a, T, F>(node: &'a AtomNodes, select: F) -> Option<&'a T> where F: Fn(&'a Atoms) -> Option<&'a T> {
+  let mut found = None;
+  node.walk(|node| if let (None, AtomNodes::Atom(atom)) = (&found, node) { found = select(atom) });
+  found
+}
+
+/// A readable name for the kind of media in a `trak`, from its handler type.
+fn track_kind(node: &AtomNodes) -> Option<&'static str> {
+  if node.atom_type() != "trak" {
+    return None;
   }
-  fn file_size(&self) -> u64 {
-    let meta = self.file.metadata();
-    if meta.is_ok() {
-      meta.unwrap().len()
-    } else {
-      0
-    }
+  let hdlr = find_atom(node, |atom| match atom { Atoms::Hdlr(hdlr) => Some(hdlr), _ => None })?;
+  match hdlr.component_subtype() {
+    "vide" => Some("video"),
+    "soun" => Some("audio"),
+    "sbtl" | "subt" | "clcp" => Some("subtitle"),
+    "text" => Some("text"),
+    "tmcd" => Some("timecode"),
+    "meta" => Some("metadata"),
+    "hint" => Some("hint"),
+    _ => None,
   }
+}
 
-  pub fn parse(&mut self) -> ParseResults {
-    self.file.seek(SeekFrom::Start(0));
-    let header: AtomHeader = self.into();
-    ParseResults::new(AtomNodes::new(header, &mut self.file))
+/// Whether `node` is what a component of a [`ParseResults::find_all`] path names.
+fn is_of_type(node: &AtomNodes, name: &str) -> bool {
+  match node {
+    AtomNodes::Atom(Atoms::Uuid(uuid)) if uuid.uuid_string().eq_ignore_ascii_case(name.trim()) => true,
+    _ => type_matches(node.atom_type(), name),
   }
 }
 
-impl AtomLike for Parser {
-  fn atom_size(&self) -> u64 {
-    self.file_size()
+/// The ANSI color the tree gives the type of `node`: one for containers, one for media data and
+/// a dim one for padding. Other atoms are left plain.
+fn type_color(node: &AtomNodes) -> Option<&'static str> {
+  match node.atom_type() {
+    _ if node.is_container() => Some("\x1b[1;34m"),
+    "mdat" => Some("\x1b[35m"),
+    "free" | "skip" | "wide" | "slack" => Some("\x1b[2m"),
+    _ => None,
   }
+}
 
-  fn atom_type(&self) -> &str {
-    "root"
+/// `bytes` in the largest binary unit that keeps it at 1 or more, such as `3.2 GiB`. `None` below
+/// 1 KiB, where the byte count says it all.
+fn human_size(bytes: u64) -> Option<String> {
+  let units = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+  let mut size = bytes as f64 / 1024.0;
+  if size < 1.0 {
+    return None;
   }
-
-  fn atom_location(&self) -> u64 {
-    0
+  let mut unit = 0;
+  while size >= 1024.0 && unit + 1 < units.len() {
+    size /= 1024.0;
+    unit += 1;
   }
+  Some(format!("{:.1} {}", size, units[unit]))
+}
 
-  fn header_size(&self) -> u32 {
-    0
+/// The line the tree shows for `node`: its type, its size in bytes and in human units, and where
+/// it starts and ends in the file. Slack is not an atom, so it only shows its size.
+fn print_atom(f: &mut dyn fmt::Write, node: &AtomNodes, color: bool) -> fmt::Result {
+  if let AtomNodes::Atom(slack @ Atoms::Slack { .. }) = node {
+    return match type_color(node).filter(|_| color) {
+      Some(code) => write!(f, "{}{}\x1b[0m", code, slack),
+      None => write!(f, "{}", slack),
+    };
   }
-}
-impl From<&mut Parser> for AtomHeader {
-  fn from(item: &mut Parser) -> Self {
-    let atom_like: &dyn AtomLike = item;
-    atom_like.into()
+  match type_color(node).filter(|_| color) {
+    Some(code) => write!(f, "type: {}{}\x1b[0m", code, node.atom_type())?,
+    None => write!(f, "type: {}", node.atom_type())?,
   }
+  write!(f, ", size: {}", node.atom_size())?;
+  if let Some(size) = human_size(node.atom_size()) {
+    write!(f, " ({})", size)?;
+  }
+  write!(f, ", location: {}, end: {}", node.atom_location(), node.byte_range().end)
 }
-#[cfg(test)]
-mod tests {
-  use super::*;
 
-  #[test]
-  fn fails_on_a_non_existant_file() {
-    let res = Parser::new("resources/test/Nonsense.mp4");
-    assert!(res.is_err());
-    match res.err().unwrap() {
-      ParseError::IoError(_) => (),
-      ref err => panic!("expected IoError, got {:?}", err),
+/// The branch drawn before the last of a list of siblings, and before the others.
+const LAST_BRANCH: &str = "\u{2514}\u{2500}\u{2500} ";
+const BRANCH: &str = "\u{251c}\u{2500}\u{2500} ";
+
+/// What is drawn in front of the children of a node, below `prefix`, the drawing in front of the
+/// node itself: a continuation line when more siblings of the node follow.
+fn child_prefix(prefix: &str, is_last: bool) -> String {
+  format!("{}{}", prefix, if is_last { "    " } else { "\u{2502}   " })
+}
+
+fn print_tree(f: &mut dyn fmt::Write, node: &AtomNodes, prefix: &str, is_last: bool, max_children: Option<usize>,
+              color: bool) -> fmt::Result {
+  write!(f, "{}{}", prefix, if is_last { LAST_BRANCH } else { BRANCH })?;
+  print_atom(f, node, color)?;
+  let prefix = child_prefix(prefix, is_last);
+  match node {
+    AtomNodes::Container(atom) => {
+      if let Some(kind) = track_kind(node) {
+        write!(f, " ({})", kind)?;
+      }
+      writeln!(f)?;
+      print_siblings(f, atom.children(), &prefix, max_children, color)
     }
+    // The sample descriptions are not atoms of the tree, but their codec configuration atoms are
+    // worth seeing.
+    AtomNodes::Atom(Atoms::Stsd(stsd)) => {
+      writeln!(f)?;
+      for (index, entry) in stsd.entries().iter().enumerate() {
+        let is_last = index + 1 == stsd.entries().len();
+        writeln!(f, "{}{}{}", prefix, if is_last { LAST_BRANCH } else { BRANCH }, entry)?;
+        let prefix = child_prefix(&prefix, is_last);
+        for (index, extension) in entry.extensions().iter().enumerate() {
+          let branch = if index + 1 == entry.extensions().len() { LAST_BRANCH } else { BRANCH };
+          writeln!(f, "{}{}{}", prefix, branch, extension)?;
+        }
+      }
+      Ok(())
+    }
+    AtomNodes::Atom(_) => writeln!(f),
   }
+}
 
-  #[test]
-  fn should_be_able_to_open_a_file() {
-    assert!(Parser::new("resources/tests/sample.mp4").is_ok());
+/// Prints `nodes` as a list of siblings below `prefix`, each with its descendants. This is the one
+/// renderer for trees of atoms: [`ParseResults`] and the `Display` of containers both use it.
+pub(crate) fn print_siblings(f: &mut dyn fmt::Write, nodes: &[AtomNodes], prefix: &str, max_children: Option<usize>,
+                             color: bool) -> fmt::Result {
+  let size = nodes.len();
+  let shown = max_children.map_or(size, |max| max.min(size));
+  for (index, child) in nodes.iter().take(shown).enumerate() {
+    print_tree(f, child, prefix, index + 1 == size, max_children, color)?;
   }
+  if shown < size {
+    writeln!(f, "{}{}\u{2026} ({} more)", prefix, LAST_BRANCH, size - shown)?;
+  }
+  Ok(())
+}
 
-  #[test]
-  fn should_reject_a_file_if_not_of_valid_size() {
-    let res = Parser::new("resources/tests/empty_file.mp4");
-    assert!(res.is_err());
-    match res.err().unwrap() {
-      ParseError::NotValidMediaFileSize(_) => (),
-      ref err => panic!("expected NotValidMediaFileSize, got {:?}", err),
-    }
+/// Prints the top-level atoms of a file as siblings, without the synthetic root above them.
+fn print_file(f: &mut dyn fmt::Write, node: &AtomNodes, max_children: Option<usize>, color: bool) -> fmt::Result {
+  match node {
+    AtomNodes::Container(root) if root.atom_type() == "root" => print_siblings(f, root.children(), "", max_children, color),
+    _ => print_tree(f, node, "", true, max_children, color),
   }
+}
 
-  #[test]
-  fn should_parse_atoms_as_nodes() {
-    let parser = Parser::new("resources/tests/sample.mp4");
-    assert!(parser.is_ok());
-    let res = parser.unwrap().parse().results.unwrap();
-    assert_eq!(res.atom_type(), "root");
+/// Whether the tree written to stdout should be colored: only when stdout is a terminal and the
+/// [`NO_COLOR`](https://no-color.org) environment variable is unset or empty.
+#[cfg(feature = "colored")]
+pub fn color_enabled() -> bool {
+  use std::io::IsTerminal;
+  std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && io::stdout().is_terminal()
+}
+
+impl ParseResults {
+  /// Writes the same tree as `Display` through a buffer, which is much faster for large trees.
+  /// With `max_children` set, containers only show that many children followed by a line noting
+  /// how many were left out.
+  pub fn write_tree_buffered<W: Write>(&self, w: &mut W, max_children: Option<usize>) -> io::Result<()> {
+    self.write_tree(w, max_children, false)
+  }
+  /// Like [`ParseResults::write_tree_buffered`], coloring atom types by category when `color` is
+  /// set. Pass [`color_enabled`] to color only interactive output.
+  #[cfg(feature = "colored")]
+  pub fn write_tree_colored<W: Write>(&self, w: &mut W, max_children: Option<usize>, color: bool) -> io::Result<()> {
+    self.write_tree(w, max_children, color)
+  }
+  fn write_tree<W: Write>(&self, w: &mut W, max_children: Option<usize>, color: bool) -> io::Result<()> {
+    struct Adapter<W: Write> {
+      out: BufWriter<W>,
+      error: Option<io::Error>,
+    }
+    impl<W: Write> fmt::Write for Adapter<W> {
+      fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_all(s.as_bytes()).map_err(|err| {
+          self.error = Some(err);
+          fmt::Error
+        })
+      }
+    }
+    let mut adapter = Adapter { out: BufWriter::new(w), error: None };
+    let written = match &self.results {
+      Ok(res) => print_file(&mut adapter, res, max_children, color).and_then(|_| self.print_summary(&mut adapter)),
+      Err(err) => fmt::Write::write_fmt(&mut adapter, format_args!("{}\n", err)),
+    };
+    match (written, adapter.error) {
+      (Err(_), Some(err)) => Err(err),
+      (Err(_), None) => Err(io::Error::other("failed to format the tree")),
+      (Ok(()), _) => adapter.out.flush(),
+    }
+  }
+}
+
+impl ParseResults {
+  /// Notes how many children were left out, after the tree.
+  fn print_summary(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+    match self.warnings.len() {
+      0 => Ok(()),
+      1 => writeln!(f, "(1 warning)"),
+      count => writeln!(f, "({} warnings)", count),
+    }
+  }
+}
+
+impl fmt::Display for ParseResults {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> fmt::Result {
+    if let Ok(res) = &self.results {
+      print_file(f, res, None, false)?;
+      self.print_summary(f)
+    } else {
+      writeln!(f, "{}", self.results.as_ref().unwrap_err())
+    }
+  }
+}
+
+/// Position of the next top-level atom for [`Parser::parse_step`]. It only holds a file offset, so
+/// a caller can persist it and resume parsing later, even from another process.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ParseCursor {
+  offset: u64,
+}
+
+impl ParseCursor {
+  pub fn new(offset: u64) -> ParseCursor {
+    ParseCursor { offset }
+  }
+  pub fn offset(&self) -> u64 { self.offset }
+}
+
+pub struct Parser<R = fs::File> {
+  file: R,
+  file_size: u64,
+  context: ParseContext,
+  /// The path the file was opened from, which lets [`Parser::parse_parallel`] open it again.
+  path: Option<PathBuf>,
+  /// How many threads [`Parser::parse_parallel`] uses, the number the system can run at once
+  /// when not set.
+  threads: Option<usize>,
+}
+
+impl Parser {
+  /// Parses the file at `path`. Any other `Read + Seek` source can be parsed with
+  /// [`Parser::new`].
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Parser> {
+    let mut parser = Parser::new(fs::File::open(&path)?)?;
+    parser.path = Some(path.as_ref().to_path_buf());
+    Ok(parser)
+  }
+}
+
+impl Parser<Cursor<Vec<u8>>> {
+  /// Parses an in-memory file. The parser owns the buffer, so neither it nor its results borrow
+  /// from the caller.
+  pub fn from_bytes_owned(data: Vec<u8>) -> Result<Parser<Cursor<Vec<u8>>>> {
+    Parser::new(Cursor::new(data))
+  }
+  /// Parses a gzip compressed file, such as `movie.mp4.gz`. A gzip stream can't be seeked, so
+  /// the file is decompressed into memory, up to [`DEFAULT_MAX_GZ_SIZE`] bytes.
+  #[cfg(feature = "flate2")]
+  pub fn open_gz(filename: &str) -> Result<Parser<Cursor<Vec<u8>>>> {
+    Parser::open_gz_with_limit(filename, DEFAULT_MAX_GZ_SIZE)
+  }
+  /// Like [`Parser::open_gz`], failing with [`ParseError::NotValidMediaFileSize`] when the file
+  /// decompresses to more than `max_size` bytes.
+  #[cfg(feature = "flate2")]
+  pub fn open_gz_with_limit(filename: &str, max_size: u64) -> Result<Parser<Cursor<Vec<u8>>>> {
+    Parser::from_bytes_owned(crate::gzip::decompress(&fs::read(filename)?, max_size)?)
+  }
+}
+
+impl<R: Read + Seek> Parser<R> {
+  /// Parses `reader`, such as an open file or a `Cursor` over a buffer, from its start. Its size
+  /// is found by seeking to its end.
+  pub fn new(mut reader: R) -> Result<Parser<R>> {
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    reader.rewind()?;
+    if file_size > MIN_FILE_READ {
+      Ok(Parser { file: reader, file_size, context: ParseContext::default(), path: None, threads: None })
+    } else {
+      Err(ParseError::NotValidMediaFileSize(String::from("Bad File Size")))
+    }
+  }
+  /// Replaces the default limits used while parsing.
+  pub fn with_context(mut self, context: ParseContext) -> Parser<R> {
+    self.context = context;
+    self
+  }
+  /// Gives up on files whose major brand is one of `brands`, see
+  /// [`ParseContext::with_unsupported_brands`].
+  pub fn with_unsupported_brands(mut self, brands: &[&str]) -> Parser<R> {
+    self.context = self.context.with_unsupported_brands(brands);
+    self
+  }
+  /// Parses with `options` and no custom handlers.
+  pub fn with_options(self, options: ParseOptions) -> Parser<R> {
+    self.with_context(ParseContext::from(options))
+  }
+  /// Sets how many threads [`Parser::parse_parallel`] parses tracks on. 1 parses in turn.
+  pub fn with_threads(mut self, threads: usize) -> Parser<R> {
+    self.threads = Some(threads);
+    self
+  }
+  fn file_size(&self) -> u64 {
+    self.file_size
+  }
+
+  pub fn parse(&mut self) -> ParseResults {
+    let context = self.context.clone();
+    self.parse_in(context)
+  }
+  fn parse_in(&mut self, context: ParseContext) -> ParseResults {
+    let context = context.collecting_dropped();
+    let parsed = self.parse_root(&context);
+    ParseResults::new(parsed).with_warnings(context.take_dropped())
+  }
+  /// Parses the whole file into the `root` container holding its top-level atoms. Only the parser
+  /// builds one, as an atom of the file typed `root` is not a container.
+  fn parse_root(&mut self, context: &ParseContext) -> Result<AtomNodes> {
+    let file_size = self.file_size();
+    let mut file = BufferedReader::new(&mut self.file)?;
+    Ok(AtomNodes::Container(ContainerAtoms::Root(RootAtom::new(&mut file, file_size, context)?)))
+  }
+
+  /// Parses like [`Parser::parse`], with the tracks of the movie parsed on worker threads, which
+  /// pays off for movies with large sample tables. Each thread opens the file again, so a parser
+  /// that was not opened from a path parses in turn. The results are the same as those of
+  /// [`Parser::parse`], except that a file with more atoms than
+  /// [`ParseLimits::max_total_atoms`] fails naming its `moov` atom.
+  pub fn parse_parallel(&mut self) -> ParseResults {
+    let threads = self.threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let path = match self.path.clone() {
+      Some(path) if threads > 1 => path,
+      _ => return self.parse(),
+    };
+    let open: OpenSource = Arc::new(move || {
+      fs::File::open(&path).and_then(BufferedReader::new).map(|file| Box::new(file) as Box<dyn ReadSeek + Send>)
+    });
+    let context = self.context.clone().in_parallel(threads, open);
+    self.parse_in(context)
+  }
+
+  /// Parses the file and validates the tree in the same pass, checking the layout of each
+  /// container as its children are read. Gives the same results and warnings as calling
+  /// [`Parser::parse`] and then [`ParseResults::validate`].
+  pub fn parse_and_validate(&mut self) -> (ParseResults, Vec<ValidationWarning>) {
+    let context = self.context.clone().collecting_warnings().collecting_dropped();
+    let parsed = self.parse_root(&context);
+    let results = ParseResults::new(parsed).with_warnings(context.take_dropped());
+    let mut warnings = if results.error().is_none() { context.take_warnings() } else { Vec::new() };
+    warnings.extend(results.file_warnings());
+    (results, warnings)
+  }
+
+  /// Parses the top-level atoms that lie entirely within the first `byte_limit` bytes, for
+  /// previewing the start of a file that is not yet fully available. The results are marked
+  /// truncated when the limit cut the file short.
+  pub fn parse_with_limit(&mut self, byte_limit: u64) -> ParseResults {
+    let context = self.context.clone().collecting_dropped();
+    let mut children = Vec::new();
+    let mut cursor = ParseCursor::default();
+    let mut truncated = None;
+    while cursor.offset < self.file_size() {
+      let offset = cursor.offset;
+      if offset >= byte_limit {
+        truncated = Some(Truncated { offset, partial_atom: None });
+        break;
+      }
+      let header = match self.parse_step(&mut cursor) {
+        Ok(Some(header)) => header,
+        Ok(None) => break,
+        Err(err) => return ParseResults::new(Err(err)),
+      };
+      if cursor.offset > byte_limit {
+        let partial_atom = Some(header).filter(|header| offset + header.header_size() as u64 <= byte_limit);
+        truncated = Some(Truncated { offset, partial_atom });
+        break;
+      }
+      let node = BufferedReader::new(&mut self.file).map_err(ParseError::from)
+        .and_then(|mut file| AtomNodes::new(header, &mut file, &context)).and_then(|node| match &node {
+        AtomNodes::Atom(Atoms::Ftyp(ftyp)) => context.check_brand(ftyp).map(|_| node),
+        _ => Ok(node),
+      });
+      match node {
+        Ok(node) => children.push(node),
+        Err(err) => return ParseResults::new(Err(err)),
+      }
+    }
+    let root = RootAtom::from_children(self.file_size(), children);
+    ParseResults { results: Ok(AtomNodes::Container(ContainerAtoms::Root(root))), truncated,
+                   warnings: context.take_dropped() }
+  }
+
+  /// Walks the headers of every atom in the file without parsing any of them, for scanning many
+  /// files quickly. Descends into the containers that hold the movie and skips everything else.
+  pub fn headers(&mut self) -> HeaderIter<'_> {
+    HeaderIter::new(&mut self.file, self.file_size)
+  }
+
+  /// Reads the header of the top-level atom at `cursor` and moves the cursor past it. Returns
+  /// `None` once the cursor reaches the end of the file.
+  pub fn parse_step(&mut self, cursor: &mut ParseCursor) -> Result<Option<AtomHeader>> {
+    if cursor.offset >= self.file_size() {
+      return Ok(None);
+    }
+    self.file.seek(SeekFrom::Start(cursor.offset))?;
+    let header = AtomHeader::new_within(&mut self.file, Some(self.file_size))?;
+    let next = header.end_location()?;
+    if next <= cursor.offset {
+      return Err(ParseError::parse_failed(&header, format!("size {} does not advance", header.atom_size())));
+    }
+    cursor.offset = next;
+    Ok(Some(header))
+  }
+}
+
+impl<R> AtomLike for Parser<R> {
+  fn atom_size(&self) -> u64 {
+    self.file_size
+  }
+
+  fn atom_type(&self) -> &str {
+    "root"
+  }
+
+  fn atom_location(&self) -> u64 {
+    0
+  }
+
+  fn header_size(&self) -> u32 {
+    0
+  }
+}
+impl<R> From<&mut Parser<R>> for AtomHeader {
+  fn from(item: &mut Parser<R>) -> Self {
+    let atom_like: &dyn AtomLike = item;
+    atom_like.into()
+  }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fails_on_a_non_existant_file() {
+    let res = Parser::from_path("resources/test/Nonsense.mp4");
+    assert!(res.is_err());
+    match res.err().unwrap() {
+      ParseError::IoError(_) => (),
+      ref err => panic!("expected IoError, got {:?}", err),
+    }
+  }
+
+  #[test]
+  fn should_be_able_to_open_a_file() {
+    assert!(Parser::from_path("resources/tests/sample.mp4").is_ok());
+  }
+
+  #[test]
+  fn should_reject_a_file_if_not_of_valid_size() {
+    let res = Parser::from_path("resources/tests/empty_file.mp4");
+    assert!(res.is_err());
+    match res.err().unwrap() {
+      ParseError::NotValidMediaFileSize(_) => (),
+      ref err => panic!("expected NotValidMediaFileSize, got {:?}", err),
+    }
+  }
+
+  #[test]
+  fn should_parse_atoms_as_nodes() {
+    let parser = Parser::from_path("resources/tests/sample.mp4");
+    assert!(parser.is_ok());
+    let res = parser.unwrap().parse().results.unwrap();
+    assert_eq!(res.atom_type(), "root");
+  }
+
+  #[test]
+  fn should_validate_a_well_formed_file_without_warnings() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(Vec::<ValidationWarning>::new(), results.validate());
+  }
+
+  #[test]
+  fn should_warn_about_an_atom_running_past_the_end_of_the_file() {
+    let results = Parser::from_path("resources/tests/truncated.mp4").unwrap().parse();
+    assert_eq!(vec!["overrun: free at 32 extends 48 bytes past the end of root"], warning_messages(&results));
+  }
+
+  #[test]
+  fn should_run_a_zero_size_top_level_atom_to_the_end_of_the_file() {
+    let mut bytes = ftyp(b"isom", &[b"isom"]);
+    let mdat = bytes.len() as u64;
+    bytes.extend_from_slice(&[0, 0, 0, 0]);
+    bytes.extend_from_slice(b"mdat");
+    bytes.extend_from_slice(&[0; 100]);
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    let types: Vec<(&str, u64, u64)> = results.top_level().iter()
+      .map(|node| (node.atom_type(), node.atom_location(), node.atom_size())).collect();
+    assert_eq!(vec![("ftyp", 0, mdat), ("mdat", mdat, 108)], types);
+    assert_eq!(Vec::<ValidationWarning>::new(), results.validate());
+  }
+
+  #[test]
+  fn should_give_the_offset_of_a_corrupted_atom() {
+    let mut bytes = fs::read("resources/tests/sample.mp4").unwrap();
+    let free = 32 + 1023163;
+    assert_eq!(b"free", &bytes[free + 4..free + 8]);
+    bytes[free + 4..free + 8].copy_from_slice(&[0x00, 0x9c, 0x01, 0xff]);
+    // The atoms before it are kept, and the rest of the file is slack.
+    let results = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+    assert_eq!(vec!["ftyp", "moov", "slack"], results.top_level().iter().map(|node| node.atom_type()).collect::<Vec<_>>());
+    assert_eq!(1, results.warnings().len());
+    assert!(matches!(results.warnings()[0].error(), ParseError::Misaligned { .. }));
+
+    let strict = ParseOptions { strict: true, ..Default::default() };
+    let results = Parser::from_bytes_owned(bytes).unwrap().with_options(strict).parse();
+    let error = results.error().unwrap();
+    assert!(matches!(error, ParseError::Misaligned { four_cc: [0x00, 0x9c, 0x01, 0xff], .. }));
+    assert_eq!(Some(free as u64), error.offset());
+    assert_eq!("probable misalignment: [00, 9c, 01, ff] at 1023195 (0xf9cdb) is not a four-CC", error.to_string());
+  }
+
+  #[test]
+  fn should_parse_an_atom_typed_root_as_an_unknown_atom() {
+    use crate::leaves::UnknownReason;
+    let file = [ftyp(b"isom", &[]), atom(b"moov", &atom(b"root", &atom(b"free", &[])))].concat();
+    let results = Parser::from_bytes_owned(file).unwrap().parse();
+    assert!(results.is_clean(), "{:?}", results.error());
+    match results.find("moov/root") {
+      Some(AtomNodes::Atom(Atoms::UnknownAtom(root))) => {
+        assert_eq!(UnknownReason::Unrecognized, *root.reason());
+        assert_eq!((24, 16), (root.atom_location(), root.atom_size()));
+      }
+      other => panic!("expected an unknown atom, got {:?}", other),
+    }
+    assert_eq!(4, results.nodes().atom_count());
+  }
+
+  #[test]
+  fn should_keep_the_top_level_atoms_before_trailing_junk() {
+    let mut file = ftyp(b"isom", &[]);
+    file.extend(atom(b"free", &[0; 8]));
+    file.extend([0; 3]);
+    let results = Parser::from_bytes_owned(file.clone()).unwrap().parse();
+    assert!(results.error().is_none(), "{:?}", results.error());
+    assert_eq!(vec!["ftyp", "free", "slack"], results.top_level().iter().map(|node| node.atom_type()).collect::<Vec<_>>());
+    assert!(matches!(results.top_level()[2], AtomNodes::Atom(Atoms::Slack { location: 32, size: 3 })));
+    assert!(matches!(results.warnings()[0].error(), ParseError::TruncatedHeader { offset: 32, expected: 8, got: 3 }));
+
+    let strict = ParseOptions { strict: true, ..Default::default() };
+    let results = Parser::from_bytes_owned(file).unwrap().with_options(strict).parse();
+    assert!(matches!(results.error(), Some(ParseError::TruncatedHeader { offset: 32, .. })), "{:?}", results.error());
+
+    // A size too small for the header is as bad as no header.
+    let mut file = ftyp(b"isom", &[]);
+    file.extend([0, 0, 0, 4, b'f', b'r', b'e', b'e']);
+    let results = Parser::from_bytes_owned(file).unwrap().parse();
+    assert!(matches!(results.top_level()[1], AtomNodes::Atom(Atoms::Slack { location: 16, size: 8 })));
+    assert!(matches!(results.warnings()[0].error(), ParseError::InvalidChildSize { offset: 16, size: 4, .. }));
+  }
+
+  #[test]
+  fn should_fail_clearly_on_a_container_cut_short_by_the_end_of_the_file() {
+    let mut bytes = fs::read("resources/tests/sample.mp4").unwrap();
+    bytes.truncate(32 + 1000);
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    match results.error() {
+      Some(ParseError::AtomPastEndOfFile { atom_type, offset, size, file_size }) =>
+        assert_eq!(("moov", 32, 1023163, 1032), (atom_type.as_str(), *offset, *size, *file_size)),
+      other => panic!("expected AtomPastEndOfFile, got {:?}", other),
+    }
+    assert_eq!("moov at 32 (0x20): size 1023163 runs past the end of the file at 1032\n", results.to_string());
+  }
+
+  #[test]
+  fn should_print_the_top_level_atoms_as_siblings() {
+    let tree = Parser::from_path("resources/tests/sample.mp4").unwrap().parse().to_string();
+    let top_level: Vec<&str> = tree.lines().filter(|line| !line.starts_with([' ', '\u{2502}'])).collect();
+    assert_eq!(vec!["\u{251c}\u{2500}\u{2500} type: ftyp, size: 32, location: 0, end: 32",
+                    "\u{251c}\u{2500}\u{2500} type: moov, size: 1023163 (999.2 KiB), location: 32, end: 1023195",
+                    "\u{251c}\u{2500}\u{2500} type: free, size: 8, location: 1023195, end: 1023203",
+                    "\u{2514}\u{2500}\u{2500} type: mdat, size: 24751080 (23.6 MiB), location: 1023203, end: 25774283"],
+               top_level);
+  }
+
+  #[test]
+  fn should_parse_from_a_borrowed_reader() {
+    let bytes = fs::read("resources/tests/sample.mp4").unwrap();
+    let results = Parser::new(Cursor::new(&bytes[..])).unwrap().parse();
+    let from_file = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(from_file.to_string(), results.to_string());
+  }
+
+  #[test]
+  fn should_parse_a_path_or_a_cursor_alike() {
+    let path = std::path::PathBuf::from("resources/tests/sample.mp4");
+    let from_path = Parser::from_path(&path).unwrap().parse();
+    let from_cursor = Parser::new(Cursor::new(fs::read(&path).unwrap())).unwrap().parse();
+    assert_eq!(from_path.to_string(), from_cursor.to_string());
+    assert!(matches!(Parser::from_path("resources/tests/does_not_exist.mp4"), Err(ParseError::IoError(_))));
+  }
+
+  #[test]
+  fn should_return_owned_results_from_an_owned_buffer() {
+    fn parse_file(path: &str) -> ParseResults {
+      let bytes = fs::read(path).unwrap();
+      Parser::from_bytes_owned(bytes).unwrap().parse()
+    }
+    let results = parse_file("resources/tests/sample.mp4");
+    match results.nodes() {
+      AtomNodes::Container(root) => assert_eq!(4, root.children().len()),
+      other => panic!("expected the root container, got {:?}", other),
+    }
+    assert!(Parser::from_bytes_owned(vec![0; 8]).is_err());
+  }
+
+  #[test]
+  fn should_find_an_ftyp_placed_after_the_moov() {
+    let mut bytes = fs::read("resources/tests/moov.mp4").unwrap();
+    bytes.extend_from_slice(&fs::read("resources/tests/ftyp.mp4").unwrap());
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    let ftyp = results.ftyp().unwrap();
+    assert_eq!(1023163, ftyp.atom_location());
+    assert_eq!(32, ftyp.atom_size());
+    assert_eq!(vec!["misplaced-ftyp: ftyp at 1023163 is not the first atom"], warning_messages(&results));
+
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(0, results.ftyp().unwrap().atom_location());
+  }
+
+  #[test]
+  fn should_allocate_the_track_id_after_the_existing_tracks() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let track_ids: Vec<u32> = results.track_headers().iter().map(|tkhd| tkhd.track_id()).collect();
+    assert_eq!(vec![1, 2], track_ids);
+    assert_eq!(3, results.allocate_track_id());
+
+    // Rewind the next track id in mvhd so it collides with track 2.
+    let mut bytes = fs::read("resources/tests/sample.mp4").unwrap();
+    let mvhd = results.mvhd().unwrap();
+    let next_track_id = (mvhd.atom_location() + mvhd.atom_size() - 4) as usize;
+    bytes[next_track_id..next_track_id + 4].copy_from_slice(&2u32.to_be_bytes());
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    assert_eq!(2, results.mvhd().unwrap().next_track_id());
+    assert_eq!(3, results.allocate_track_id());
+    assert_eq!(vec!["track-id: next track id 2 in mvhd is not greater than track id 2"], warning_messages(&results));
+  }
+
+  #[test]
+  fn should_compute_the_video_frame_rate() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let frame_rate = results.video_frame_rate().unwrap();
+    assert!((frame_rate - 29.97).abs() < 0.01, "{}", frame_rate);
+    assert_eq!(Some(false), results.is_variable_frame_rate());
+
+    let results = Parser::from_path("resources/tests/moov.mp4").unwrap().parse();
+    assert!(results.video_frame_rate().is_some());
+    let results = Parser::from_path("resources/tests/ftyp.mp4").unwrap().parse();
+    assert_eq!(None, results.video_frame_rate());
+    assert_eq!(None, results.is_variable_frame_rate());
+  }
+
+  #[test]
+  fn should_write_the_tree_through_a_buffer() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let mut out = Vec::new();
+    results.write_tree_buffered(&mut out, None).unwrap();
+    assert_eq!(results.to_string(), String::from_utf8(out).unwrap());
+
+    let mut out = Vec::new();
+    results.write_tree_buffered(&mut out, Some(2)).unwrap();
+    let tree = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = tree.lines().collect();
+    assert!(lines[0].contains("type: ftyp"));
+    assert!(lines[1].contains("type: moov"));
+    assert_eq!("\u{2514}\u{2500}\u{2500} \u{2026} (2 more)", *lines.last().unwrap());
+    assert!(!tree.contains("type: mdat"));
+  }
+
+  #[test]
+  fn should_apply_the_edit_list_to_the_presentation_duration() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(Some(Duration::from_millis(973707)), results.track_presentation_duration(1));
+    assert_eq!(Some(Duration::from_millis(973753)), results.track_presentation_duration(2));
+    assert_eq!(None, results.track_presentation_duration(3));
+
+    // Trim the video edit to the first 500 seconds.
+    let mut bytes = fs::read("resources/tests/sample.mp4").unwrap();
+    let mut elst = None;
+    results.nodes().walk(|node| if let AtomNodes::Atom(Atoms::Elst(atom)) = node { elst = elst.or(Some(atom)) });
+    let segment_duration = elst.unwrap().atom_location() as usize + 16;
+    bytes[segment_duration..segment_duration + 4].copy_from_slice(&500_000u32.to_be_bytes());
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    let mdhd = find_atom(results.track_node(1).unwrap(), |atom| match atom { Atoms::Mdhd(mdhd) => Some(mdhd), _ => None });
+    let media_duration = Duration::from_secs_f64(mdhd.unwrap().duration() as f64 / 30000.0);
+    let presentation_duration = results.track_presentation_duration(1).unwrap();
+    assert_eq!(Duration::from_secs(500), presentation_duration);
+    assert!(presentation_duration < media_duration);
+  }
+
+  fn atom(atom_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(atom_type);
+    bytes.extend_from_slice(payload);
+    bytes
+  }
+
+  fn ftyp(major_brand: &[u8; 4], compatible_brands: &[&[u8; 4]]) -> Vec<u8> {
+    let mut payload = major_brand.to_vec();
+    payload.extend_from_slice(&[0; 4]);
+    for brand in compatible_brands {
+      payload.extend_from_slice(*brand);
+    }
+    atom(b"ftyp", &payload)
+  }
+
+  fn track_with_handler(handler_type: &[u8; 4]) -> Vec<u8> {
+    let mut hdlr = vec![0; 8];
+    hdlr.extend_from_slice(handler_type);
+    hdlr.extend_from_slice(&[0; 13]);
+    atom(b"trak", &atom(b"mdia", &atom(b"hdlr", &hdlr)))
+  }
+
+  fn mime_type_of(bytes: Vec<u8>) -> &'static str {
+    Parser::from_bytes_owned(bytes).unwrap().parse().mime_type()
+  }
+
+  #[test]
+  fn should_guess_the_mime_type_from_the_brands() {
+    assert_eq!("video/mp4", Parser::from_path("resources/tests/sample.mp4").unwrap().parse().mime_type());
+    assert_eq!("video/quicktime", mime_type_of(ftyp(b"qt  ", &[b"qt  "])));
+    assert_eq!("image/heic", mime_type_of(ftyp(b"heic", &[b"mif1", b"heic"])));
+    assert_eq!("image/avif", mime_type_of(ftyp(b"avif", &[b"mif1", b"avif"])));
+    assert_eq!("audio/mp4", mime_type_of(ftyp(b"M4A ", &[b"M4A ", b"isom"])));
+    assert_eq!("video/3gpp", mime_type_of(ftyp(b"3gp5", &[b"isom"])));
+    assert_eq!("video/mp4", mime_type_of(ftyp(b"mp42", &[b"isom", b"mp42"])));
+    assert_eq!("video/quicktime", mime_type_of(fs::read("resources/tests/moov.mp4").unwrap()));
+    assert_eq!("application/octet-stream", mime_type_of(atom(b"free", &[0; 8])));
+  }
+
+  #[test]
+  fn should_guess_audio_for_an_mpeg_4_file_without_video_tracks() {
+    let mut bytes = ftyp(b"isom", &[b"isom", b"mp41"]);
+    bytes.extend(atom(b"moov", &track_with_handler(b"soun")));
+    assert_eq!("audio/mp4", mime_type_of(bytes));
+
+    let mut bytes = ftyp(b"isom", &[b"isom", b"mp41"]);
+    let mut tracks = track_with_handler(b"soun");
+    tracks.extend(track_with_handler(b"vide"));
+    bytes.extend(atom(b"moov", &tracks));
+    assert_eq!("video/mp4", mime_type_of(bytes));
+  }
+
+  #[test]
+  fn should_parse_only_the_atoms_within_a_byte_limit() {
+    let mut parser = Parser::from_path("resources/tests/sample.mp4").unwrap();
+    let results = parser.parse_with_limit(64 * 1024);
+    assert!(results.ftyp().is_some());
+    let types: Vec<&str> = results.top_level().iter().map(|node| node.atom_type()).collect();
+    assert_eq!(vec!["ftyp"], types);
+    let truncated = results.truncated().unwrap();
+    assert_eq!(32, truncated.offset());
+    assert_eq!("moov", truncated.partial_atom().unwrap().atom_type());
+
+    // The same preview from a download of only the first 64 KiB.
+    let bytes = fs::read("resources/tests/sample.mp4").unwrap();
+    let preview = Parser::from_bytes_owned(bytes[..64 * 1024].to_vec()).unwrap().parse_with_limit(64 * 1024);
+    assert_eq!(1, preview.top_level().len());
+    assert_eq!(results.top_level()[0].structural_hash(), preview.top_level()[0].structural_hash());
+    assert_eq!("moov", preview.truncated().unwrap().partial_atom().unwrap().atom_type());
+
+    let results = parser.parse_with_limit(1023195);
+    let types: Vec<&str> = results.top_level().iter().map(|node| node.atom_type()).collect();
+    assert_eq!(vec!["ftyp", "moov"], types);
+    assert!(results.truncated().unwrap().partial_atom().is_none());
+    assert!(parser.parse_with_limit(u64::MAX).truncated().is_none());
+  }
+
+  #[test]
+  fn should_show_the_sample_descriptions_and_their_extensions_in_the_tree() {
+    let tree = Parser::from_path("resources/tests/sample.mp4").unwrap().parse().to_string();
+    let lines: Vec<&str> = tree.lines().collect();
+    let stsd = lines.iter().position(|line| line.contains("type: stsd")).unwrap();
+    assert_eq!("\u{2502}   \u{2502}               \u{2502}   \u{2514}\u{2500}\u{2500} avc1 960x540, depth: 24", lines[stsd + 1]);
+    assert!(lines[stsd + 2].starts_with("\u{2502}   \u{2502}               \u{2502}       \u{2514}\u{2500}\u{2500} type: avcC, size: "),
+            "{}", lines[stsd + 2]);
+    assert!(tree.contains("\u{2514}\u{2500}\u{2500} type: esds"));
+  }
+
+  #[test]
+  fn should_name_the_kind_of_each_track_in_the_tree() {
+    let tree = Parser::from_path("resources/tests/sample.mp4").unwrap().parse().to_string();
+    let tracks: Vec<&str> = tree.lines().filter(|line| line.contains("type: trak")).collect();
+    assert_eq!(vec!["\u{2502}   \u{251c}\u{2500}\u{2500} type: trak, size: 431981 (421.9 KiB), location: 148, end: 432129 (video)",
+                    "\u{2502}   \u{251c}\u{2500}\u{2500} type: trak, size: 590968 (577.1 KiB), location: 432129, end: 1023097 (audio)"],
+               tracks);
+  }
+
+  #[test]
+  fn should_draw_the_branches_of_nested_containers() {
+    let tree = Parser::from_path("resources/tests/trak.mp4").unwrap().parse().to_string();
+    let expected = "\
+\u{2514}\u{2500}\u{2500} type: trak, size: 431981 (421.9 KiB), location: 0, end: 431981 (video)
+    \u{251c}\u{2500}\u{2500} type: tkhd, size: 92, location: 8, end: 100
+    \u{251c}\u{2500}\u{2500} type: edts, size: 36, location: 100, end: 136
+    \u{2502}   \u{2514}\u{2500}\u{2500} type: elst, size: 28, location: 108, end: 136
+    \u{2514}\u{2500}\u{2500} type: mdia, size: 431845 (421.7 KiB), location: 136, end: 431981
+        \u{251c}\u{2500}\u{2500} type: mdhd, size: 32, location: 144, end: 176
+        \u{251c}\u{2500}\u{2500} type: hdlr, size: 45, location: 176, end: 221
+        \u{2514}\u{2500}\u{2500} type: minf, size: 431760 (421.6 KiB), location: 221, end: 431981
+            \u{251c}\u{2500}\u{2500} type: vmhd, size: 20, location: 229, end: 249
+            \u{251c}\u{2500}\u{2500} type: dinf, size: 36, location: 249, end: 285
+            \u{2502}   \u{2514}\u{2500}\u{2500} type: dref, size: 28, location: 257, end: 285
+            \u{2514}\u{2500}\u{2500} type: stbl, size: 431696 (421.6 KiB), location: 285, end: 431981
+                \u{251c}\u{2500}\u{2500} type: stsd, size: 152, location: 293, end: 445
+                \u{2502}   \u{2514}\u{2500}\u{2500} avc1 960x540, depth: 24
+                \u{2502}       \u{2514}\u{2500}\u{2500} type: avcC, size: 50, location: 395
+                \u{251c}\u{2500}\u{2500} type: stts, size: 24, location: 445, end: 469
+                \u{251c}\u{2500}\u{2500} type: stss, size: 500, location: 469, end: 969
+                \u{251c}\u{2500}\u{2500} type: ctts, size: 197488 (192.9 KiB), location: 969, end: 198457
+                \u{251c}\u{2500}\u{2500} type: stsc, size: 40, location: 198457, end: 198497
+                \u{251c}\u{2500}\u{2500} type: stsz, size: 116748 (114.0 KiB), location: 198497, end: 315245
+                \u{2514}\u{2500}\u{2500} type: stco, size: 116736 (114.0 KiB), location: 315245, end: 431981
+";
+    assert_eq!(expected, tree);
+
+    let results = Parser::from_path("resources/tests/gopro_udta.mp4").unwrap().parse();
+    let moov = match results.find("moov").unwrap() {
+      AtomNodes::Container(moov) => moov.to_string(),
+      other => panic!("expected a container, got {:?}", other),
+    };
+    assert_eq!("\
+Moov: type: moov, size: 116, location: 24
+\u{2514}\u{2500}\u{2500} type: udta, size: 108, location: 32, end: 140
+    \u{251c}\u{2500}\u{2500} type: FIRM, size: 24, location: 40, end: 64
+    \u{251c}\u{2500}\u{2500} type: LENS, size: 24, location: 64, end: 88
+    \u{251c}\u{2500}\u{2500} type: CAME, size: 24, location: 88, end: 112
+    \u{2514}\u{2500}\u{2500} type: GPMF, size: 28, location: 112, end: 140
+", moov);
+  }
+
+  #[test]
+  fn should_give_sizes_in_human_units() {
+    assert_eq!(None, human_size(1023));
+    assert_eq!(Some(String::from("1.0 KiB")), human_size(1024));
+    assert_eq!(Some(String::from("23.6 MiB")), human_size(24751080));
+    assert_eq!(Some(String::from("3.2 GiB")), human_size(3_435_973_837));
+  }
+
+  #[test]
+  fn should_keep_every_locale_of_a_metadata_item() {
+    fn data(locale: Locale, text: &str) -> Vec<u8> {
+      let mut payload = vec![0, 0, 0, 1];
+      payload.extend_from_slice(&locale.country.to_be_bytes());
+      payload.extend_from_slice(&locale.language.to_be_bytes());
+      payload.extend_from_slice(text.as_bytes());
+      atom(b"data", &payload)
+    }
+    let japanese = Locale { country: u16::from_be_bytes(*b"JP"), language: 0x2a0e };
+    let mut title = data(Locale::default(), "Title");
+    title.extend(data(japanese, "タイトル"));
+    let mut meta = vec![0; 4];
+    meta.extend(atom(b"ilst", &atom(b"\xa9nam", &title)));
+    let mut bytes = ftyp(b"isom", &[b"isom"]);
+    bytes.extend(atom(b"moov", &atom(b"udta", &atom(b"meta", &meta))));
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+
+    let titles = &results.metadata_localized()["©nam"];
+    assert_eq!(2, titles.len());
+    assert_eq!(Some("Title"), titles[&Locale::default()].as_text());
+    assert_eq!(Some("タイトル"), titles[&japanese].as_text());
+    assert_eq!(Some("Title"), results.metadata()["©nam"].as_text());
+  }
+
+  #[test]
+  fn should_extract_the_cover_art() {
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    let mut payload = vec![0, 0, 0, 14, 0, 0, 0, 0];
+    payload.extend_from_slice(png);
+    let mut meta = vec![0; 4];
+    meta.extend(atom(b"ilst", &atom(b"covr", &atom(b"data", &payload))));
+    let mut bytes = ftyp(b"M4A ", &[b"M4A ", b"isom"]);
+    bytes.extend(atom(b"moov", &atom(b"udta", &atom(b"meta", &meta))));
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+
+    let (format, data) = results.cover_art().unwrap();
+    assert_eq!(ImageFormat::Png, format);
+    assert_eq!(b"\x89PNG\r\n\x1a\n", &data[..8]);
+    assert!(Parser::from_path("resources/tests/sample.mp4").unwrap().parse().cover_art().is_none());
+  }
+
+  #[test]
+  fn should_read_the_tags_of_an_item_list() {
+    let data = |type_indicator: u32, value: &[u8]| {
+      let mut payload = type_indicator.to_be_bytes().to_vec();
+      payload.extend_from_slice(&[0; 4]);
+      payload.extend_from_slice(value);
+      atom(b"data", &payload)
+    };
+    let mut items = atom(b"\xa9nam", &data(1, b"My Movie"));
+    items.extend(atom(b"\xa9too", &data(1, b"Lavf58.76.100")));
+    items.extend(atom(b"covr", &data(0, b"\0\x01artwork")));
+    let mut meta = vec![0; 4];
+    meta.extend(atom(b"hdlr", &[0; 25]));
+    meta.extend(atom(b"ilst", &items));
+    let mut bytes = ftyp(b"isom", &[b"isom"]);
+    bytes.extend(atom(b"moov", &atom(b"udta", &atom(b"meta", &meta))));
+    let metadata = Parser::from_bytes_owned(bytes).unwrap().parse().metadata();
+
+    assert_eq!(Some(&MetadataValue::Text(String::from("My Movie"))), metadata.get("©nam"));
+    assert_eq!(Some("Lavf58.76.100"), metadata["©too"].as_text());
+    assert_eq!(MetadataValue::Binary { type_indicator: 0, data: b"\0\x01artwork".to_vec() }, metadata["covr"]);
+    assert_eq!(3, metadata.len());
+  }
+
+  #[test]
+  fn should_keep_the_siblings_of_a_child_that_fails_to_parse() {
+    let mut bytes = fs::read("resources/tests/moov.mp4").unwrap();
+    // The elst in the edts of the video track claims to be smaller than its header.
+    bytes[224..228].copy_from_slice(&4u32.to_be_bytes());
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+
+    assert!(!results.is_clean());
+    assert_eq!(1, results.warnings().len());
+    let warning = &results.warnings()[0];
+    assert_eq!(("trak", 216), (warning.parent(), warning.offset()));
+    assert!(matches!(warning.error(), ParseError::InvalidChildSize { offset: 224, size: 4, .. }));
+    assert_eq!("trak: left out the child at 216 (0xd8): elst at 224 (0xe0): size 4 is smaller than its header", warning.to_string());
+    let video = match results.find_all("trak")[0] {
+      AtomNodes::Container(trak) => trak.children().iter().map(|node| node.atom_type()).collect::<Vec<&str>>(),
+      other => panic!("expected trak, got {:?}", other),
+    };
+    assert_eq!(vec!["tkhd", "mdia"], video);
+    assert_eq!(2, results.track_headers().len());
+    assert!(results.to_string().ends_with("\n(1 warning)\n"));
+
+    assert!(Parser::from_path("resources/tests/moov.mp4").unwrap().parse().is_clean());
+  }
+
+  #[test]
+  fn should_fail_a_strict_parse_where_a_lenient_one_warns() {
+    use crate::leaves::UnknownReason;
+    let mut bytes = fs::read("resources/tests/moov.mp4").unwrap();
+    bytes[224..228].copy_from_slice(&4u32.to_be_bytes());
+    let lenient = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+    assert!(lenient.error().is_none());
+    assert_eq!(1, lenient.warnings().len());
+
+    let options = ParseOptions { strict: true, ..Default::default() };
+    let strict = Parser::from_bytes_owned(bytes).unwrap().with_options(options.clone()).parse();
+    assert!(matches!(strict.error(), Some(ParseError::InvalidChildSize { offset: 224, size: 4, .. })), "{:?}", strict.error());
+
+    // Leaves that fail are errors too, rather than unknown atoms.
+    let options = ParseOptions { max_atom_read: Some(1024), ..options };
+    let strict = Parser::from_path("resources/tests/moov.mp4").unwrap().with_options(options.clone()).parse();
+    assert!(matches!(strict.error(), Some(ParseError::AtomTooLarge { limit: 1024, .. })), "{:?}", strict.error());
+    let lenient = Parser::from_path("resources/tests/moov.mp4").unwrap().with_options(ParseOptions { strict: false, ..options }).parse();
+    assert!(lenient.error().is_none());
+    assert!(lenient.find_all("stsz").iter().all(|stsz| matches!(stsz, AtomNodes::Atom(Atoms::UnknownAtom(unknown))
+      if matches!(unknown.reason(), UnknownReason::ParseFailed(_)))));
+  }
+
+  #[test]
+  fn should_descend_into_configured_containers_and_leave_skipped_atoms_unread() {
+    use crate::leaves::UnknownReason;
+    let mut bytes = ftyp(b"isom", &[]);
+    bytes.extend(atom(b"abcd", &atom(b"free", &[0; 8])));
+    bytes.extend(atom(b"mdat", &[0; 8]));
+    let results = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+    assert!(results.find_all("free").is_empty());
+
+    let options = ParseOptions { containers: vec![FourCC::from("abcd")], skip_types: vec![FourCC::from("mdat")], ..Default::default() };
+    let results = Parser::from_bytes_owned(bytes).unwrap().with_options(options).parse();
+    match results.find_all("abcd")[..] {
+      [AtomNodes::Container(ContainerAtoms::Generic(abcd))] => assert_eq!("Container: type: abcd, size: 24, location: 16", abcd.to_string()),
+      ref other => panic!("expected an abcd container, got {:?}", other),
+    }
+    assert_eq!(vec![24], results.find_all("free").iter().map(|free| free.atom_location()).collect::<Vec<u64>>());
+    match results.find_all("mdat")[..] {
+      [AtomNodes::Atom(Atoms::UnknownAtom(mdat))] => assert_eq!(UnknownReason::Skipped, *mdat.reason()),
+      ref other => panic!("expected a skipped mdat, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn should_build_the_options_of_a_run() {
+    let config = crate::Config::new("resources/tests/gopro_udta.mp4").strict(true).skip_types(&["mdat"])
+      .max_atom_read(64 * 1024 * 1024).containers(&["abcd"]);
+    let expected = ParseOptions { strict: true, skip_types: vec![FourCC::from("mdat")], max_atom_read: Some(64 << 20),
+                                  containers: vec![FourCC::from("abcd")], ..Default::default() };
+    assert_eq!(&expected, config.options());
+    assert!(crate::run(config).unwrap().is_clean());
+    assert!(matches!(crate::run(crate::Config::new("resources/test/Nonsense.mp4")), Err(ParseError::IoError(_))));
+  }
+
+  #[test]
+  fn should_keep_the_slack_at_the_end_of_a_container() {
+    // A moov at 16 whose free child ends at 40, followed by `trailing` bytes of padding.
+    let parse = |trailing: &[u8]| {
+      let mut moov = atom(b"free", &[0; 8]);
+      moov.extend_from_slice(trailing);
+      let mut bytes = ftyp(b"isom", &[]);
+      bytes.extend(atom(b"moov", &moov));
+      bytes.extend(atom(b"mdat", &[0; 4]));
+      Parser::from_bytes_owned(bytes).unwrap().parse()
+    };
+    let children = |results: &ParseResults| match results.find("moov") {
+      Some(AtomNodes::Container(moov)) => moov.children().iter()
+        .map(|child| (child.atom_type().to_string(), child.atom_location(), child.atom_size())).collect::<Vec<_>>(),
+      other => panic!("expected a moov, got {:?}", other),
+    };
+
+    // A child that fills its container exactly leaves no slack.
+    let results = parse(&[]);
+    assert_eq!(vec![(String::from("free"), 24, 16)], children(&results));
+    assert_eq!(0, results.statistics().slack_bytes);
+    assert_eq!(Vec::<ValidationWarning>::new(), results.validate());
+
+    for trailing in [5, 7] {
+      let results = parse(&vec![0xff; trailing]);
+      assert_eq!(vec![(String::from("free"), 24, 16), (String::from("slack"), 40, trailing as u64)], children(&results));
+      assert!(results.to_string().contains(&format!("\u{2514}\u{2500}\u{2500} slack ({} bytes)\n", trailing)));
+      let stats = results.statistics();
+      assert_eq!(trailing as u64, stats.slack_bytes);
+      assert_eq!(stats.file_size, stats.counts.values().map(|(_, bytes)| bytes).sum::<u64>() + stats.slack_bytes);
+      assert_eq!(vec![ValidationWarning::Slack { container: String::from("moov"), location: 40, bytes: trailing as u64 }],
+                 results.validate());
+    }
+
+    // Zero padding long enough to hold a header does not start with a four-CC, so it is slack too.
+    assert_eq!(vec![(String::from("free"), 24, 16), (String::from("slack"), 40, 12)], children(&parse(&[0; 12])));
+  }
+
+  #[test]
+  fn should_tell_uuid_atoms_apart_by_their_uuid() {
+    let uuid = |uuid: [u8; 16], payload: &[u8]| atom(b"uuid", &[&uuid[..], payload].concat());
+    let cnth = [0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48];
+    let prof = [0x50, 0x52, 0x4f, 0x46, 0x21, 0xd2, 0x4f, 0xce, 0xbb, 0x88, 0x69, 0x5c, 0xfa, 0xc9, 0xc7, 0x40];
+    let mut moov = uuid(cnth, &[0; 6]);
+    moov.extend(uuid(prof, &[1; 10]));
+    moov.extend(atom(b"free", &[0; 4]));
+    let mut bytes = ftyp(b"isom", &[]);
+    bytes.extend(atom(b"moov", &moov));
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+
+    let uuids: Vec<String> = results.find_all("moov/uuid").iter().map(|node| match node {
+      AtomNodes::Atom(Atoms::Uuid(uuid)) => uuid.to_string(),
+      other => panic!("expected a uuid atom, got {:?}", other),
+    }).collect();
+    assert_eq!(vec!["Uuid: type: uuid, size: 30, location: 24, uuid: 85c0b687-820f-11e0-8111-f4ce462b6a48, payload: 6 bytes",
+                    "Uuid: type: uuid, size: 34, location: 54, uuid: 50524f46-21d2-4fce-bb88-695cfac9c740, payload: 10 bytes"],
+               uuids);
+    // The payload after the UUID is skipped, so the child after them is where it should be.
+    let free = results.find("moov/free").unwrap();
+    assert_eq!((88, 12), (free.atom_location(), free.atom_size()));
+    assert_eq!(Vec::<ValidationWarning>::new(), results.validate());
+
+    let found = results.find_all("moov/50524F46-21D2-4FCE-BB88-695CFAC9C740");
+    assert_eq!(vec![54], found.iter().map(|node| node.atom_location()).collect::<Vec<u64>>());
+    assert_eq!(1, results.find_all("85c0b687-820f-11e0-8111-f4ce462b6a48").len());
+    assert_eq!(Some(16), results.find("MOOV").map(|moov| moov.atom_location()));
+  }
+
+  #[test]
+  fn should_parse_every_fixture_the_same_with_and_without_buffering() {
+    let mut paths: Vec<_> = fs::read_dir("resources/tests").unwrap().map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    let mut compared = 0;
+    for path in paths {
+      let mut parser = match Parser::from_path(&path) {
+        Ok(parser) => parser,
+        Err(_) => continue,
+      };
+      let buffered = parser.parse();
+      let context = ParseContext::default().collecting_dropped();
+      let root = RootAtom::new(&mut fs::File::open(&path).unwrap(), parser.file_size(), &context);
+      let unbuffered = ParseResults::new(root.map(|root| AtomNodes::Container(ContainerAtoms::Root(root))))
+        .with_warnings(context.take_dropped());
+      assert_eq!(format!("{:?}", unbuffered), format!("{:?}", buffered), "{}", path.display());
+      assert_eq!(unbuffered.to_string(), buffered.to_string(), "{}", path.display());
+      compared += 1;
+    }
+    assert!(compared >= 15, "only {} fixtures were compared", compared);
+  }
+
+  #[test]
+  fn should_find_the_atoms_holding_an_offset() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let types = |offset| results.atom_at_offset(offset).map(|chain| chain.into_iter().map(|node| node.atom_type()).collect::<Vec<&str>>());
+    assert_eq!(Some(vec!["ftyp"]), types(0));
+    // The first byte of the stco of the video track, and the last byte before it.
+    assert_eq!(Some(vec!["moov", "trak", "mdia", "minf", "stbl", "stco"]), types(315393));
+    assert_eq!(Some(vec!["moov", "trak", "mdia", "minf", "stbl", "stsz"]), types(315392));
+    // The header of the video track belongs to no child of it.
+    assert_eq!(Some(vec!["moov", "trak"]), types(148 + 4));
+    assert_eq!(Some(vec!["mdat"]), types(25774282));
+    assert_eq!(None, types(25774283));
+
+    // Padding after the last child of a container is held by the slack after it.
+    let mut moov = atom(b"free", &[0; 8]);
+    moov.extend_from_slice(&[0; 4]);
+    let mut bytes = ftyp(b"isom", &[]);
+    bytes.extend(atom(b"moov", &moov));
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    let chain = results.atom_at_offset(41).unwrap();
+    assert_eq!(vec!["moov", "slack"], chain.iter().map(|node| node.atom_type()).collect::<Vec<&str>>());
+    assert!(chain[1].contains_offset(43) && !chain[1].contains_offset(44));
+  }
+
+  #[test]
+  fn should_list_the_fragments_of_a_fragmented_file() {
+    let results = Parser::from_path("resources/tests/fragmented.mp4").unwrap().parse();
+    let fragments = results.fragments();
+    assert_eq!(vec![(180, Some(1)), (284, Some(2))],
+               fragments.iter().map(|moof| (moof.atom_location(), moof.sequence_number())).collect::<Vec<_>>());
+    assert_eq!(2, results.find_all("moof/traf/trun").len());
+    assert!(Parser::from_path("resources/tests/sample.mp4").unwrap().parse().fragments().is_empty());
+  }
+
+  #[test]
+  fn should_read_the_encoder_from_the_metadata() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(Some("Lavf53.29.100"), results.metadata()["©too"].as_text());
+  }
+
+  #[cfg(feature = "flate2")]
+  #[test]
+  fn should_parse_a_gzip_compressed_file_like_the_original() {
+    let compressed = Parser::open_gz("resources/tests/moov.mp4.gz").unwrap().parse();
+    let original = Parser::from_path("resources/tests/moov.mp4").unwrap().parse();
+    assert_eq!(original.to_string(), compressed.to_string());
+    assert!(Parser::open_gz_with_limit("resources/tests/moov.mp4.gz", 1024).is_err());
+  }
+
+  #[cfg(feature = "flate2")]
+  #[test]
+  fn should_parse_a_compressed_movie_like_the_original() {
+    // cmov.mp4 holds moov.mp4 compressed with zlib in a moov/cmov/cmvd.
+    let compressed = Parser::from_path("resources/tests/cmov.mp4").unwrap().parse();
+    let original = Parser::from_path("resources/tests/moov.mp4").unwrap().parse();
+    assert!(compressed.is_clean(), "{:?}", compressed.error());
+    assert_eq!(2, compressed.track_headers().len());
+    assert_eq!(original.track_headers().len(), compressed.track_headers().len());
+    let children = |results: &ParseResults| -> Vec<String> {
+      match results.find("moov") {
+        Some(AtomNodes::Container(moov)) => moov.children().iter().map(|child| child.to_string()).collect(),
+        other => panic!("expected a moov, got {:?}", other.map(AtomNodes::atom_type)),
+      }
+    };
+    assert_eq!(children(&original), children(&compressed));
+    assert_eq!(crate::MovieInfo::from_results(&original).unwrap(), crate::MovieInfo::from_results(&compressed).unwrap());
+    assert!(compressed.find("moov/trak/mdia/hdlr").is_some());
+    assert!(compressed.validate().is_empty(), "{:?}", compressed.validate());
+  }
+
+  #[test]
+  fn should_refuse_to_copy_the_atoms_of_a_compressed_movie_from_the_file() {
+    let path = "resources/tests/cmov.mp4";
+    let results = Parser::from_path(path).unwrap().parse();
+    let mut source = std::fs::File::open(path).unwrap();
+    match results.make_fast_start(&mut source, &mut Vec::new()) {
+      Err(err @ ParseError::EditFailed(_)) => assert_eq!("fast start: the movie is compressed", err.to_string()),
+      other => panic!("expected EditFailed, got {:?}", other),
+    }
+    // The moov itself is in the file, and is copied as it is there.
+    let moov = results.find("moov").unwrap().byte_range();
+    let mut out = Vec::new();
+    assert_eq!(moov.end - moov.start, results.extract_atom("moov", &mut source, &mut out).unwrap());
+    assert_eq!(&std::fs::read(path).unwrap()[moov.start as usize..moov.end as usize], &out[..]);
+    assert_eq!(0, results.statistics().unaccounted_bytes);
+
+    if cfg!(feature = "flate2") {
+      match results.extract_atom("moov/trak", &mut source, &mut Vec::new()) {
+        Err(err @ ParseError::EditFailed(_)) =>
+          assert_eq!("moov/trak is in a compressed movie rather than in the file", err.to_string()),
+        other => panic!("expected EditFailed, got {:?}", other),
+      }
+      assert!(crate::write_node(results.nodes(), &mut source, &mut Vec::new()).is_err());
+      assert!(!results.statistics().counts.contains_key(&FourCC::from("trak")));
+    }
+  }
+
+  #[test]
+  fn should_refuse_movies_compressed_in_an_unsupported_way() {
+    use crate::leaves::UnknownReason;
+    let cmov = |compression: &[u8], strict: bool| {
+      let mut cmvd = 8u32.to_be_bytes().to_vec();
+      cmvd.extend_from_slice(&[0x78, 0x9c, 3, 0, 0, 0, 0, 1]);
+      let cmov = [atom(b"dcom", compression), atom(b"cmvd", &cmvd)].concat();
+      let mut file = ftyp(b"qt  ", &[b"qt  "]);
+      file.extend(atom(b"moov", &atom(b"cmov", &cmov)));
+      let options = ParseOptions { strict, ..Default::default() };
+      Parser::from_bytes_owned(file).unwrap().with_options(options).parse()
+    };
+    // The compressed movie is kept as an atom that failed to parse, and fails the parse only in
+    // strict mode.
+    let failure = |results: &ParseResults| match results.find("moov/cmov") {
+      Some(AtomNodes::Atom(Atoms::UnknownAtom(cmov))) => match cmov.reason() {
+        UnknownReason::ParseFailed(reason) => reason.clone(),
+        other => panic!("expected a cmov that failed to parse, got {:?}", other),
+      },
+      other => panic!("expected an unknown cmov, got {:?}", other.map(AtomNodes::atom_type)),
+    };
+    let results = cmov(b"lzo ", false);
+    assert!(results.error().is_none(), "{:?}", results.error());
+    assert_eq!("cmov at 28 (0x1c): movies compressed with \"lzo \" are not supported", failure(&results));
+    match cmov(b"lzo ", true).error() {
+      Some(ParseError::UnsupportedCompression { offset: 28, .. }) => {}
+      other => panic!("expected UnsupportedCompression, got {:?}", other),
+    }
+    // The zlib stream decompresses to nothing rather than the 8 bytes the cmvd promises.
+    let results = cmov(b"zlib", false);
+    assert_eq!(!cfg!(feature = "flate2"), failure(&results).contains("are not supported"), "{}", failure(&results));
+  }
+
+  #[cfg(feature = "colored")]
+  #[test]
+  fn should_color_atom_types_only_when_asked() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let mut plain = Vec::new();
+    results.write_tree_colored(&mut plain, None, false).unwrap();
+    assert!(!plain.contains(&0x1b));
+    assert_eq!(results.to_string().into_bytes(), plain);
+
+    let mut colored = Vec::new();
+    results.write_tree_colored(&mut colored, None, true).unwrap();
+    let colored = String::from_utf8(colored).unwrap();
+    let moov = colored.lines().find(|line| line.contains("moov")).unwrap();
+    assert!(moov.contains("type: \x1b[1;34mmoov\x1b[0m, size: 1023163"));
+  }
+
+  #[test]
+  fn should_parse_with_the_given_options() {
+    let options = ParseOptions { limits: ParseLimits { max_children_per_container: 3, ..Default::default() },
+                                 ..Default::default() };
+    assert!(!options.skip_sample_tables);
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().with_options(options).parse();
+    let err = results.error().unwrap().to_string();
+    assert_eq!("moov at 32 (0x20): too many children, the limit is 3", err);
+
+    let options = ParseOptions { skip_sample_tables: true, ..Default::default() };
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().with_options(options).parse();
+    let stsz = find_atom(results.nodes(), |atom| match atom { Atoms::Stsz(stsz) => Some(stsz), _ => None }).unwrap();
+    assert_eq!(29182, stsz.sample_count());
+    assert!(stsz.sample_sizes().is_empty());
+  }
+
+  /// `depth` `trak` atoms, each holding the next.
+  fn nested(depth: usize) -> Vec<u8> {
+    (0..depth).fold(Vec::new(), |inner, _| atom(b"trak", &inner))
+  }
+
+  #[test]
+  fn should_stop_at_containers_nested_too_deeply() {
+    let results = Parser::from_bytes_owned(nested(100)).unwrap().parse();
+    match results.error() {
+      Some(ParseError::LimitExceeded { limit: Limit::Depth, max: 32, atom_type, offset }) =>
+        assert_eq!((*atom_type, *offset), (FourCC::from("trak"), 31 * 8)),
+      other => panic!("expected LimitExceeded, got {:?}", other),
+    }
+    assert!(results.warnings().is_empty());
+
+    let config = crate::Config::new("nested.mp4").with_limits(ParseLimits { max_depth: 128, ..Default::default() });
+    let results = Parser::from_bytes_owned(nested(100)).unwrap().with_options(config.options().clone()).parse();
+    assert!(results.error().is_none(), "{:?}", results.error());
+    assert_eq!(100, results.max_depth());
+  }
+
+  #[test]
+  fn should_stop_at_too_many_atoms() {
+    let bytes = [atom(b"moov", &atom(b"free", &[]).repeat(10)), atom(b"free", &[])].concat();
+    let limits = ParseLimits { max_total_atoms: 8, ..Default::default() };
+    let context = ParseContext::default().with_limits(limits);
+    let results = Parser::from_bytes_owned(bytes.clone()).unwrap().with_context(context).parse();
+    let error = results.error().unwrap();
+    assert!(matches!(error, ParseError::LimitExceeded { limit: Limit::TotalAtoms, max: 8, offset: 0, .. }));
+    assert_eq!("moov at 0 (0x0): too many atoms in the file, the limit is 8", error.to_string());
+
+    let limits = ParseLimits { max_total_atoms: 12, ..Default::default() };
+    let mut parser = Parser::from_bytes_owned(bytes).unwrap().with_context(ParseContext::default().with_limits(limits));
+    assert!(parser.parse().error().is_none());
+    assert!(parser.parse().error().is_none(), "each parse counts atoms from zero");
+  }
+
+  #[test]
+  fn should_read_options_from_environment_variables() {
+    let options = ParseOptions::from_vars(|name| match name {
+      "MEDIA_ATOMS_MAX_CHILDREN" => Some("12".to_string()),
+      "MEDIA_ATOMS_SKIP_SAMPLE_TABLES" => Some("true".to_string()),
+      _ => None,
+    });
+    let limits = ParseLimits { max_children_per_container: 12, ..Default::default() };
+    assert_eq!(ParseOptions { limits, skip_sample_tables: true, ..Default::default() }, options);
+    let options = ParseOptions::from_vars(|_| Some("many".to_string()));
+    assert_eq!(ParseOptions::default(), options);
+  }
+
+  fn tkhd(track_id: u32, width: u32, height: u32) -> Vec<u8> {
+    let mut payload = vec![0; 12];
+    payload.extend_from_slice(&track_id.to_be_bytes());
+    payload.extend_from_slice(&[0; 60]);
+    payload.extend_from_slice(&(width << 16).to_be_bytes());
+    payload.extend_from_slice(&(height << 16).to_be_bytes());
+    atom(b"tkhd", &payload)
+  }
+
+  #[test]
+  fn should_prefer_the_clean_aperture_for_the_display_dimensions() {
+    // Anamorphic 720x480 video with square pixel clean aperture of 853x480.
+    let mut clef = vec![0; 4];
+    clef.extend_from_slice(&(853u32 << 16).to_be_bytes());
+    clef.extend_from_slice(&(480u32 << 16).to_be_bytes());
+    let mut anamorphic = tkhd(1, 720, 480);
+    anamorphic.extend(atom(b"tapt", &atom(b"clef", &clef)));
+    let mut tracks = atom(b"trak", &anamorphic);
+    tracks.extend(atom(b"trak", &tkhd(2, 0, 0)));
+    let mut bytes = ftyp(b"qt  ", &[b"qt  "]);
+    bytes.extend(atom(b"moov", &tracks));
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    assert_eq!(Some((853.0, 480.0)), results.display_dimensions(1));
+    assert_eq!(None, results.display_dimensions(2));
+
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(Some((960.0, 540.0)), results.display_dimensions(1));
+    assert_eq!(None, results.display_dimensions(2));
+  }
+
+  #[test]
+  fn should_move_moov_in_front_of_the_media_data() {
+    fn chunk_offsets(results: &ParseResults) -> Vec<u64> {
+      let mut offsets = Vec::new();
+      results.nodes().walk(|node| if let AtomNodes::Atom(Atoms::Stco(stco)) = node {
+        offsets.extend(stco.chunk_offsets().iter().map(|x| *x as u64));
+      });
+      offsets
+    }
+    // Build a file that is not fast start out of the sample, with moov moved to the end:
+    // ftyp, free, mdat, moov.
+    let original = fs::read("resources/tests/sample.mp4").unwrap();
+    let parsed = Parser::from_bytes_owned(original.clone()).unwrap().parse();
+    let top: Vec<&AtomNodes> = parsed.top_level().iter().collect();
+    let (ftyp, moov, free, mdat) = (top[0], top[1], top[2], top[3]);
+    let mut moved_moov = moov.clone();
+    crate::shift_chunk_offsets(&mut moved_moov, 40 - mdat.atom_location() as i64).unwrap();
+    let mut slow = Vec::new();
+    for node in [ftyp, free, mdat, &moved_moov] {
+      crate::write_node(node, &mut Cursor::new(&original[..]), &mut slow).unwrap();
+    }
+    let slow_results = Parser::from_bytes_owned(slow.clone()).unwrap().parse();
+    assert!(!slow_results.is_fast_start());
+
+    let mut fast = Vec::new();
+    let written = slow_results.make_fast_start(&mut Cursor::new(&slow[..]), &mut fast).unwrap();
+    assert_eq!(fast.len() as u64, written);
+    assert_eq!(original.len() - 8, fast.len());
+    let fast_results = Parser::from_bytes_owned(fast.clone()).unwrap().parse();
+    assert!(fast_results.is_fast_start());
+    let types: Vec<&str> = fast_results.top_level().iter().map(|node| node.atom_type()).collect();
+    assert_eq!(vec!["ftyp", "moov", "mdat"], types);
+
+    // Every chunk still starts with the same media data.
+    let before = chunk_offsets(&parsed);
+    let after = chunk_offsets(&fast_results);
+    assert_eq!(before.len(), after.len());
+    for (before, after) in before.iter().zip(after.iter()) {
+      let (before, after) = (*before as usize, *after as usize);
+      assert_eq!(&original[before..before + 16], &fast[after..after + 16]);
+    }
+  }
+
+  #[test]
+  fn should_validate_while_parsing_like_validating_afterwards() {
+    // A udta running past the end of moov, which is left out as slack, an overrun past the end
+    // of the file and a misplaced ftyp.
+    let mut bytes = fs::read("resources/tests/moov.mp4").unwrap();
+    bytes.extend_from_slice(&fs::read("resources/tests/ftyp.mp4").unwrap());
+    bytes.extend_from_slice(&atom(b"free", &[0; 8]));
+    let next_size = bytes.len() - 16;
+    bytes[next_size..next_size + 4].copy_from_slice(&64u32.to_be_bytes());
+    bytes[1023065..1023069].copy_from_slice(&(98u32 + 8).to_be_bytes());
+    let paths = ["resources/tests/sample.mp4", "resources/tests/truncated.mp4"];
+    let files: Vec<Vec<u8>> = paths.iter().map(|path| fs::read(path).unwrap()).chain(std::iter::once(bytes)).collect();
+    let mut counts = Vec::new();
+    for bytes in files {
+      let separate = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+      let (fused, warnings) = Parser::from_bytes_owned(bytes).unwrap().parse_and_validate();
+      assert_eq!(separate.to_string(), fused.to_string());
+      assert_eq!(separate.validate(), warnings);
+      counts.push(warnings.len());
+    }
+    assert_eq!(vec![0, 1, 3], counts);
+  }
+
+  fn warning_messages(results: &ParseResults) -> Vec<String> {
+    results.validate().iter().map(|warning| warning.to_string()).collect()
+  }
+
+  #[test]
+  fn should_measure_how_deeply_atoms_nest() {
+    // moov/trak/mdia/minf/stbl/stsd
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(6, results.max_depth());
+    assert_eq!(5, results.track_node(1).unwrap().depth());
+    assert_eq!(0, ParseResults::default().max_depth());
+  }
+
+  #[test]
+  fn should_check_compatibility_with_a_brand() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert!(results.is_compatible_with("isom"));
+    assert!(!results.is_compatible_with("qt  "));
+    let results = Parser::from_path("resources/tests/moov.mp4").unwrap().parse();
+    assert!(!results.is_compatible_with("isom"));
+  }
+
+  #[test]
+  fn should_describe_a_misplaced_ftyp_with_its_location() {
+    let mut bytes = fs::read("resources/tests/moov.mp4").unwrap();
+    bytes.extend_from_slice(&fs::read("resources/tests/ftyp.mp4").unwrap());
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    match &results.validate()[..] {
+      [ValidationWarning::MisplacedFtyp { location }] => assert_eq!(1023163, *location),
+      other => panic!("unexpected warnings {:?}", other),
+    }
+  }
+
+  #[test]
+  fn should_resolve_the_thumbnail_and_rotation_of_a_heic_image() {
+    let full = |payload: &[u8]| -> Vec<u8> { [&[0u8; 4][..], payload].concat() };
+    let ispe = |width: u32, height: u32| atom(b"ispe", &full(&[width.to_be_bytes(), height.to_be_bytes()].concat()));
+    let mut hdlr = vec![0; 8];
+    hdlr.extend_from_slice(b"pict");
+    hdlr.extend_from_slice(&[0; 13]);
+    // Item 1 is the 4032x3024 primary image, rotated by 90 degrees, and item 2 its 320x240
+    // thumbnail.
+    let mut properties = ispe(4032, 3024);
+    properties.extend(atom(b"irot", &[1]));
+    properties.extend(ispe(320, 240));
+    let mut ipma = full(&2u32.to_be_bytes());
+    ipma.extend_from_slice(&[0, 1, 2, 0x01, 0x82]);
+    ipma.extend_from_slice(&[0, 2, 2, 0x03, 0x82]);
+    let mut iprp = atom(b"ipco", &properties);
+    iprp.extend(atom(b"ipma", &ipma));
+    let mut meta = full(&atom(b"hdlr", &hdlr));
+    meta.extend(atom(b"pitm", &full(&[0, 1])));
+    meta.extend(atom(b"iref", &full(&atom(b"thmb", &[0, 2, 0, 1, 0, 1]))));
+    meta.extend(atom(b"iprp", &iprp));
+    let mut bytes = ftyp(b"heic", &[b"mif1", b"heic"]);
+    bytes.extend(atom(b"meta", &meta));
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+
+    assert_eq!(Some(1), results.primary_item_id());
+    assert_eq!(vec![(2, "thmb".to_string(), vec![1])], results.item_references());
+    assert_eq!(vec![ItemProperty::ImageSpatialExtents { width: 4032, height: 3024 }, ItemProperty::Rotation { degrees: 90 }],
+               results.item_properties(1));
+    assert_eq!(vec![ItemProperty::ImageSpatialExtents { width: 320, height: 240 }, ItemProperty::Rotation { degrees: 90 }],
+               results.item_properties(2));
+    assert!(results.item_properties(3).is_empty());
+    assert!(results.validate().is_empty());
+  }
+
+  #[test]
+  fn should_know_how_many_atoms_are_left_to_iterate() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(results.atom_count(), results.iter().count());
+    let mut iter = results.iter();
+    assert_eq!(Some("ftyp"), iter.next().map(|node| node.atom_type()));
+    assert_eq!(results.atom_count() - 1, iter.size_hint().0);
+    assert!(iter.size_hint().0 > 0);
+    let types: Vec<&str> = iter.take(3).map(|node| node.atom_type()).collect();
+    assert_eq!(vec!["moov", "mvhd", "trak"], types);
+  }
+
+  #[test]
+  fn should_iterate_over_every_atom_of_the_printed_tree() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    // The tree also lists the entries of each stsd and their extension atoms.
+    let description_lines: usize = results.iter().filter_map(|node| match node {
+      AtomNodes::Atom(Atoms::Stsd(stsd)) => Some(stsd.entries().iter().map(|entry| 1 + entry.extensions().len()).sum::<usize>()),
+      _ => None,
+    }).sum();
+    assert_eq!(results.to_string().lines().count(), results.atom_count() + description_lines);
+
+    let moov = match results.top_level().iter().find(|node| node.atom_type() == "moov") {
+      Some(AtomNodes::Container(moov)) => moov,
+      other => panic!("expected moov, got {:?}", other),
+    };
+    assert_eq!(2, moov.descendants().filter(|node| node.atom_type() == "trak").count());
+    assert_eq!(2, moov.descendants().filter(|node| node.atom_type() == "stbl").count());
+    // Everything but ftyp, moov, free and mdat.
+    assert_eq!(results.atom_count() - 4, moov.descendants().len());
+  }
+
+  #[test]
+  fn should_find_atoms_by_path() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let locations = |path: &str| -> Vec<u64> { results.find_all(path).iter().map(|node| node.atom_location()).collect() };
+    assert_eq!(vec![148, 432129], locations("moov/trak"));
+    assert_eq!(vec![441, 432418], locations("moov/trak/mdia/minf/stbl/stsd"));
+    assert_eq!(locations("moov/trak/mdia/minf/stbl/stsd"), locations("stsd"));
+    assert_eq!(vec![1023150], locations("moov/udta/meta/ilst"));
+    assert_eq!(Some(441), results.find("moov/trak/mdia/minf/stbl/stsd").map(|node| node.atom_location()));
+
+    assert!(results.find_all("moov/trak/stsd").is_empty());
+    assert!(results.find_all("trak/mdia").is_empty());
+    assert!(results.find("moov/mvhd/mdia").is_none());
+    assert!(results.find("").is_none());
+    assert!(results.find("moov//trak").is_none());
+    assert!(ParseResults::default().find("moov").is_none());
+  }
+
+  #[test]
+  fn should_parse_a_file_that_starts_with_mdat() {
+    let mut bytes = atom(b"mdat", &[0xaa; 64]);
+    bytes.extend(fs::read("resources/tests/moov.mp4").unwrap());
+    let results = Parser::from_bytes_owned(bytes).unwrap().parse();
+    assert!(results.error().is_none());
+    assert!(!results.has_ftyp());
+    let types: Vec<&str> = results.top_level().iter().map(|node| node.atom_type()).collect();
+    assert_eq!(vec!["mdat", "moov"], types);
+    assert_eq!(vec![ValidationWarning::MissingFtyp { first: "mdat".to_string() }], results.validate());
+    assert!(Parser::from_path("resources/tests/sample.mp4").unwrap().parse().has_ftyp());
+  }
+
+  #[test]
+  fn should_report_the_encoding_tool() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    assert_eq!(Some("Lavf53.29.100".to_string()), results.encoder());
+    let untagged = Parser::from_bytes_owned(ftyp(b"isom", &[b"isom"])).unwrap().parse();
+    assert_eq!(None, untagged.encoder());
+  }
+
+  #[test]
+  fn should_step_through_top_level_atoms() {
+    let mut parser = Parser::from_path("resources/tests/sample.mp4").unwrap();
+    let mut cursor = ParseCursor::default();
+    let mut types = Vec::new();
+    while let Some(header) = parser.parse_step(&mut cursor).unwrap() {
+      types.push(header.atom_type().to_string());
+    }
+    assert_eq!(vec!["ftyp", "moov", "free", "mdat"], types);
+    assert_eq!(25774283, cursor.offset());
+
+    let mut resumed = ParseCursor::new(32);
+    let header = parser.parse_step(&mut resumed).unwrap().unwrap();
+    assert_eq!("moov", header.atom_type());
+    assert_eq!(32 + header.atom_size(), resumed.offset());
+  }
+
+  #[test]
+  fn should_extract_the_bytes_of_an_atom() {
+    let bytes = fs::read("resources/tests/sample.mp4").unwrap();
+    let results = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+    let mut out = Vec::new();
+    assert_eq!(32, results.extract_atom("ftyp", &mut Cursor::new(&bytes[..]), &mut out).unwrap());
+    assert_eq!(&bytes[..32], &out[..]);
+    assert_eq!(0..32, results.find("ftyp").unwrap().byte_range());
+
+    let mut out = Vec::new();
+    let elst = results.find("moov/trak/edts/elst").unwrap().byte_range();
+    results.extract_atom("moov/trak/edts/elst", &mut Cursor::new(&bytes[..]), &mut out).unwrap();
+    assert_eq!(&bytes[elst.start as usize..elst.end as usize], &out[..]);
+
+    let mut out = Vec::new();
+    assert_eq!(bytes.len() as u64, results.extract_atom("/", &mut Cursor::new(&bytes[..]), &mut out).unwrap());
+    assert_eq!(bytes, out);
+
+    let err = results.extract_atom("moov/trak/mdia/tkhd", &mut Cursor::new(&bytes[..]), &mut Vec::new()).unwrap_err();
+    assert_eq!("there is no atom at moov/trak/mdia/tkhd", err.to_string());
+  }
+
+  #[test]
+  fn should_extract_an_atom_with_a_64_bit_size() {
+    let mut bytes = ftyp(b"isom", &[b"isom"]);
+    bytes.extend_from_slice(&[0, 0, 0, 1]);
+    bytes.extend_from_slice(b"free");
+    bytes.extend_from_slice(&24u64.to_be_bytes());
+    bytes.extend_from_slice(&[7; 8]);
+    let results = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+    let mut out = Vec::new();
+    assert_eq!(24, results.extract_atom("free", &mut Cursor::new(&bytes[..]), &mut out).unwrap());
+    assert_eq!(&bytes[bytes.len() - 24..], &out[..]);
+  }
+
+  #[test]
+  fn should_copy_what_there_is_of_an_atom_that_runs_past_the_end_of_the_file() {
+    let bytes = fs::read("resources/tests/truncated.mp4").unwrap();
+    let results = Parser::from_bytes_owned(bytes.clone()).unwrap().parse();
+    let mut out = Vec::new();
+    match results.extract_atom("free", &mut Cursor::new(&bytes[..]), &mut out) {
+      Err(ParseError::TruncatedAtom { offset: 32, expected: 64, got: 16, .. }) => (),
+      other => panic!("expected a truncated atom, got {:?}", other),
+    }
+    assert_eq!(&bytes[32..], &out[..]);
+  }
+
+  /// A movie of `tracks` tracks whose second track has an edit list atom too small for its own
+  /// header, which leaves the `edts` holding it out of the tree.
+  fn tracks(tracks: usize) -> Vec<u8> {
+    let mut moov = Vec::new();
+    for track in 0..tracks {
+      let stco = atom(b"stco", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, track as u8]);
+      let mut trak = atom(b"mdia", &atom(b"minf", &atom(b"stbl", &stco)));
+      if track == 1 {
+        trak.extend(atom(b"edts", &[0, 0, 0, 4, b'e', b'l', b's', b't']));
+      }
+      moov.extend(atom(b"trak", &trak));
+    }
+    let mut bytes = ftyp(b"isom", &[b"isom"]);
+    bytes.extend(atom(b"moov", &moov));
+    bytes
+  }
+
+  #[test]
+  fn should_parse_the_same_tree_in_parallel() {
+    let path = std::env::temp_dir().join(format!("media_atoms_parallel_{}.mp4", std::process::id()));
+    fs::write(&path, tracks(5)).unwrap();
+    let sequential = Parser::from_path(&path).unwrap().parse();
+    let parallel = Parser::from_path(&path).unwrap().with_threads(3).parse_parallel();
+    assert_eq!(1, sequential.warnings().len());
+    assert_eq!(sequential.to_string(), parallel.to_string());
+    assert_eq!(sequential.nodes().structural_hash(), parallel.nodes().structural_hash());
+    let warnings = |results: &ParseResults| results.warnings().iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert_eq!(warnings(&sequential), warnings(&parallel));
+
+    let limits = ParseLimits { max_total_atoms: 12, ..Default::default() };
+    let limited = Parser::from_path(&path).unwrap().with_threads(3)
+      .with_context(ParseContext::default().with_limits(limits)).parse_parallel();
+    assert!(matches!(limited.error(), Some(ParseError::LimitExceeded { limit: Limit::TotalAtoms, .. })));
+    fs::remove_file(&path).unwrap();
+
+    let sample = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let parallel = Parser::from_path("resources/tests/sample.mp4").unwrap().with_threads(2).parse_parallel();
+    assert_eq!(sample.to_string(), parallel.to_string());
+  }
+
+  #[test]
+  fn should_give_up_on_a_file_of_an_unsupported_brand() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().with_unsupported_brands(&["qt", "isom"]).parse();
+    match results.error() {
+      Some(err @ ParseError::UnsupportedBrand { offset: 0, .. }) =>
+        assert_eq!("ftyp at 0 (0x0): the major brand \"isom\" is not supported", err.to_string()),
+      other => panic!("expected UnsupportedBrand, got {:?}", other),
+    }
+    let mut parser = Parser::from_path("resources/tests/sample.mp4").unwrap().with_unsupported_brands(&["qt", "heic"]);
+    assert!(parser.parse().is_clean());
+    assert!(parser.parse_with_limit(32).error().is_none());
+    let mut parser = Parser::from_path("resources/tests/sample.mp4").unwrap().with_unsupported_brands(&["isom"]);
+    assert!(matches!(parser.parse_with_limit(32).error(), Some(ParseError::UnsupportedBrand { .. })));
   }
 }
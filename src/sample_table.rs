@@ -0,0 +1,323 @@
+use super::atoms::{AtomLike, AtomNodes, ChunkOffsetTable, FourCC, leaves::{Atoms, TimeToSampleEntry}};
+use super::parse_state::{find_atom, ParseError, Result};
+
+/// One sample of a track, as yielded by [`SampleTable::samples`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SampleInfo {
+  /// The position of the sample in the track, counting from 0.
+  pub index: u32,
+  /// The absolute file offset of the sample's data.
+  pub offset: u64,
+  pub size: u32,
+  /// When the sample is decoded, in media time scale units.
+  pub decode_time: u64,
+  /// Whether the sample can be decoded without the ones before it.
+  pub is_sync: bool,
+}
+
+/// Per-track view of the sample table atoms found under `stbl`. Chunk offsets are held as 64-bit
+/// values no matter whether they came from an `stco` or a `co64` atom. Samples are counted from
+/// 0, unlike the sample numbers of the atoms, which count from 1.
+#[derive(Debug, Default, Clone)]
+pub struct SampleTable {
+  chunk_offsets: Vec<u64>,
+  /// The index of the first sample of each chunk. A chunk without samples starts where the next
+  /// one does.
+  chunk_first_samples: Vec<u32>,
+  /// The size of every sample, or 0 when each has its own in `sample_sizes`.
+  sample_size: u32,
+  sample_sizes: Vec<u32>,
+  sample_count: u32,
+  time_to_sample: Vec<TimeToSampleEntry>,
+  /// The sample numbers of the sync samples, `None` when every sample is one.
+  sync_samples: Option<Vec<u32>>,
+}
+
+impl SampleTable {
+  /// A table of just the chunk offsets, without any samples.
+  pub fn new(chunk_offsets: &dyn ChunkOffsetTable) -> SampleTable {
+    SampleTable { chunk_offsets: chunk_offsets.offsets(), ..Default::default() }
+  }
+  /// Gathers the sample table atoms of the `stbl` container `stbl`. Fails with
+  /// [`ParseError::MissingAtom`] without a chunk offset, sample-to-chunk, sample size or
+  /// time-to-sample table, and with [`ParseError::InconsistentSampleTable`] when they disagree
+  /// about the samples, including when the sample sizes were skipped while parsing.
+  pub fn from_stbl(stbl: &AtomNodes) -> Result<SampleTable> {
+    let missing = |atom_type| ParseError::MissingAtom { atom_type: FourCC::from(atom_type), needed_for: "a sample table" };
+    let inconsistent = |reason: String| ParseError::InconsistentSampleTable { offset: stbl.atom_location(), reason };
+    let stco = find_atom(stbl, |atom| match atom { Atoms::Stco(stco) => Some(stco), _ => None });
+    let co64 = find_atom(stbl, |atom| match atom { Atoms::Co64(co64) => Some(co64), _ => None });
+    let chunk_offsets = match (stco, co64) {
+      (Some(stco), _) => stco.offsets(),
+      (None, Some(co64)) => co64.offsets(),
+      (None, None) => return Err(missing("stco")),
+    };
+    let stsc = find_atom(stbl, |atom| match atom { Atoms::Stsc(stsc) => Some(stsc), _ => None }).ok_or_else(|| missing("stsc"))?;
+    let stsz = find_atom(stbl, |atom| match atom { Atoms::Stsz(stsz) => Some(stsz), _ => None }).ok_or_else(|| missing("stsz"))?;
+    let stts = find_atom(stbl, |atom| match atom { Atoms::Stts(stts) => Some(stts), _ => None }).ok_or_else(|| missing("stts"))?;
+    let stss = find_atom(stbl, |atom| match atom { Atoms::Stss(stss) => Some(stss), _ => None });
+
+    let sample_count = stsz.sample_count();
+    if stsz.sample_size() == 0 && stsz.sample_sizes().len() != sample_count as usize {
+      return Err(inconsistent(format!("stsz has {} sizes for {} samples", stsz.sample_sizes().len(), sample_count)));
+    }
+    if stts.sample_count() != sample_count as u64 {
+      return Err(inconsistent(format!("stsz has {} samples but stts has {}", sample_count, stts.sample_count())));
+    }
+
+    // Each run of the sample-to-chunk table covers the chunks up to the first chunk of the next
+    // run, and the last run covers the rest of the chunks. Chunks are numbered from 1.
+    let chunk_count = chunk_offsets.len() as u64;
+    let entries = stsc.entries();
+    let mut chunk_first_samples = Vec::with_capacity(chunk_offsets.len());
+    let mut mapped: u64 = 0;
+    for (i, entry) in entries.iter().enumerate() {
+      let first_chunk = entry.first_chunk as u64;
+      let previous = if i == 0 { 0 } else { entries[i - 1].first_chunk as u64 };
+      if (i == 0 && first_chunk != 1) || first_chunk <= previous {
+        return Err(inconsistent(format!("stsc run {} starts at chunk {}", i + 1, first_chunk)));
+      }
+      let next_chunk = entries.get(i + 1).map_or(chunk_count + 1, |next| next.first_chunk as u64);
+      if next_chunk > chunk_count + 1 {
+        return Err(inconsistent(format!("stsc maps chunk {} but there are {} chunks", next_chunk - 1, chunk_count)));
+      }
+      for _ in first_chunk..next_chunk {
+        chunk_first_samples.push(mapped.min(u32::MAX as u64) as u32);
+        mapped += entry.samples_per_chunk as u64;
+      }
+    }
+    if (chunk_first_samples.len() as u64) < chunk_count {
+      return Err(inconsistent(format!("stsc maps none of the {} chunks", chunk_count)));
+    }
+    if mapped != sample_count as u64 {
+      return Err(inconsistent(format!("stsc maps {} samples but stsz has {}", mapped, sample_count)));
+    }
+
+    Ok(SampleTable {
+      chunk_offsets,
+      chunk_first_samples,
+      sample_size: stsz.sample_size(),
+      sample_sizes: stsz.sample_sizes().to_vec(),
+      sample_count,
+      time_to_sample: stts.entries().clone(),
+      sync_samples: stss.map(|stss| stss.sync_samples().to_vec()),
+    })
+  }
+  pub fn chunk_count(&self) -> usize { self.chunk_offsets.len() }
+  pub fn chunk_offsets(&self) -> &[u64] { &self.chunk_offsets }
+  /// File offset of the chunk at `index`, counting from 0.
+  pub fn chunk_offset(&self, index: usize) -> Option<u64> { self.chunk_offsets.get(index).copied() }
+  /// The number of samples, 0 for a table of just chunk offsets.
+  pub fn sample_count(&self) -> u32 { self.sample_count }
+  /// The size in bytes of the sample at `index`.
+  pub fn sample_size(&self, index: u32) -> Option<u32> {
+    match self.sample_size {
+      _ if index >= self.sample_count => None,
+      0 => self.sample_sizes.get(index as usize).copied(),
+      size => Some(size),
+    }
+  }
+  /// The absolute file offset of the data of the sample at `index`: the offset of its chunk and
+  /// the sizes of the samples before it in the chunk.
+  pub fn sample_file_offset(&self, index: u32) -> Option<u64> {
+    if index >= self.sample_count {
+      return None;
+    }
+    // The last chunk that starts at or before the sample, which skips chunks without samples.
+    let chunk = self.chunk_first_samples.partition_point(|first| *first <= index).checked_sub(1)?;
+    let first = self.chunk_first_samples[chunk];
+    let before: u64 = match self.sample_size {
+      0 => self.sample_sizes[first as usize..index as usize].iter().map(|x| *x as u64).sum(),
+      size => size as u64 * (index - first) as u64,
+    };
+    Some(self.chunk_offsets[chunk] + before)
+  }
+  /// When the sample at `index` is decoded, the durations of the samples before it added up, in
+  /// media time scale units.
+  pub fn sample_decode_time(&self, index: u32) -> Option<u64> {
+    if index >= self.sample_count {
+      return None;
+    }
+    let mut remaining = index as u64;
+    let mut time = 0;
+    for entry in &self.time_to_sample {
+      let count = remaining.min(entry.sample_count as u64);
+      time += count * entry.sample_delta as u64;
+      remaining -= count;
+      if remaining == 0 {
+        break;
+      }
+    }
+    Some(time)
+  }
+  /// Whether the sample at `index` can be decoded without the ones before it. Every sample of a
+  /// track without a sync sample table is.
+  pub fn is_sync_sample(&self, index: u32) -> bool {
+    index < self.sample_count
+      && self.sync_samples.as_ref().is_none_or(|sync| sync.binary_search(&(index + 1)).is_ok())
+  }
+  /// Every sample in decoding order.
+  pub fn samples(&self) -> Samples<'_> {
+    Samples { table: self, index: 0, chunk: 0, offset: self.chunk_offset(0).unwrap_or_default(),
+              durations: &self.time_to_sample, duration_index: 0, decode_time: 0 }
+  }
+}
+
+/// Steps through the samples of a [`SampleTable`], keeping track of the chunk and decode time
+/// rather than looking each sample up.
+#[derive(Debug, Clone)]
+pub struct Samples<'a> {
+  table: &'a SampleTable,
+  index: u32,
+  chunk: usize,
+  /// The file offset of the next sample.
+  offset: u64,
+  durations: &'a [TimeToSampleEntry],
+  /// How many samples of the first run of `durations` have been yielded.
+  duration_index: u32,
+  decode_time: u64,
+}
+
+impl Iterator for Samples<'_> {
+  type Item = SampleInfo;
+
+  fn next(&mut self) -> Option<SampleInfo> {
+    let size = self.table.sample_size(self.index)?;
+    let starts = &self.table.chunk_first_samples;
+    while self.chunk + 1 < starts.len() && starts[self.chunk + 1] <= self.index {
+      self.chunk += 1;
+      self.offset = self.table.chunk_offsets[self.chunk];
+    }
+    while self.durations.first().is_some_and(|entry| self.duration_index >= entry.sample_count) {
+      self.durations = &self.durations[1..];
+      self.duration_index = 0;
+    }
+    let sample = SampleInfo { index: self.index, offset: self.offset, size, decode_time: self.decode_time,
+                              is_sync: self.table.is_sync_sample(self.index) };
+    self.index += 1;
+    self.offset += size as u64;
+    self.duration_index += 1;
+    self.decode_time += self.durations.first().map_or(0, |entry| entry.sample_delta as u64);
+    Some(sample)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let remaining = (self.table.sample_count - self.index.min(self.table.sample_count)) as usize;
+    (remaining, Some(remaining))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::atoms::{AtomHeader, leaves::Co64Atom};
+  use crate::parse_state::ParseContext;
+  use std::io::Cursor;
+
+  #[test]
+  fn should_build_a_sample_table_from_a_co64_atom() {
+    let offsets: [u64; 2] = [0x1_0000_0000, 0x2_0000_0010];
+    let mut bytes = vec![0, 0, 0, 32];
+    bytes.extend_from_slice(b"co64");
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2]);
+    for offset in &offsets {
+      bytes.extend_from_slice(&offset.to_be_bytes());
+    }
+    let mut file = Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    let co64 = Co64Atom::new(header, &mut file).unwrap();
+    let table = SampleTable::new(&co64);
+    assert_eq!(2, table.chunk_count());
+    assert_eq!(Some(0x1_0000_0000), table.chunk_offset(0));
+    assert_eq!(Some(0x2_0000_0010), table.chunk_offset(1));
+    assert_eq!(None, table.chunk_offset(2));
+    assert_eq!(0, table.sample_count());
+    assert_eq!(None, table.sample_file_offset(0));
+  }
+
+  fn atom(atom_type: &[u8; 4], payload: &[u32]) -> Vec<u8> {
+    let mut bytes = ((payload.len() * 4 + 8) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(atom_type);
+    bytes.extend(payload.iter().flat_map(|x| x.to_be_bytes()));
+    bytes
+  }
+
+  fn stbl(children: &[Vec<u8>]) -> AtomNodes {
+    let payload: Vec<u8> = children.concat();
+    let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(b"stbl");
+    bytes.extend_from_slice(&payload);
+    let mut file = Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap()
+  }
+
+  /// 7 samples in 4 chunks of 2, none, 3 and 2 samples, a run of chunks each.
+  fn chunked(stts: &[u32]) -> AtomNodes {
+    stbl(&[
+      atom(b"stts", stts),
+      atom(b"stsc", &[0, 4, 1, 2, 1, 2, 0, 1, 3, 3, 1, 4, 2, 1]),
+      atom(b"stsz", &[0, 0, 7, 10, 11, 12, 13, 14, 15, 16]),
+      atom(b"stco", &[0, 4, 1000, 2000, 3000, 4000]),
+      atom(b"stss", &[0, 2, 1, 5]),
+    ])
+  }
+
+  #[test]
+  fn should_find_the_samples_of_runs_of_chunks() {
+    let table = SampleTable::from_stbl(&chunked(&[0, 2, 4, 100, 3, 200])).unwrap();
+    assert_eq!(7, table.sample_count());
+    let offsets: Vec<Option<u64>> = (0..8).map(|x| table.sample_file_offset(x)).collect();
+    assert_eq!(vec![Some(1000), Some(1010), Some(3000), Some(3012), Some(3025), Some(4000), Some(4015), None], offsets);
+    assert_eq!(Some(12), table.sample_size(2));
+    assert_eq!(None, table.sample_size(7));
+    let times: Vec<Option<u64>> = (0..8).map(|x| table.sample_decode_time(x)).collect();
+    assert_eq!(vec![Some(0), Some(100), Some(200), Some(300), Some(400), Some(600), Some(800), None], times);
+    assert!(table.is_sync_sample(0) && table.is_sync_sample(4));
+    assert!(!table.is_sync_sample(1) && !table.is_sync_sample(7));
+
+    let samples: Vec<SampleInfo> = table.samples().collect();
+    assert_eq!(7, samples.len());
+    for sample in &samples {
+      assert_eq!(table.sample_file_offset(sample.index), Some(sample.offset));
+      assert_eq!(table.sample_size(sample.index), Some(sample.size));
+      assert_eq!(table.sample_decode_time(sample.index), Some(sample.decode_time));
+      assert_eq!(table.is_sync_sample(sample.index), sample.is_sync);
+    }
+  }
+
+  #[test]
+  fn should_report_sample_tables_that_disagree() {
+    let err = SampleTable::from_stbl(&chunked(&[0, 1, 6, 100])).unwrap_err();
+    assert!(matches!(err, ParseError::InconsistentSampleTable { offset: 0, .. }), "{:?}", err);
+    assert_eq!("stbl at 0 (0x0): inconsistent sample tables: stsz has 7 samples but stts has 6", err.to_string());
+
+    let too_many_chunks = stbl(&[
+      atom(b"stts", &[0, 1, 2, 1]),
+      atom(b"stsc", &[0, 2, 1, 1, 1, 3, 1, 1]),
+      atom(b"stsz", &[0, 5, 2]),
+      atom(b"stco", &[0, 1, 1000]),
+    ]);
+    let err = SampleTable::from_stbl(&too_many_chunks).unwrap_err();
+    assert_eq!("stbl at 0 (0x0): inconsistent sample tables: stsc maps chunk 2 but there are 1 chunks", err.to_string());
+
+    let no_sizes = stbl(&[atom(b"stco", &[0, 0])]);
+    assert!(matches!(SampleTable::from_stbl(&no_sizes), Err(ParseError::MissingAtom { atom_type, .. }) if atom_type == "stsc"));
+  }
+
+  #[test]
+  fn should_find_the_first_and_last_samples_of_the_video_track() {
+    let results = crate::Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let stbl = results.find("moov/trak/mdia/minf/stbl").unwrap();
+    let table = SampleTable::from_stbl(stbl).unwrap();
+    assert_eq!(29182, table.sample_count());
+    assert_eq!(Some(1023211), table.sample_file_offset(0));
+    assert_eq!(Some(835), table.sample_size(0));
+    assert_eq!(Some(0), table.sample_decode_time(0));
+    assert!(table.is_sync_sample(0));
+    let last = table.samples().last().unwrap();
+    assert_eq!(SampleInfo { index: 29181, offset: 25774203, size: 26, decode_time: 29181 * 1001, is_sync: false }, last);
+    assert_eq!(Some(last.offset), table.sample_file_offset(29181));
+    assert!(table.is_sync_sample(29031));
+  }
+}
@@ -0,0 +1,160 @@
+use std::io::SeekFrom;
+
+use super::atoms::{AtomHeader, AtomLike, ReadSeek};
+use super::parse_state::{ParseError, Result};
+
+/// The containers [`HeaderIter`] descends into. Every other atom, `mdat` included, is skipped
+/// without reading its payload.
+const SCANNED_CONTAINERS: [&str; 7] = ["moov", "trak", "mdia", "minf", "stbl", "udta", "edts"];
+
+/// Walks the headers of a file in document order without parsing any atom, see
+/// [`crate::Parser::headers`]. Each header comes with its depth, 0 for a top-level atom.
+///
+/// The payloads of leaves are seeked over rather than read, so a scan costs one small read per
+/// atom. The first error ends the scan.
+pub struct HeaderIter<'a> {
+  file: &'a mut dyn ReadSeek,
+  /// Where the next header starts.
+  offset: u64,
+  /// Where the file is, once the scan has started reading.
+  position: Option<u64>,
+  /// The ends of the file and of the containers the next header is in, innermost last.
+  ends: Vec<u64>,
+  done: bool,
+}
+
+impl<'a> HeaderIter<'a> {
+  pub(crate) fn new(file: &'a mut dyn ReadSeek, file_size: u64) -> HeaderIter<'a> {
+    let mut ends = Vec::with_capacity(8);
+    ends.push(file_size);
+    HeaderIter { file, offset: 0, position: None, ends, done: false }
+  }
+
+  fn read_header(&mut self) -> Result<Option<(usize, AtomHeader)>> {
+    while let Some(&end) = self.ends.last() {
+      // A container can end with a few bytes of padding that are too short to be an atom.
+      let slack = if self.ends.len() > 1 { 8 } else { 1 };
+      if self.offset.saturating_add(slack) <= end {
+        break;
+      }
+      self.offset = self.offset.max(end);
+      self.ends.pop();
+    }
+    let end = match self.ends.last() {
+      Some(&end) => end,
+      None => return Ok(None),
+    };
+    match self.position {
+      Some(position) if position == self.offset => (),
+      Some(position) => { self.file.seek(SeekFrom::Current((self.offset - position) as i64))?; }
+      None => { self.file.seek(SeekFrom::Start(self.offset))?; }
+    }
+    let header = AtomHeader::new_within(self.file, Some(end))?;
+    let next = header.end_location()?;
+    if next <= self.offset {
+      return Err(ParseError::parse_failed(&header, format!("size {} does not advance", header.atom_size())));
+    }
+    let depth = self.ends.len() - 1;
+    let children = self.offset + header.header_size() as u64;
+    self.position = Some(children);
+    if SCANNED_CONTAINERS.contains(&header.atom_type()) {
+      self.offset = children;
+      self.ends.push(next);
+    } else {
+      self.offset = next;
+    }
+    Ok(Some((depth, header)))
+  }
+}
+
+impl Iterator for HeaderIter<'_> {
+  type Item = Result<(usize, AtomHeader)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let header = self.read_header().transpose();
+    self.done = !matches!(header, Some(Ok(_)));
+    header
+  }
+}
+
+impl std::iter::FusedIterator for HeaderIter<'_> {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{AtomNodes, Container, Parser};
+
+  fn scan(file: &mut dyn ReadSeek) -> Vec<Result<(usize, AtomHeader)>> {
+    let file_size = file.seek(SeekFrom::End(0)).unwrap();
+    HeaderIter::new(file, file_size).collect()
+  }
+
+  #[test]
+  fn should_scan_the_same_atoms_as_a_full_parse() {
+    let mut parser = Parser::from_path("resources/tests/sample.mp4").unwrap();
+    let results = parser.parse();
+    let mut expected = Vec::new();
+    fn visit(node: &AtomNodes, depth: usize, expected: &mut Vec<(usize, String)>) {
+      expected.push((depth, node.atom_type().to_string()));
+      if let AtomNodes::Container(container) = node {
+        if SCANNED_CONTAINERS.contains(&node.atom_type()) {
+          for child in container.children() {
+            visit(child, depth + 1, expected);
+          }
+        }
+      }
+    }
+    if let AtomNodes::Container(root) = results.nodes() {
+      for node in root.children() {
+        visit(node, 0, &mut expected);
+      }
+    }
+    let scanned: Vec<(usize, String)> = parser.headers()
+      .map(|header| header.map(|(depth, header)| (depth, header.atom_type().to_string())).unwrap())
+      .collect();
+    assert_eq!(expected, scanned);
+    assert_eq!(Some(&(0, "mdat".to_string())), scanned.last());
+  }
+
+  #[test]
+  fn should_give_the_headers_of_the_atoms() {
+    let mut parser = Parser::from_path("resources/tests/gopro_udta.mp4").unwrap();
+    let headers: Vec<String> = parser.headers().map(|header| {
+      let (depth, header) = header.unwrap();
+      format!("{} {}", depth, header)
+    }).collect();
+    assert_eq!(vec!["0 type: ftyp, size: 24, location: 0",
+                    "0 type: moov, size: 116, location: 24",
+                    "1 type: udta, size: 108, location: 32",
+                    "2 type: FIRM, size: 24, location: 40",
+                    "2 type: LENS, size: 24, location: 64",
+                    "2 type: CAME, size: 24, location: 88",
+                    "2 type: GPMF, size: 28, location: 112"], headers);
+  }
+
+  #[test]
+  fn should_stop_at_trailing_junk() {
+    let mut data = std::fs::read("resources/tests/gopro_udta.mp4").unwrap();
+    data.extend_from_slice(&[0; 16]);
+    let headers = scan(&mut std::io::Cursor::new(data.clone()));
+    assert_eq!(8, headers.len());
+    assert!(matches!(headers.last(), Some(Err(ParseError::Misaligned { offset: 140, .. }))));
+
+    data.truncate(143);
+    let headers = scan(&mut std::io::Cursor::new(data));
+    assert!(matches!(headers.last(), Some(Err(ParseError::TruncatedHeader { offset: 140, expected: 8, got: 3 }))));
+
+    // An atom of size 0 inside a container takes up the rest of it rather than nothing.
+    let mut data = b"\0\0\0\x18udta\0\0\0\0free".to_vec();
+    data.extend_from_slice(&[0; 8]);
+    let headers: Vec<(usize, String)> = scan(&mut std::io::Cursor::new(data)).into_iter()
+      .map(|header| header.map(|(depth, header)| (depth, header.to_string())).unwrap())
+      .collect();
+    assert_eq!(vec![(0, "type: udta, size: 24, location: 0".to_string()),
+                    (1, "type: free, size: 16, location: 8".to_string())], headers);
+    assert!(scan(&mut std::io::Cursor::new(Vec::new())).is_empty());
+  }
+}
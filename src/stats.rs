@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fmt;
+use super::atoms::{AtomLike, AtomNodes, Container, FourCC};
+use super::atoms::containers::ContainerAtoms;
+use super::atoms::leaves::Atoms;
+use super::parse_state::ParseResults;
+
+/// How the atoms of a file are laid out, for checking its structure at a glance, see
+/// [`ParseResults::statistics`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileStats {
+  /// The size of the file the results were parsed from.
+  pub file_size: u64,
+  /// How many atoms there are of each type, and how many bytes they take up. The bytes of a
+  /// container are only those that none of its children cover, such as its header, so the bytes
+  /// of every type and the slack bytes add up to the bytes the top-level atoms cover.
+  pub counts: HashMap<FourCC, (u32, u64)>,
+  /// The bytes at the ends of containers that can not hold an atom, see [`Atoms::Slack`].
+  pub slack_bytes: u64,
+  /// Whether the `moov` comes before the `mdat`, see [`ParseResults::is_fast_start`].
+  pub is_fast_start: bool,
+  /// The bytes of the file that no top-level atom covers, in gaps between atoms or at the end.
+  pub unaccounted_bytes: u64,
+  /// The type and size of the biggest atom, at any depth.
+  pub largest_atom: (FourCC, u64),
+  /// The type and location of every atom that starts before its previous sibling ends. The bytes
+  /// they share are counted once, for the earlier atom.
+  pub overlapping_atoms: Vec<(FourCC, u64)>,
+}
+
+impl FileStats {
+  pub fn from_results(results: &ParseResults) -> FileStats {
+    let mut stats = FileStats { is_fast_start: results.is_fast_start(), ..Default::default() };
+    if let Some(AtomNodes::Container(root)) = results.root() {
+      stats.file_size = root.atom_size();
+      let covered = stats.count_siblings(root.children(), 0..root.atom_size());
+      stats.unaccounted_bytes = stats.file_size - covered;
+    }
+    stats
+  }
+  /// Counts `nodes` and everything below them, returning how many bytes of `range` they cover.
+  fn count_siblings(&mut self, nodes: &[AtomNodes], range: std::ops::Range<u64>) -> u64 {
+    let mut covered = 0;
+    let mut end = range.start;
+    for node in nodes {
+      let node_range = node.byte_range();
+      if node_range.start < end {
+        self.overlapping_atoms.push((FourCC::from(node.atom_type()), node.atom_location()));
+      }
+      let start = node_range.start.max(end).min(range.end);
+      let node_end = node_range.end.min(range.end).max(start);
+      let children = match node {
+        // The children of a compressed movie lie in the decompressed movie rather than the file.
+        AtomNodes::Container(ContainerAtoms::Moov(moov)) if moov.decompressed().is_some() => 0,
+        AtomNodes::Container(container) => self.count_siblings(container.children(), start..node_end),
+        AtomNodes::Atom(_) => 0,
+      };
+      self.count(node, node_end - start - children);
+      covered += node_end - start;
+      end = end.max(node_end);
+    }
+    covered
+  }
+  fn count(&mut self, node: &AtomNodes, bytes: u64) {
+    if let AtomNodes::Atom(Atoms::Slack { .. }) = node {
+      self.slack_bytes += bytes;
+      return;
+    }
+    let four_cc = FourCC::from(node.atom_type());
+    let (count, total) = self.counts.entry(four_cc).or_default();
+    *count += 1;
+    *total += bytes;
+    if node.atom_size() > self.largest_atom.1 {
+      self.largest_atom = (four_cc, node.atom_size());
+    }
+  }
+  /// The share of the file the top-level atoms cover, as a percentage.
+  pub fn coverage(&self) -> f64 {
+    if self.file_size == 0 {
+      return 0.0;
+    }
+    (self.file_size - self.unaccounted_bytes) as f64 * 100.0 / self.file_size as f64
+  }
+}
+
+impl fmt::Display for FileStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut rows: Vec<_> = self.counts.iter().collect();
+    rows.sort_by(|(a, (_, a_bytes)), (b, (_, b_bytes))| b_bytes.cmp(a_bytes).then_with(|| a.as_str().cmp(b.as_str())));
+    writeln!(f, "{:<6}{:>8}{:>14}", "type", "count", "bytes")?;
+    for (four_cc, (count, bytes)) in rows {
+      writeln!(f, "{:<6}{:>8}{:>14}", four_cc.as_str(), count, bytes)?;
+    }
+    writeln!(f, "file size: {}, covered: {:.1}%, unaccounted: {}", self.file_size, self.coverage(),
+             self.unaccounted_bytes)?;
+    if self.slack_bytes > 0 {
+      writeln!(f, "slack: {} bytes", self.slack_bytes)?;
+    }
+    writeln!(f, "largest atom: {}, {} bytes", self.largest_atom.0, self.largest_atom.1)?;
+    writeln!(f, "fast start: {}", if self.is_fast_start { "yes" } else { "no" })?;
+    for (four_cc, location) in &self.overlapping_atoms {
+      writeln!(f, "overlapping: {} at {}", four_cc, location)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Parser;
+
+  fn atom(atom_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(atom_type.as_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+  }
+
+  fn accounted_bytes(stats: &FileStats) -> u64 {
+    stats.counts.values().map(|(_, bytes)| bytes).sum()
+  }
+
+  #[test]
+  fn should_count_the_atoms_of_a_fast_start_file() {
+    let stats = Parser::from_path("resources/tests/sample.mp4").unwrap().parse().statistics();
+    assert!(stats.is_fast_start);
+    assert_eq!(25774283, stats.file_size);
+    assert_eq!(0, stats.unaccounted_bytes);
+    assert_eq!(stats.file_size, accounted_bytes(&stats));
+    assert_eq!(Some(&(1, 32)), stats.counts.get(&FourCC::from("ftyp")));
+    assert_eq!(Some(&(1, 8)), stats.counts.get(&FourCC::from("moov")));
+    assert_eq!(Some(&(2, 16)), stats.counts.get(&FourCC::from("trak")));
+    assert_eq!(Some(&(2, 184)), stats.counts.get(&FourCC::from("tkhd")));
+    assert_eq!((FourCC::from("mdat"), 24751080), stats.largest_atom);
+    assert!(stats.overlapping_atoms.is_empty());
+    assert_eq!(100.0, stats.coverage());
+    let table = stats.to_string();
+    assert!(table.starts_with("type     count         bytes\nmdat         1      24751080\n"), "{}", table);
+    assert!(table.ends_with("largest atom: mdat, 24751080 bytes\nfast start: yes\n"), "{}", table);
+  }
+
+  #[test]
+  fn should_count_the_bytes_left_out_of_a_movie_at_the_end() {
+    let mut file = atom("ftyp", b"isom\0\0\0\0");
+    file.extend(atom("mdat", &[0; 100]));
+    file.extend(atom("moov", &atom("free", &[0; 8])));
+    let stats = Parser::from_bytes_owned(file.clone()).unwrap().parse().statistics();
+    assert!(!stats.is_fast_start);
+    assert_eq!((0, 148), (stats.unaccounted_bytes, accounted_bytes(&stats)));
+    assert_eq!(Some(&(1, 8)), stats.counts.get(&FourCC::from("moov")));
+    assert_eq!(Some(&(1, 16)), stats.counts.get(&FourCC::from("free")));
+
+    // Parsing only the first 24 bytes leaves the rest of the file unaccounted for.
+    let stats = Parser::from_bytes_owned(file).unwrap().parse_with_limit(24).statistics();
+    assert_eq!((132, 16), (stats.unaccounted_bytes, accounted_bytes(&stats)));
+    assert_eq!(stats.file_size - stats.unaccounted_bytes, accounted_bytes(&stats));
+    assert!(stats.to_string().contains("file size: 148, covered: 10.8%, unaccounted: 132\n"));
+  }
+
+  #[test]
+  fn should_flag_atoms_that_overlap_without_counting_their_bytes_twice() {
+    let ctx = crate::ParseContext::default();
+    let mut file = std::io::Cursor::new(vec![0; 32]);
+    let children = [(0, 16), (8, 16), (24, 8)].iter().map(|(location, size)| {
+      AtomNodes::new(crate::AtomHeader::synthesized(b"free", *size, *location), &mut file, &ctx).unwrap()
+    }).collect();
+    let root = crate::containers::RootAtom::from_children(40, children);
+    let results = ParseResults::new(Ok(AtomNodes::Container(crate::containers::ContainerAtoms::Root(root))));
+    let stats = results.statistics();
+    assert_eq!(vec![(FourCC::from("free"), 8)], stats.overlapping_atoms);
+    assert_eq!(Some(&(3, 32)), stats.counts.get(&FourCC::from("free")));
+    assert_eq!(8, stats.unaccounted_bytes);
+    assert!(stats.to_string().ends_with("overlapping: free at 8\n"));
+  }
+}
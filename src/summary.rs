@@ -0,0 +1,180 @@
+use std::fmt;
+use super::atoms::{FourCC, leaves::SampleDescription};
+use super::parse_state::{ParseError, ParseResults, Result};
+use super::track::Track;
+
+/// The kind of media a track holds, from the handler type of its `hdlr` atom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+  Video,
+  Audio,
+  Other,
+}
+
+/// What is most often wanted to know about a track, without walking its atoms. Anything the file
+/// does not say is `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackInfo {
+  pub track_id: u32,
+  pub media_type: MediaType,
+  /// How long the track's media lasts, from its media header.
+  pub duration_seconds: Option<f64>,
+  /// The ISO 639-2 language code of the media header, such as `eng`.
+  pub language: Option<String>,
+  /// The data format of the first sample description, such as `avc1` or `mp4a`.
+  pub codec_fourcc: Option<FourCC>,
+  /// The size of the video in pixels, from the sample description or the track header.
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  /// The sample rate of sound, in Hz.
+  pub sample_rate: Option<u32>,
+  pub channel_count: Option<u32>,
+}
+
+impl TrackInfo {
+  fn new(track: &Track) -> TrackInfo {
+    let media_type = match track.handler_type() {
+      Some("vide") => MediaType::Video,
+      Some("soun") => MediaType::Audio,
+      _ => MediaType::Other,
+    };
+    let entry = track.stsd().and_then(|stsd| stsd.entries().first());
+    let (mut width, mut height) = match entry {
+      Some(SampleDescription::Video(video)) => (Some(video.dimensions().0 as u32), Some(video.dimensions().1 as u32)),
+      _ => (None, None),
+    };
+    if width.is_none() && track.tkhd().width_f32() > 0.0 {
+      width = Some(track.tkhd().width_f32() as u32);
+      height = Some(track.tkhd().height_f32() as u32);
+    }
+    let audio = match entry {
+      Some(SampleDescription::Audio(audio)) => Some(audio),
+      _ => None,
+    };
+    TrackInfo {
+      track_id: track.track_id(),
+      media_type,
+      duration_seconds: track.duration().map(|duration| duration.as_secs_f64()),
+      language: track.mdhd().map(|mdhd| mdhd.language()),
+      codec_fourcc: track.codec().map(FourCC::from),
+      width,
+      height,
+      sample_rate: audio.map(|audio| audio.sample_rate()),
+      channel_count: audio.map(|audio| audio.channels()),
+    }
+  }
+}
+
+impl fmt::Display for TrackInfo {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let media_type = match self.media_type {
+      MediaType::Video => "video",
+      MediaType::Audio => "audio",
+      MediaType::Other => "other",
+    };
+    write!(f, "track {}: {}", self.track_id, media_type)?;
+    if let Some(codec) = self.codec_fourcc {
+      write!(f, " {}", codec)?;
+    }
+    if let (Some(width), Some(height)) = (self.width, self.height) {
+      write!(f, ", {}x{}", width, height)?;
+    }
+    if let Some(sample_rate) = self.sample_rate {
+      write!(f, ", {} Hz", sample_rate)?;
+    }
+    if let Some(channel_count) = self.channel_count {
+      write!(f, ", {} channels", channel_count)?;
+    }
+    if let Some(duration) = self.duration_seconds {
+      write!(f, ", {:.3}s", duration)?;
+    }
+    if let Some(language) = &self.language {
+      write!(f, ", {}", language)?;
+    }
+    Ok(())
+  }
+}
+
+/// A summary of a movie and its tracks, for when the atoms themselves are not of interest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MovieInfo {
+  /// How long the movie lasts, from its movie header.
+  pub duration_seconds: Option<f64>,
+  /// The units per second of the movie's times, from its movie header.
+  pub time_scale: Option<u32>,
+  pub tracks: Vec<TrackInfo>,
+}
+
+impl MovieInfo {
+  /// Summarizes the movie of `results`, which needs a `moov` atom. Results that could not be
+  /// parsed have none.
+  pub fn from_results(results: &ParseResults) -> Result<MovieInfo> {
+    if results.find("moov").is_none() {
+      return Err(ParseError::MissingAtom { atom_type: FourCC::from("moov"), needed_for: "a movie summary" });
+    }
+    let mvhd = results.mvhd();
+    let tracks = results.track_headers().iter()
+      .filter_map(|tkhd| results.track(tkhd.track_id()))
+      .map(|track| TrackInfo::new(&track))
+      .collect();
+    Ok(MovieInfo {
+      duration_seconds: mvhd.map(|mvhd| mvhd.duration_seconds()),
+      time_scale: mvhd.map(|mvhd| mvhd.time_scale()),
+      tracks,
+    })
+  }
+  pub fn track_count(&self) -> usize { self.tracks.len() }
+}
+
+impl fmt::Display for MovieInfo {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.duration_seconds {
+      Some(duration) => write!(f, "duration: {:.3}s", duration)?,
+      None => write!(f, "duration: unknown")?,
+    }
+    if let Some(time_scale) = self.time_scale {
+      write!(f, ", time scale: {}", time_scale)?;
+    }
+    writeln!(f, ", tracks: {}", self.track_count())?;
+    for track in &self.tracks {
+      writeln!(f, "{}", track)?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Parser;
+
+  #[test]
+  fn should_summarize_a_movie_with_video_and_sound() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let info = MovieInfo::from_results(&results).unwrap();
+    assert_eq!(2, info.track_count());
+    assert_eq!(Some(1000), info.time_scale);
+    assert!((info.duration_seconds.unwrap() - 973.753).abs() < 0.001);
+
+    let video = &info.tracks[0];
+    assert_eq!((1, MediaType::Video), (video.track_id, video.media_type));
+    assert_eq!(Some(FourCC::from("avc1")), video.codec_fourcc);
+    assert_eq!((Some(960), Some(540)), (video.width, video.height));
+    assert_eq!((None, None), (video.sample_rate, video.channel_count));
+
+    let audio = &info.tracks[1];
+    assert_eq!((2, MediaType::Audio), (audio.track_id, audio.media_type));
+    assert_eq!(Some(FourCC::from("mp4a")), audio.codec_fourcc);
+    assert_eq!((None, None), (audio.width, audio.height));
+    assert_eq!((Some(44100), Some(2)), (audio.sample_rate, audio.channel_count));
+    assert_eq!(Some("und"), audio.language.as_deref());
+  }
+
+  #[test]
+  fn should_refuse_to_summarize_a_file_without_a_movie() {
+    let results = Parser::from_path("resources/tests/ftyp.mp4").unwrap().parse();
+    let err = MovieInfo::from_results(&results).unwrap_err();
+    assert!(matches!(err, ParseError::MissingAtom { atom_type, .. } if atom_type == "moov"));
+    assert_eq!("there is no moov atom, which a movie summary needs", err.to_string());
+  }
+}
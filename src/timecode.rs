@@ -0,0 +1,122 @@
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use super::atoms::FourCC;
+use super::atoms::leaves::{SampleDescription, TmcdSampleDescription};
+use super::parse_state::{ParseError, ParseResults, Result};
+
+/// A SMPTE timecode, hours, minutes, seconds and frames. `Display` gives `HH:MM:SS:FF`, with a
+/// `;` before the frames of a drop frame timecode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+  pub hours: u32,
+  pub minutes: u8,
+  pub seconds: u8,
+  pub frames: u8,
+  /// Whether frame numbers are skipped to keep the timecode in step with the clock, as for
+  /// 29.97 frames a second.
+  pub drop_frame: bool,
+}
+
+impl Timecode {
+  /// The timecode of frame `frame_number` counting from 00:00:00:00, at `frames_per_second`
+  /// frames of timecode a second. Drop frame timecode skips the first two frame numbers of every
+  /// minute but every tenth at 30 frames a second, four at 60, and is only counted for multiples
+  /// of 30 frames a second.
+  pub fn from_frame_number(frame_number: u64, frames_per_second: u8, drop_frame: bool) -> Timecode {
+    let fps = u64::from(frames_per_second.max(1));
+    let mut frame_number = frame_number;
+    let drop_frame = drop_frame && fps % 30 == 0;
+    if drop_frame {
+      let dropped = fps / 15;
+      let frames_per_minute = fps * 60 - dropped;
+      let frames_per_10_minutes = frames_per_minute * 10 + dropped;
+      let (tens, rest) = (frame_number / frames_per_10_minutes, frame_number % frames_per_10_minutes);
+      // The first minute of every ten keeps all its frame numbers.
+      let minutes = if rest < dropped { 0 } else { (rest - dropped) / frames_per_minute };
+      frame_number += dropped * (9 * tens + minutes);
+    }
+    let seconds = frame_number / fps;
+    Timecode {
+      hours: (seconds / 3600) as u32,
+      minutes: (seconds / 60 % 60) as u8,
+      seconds: (seconds % 60) as u8,
+      frames: (frame_number % fps) as u8,
+      drop_frame,
+    }
+  }
+  /// The timecode of frame `frame_number` of a timecode track described by `description`.
+  pub fn from_description(frame_number: u32, description: &TmcdSampleDescription) -> Timecode {
+    let mut timecode = Timecode::from_frame_number(u64::from(frame_number), description.number_of_frames(),
+                                                   description.is_drop_frame());
+    if description.is_24_hour_max() {
+      timecode.hours %= 24;
+    }
+    timecode
+  }
+}
+
+impl fmt::Display for Timecode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds,
+           if self.drop_frame { ';' } else { ':' }, self.frames)
+  }
+}
+
+/// The timecode the timecode track with `track_id` starts at. The frame number it counts from
+/// is its first sample, read from `source`, the file the results were parsed from.
+pub fn timecode_for_track(results: &ParseResults, source: &mut (impl Read + Seek), track_id: u32) -> Result<Timecode> {
+  let missing = |atom_type| ParseError::MissingAtom { atom_type: FourCC::from(atom_type), needed_for: "a timecode" };
+  let track = results.track(track_id).ok_or_else(|| missing("trak"))?;
+  let stsd = track.stsd().ok_or_else(|| missing("stsd"))?;
+  let description = stsd.entries().iter().find_map(|entry| match entry {
+    SampleDescription::Timecode(timecode) => Some(timecode),
+    _ => None,
+  }).ok_or_else(|| missing("tmcd"))?;
+  if description.number_of_frames() == 0 {
+    return Err(ParseError::parse_failed(stsd, "the timecode has no frames a second"));
+  }
+  let table = track.sample_table().ok_or_else(|| missing("stco"))?;
+  let offset = table.sample_file_offset(0).or_else(|| table.chunk_offset(0))
+    .ok_or_else(|| ParseError::parse_failed(stsd, "the timecode track has no samples"))?;
+  let mut frame_number = [0; 4];
+  source.seek(SeekFrom::Start(offset))?;
+  source.read_exact(&mut frame_number)?;
+  Ok(Timecode::from_description(u32::from_be_bytes(frame_number), description))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Parser;
+
+  #[test]
+  fn should_count_drop_frame_timecode() {
+    let timecode = |frame_number| Timecode::from_frame_number(frame_number, 30, true).to_string();
+    assert_eq!("00:00:00;00", timecode(0));
+    assert_eq!("00:00:59;29", timecode(1799));
+    // 00:01:00;00 and 00:01:00;01 are skipped.
+    assert_eq!("00:01:00;02", timecode(1800));
+    assert_eq!("00:09:59;29", timecode(17981));
+    // Every tenth minute keeps its first frame numbers.
+    assert_eq!("00:10:00;00", timecode(17982));
+    assert_eq!("00:10:59;29", timecode(17982 + 1799));
+    assert_eq!("00:11:00;02", timecode(17982 + 1800));
+    assert_eq!("01:00:00;00", timecode(107892));
+    assert_eq!("00:01:00;04", Timecode::from_frame_number(3600, 60, true).to_string());
+    // Drop frame is only counted at multiples of 30 frames a second.
+    assert_eq!("00:00:01:00", Timecode::from_frame_number(25, 25, true).to_string());
+  }
+
+  #[test]
+  fn should_read_the_start_of_a_timecode_track() {
+    for (path, expected) in [("resources/tests/timecode_25.mp4", "01:00:00:00"),
+                             ("resources/tests/timecode_2997df.mp4", "01:00:00;00")] {
+      let mut source = std::fs::File::open(path).unwrap();
+      let results = Parser::from_path(path).unwrap().parse();
+      let track = results.track(1).unwrap();
+      assert_eq!(Some("tmcd"), track.handler_type());
+      assert_eq!(expected, timecode_for_track(&results, &mut source, 1).unwrap().to_string());
+      assert!(matches!(timecode_for_track(&results, &mut source, 2), Err(ParseError::MissingAtom { .. })));
+    }
+  }
+}
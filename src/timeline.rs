@@ -0,0 +1,87 @@
+use super::atoms::leaves::{CttsAtom, SttsAtom, TimeToSampleEntry, CompositionOffsetEntry};
+
+/// When a sample is decoded and when it is presented, in media time scale units.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SampleTime {
+  pub decode_time: u64,
+  /// Negative only for version 1 composition offsets that move a sample before time 0.
+  pub composition_time: i64,
+}
+
+/// Steps through the samples of a track, combining the durations of the time-to-sample table with
+/// the offsets of the composition offset table. Without a composition offset table samples are
+/// presented as they are decoded.
+#[derive(Debug, Clone)]
+pub struct Timeline<'a> {
+  durations: &'a [TimeToSampleEntry],
+  offsets: &'a [CompositionOffsetEntry],
+  /// How many samples of the first run of each table have been yielded.
+  duration_index: u32,
+  offset_index: u32,
+  decode_time: u64,
+}
+
+impl<'a> Timeline<'a> {
+  pub fn new(stts: &'a SttsAtom, ctts: Option<&'a CttsAtom>) -> Timeline<'a> {
+    Timeline {
+      durations: stts.entries(),
+      offsets: ctts.map(|ctts| ctts.entries().as_slice()).unwrap_or_default(),
+      duration_index: 0,
+      offset_index: 0,
+      decode_time: 0,
+    }
+  }
+  fn composition_offset(&mut self) -> i64 {
+    while let Some(entry) = self.offsets.first() {
+      if self.offset_index < entry.sample_count {
+        self.offset_index += 1;
+        return entry.sample_offset;
+      }
+      self.offsets = &self.offsets[1..];
+      self.offset_index = 0;
+    }
+    0
+  }
+}
+
+impl Iterator for Timeline<'_> {
+  type Item = SampleTime;
+
+  fn next(&mut self) -> Option<SampleTime> {
+    while let Some(entry) = self.durations.first() {
+      if self.duration_index < entry.sample_count {
+        self.duration_index += 1;
+        let decode_time = self.decode_time;
+        self.decode_time += entry.sample_delta as u64;
+        let composition_time = decode_time as i64 + self.composition_offset();
+        return Some(SampleTime { decode_time, composition_time });
+      }
+      self.durations = &self.durations[1..];
+      self.duration_index = 0;
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Parser;
+  use super::*;
+
+  #[test]
+  fn should_present_b_frames_after_the_frames_they_depend_on() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let times: Vec<(u64, i64)> = results.timeline(1).unwrap().skip(5).take(6)
+      .map(|x| (x.decode_time, x.composition_time)).collect();
+    assert_eq!(vec![(5005, 7007), (6006, 9009), (7007, 8008), (8008, 10010), (9009, 12012), (10010, 11011)], times);
+    assert_eq!(29182, results.timeline(1).unwrap().count());
+  }
+
+  #[test]
+  fn should_present_samples_as_decoded_without_composition_offsets() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let times: Vec<SampleTime> = results.timeline(2).unwrap().take(3).collect();
+    assert!(times.iter().all(|x| x.composition_time == x.decode_time as i64));
+    assert_eq!(times[1].decode_time - times[0].decode_time, times[2].decode_time - times[1].decode_time);
+  }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+use super::atoms::{AtomNodes, containers::ContainerAtoms, leaves::{Atoms, TkhdAtom, MdhdAtom, HdlrAtom, StsdAtom, SttsAtom, StssAtom, StszAtom}};
+use super::parse_state::{find_atom, to_duration};
+use super::sample_table::SampleTable;
+
+/// One track of a movie: the atoms of its `trak` that are most often needed, gathered so they
+/// don't each have to be searched for. Returned by [`crate::ParseResults::track`].
+#[derive(Debug, Clone)]
+pub struct Track<'a> {
+  node: &'a AtomNodes,
+  tkhd: &'a TkhdAtom,
+  mdhd: Option<&'a MdhdAtom>,
+  hdlr: Option<&'a HdlrAtom>,
+  stsd: Option<&'a StsdAtom>,
+  stts: Option<&'a SttsAtom>,
+  stsz: Option<&'a StszAtom>,
+  stss: Option<&'a StssAtom>,
+  sample_table: Option<SampleTable>,
+}
+
+impl<'a> Track<'a> {
+  /// Gathers the atoms of the `trak` container `node`, which needs a track header.
+  pub fn new(node: &'a AtomNodes) -> Option<Track<'a>> {
+    let tkhd = find_atom(node, |atom| match atom { Atoms::Tkhd(tkhd) => Some(tkhd), _ => None })?;
+    let mut sample_table = None;
+    let mut stbl = None;
+    node.walk(|node| match node {
+      AtomNodes::Container(ContainerAtoms::Stbl(_)) if stbl.is_none() => stbl = Some(node),
+      AtomNodes::Atom(Atoms::Stco(stco)) if sample_table.is_none() => sample_table = Some(SampleTable::new(stco)),
+      AtomNodes::Atom(Atoms::Co64(co64)) if sample_table.is_none() => sample_table = Some(SampleTable::new(co64)),
+      _ => {}
+    });
+    if let Some(table) = stbl.and_then(|stbl| SampleTable::from_stbl(stbl).ok()) {
+      sample_table = Some(table);
+    }
+    Some(Track {
+      node,
+      tkhd,
+      mdhd: find_atom(node, |atom| match atom { Atoms::Mdhd(mdhd) => Some(mdhd), _ => None }),
+      hdlr: find_atom(node, |atom| match atom { Atoms::Hdlr(hdlr) => Some(hdlr), _ => None }),
+      stsd: find_atom(node, |atom| match atom { Atoms::Stsd(stsd) => Some(stsd), _ => None }),
+      stts: find_atom(node, |atom| match atom { Atoms::Stts(stts) => Some(stts), _ => None }),
+      stsz: find_atom(node, |atom| match atom { Atoms::Stsz(stsz) => Some(stsz), _ => None }),
+      stss: find_atom(node, |atom| match atom { Atoms::Stss(stss) => Some(stss), _ => None }),
+      sample_table,
+    })
+  }
+  /// The `trak` container itself.
+  pub fn node(&self) -> &'a AtomNodes { self.node }
+  pub fn track_id(&self) -> u32 { self.tkhd.track_id() }
+  pub fn tkhd(&self) -> &'a TkhdAtom { self.tkhd }
+  pub fn mdhd(&self) -> Option<&'a MdhdAtom> { self.mdhd }
+  pub fn hdlr(&self) -> Option<&'a HdlrAtom> { self.hdlr }
+  pub fn stsd(&self) -> Option<&'a StsdAtom> { self.stsd }
+  /// The sync sample table. `None` when the track has none, which means every sample is a sync
+  /// sample, unlike an empty table.
+  pub fn stss(&self) -> Option<&'a StssAtom> { self.stss }
+  /// Whether sample `sample_number`, counting from 1, can be decoded on its own, see
+  /// [`Track::stss`].
+  pub fn is_sync_sample(&self, sample_number: u32) -> bool {
+    self.stss.is_none_or(|stss| stss.is_sync_sample(sample_number))
+  }
+  /// The chunk offsets and samples of the track's media. When the sample table atoms are missing
+  /// or disagree it has only the chunk offsets, see [`SampleTable::from_stbl`] for why.
+  pub fn sample_table(&self) -> Option<&SampleTable> { self.sample_table.as_ref() }
+  /// The handler type, such as `vide` or `soun`.
+  pub fn handler_type(&self) -> Option<&'a str> { self.hdlr.map(|hdlr| hdlr.component_subtype()) }
+  /// The data format of the first sample description, such as `avc1` or `mp4a`.
+  pub fn codec(&self) -> Option<&'a str> {
+    self.stsd?.entries().first().map(|entry| entry.entry().data_format())
+  }
+  /// How long the track's media lasts, from its media header. Edits are not taken into account,
+  /// see [`crate::ParseResults::track_presentation_duration`] for that.
+  pub fn duration(&self) -> Option<Duration> {
+    let mdhd = self.mdhd?;
+    to_duration(mdhd.duration(), mdhd.time_scale())
+  }
+  /// The number of samples, from the sample size table, or the time-to-sample table when there
+  /// is no sample size table.
+  pub fn sample_count(&self) -> u64 {
+    match (self.stsz, self.stts) {
+      (Some(stsz), _) => stsz.sample_count() as u64,
+      (None, Some(stts)) => stts.sample_count(),
+      (None, None) => 0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Parser;
+  use std::time::Duration;
+
+  #[test]
+  fn should_gather_the_atoms_of_a_track() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let video = results.track(1).unwrap();
+    assert_eq!(1, video.track_id());
+    assert_eq!(Some("avc1"), video.codec());
+    assert_eq!(Some("vide"), video.handler_type());
+    assert_eq!(29182, video.sample_count());
+    assert_eq!(Some(Duration::from_nanos(973_706_066_666)), video.duration());
+    assert!(video.sample_table().unwrap().chunk_count() > 0);
+    assert_eq!(29182, video.sample_table().unwrap().sample_count());
+
+    let audio = results.track(2).unwrap();
+    assert_eq!(Some("mp4a"), audio.codec());
+    assert!(results.track(3).is_none());
+  }
+
+  #[test]
+  fn should_treat_every_sample_of_a_track_without_a_sync_sample_table_as_a_sync_sample() {
+    let results = Parser::from_path("resources/tests/sample.mp4").unwrap().parse();
+    let video = results.track(1).unwrap();
+    assert!(video.stss().is_some());
+    assert!(video.is_sync_sample(1));
+    assert!(!video.is_sync_sample(2));
+
+    let audio = results.track(2).unwrap();
+    assert!(audio.stss().is_none());
+    assert!(audio.is_sync_sample(1) && audio.is_sync_sample(2));
+  }
+}
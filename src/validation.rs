@@ -0,0 +1,97 @@
+use std::fmt;
+use crate::atoms::{AtomLike, AtomNodes};
+use crate::atoms::leaves::Atoms;
+
+/// A problem found by [`crate::ParseResults::validate`]. `Display` gives a one line description
+/// starting with a short name for the kind of problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationWarning {
+  /// Unused bytes between two siblings, or between a container's header and its first child.
+  Gap { after: String, before: String, location: u64, bytes: u64 },
+  /// An atom that starts before the previous sibling ends.
+  Overlap { atom_type: String, location: u64, bytes: u64 },
+  /// An atom whose size takes it past the end of its parent.
+  SizeMismatch { atom_type: String, location: u64, parent: String, overrun: u64 },
+  /// Unused bytes after the last child of a container, kept in the tree as an
+  /// [`Atoms::Slack`] when they can not hold an atom.
+  Slack { container: String, location: u64, bytes: u64 },
+  /// A file type atom that is not the first atom of the file.
+  MisplacedFtyp { location: u64 },
+  /// A file without a file type atom, such as a raw capture starting with `mdat`. `first` is the
+  /// type of the atom the file starts with.
+  MissingFtyp { first: String },
+  /// A movie header whose next track id is already taken by a track.
+  TrackIdInUse { next_track_id: u32, track_id: u32 },
+}
+
+/// Checks the children of a container one at a time, in file order, for gaps, overlaps and
+/// atoms running past the end of the container.
+pub(crate) struct LayoutCheck {
+  container: String,
+  end: u64,
+  expected: u64,
+  previous: String,
+}
+
+impl LayoutCheck {
+  /// Checks a container whose first child should start at `start` and whose last child should end
+  /// at `end`.
+  pub(crate) fn new(container: &str, start: u64, end: u64) -> LayoutCheck {
+    LayoutCheck { container: container.to_string(), end, expected: start, previous: container.to_string() }
+  }
+  pub(crate) fn child(&mut self, child: &AtomNodes) -> Vec<ValidationWarning> {
+    if let AtomNodes::Atom(Atoms::Slack { location, size }) = child {
+      self.expected = self.expected.max(location + size);
+      return vec![ValidationWarning::Slack { container: self.container.clone(), location: *location, bytes: *size }];
+    }
+    let mut warnings = Vec::new();
+    if child.atom_location() > self.expected {
+      warnings.push(ValidationWarning::Gap { after: self.previous.clone(), before: child.atom_type().to_string(),
+                                             location: child.atom_location(),
+                                             bytes: child.atom_location() - self.expected });
+    } else if child.atom_location() < self.expected {
+      warnings.push(ValidationWarning::Overlap { atom_type: child.atom_type().to_string(),
+                                                 location: child.atom_location(),
+                                                 bytes: self.expected - child.atom_location() });
+    }
+    let child_end = child.atom_location().saturating_add(child.atom_size());
+    if child_end > self.end {
+      warnings.push(ValidationWarning::SizeMismatch { atom_type: child.atom_type().to_string(),
+                                                      location: child.atom_location(),
+                                                      parent: self.container.clone(),
+                                                      overrun: child_end - self.end });
+    }
+    self.expected = self.expected.max(child_end);
+    self.previous = child.atom_type().to_string();
+    warnings
+  }
+  /// Unused bytes after the last child.
+  pub(crate) fn finish(self) -> Option<ValidationWarning> {
+    if self.expected < self.end {
+      Some(ValidationWarning::Slack { container: self.container, location: self.expected, bytes: self.end - self.expected })
+    } else {
+      None
+    }
+  }
+}
+
+impl fmt::Display for ValidationWarning {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ValidationWarning::Gap { before, location, bytes, .. } =>
+        write!(f, "gap: {} bytes before {} at {}", bytes, before, location),
+      ValidationWarning::Overlap { atom_type, location, bytes } =>
+        write!(f, "overlap: {} at {} overlaps the previous atom by {} bytes", atom_type, location, bytes),
+      ValidationWarning::SizeMismatch { atom_type, location, parent, overrun } =>
+        write!(f, "overrun: {} at {} extends {} bytes past the end of {}", atom_type, location, overrun, parent),
+      ValidationWarning::Slack { container, location, bytes } =>
+        write!(f, "slack: {} bytes at the end of {} at {}", bytes, container, location),
+      ValidationWarning::MisplacedFtyp { location } =>
+        write!(f, "misplaced-ftyp: ftyp at {} is not the first atom", location),
+      ValidationWarning::MissingFtyp { first } =>
+        write!(f, "missing-ftyp: there is no ftyp, the file starts with {}", first),
+      ValidationWarning::TrackIdInUse { next_track_id, track_id } =>
+        write!(f, "track-id: next track id {} in mvhd is not greater than track id {}", next_track_id, track_id),
+    }
+  }
+}
@@ -0,0 +1,404 @@
+use std::io::{BufReader, Read, SeekFrom, Write};
+use super::atoms::{AtomLike, AtomHeader, AtomNodes, Container, FourCC, ReadSeek};
+use super::atoms::containers::ContainerAtoms;
+use super::atoms::leaves::{Atoms, Co64Atom, FreeAtom, FtypAtom, WideAtom};
+use super::parse_state::{ParseError, Result};
+
+/// The most bytes read from the source at once when copying the bytes of an atom.
+const COPY_BLOCK_SIZE: u64 = 1 << 20;
+
+/// An atom that can be written out on its own, without the file it was parsed from. Sizes are
+/// worked out from what is written rather than taken from the parsed header.
+pub trait WriteAtom {
+  /// Writes the atom to `w` and returns the number of bytes written.
+  fn write_to<W: Write>(&self, w: &mut W) -> Result<u64>;
+}
+
+impl WriteAtom for AtomHeader {
+  /// Writes the header alone, with the extended 64-bit size only when the size needs it, followed
+  /// by the extended type of a `uuid` atom.
+  fn write_to<W: Write>(&self, w: &mut W) -> Result<u64> {
+    let written = write_header(self.four_cc().raw(), self.atom_size(), w)?;
+    match self.extended_type() {
+      Some(uuid) => {
+        w.write_all(&uuid)?;
+        Ok(written + 16)
+      }
+      None => Ok(written),
+    }
+  }
+}
+
+impl WriteAtom for FtypAtom {
+  fn write_to<W: Write>(&self, w: &mut W) -> Result<u64> {
+    let brands = self.compatible_brands();
+    let mut written = write_header(*b"ftyp", 16 + 4 * brands.len() as u64, w)?;
+    w.write_all(&self.major_brand().raw())?;
+    w.write_all(&self.minor_version().to_be_bytes())?;
+    for brand in brands {
+      w.write_all(&brand.raw())?;
+    }
+    written += 8 + 4 * brands.len() as u64;
+    Ok(written)
+  }
+}
+
+impl WriteAtom for FreeAtom {
+  /// Writes the header followed by zeros up to the size of the free space.
+  fn write_to<W: Write>(&self, w: &mut W) -> Result<u64> {
+    let size = self.atom_size().max(8);
+    let written = write_header(FourCC::from(self.atom_type()).raw(), size, w)?;
+    Ok(written + std::io::copy(&mut std::io::repeat(0).take(size - written), w)?)
+  }
+}
+
+impl WriteAtom for WideAtom {
+  fn write_to<W: Write>(&self, w: &mut W) -> Result<u64> {
+    write_header(*b"wide", 8, w)
+  }
+}
+
+/// The number of bytes `node` takes once written with [`write_node`]. Containers and chunk offset
+/// tables are sized from their contents, so edits to the tree are accounted for.
+pub fn serialized_size(node: &AtomNodes) -> u64 {
+  match node {
+    AtomNodes::Container(atom) if atom.atom_type() == "root" => atom.children().iter().map(serialized_size).sum(),
+    AtomNodes::Container(atom) => {
+      let payload = container_prefix_size(atom) + atom.children().iter().map(serialized_size).sum::<u64>();
+      header_size_for(8 + payload) as u64 + payload
+    }
+    AtomNodes::Atom(Atoms::Stco(atom)) => 16 + 4 * atom.chunk_offsets().len() as u64,
+    AtomNodes::Atom(Atoms::Co64(atom)) => 16 + 8 * atom.chunk_offsets().len() as u64,
+    AtomNodes::Atom(atom) => atom.atom_size(),
+  }
+}
+
+/// Writes `node` and everything below it to `out` and returns the number of bytes written.
+/// Container headers and the `stco`/`co64` tables are regenerated from the tree, every other leaf
+/// is copied unchanged from `source`, the file the tree was parsed from. A `moov` whose children
+/// were decompressed from a `cmov` can't be written, as they are not in `source`.
+pub fn write_node(node: &AtomNodes, source: &mut dyn ReadSeek, out: &mut dyn Write) -> Result<u64> {
+  match node {
+    AtomNodes::Container(atom) => {
+      refuse_compressed_movie(atom)?;
+      let mut written = 0;
+      if atom.atom_type() != "root" {
+        let size = serialized_size(node);
+        written += write_header(FourCC::from(atom.atom_type()).raw(), size, out)?;
+        let prefix = container_prefix_size(atom);
+        written += copy_range(source, atom.atom_type(), atom.atom_location() + atom.header_size() as u64, prefix, out)?;
+      }
+      for child in atom.children() {
+        written += write_node(child, source, out)?;
+      }
+      Ok(written)
+    }
+    AtomNodes::Atom(Atoms::Stco(atom)) => {
+      let mut written = write_header(*b"stco", serialized_size(node), out)?;
+      out.write_all(&atom.full_atom().to_bytes())?;
+      out.write_all(&(atom.chunk_offsets().len() as u32).to_be_bytes())?;
+      for offset in atom.chunk_offsets() {
+        out.write_all(&offset.to_be_bytes())?;
+      }
+      written += 8 + 4 * atom.chunk_offsets().len() as u64;
+      Ok(written)
+    }
+    AtomNodes::Atom(Atoms::Co64(atom)) => {
+      let mut written = write_header(*b"co64", serialized_size(node), out)?;
+      out.write_all(&atom.full_atom().to_bytes())?;
+      out.write_all(&(atom.chunk_offsets().len() as u32).to_be_bytes())?;
+      for offset in atom.chunk_offsets() {
+        out.write_all(&offset.to_be_bytes())?;
+      }
+      written += 8 + 8 * atom.chunk_offsets().len() as u64;
+      Ok(written)
+    }
+    AtomNodes::Atom(atom) => copy_range(source, atom.atom_type(), atom.atom_location(), atom.atom_size(), out),
+  }
+}
+
+/// Moves every chunk offset under `node` by `delta` bytes, as needed when media data moves within
+/// the file. An `stco` table whose offsets no longer fit in 32 bits is replaced by a `co64` table,
+/// which [`write_node`] sizes its parent for.
+pub fn shift_chunk_offsets(node: &mut AtomNodes, delta: i64) -> Result<()> {
+  map_chunk_offsets(node, |offset| offset.checked_add_signed(delta).ok_or_else(|| {
+    ParseError::EditFailed(format!("chunk offset {} can not be moved by {}", offset, delta))
+  }))
+}
+
+/// Replaces every chunk offset under `node` with `map` of it, upgrading `stco` tables to `co64`
+/// when the new offsets need it.
+fn map_chunk_offsets<F>(node: &mut AtomNodes, map: F) -> Result<()> where F: Fn(u64) -> Result<u64> {
+  let mut result = Ok(());
+  node.walk_mut(|node| {
+    if result.is_err() {
+      return;
+    }
+    let upgraded = match node {
+      AtomNodes::Atom(Atoms::Co64(atom)) => {
+        result = atom.chunk_offsets_mut().iter_mut().try_for_each(|offset| {
+          *offset = map(*offset)?;
+          Ok(())
+        });
+        None
+      }
+      AtomNodes::Atom(Atoms::Stco(atom)) => {
+        match atom.chunk_offsets().iter().map(|offset| map(*offset as u64)).collect::<Result<Vec<u64>>>() {
+          Ok(offsets) if offsets.iter().all(|offset| *offset <= u32::MAX as u64) => {
+            *atom.chunk_offsets_mut() = offsets.iter().map(|offset| *offset as u32).collect();
+            None
+          }
+          Ok(offsets) => Some(Co64Atom::from_offsets(atom.full_atom().clone(), offsets, atom.atom_location())),
+          Err(err) => {
+            result = Err(err);
+            None
+          }
+        }
+      }
+      _ => None,
+    };
+    if let Some(co64) = upgraded {
+      *node = AtomNodes::Atom(Atoms::Co64(co64));
+    }
+  });
+  result
+}
+
+/// Rewrites the file `root` was parsed from with its `moov` atom just after the `ftyp` atom, so
+/// players can start before the media data has downloaded. Every other top-level atom follows in
+/// its original order, except top-level `free` and `skip` padding, which is dropped. Chunk
+/// offsets are moved along with the atoms they point into. Returns the number of bytes written.
+pub fn make_fast_start(root: &AtomNodes, source: &mut dyn ReadSeek, out: &mut dyn Write) -> Result<u64> {
+  let top_level = match root {
+    AtomNodes::Container(root) => root.children(),
+    AtomNodes::Atom(atom) => return Err(ParseError::EditFailed(format!("fast start: {} is not a root", atom.atom_type()))),
+  };
+  let moov = top_level.iter().find(|node| node.atom_type() == "moov")
+    .ok_or_else(|| ParseError::EditFailed(String::from("fast start: no moov atom")))?;
+  // The chunk offsets of a compressed movie can't be moved, whether or not it was decompressed.
+  if let AtomNodes::Container(ContainerAtoms::Moov(atom)) = moov {
+    if atom.decompressed().is_some() || atom.children().iter().any(|child| child.atom_type() == "cmov") {
+      return Err(ParseError::EditFailed(String::from("fast start: the movie is compressed")));
+    }
+  }
+  let ftyp: Vec<&AtomNodes> = top_level.iter().filter(|node| node.atom_type() == "ftyp").collect();
+  let rest: Vec<&AtomNodes> = top_level.iter()
+    .filter(|node| !matches!(node.atom_type(), "ftyp" | "moov" | "free" | "skip")).collect();
+  let start = ftyp.iter().map(|node| serialized_size(node)).sum::<u64>();
+  // Moving the media data can turn stco tables into larger co64 ones, which moves the media data
+  // again, so settle the size of moov first.
+  let mut moov_size = serialized_size(moov);
+  loop {
+    let mut moved = Vec::new();
+    let mut location = start + moov_size;
+    for node in &rest {
+      moved.push((node.atom_location(), node.atom_size(), location));
+      location += serialized_size(node);
+    }
+    let mut relocated = moov.clone();
+    map_chunk_offsets(&mut relocated, |offset| {
+      moved.iter().find(|(old, size, _)| (*old..*old + *size).contains(&offset))
+        .map(|(old, _, new)| new + (offset - old))
+        .ok_or_else(|| ParseError::EditFailed(format!("fast start: chunk offset {} is outside of the media data", offset)))
+    })?;
+    if serialized_size(&relocated) == moov_size {
+      let mut written = 0;
+      for node in ftyp.iter().chain(std::iter::once(&&relocated)).chain(rest.iter()) {
+        written += write_node(node, source, out)?;
+      }
+      return Ok(written);
+    }
+    moov_size = serialized_size(&relocated);
+  }
+}
+
+/// Copies the bytes of `node`, its header included, from `source`, the file it was parsed from, to
+/// `out` as they are in the file. Large atoms such as `mdat` are streamed rather than read into
+/// memory. An atom that runs past the end of `source` fails with [`ParseError::TruncatedAtom`]
+/// once the bytes that are there have been copied. The atoms below a `moov` decompressed from a
+/// `cmov` are not in `source`, see [`ParseResults::extract_atom`](crate::ParseResults::extract_atom),
+/// which refuses them. Returns the number of bytes written.
+pub fn extract_atom(node: &AtomNodes, source: &mut dyn ReadSeek, out: &mut dyn Write) -> Result<u64> {
+  let range = node.byte_range();
+  copy_range(source, node.atom_type(), range.start, range.end - range.start, out)
+}
+
+/// Fails for a `moov` whose children were decompressed from a `cmov`, as their locations are
+/// offsets into the decompressed movie rather than into the file.
+fn refuse_compressed_movie(atom: &ContainerAtoms) -> Result<()> {
+  match atom {
+    ContainerAtoms::Moov(moov) if moov.decompressed().is_some() => Err(ParseError::EditFailed(
+      format!("moov at {}: the atoms of a compressed movie are not in the file", moov.atom_location()))),
+    _ => Ok(()),
+  }
+}
+
+/// Bytes between a container's header and its first child, such as the version and flags of a
+/// full atom container. A container without children keeps its whole payload.
+fn container_prefix_size(atom: &ContainerAtoms) -> u64 {
+  let payload_start = atom.atom_location() + atom.header_size() as u64;
+  match atom.children().first() {
+    Some(child) => child.atom_location().saturating_sub(payload_start),
+    None => atom.atom_size().saturating_sub(atom.header_size() as u64),
+  }
+}
+
+fn header_size_for(atom_size: u64) -> u32 {
+  AtomHeader::synthesized(b"free", atom_size, 0).header_size()
+}
+
+fn write_header(atom_type: [u8; 4], atom_size: u64, out: &mut dyn Write) -> Result<u64> {
+  if atom_size > u32::MAX as u64 {
+    out.write_all(&1u32.to_be_bytes())?;
+    out.write_all(&atom_type)?;
+    out.write_all(&atom_size.to_be_bytes())?;
+    Ok(16)
+  } else {
+    out.write_all(&(atom_size as u32).to_be_bytes())?;
+    out.write_all(&atom_type)?;
+    Ok(8)
+  }
+}
+
+/// Copies the `size` bytes at `location` in `source`, part of an atom of `atom_type`, to `out`, a
+/// block of at most [`COPY_BLOCK_SIZE`] bytes at a time.
+fn copy_range(source: &mut dyn ReadSeek, atom_type: &str, location: u64, size: u64, out: &mut dyn Write)
+  -> Result<u64> {
+  source.seek(SeekFrom::Start(location))?;
+  let mut reader = BufReader::with_capacity(size.min(COPY_BLOCK_SIZE) as usize, source.take(size));
+  let copied = std::io::copy(&mut reader, out)?;
+  if copied == size {
+    Ok(copied)
+  } else {
+    Err(ParseError::TruncatedAtom { atom_type: FourCC::from(atom_type), offset: location, expected: size, got: copied })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parse_state::ParseContext;
+  use std::io::Cursor;
+
+  fn parse(bytes: Vec<u8>) -> AtomNodes {
+    let mut file = Cursor::new(bytes);
+    let header = AtomHeader::new(&mut file).unwrap();
+    AtomNodes::new(header, &mut file, &ParseContext::default()).unwrap()
+  }
+
+  #[test]
+  fn should_write_an_unedited_tree_back_unchanged() {
+    let bytes = std::fs::read("resources/tests/moov.mp4").unwrap();
+    let moov = parse(bytes.clone());
+    let mut out = Vec::new();
+    let written = write_node(&moov, &mut Cursor::new(&bytes[..]), &mut out).unwrap();
+    assert_eq!(bytes.len() as u64, written);
+    assert_eq!(serialized_size(&moov), written);
+    assert!(bytes == out);
+  }
+
+  #[test]
+  fn should_upgrade_stco_to_co64_when_offsets_pass_4_gib() {
+    let mut stco = vec![0, 0, 0, 24];
+    stco.extend_from_slice(b"stco");
+    stco.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2]);
+    stco.extend_from_slice(&0x1000u32.to_be_bytes());
+    stco.extend_from_slice(&0xffff_0000u32.to_be_bytes());
+    let mut bytes = vec![0, 0, 0, 32];
+    bytes.extend_from_slice(b"stbl");
+    bytes.extend_from_slice(&stco);
+    let mut stbl = parse(bytes.clone());
+
+    shift_chunk_offsets(&mut stbl, 0x1_0000_0000).unwrap();
+    let mut out = Vec::new();
+    write_node(&stbl, &mut Cursor::new(&bytes[..]), &mut out).unwrap();
+    assert_eq!(8 + 16 + 16, out.len());
+    assert_eq!(&(out.len() as u32).to_be_bytes(), &out[0..4]);
+
+    match parse(out) {
+      AtomNodes::Container(stbl) => match &stbl.children()[..] {
+        [AtomNodes::Atom(Atoms::Co64(co64))] => {
+          assert_eq!(&vec![0x1_0000_1000, 0x1_ffff_0000], co64.chunk_offsets());
+          assert_eq!(32, co64.atom_size());
+        }
+        other => panic!("expected a single co64, got {:?}", other),
+      },
+      other => panic!("expected stbl, got {:?}", other),
+    }
+    assert!(shift_chunk_offsets(&mut stbl, -0x2_0000_0000).is_err());
+  }
+
+  #[test]
+  fn should_write_a_parsed_ftyp_back_field_for_field() {
+    let bytes = std::fs::read("resources/tests/ftyp.mp4").unwrap();
+    let mut file = Cursor::new(&bytes[..]);
+    let ftyp = FtypAtom::new(AtomHeader::new(&mut file).unwrap(), &mut file).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(ftyp.atom_size(), ftyp.write_to(&mut out).unwrap());
+    assert!(bytes[..ftyp.atom_size() as usize] == out[..]);
+
+    let mut file = Cursor::new(out);
+    let reparsed = FtypAtom::new(AtomHeader::new(&mut file).unwrap(), &mut file).unwrap();
+    assert_eq!(ftyp.atom_size(), reparsed.atom_size());
+    assert_eq!(ftyp.major_brand(), reparsed.major_brand());
+    assert_eq!(ftyp.minor_version(), reparsed.minor_version());
+    assert_eq!(ftyp.compatible_brands(), reparsed.compatible_brands());
+  }
+
+  #[test]
+  fn should_write_a_built_ftyp_with_its_size_worked_out() {
+    let ftyp = FtypAtom::builder().major_brand("isom").minor_version(512).compatible(&["isom", "mp42"]).build();
+    let mut out = Vec::new();
+    assert_eq!(24, ftyp.write_to(&mut out).unwrap());
+    assert!(b"\0\0\0\x18ftypisom\0\0\x02\0isommp42"[..] == out[..]);
+    let mut file = Cursor::new(out);
+    let reparsed = FtypAtom::new(AtomHeader::new(&mut file).unwrap(), &mut file).unwrap();
+    assert!(reparsed.is_compatible_with("mp42"));
+  }
+
+  #[test]
+  fn should_write_a_built_free_atom_as_zero_padding() {
+    let mut out = Vec::new();
+    assert_eq!(24, FreeAtom::builder().size(24).build().write_to(&mut out).unwrap());
+    let mut expected = vec![0, 0, 0, 24];
+    expected.extend_from_slice(b"free");
+    expected.resize(24, 0);
+    assert_eq!(expected, out);
+
+    let mut out = Vec::new();
+    assert_eq!(8, WideAtom::new(AtomHeader::synthesized(b"wide", 8, 0)).unwrap().write_to(&mut out).unwrap());
+    assert_eq!(b"\0\0\0\x08wide".to_vec(), out);
+  }
+
+  #[test]
+  fn should_write_the_extended_size_form_only_when_needed() {
+    let mut out = Vec::new();
+    assert_eq!(16, AtomHeader::synthesized(b"mdat", 1 << 33, 0).write_to(&mut out).unwrap());
+    assert_eq!([0, 0, 0, 1], out[..4]);
+    assert_eq!((1u64 << 33).to_be_bytes(), out[8..]);
+    let mut out = Vec::new();
+    assert_eq!(8, AtomHeader::synthesized(b"mdat", 100, 0).write_to(&mut out).unwrap());
+    assert_eq!(b"\0\0\0\x64mdat".to_vec(), out);
+  }
+
+  #[test]
+  fn should_write_the_extended_type_of_a_uuid_header() {
+    let uuid = [0xbe, 0x7a, 0xcf, 0xcb, 0x97, 0xa9, 0x42, 0xe8, 0x9c, 0x71, 0x99, 0x94, 0x91, 0xe3, 0xaf, 0xac];
+    let bytes = [&40u32.to_be_bytes()[..], b"uuid", &uuid, &[0; 16]].concat();
+    let header = AtomHeader::new(&mut Cursor::new(&bytes)).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(24, header.header_size());
+    assert_eq!(24, header.write_to(&mut out).unwrap());
+    assert_eq!(&bytes[..24], &out[..]);
+  }
+
+  #[test]
+  fn should_keep_stco_when_shifted_offsets_still_fit() {
+    let bytes = std::fs::read("resources/tests/trak.mp4").unwrap();
+    let mut trak = parse(bytes);
+    shift_chunk_offsets(&mut trak, 1024).unwrap();
+    let mut offsets = Vec::new();
+    trak.walk(|node| if let AtomNodes::Atom(Atoms::Stco(stco)) = node { offsets.push(stco.chunk_offsets()[0]) });
+    assert_eq!(vec![0xf9ceb + 1024], offsets);
+  }
+}
@@ -0,0 +1,153 @@
+use std::process::Command;
+
+fn validate(path: &str) -> Option<i32> {
+  Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--validate", path])
+    .output()
+    .unwrap()
+    .status
+    .code()
+}
+
+#[test]
+fn validate_exits_cleanly_for_a_well_formed_file() {
+  assert_eq!(Some(0), validate("resources/tests/sample.mp4"));
+}
+
+#[test]
+fn validate_reports_warnings_with_their_own_exit_code() {
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--validate", "resources/tests/truncated.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(2), output.status.code());
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr.lines().all(|line| line.starts_with("warning: ")));
+  assert!(stderr.contains("overrun: free at 32"));
+}
+
+#[test]
+fn validate_reports_unparseable_files() {
+  assert_eq!(Some(3), validate("resources/tests/empty_file.mp4"));
+  assert_eq!(Some(3), validate("resources/tests/does_not_exist.mp4"));
+}
+
+#[test]
+fn validate_applies_the_limits_set_in_the_environment() {
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--validate", "resources/tests/sample.mp4"])
+    .env("MEDIA_ATOMS_MAX_DEPTH", "2")
+    .output()
+    .unwrap();
+  assert_eq!(Some(3), output.status.code());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("nested too deeply, the limit is 2"));
+}
+
+#[test]
+fn json_prints_the_atoms_instead_of_the_tree() {
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--json", "resources/tests/moov.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(0), output.status.code());
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert!(stdout.starts_with("{\n  \"atoms\": [\n    {\n      \"atom_type\": \"moov\","), "{}", stdout);
+  assert!(stdout.contains("\"children\": ["));
+  assert!(stdout.trim_end().ends_with('}'));
+}
+
+#[test]
+fn summary_prints_the_tracks_of_the_movie() {
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--summary", "resources/tests/sample.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(0), output.status.code());
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert_eq!(vec!["duration: 973.753s, time scale: 1000, tracks: 2",
+                  "track 1: video avc1, 960x540, 973.706s, und",
+                  "track 2: audio mp4a, 44100 Hz, 2 channels, 973.752s, und"], stdout.lines().collect::<Vec<_>>());
+
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--summary", "resources/tests/ftyp.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(3), output.status.code());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("there is no moov atom"));
+}
+
+#[test]
+fn extract_copies_an_atom_to_the_out_file() {
+  let out = std::env::temp_dir().join(format!("media_atoms_extract_{}.bin", std::process::id()));
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--extract", "ftyp", "--out", out.to_str().unwrap(), "resources/tests/sample.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(0), output.status.code());
+  let extracted = std::fs::read(&out).unwrap();
+  std::fs::remove_file(&out).unwrap();
+  assert_eq!(&std::fs::read("resources/tests/sample.mp4").unwrap()[..32], &extracted[..]);
+
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--extract", "moov/udta/meta/ilst/nothing", "resources/tests/sample.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(3), output.status.code());
+  assert!(output.stdout.is_empty());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("there is no atom at moov/udta/meta/ilst/nothing"));
+}
+
+#[test]
+fn scan_prints_a_line_per_header() {
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--scan", "resources/tests/gopro_udta.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(0), output.status.code());
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  let lines: Vec<&str> = stdout.lines().collect();
+  assert_eq!(7, lines.len());
+  assert_eq!("type: moov, size: 116, location: 24", lines[1]);
+  assert_eq!("    type: FIRM, size: 24, location: 40", lines[3]);
+}
+
+#[test]
+fn stats_prints_a_table_of_atom_types() {
+  let output = Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--stats", "resources/tests/sample.mp4"])
+    .output()
+    .unwrap();
+  assert_eq!(Some(0), output.status.code());
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert!(stdout.starts_with("type     count         bytes\n"), "{}", stdout);
+  assert!(stdout.contains("\ntrak         2            16\n"), "{}", stdout);
+  assert!(stdout.contains("fast start: yes\n"));
+}
+
+#[test]
+fn locate_prints_the_atoms_holding_an_offset() {
+  let locate = |offset: &str| Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--locate", offset, "resources/tests/sample.mp4"])
+    .output()
+    .unwrap();
+  let output = locate("0x4d025");
+  assert_eq!(Some(0), output.status.code());
+  assert_eq!("moov > trak > mdia > minf > stbl > stco @ +0x24\n", String::from_utf8(output.stdout).unwrap());
+  let output = locate("25774283");
+  assert_eq!(Some(4), output.status.code());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("no atom of resources/tests/sample.mp4 holds offset 0x18948cb"));
+}
+
+#[test]
+fn diff_prints_the_atoms_that_differ() {
+  let diff = |other: &str| Command::new(env!("CARGO_BIN_EXE_media_atoms"))
+    .args(["--diff", other, "resources/tests/moov.mp4"])
+    .output()
+    .unwrap();
+  let output = diff("resources/tests/moov.mp4");
+  assert_eq!((Some(0), 0), (output.status.code(), output.stdout.len()));
+  let output = diff("resources/tests/trak.mp4");
+  assert_eq!(Some(2), output.status.code());
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert!(stdout.starts_with("--- resources/tests/moov.mp4\n+++ resources/tests/trak.mp4\n- moov (1023163 bytes)\n+ trak (431981 bytes)\n"), "{}", stdout);
+}